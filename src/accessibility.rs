@@ -0,0 +1,77 @@
+//! # Accessibility Module
+//!
+//! This module describes the structure of the rendered document to screen readers through
+//! AccessKit: headings with their level, links, lists, tables and code blocks, instead of
+//! a flat series of text labels. Nodes are only built while a screen reader is active.
+
+use egui::accesskit::{self, Role};
+use egui::text::CCursor;
+use egui::{Context, Galley, Id, Response};
+
+/// Renders `add_contents` as the children of a node with the given role, such as a list
+/// or a table row.
+pub fn group<R>(
+    ctx: &Context,
+    id: Id,
+    role: Role,
+    describe: impl FnOnce(&mut accesskit::NodeBuilder),
+    add_contents: impl FnOnce() -> R,
+) -> R {
+    ctx.accesskit_node_builder(id, |builder| {
+        builder.set_role(role);
+        describe(builder);
+    });
+    let mut result = None;
+    ctx.with_accessibility_parent(id, || result = Some(add_contents()));
+    result.expect("with_accessibility_parent always calls its closure")
+}
+
+/// Gives a rendered widget another role, e.g. a label that is a table cell.
+pub fn set_role(response: &Response, role: Role) {
+    response
+        .ctx
+        .accesskit_node_builder(response.id, |builder| builder.set_role(role));
+}
+
+/// Marks a rendered heading with its level.
+pub fn heading(response: &Response, level: u8) {
+    response.ctx.accesskit_node_builder(response.id, |builder| {
+        builder.set_role(Role::Heading);
+        builder.set_hierarchical_level(level as usize);
+    });
+}
+
+/// Adds a link node for each link of a rendered paragraph, given as its destination and
+/// the byte range of its text in the galley.
+pub fn links<'a>(
+    response: &Response,
+    galley: &Galley,
+    links: impl IntoIterator<Item = (&'a str, std::ops::Range<usize>)>,
+) {
+    let text = galley.text();
+    let origin = response.rect.min.to_vec2();
+    response.ctx.with_accessibility_parent(response.id, || {
+        for (url, range) in links {
+            let Some(name) = text.get(range.clone()) else {
+                continue;
+            };
+            let start = galley.pos_from_ccursor(CCursor::new(text[..range.start].chars().count()));
+            let end = galley.pos_from_ccursor(CCursor::new(text[..range.end].chars().count()));
+            let rect = start.union(end).translate(origin);
+            response.ctx.accesskit_node_builder(
+                response.id.with(("link", range.start)),
+                |builder| {
+                    builder.set_role(Role::Link);
+                    builder.set_name(name);
+                    builder.set_url(url);
+                    builder.set_bounds(accesskit::Rect {
+                        x0: rect.min.x.into(),
+                        y0: rect.min.y.into(),
+                        x1: rect.max.x.into(),
+                        y1: rect.max.y.into(),
+                    });
+                },
+            );
+        }
+    });
+}
@@ -0,0 +1,153 @@
+//! # Annotations Module
+//!
+//! This module stores the highlights and margin notes added to a document. They are kept
+//! in a hidden sidecar file next to the document (`.notes.md.annotations.json` for
+//! `notes.md`), so they travel with it, and anchored by the highlighted text itself so
+//! they survive edits elsewhere in the document.
+//!
+//! It also reads the text selected in the rendered document, which egui only hands out
+//! by copying it to the clipboard.
+
+use crate::storage;
+use egui::text_selection::LabelSelectionState;
+use egui::Context;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Extension of the sidecar file holding a document's annotations.
+const SIDECAR_EXTENSION: &str = "annotations.json";
+
+/// What to do with the selected text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotationKind {
+    /// Highlight it
+    Highlight,
+    /// Highlight it and attach a note
+    Note,
+}
+
+/// A highlighted passage, possibly with a note.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    /// Highlighted text, as rendered; every line is matched within a paragraph
+    pub quote: String,
+    /// Note shown in the margin; None for a plain highlight
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Annotations of one document.
+#[derive(Debug, Default)]
+pub struct Annotations {
+    /// Sidecar file the annotations are stored in
+    path: Option<PathBuf>,
+    /// Annotations in the order they were added
+    pub items: Vec<Annotation>,
+}
+
+impl Annotations {
+    /// Loads the annotations of a document from its sidecar file, if it has one.
+    pub fn load(document: &Path) -> Self {
+        let path = storage::sidecar_path(document, SIDECAR_EXTENSION);
+        let items = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            items,
+        }
+    }
+
+    /// Writes the annotations to the sidecar file, removing it once there are none left.
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = if self.items.is_empty() {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            }
+        } else {
+            serde_json::to_string_pretty(&self.items)
+                .map_err(anyhow::Error::from)
+                .and_then(|content| Ok(fs::write(path, content)?))
+        };
+        if let Err(e) = result {
+            eprintln!("Error saving annotations: {e}");
+        }
+    }
+
+    /// Adds an annotation for a quote and returns its index.
+    pub fn add(&mut self, quote: String, kind: AnnotationKind) -> usize {
+        self.items.push(Annotation {
+            quote,
+            note: (kind == AnnotationKind::Note).then(String::new),
+        });
+        self.save();
+        self.items.len() - 1
+    }
+
+    /// Removes an annotation.
+    pub fn remove(&mut self, index: usize) {
+        if index < self.items.len() {
+            self.items.remove(index);
+            self.save();
+        }
+    }
+}
+
+/// Reads the text selected in the document without touching the clipboard.
+///
+/// egui only hands out the selection by copying it at the end of the frame. `request`
+/// makes the current frame copy it, and a hook running after egui's takes the text out
+/// of the frame's output before it reaches the clipboard.
+pub struct SelectionReader {
+    /// Whether the text copied this frame is the requested selection
+    wanted: Arc<AtomicBool>,
+    /// Selection taken from the last requested copy
+    captured: Arc<Mutex<Option<String>>>,
+}
+
+impl SelectionReader {
+    /// Installs the hook taking the copied selection out of the frame output.
+    pub fn install(ctx: &Context) -> Self {
+        let wanted = Arc::new(AtomicBool::new(false));
+        let captured = Arc::new(Mutex::new(None));
+        let hook_wanted = wanted.clone();
+        let hook_captured = captured.clone();
+        ctx.on_end_frame(
+            "SelectionReader",
+            Arc::new(move |ctx| {
+                if hook_wanted.swap(false, Ordering::Relaxed) {
+                    let text = ctx.output_mut(|o| std::mem::take(&mut o.copied_text));
+                    if let Ok(mut captured) = hook_captured.lock() {
+                        *captured = Some(text).filter(|text| !text.trim().is_empty());
+                    }
+                }
+            }),
+        );
+        Self { wanted, captured }
+    }
+
+    /// Copies the selection this frame, if there is one; `take` returns it on the next
+    /// frame. Must be called before the document is drawn. Returns false if nothing is
+    /// selected.
+    pub fn request(&self, ctx: &Context) -> bool {
+        if !LabelSelectionState::load(ctx).has_selection() {
+            return false;
+        }
+        ctx.input_mut(|i| i.events.push(egui::Event::Copy));
+        self.wanted.store(true, Ordering::Relaxed);
+        true
+    }
+
+    /// Takes the selection copied by the last request.
+    pub fn take(&self) -> Option<String> {
+        self.captured.lock().ok()?.take()
+    }
+}
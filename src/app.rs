@@ -3,11 +3,51 @@
 //! This module contains the main application logic for mdzen,
 //! including the GUI state management, file operations, and user interactions.
 
-use crate::markdown::MarkdownRenderer;
+use crate::annotations::{AnnotationKind, Annotations, SelectionReader};
+use crate::bookmarks::{Bookmark, Bookmarks};
+use crate::cli::StartupOptions;
+use crate::critic::{self, CriticMode};
+use crate::dashboard::Dashboard;
+use crate::file_tree::FileTree;
+use crate::filters::Filters;
+use crate::folder_search::FolderSearch;
+use crate::fonts::{self, FontChoice};
+use crate::formats;
+use crate::frontmatter;
+use crate::history::{History, Location};
+use crate::hooks::{self, CommandHook, HookRun};
+use crate::instance::{HandOff, Instance, OpenRequest};
+use crate::lightbox::Lightbox;
+use crate::markdown::{
+    search_scope_ranges, slugify, unique_slug, DocumentLayout, MarkdownRenderer,
+    DEFAULT_DARK_CODE_THEME, DEFAULT_LIGHT_CODE_THEME,
+};
+use crate::pane::{synced_offset, Pane, SyncMode};
+use crate::plantuml::{self, PlantUmlRenderer};
+use crate::plugins;
+use crate::preferences::Preferences;
+use crate::preprocess::{Preprocessed, Preprocessor};
+use crate::presentation::Presentation;
+use crate::read_state::ReadTracker;
+use crate::saved_searches::{self, SavedSearch};
+use crate::scratchpad::Scratchpad;
+use crate::search::{self, SearchScope};
+use crate::speech::{self, Speaker};
+use crate::stats::{format_duration, ReadingStats, TextStats};
+use crate::storage;
+use crate::switcher::{FileSwitcher, HeadingSwitcher, SwitcherAction};
+use crate::theme::{self, ColorScheme, NightLight};
+use crate::vault::Vault;
+use crate::view_settings::{ViewSettings, ViewSettingsStore};
+use crate::watcher::{FolderScan, FolderWatcher};
 use egui::*;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc;
 
 /// Main application state for the markdown reader.
 ///
@@ -23,6 +63,49 @@ pub struct MarkdownReaderApp {
     current_file: Option<PathBuf>,
     /// Raw markdown content of the current file
     content: String,
+    /// Content of the current file as read from disk, before filters are applied
+    source: String,
+    /// Whether pre-render filters are applied to documents
+    apply_filters: bool,
+    /// Whether the `.mdzen-filter.rhai` scripts found next to documents run too
+    folder_filters: bool,
+    /// Whether filters changed the current document
+    filtered: bool,
+    /// Whether `{{ key }}` placeholders are replaced with front matter values
+    substitute_variables: bool,
+    /// How CriticMarkup annotations are presented
+    critic_mode: CriticMode,
+    /// Whether HTML comments are shown instead of hidden
+    show_comments: bool,
+    /// Distance kept between a jump target and the top of the view, in points
+    scroll_anchor: f32,
+    /// Whether jump targets are centered in the view instead of placed near the top
+    center_jump_target: bool,
+    /// How long a jump scrolls to its target, in milliseconds; 0 jumps at once
+    scroll_animation_ms: u32,
+    /// Whether animations are turned off, including animated jumps
+    reduce_motion: bool,
+    /// Jump being animated, applied a step per frame
+    scroll_animation: Option<ScrollAnimation>,
+    /// Global and per-document view settings
+    view_settings: ViewSettingsStore,
+    /// View settings in effect at the end of the last frame, to detect changes
+    applied_view_settings: ViewSettings,
+    /// Second document shown next to the main one in split view
+    split_pane: Option<Pane>,
+    /// Whether the file dialog for the split view pane should be shown
+    show_split_dialog: bool,
+    /// How the split view panes scroll together
+    sync_mode: SyncMode,
+    /// Scroll offsets of the main document and the pane after the last sync
+    synced_offsets: (f32, f32),
+    /// External preprocessor applied before the filters
+    preprocessor: Preprocessor,
+    /// Error from the last preprocessor run; the unprocessed source is shown instead
+    preprocess_error: Option<String>,
+    /// Whether the preprocessor is still running for the current file, whose
+    /// unprocessed source is shown meanwhile
+    preprocessing: bool,
     /// Whether the file open dialog should be shown
     show_open_dialog: bool,
     /// Current font size for text rendering
@@ -39,14 +122,184 @@ pub struct MarkdownReaderApp {
     current_search_index: usize,
     /// Whether search should be case sensitive
     search_case_sensitive: bool,
+    /// Whether search also finds words within a small edit distance of the query
+    search_fuzzy: bool,
+    /// Part of the document the search is limited to
+    search_scope: SearchScope,
+    /// Scroll offset when typing in the search box began; Escape returns there
+    search_origin: Option<f32>,
+    /// Whether every search result is listed in a panel below the document
+    show_search_results: bool,
     /// Cache for loaded images to avoid reloading
     image_cache: HashMap<String, Result<egui::TextureHandle, String>>,
     /// Whether the table of contents sidebar is visible
     show_toc: bool,
     /// List of headers for the table of contents
     toc_headers: Vec<TocHeader>,
+    /// Slugs of the TOC entries whose subsections are collapsed
+    toc_collapsed: HashSet<String>,
+    /// TOC entry highlighted as the current section, kept in view in the TOC panel
+    toc_highlighted: Option<usize>,
+    /// Whether the auto-hidden TOC is slid in
+    toc_revealed: bool,
+    /// Whether the TOC width was set in the menu and has to be applied to the panel
+    toc_width_changed: bool,
     /// Header to scroll to (if any)
     scroll_to_header: Option<String>,
+    /// Vertical scroll offset of the document view as of the last frame
+    scroll_offset: f32,
+    /// Scroll offset to apply to the document view on the next frame
+    pending_scroll_offset: Option<f32>,
+    /// Largest scroll offset of the document view (content height minus viewport)
+    max_scroll_offset: f32,
+    /// Block and heading positions of the last frame, relative to the document top
+    layout: DocumentLayout,
+    /// Whether the go-to dialog is visible
+    show_goto: bool,
+    /// Text typed into the go-to dialog
+    goto_input: String,
+    /// Error from the last go-to attempt, shown in the dialog
+    goto_error: Option<String>,
+    /// Time spent reading each document
+    reading_stats: ReadingStats,
+    /// Whether the reading statistics window is visible
+    show_stats: bool,
+    /// Built-in and user-defined color schemes
+    color_schemes: Vec<ColorScheme>,
+    /// Name of the selected color scheme
+    theme_name: String,
+    /// Color scheme given with `--theme`, shown over the selected one without being saved
+    startup_theme: Option<String>,
+    /// Syntax theme of code blocks; None follows the color scheme
+    code_theme: Option<String>,
+    /// Syntax theme following dark color schemes
+    code_theme_dark: String,
+    /// Syntax theme following light color schemes
+    code_theme_light: String,
+    /// Base URL of the PlantUML server, shared with the PlantUML fence renderer
+    plantuml_server: Rc<RefCell<String>>,
+    /// PlantUML server URL being edited in the View menu
+    plantuml_server_input: String,
+    /// Warm color (night light) settings
+    night_light: NightLight,
+    /// Night-light strength currently applied to the visuals
+    applied_warmth: f32,
+    /// Whether body text uses the dyslexia-friendly font with wider letter spacing
+    accessible_font: bool,
+    /// Why the dyslexia-friendly font couldn't be enabled
+    font_error: Option<String>,
+    /// Font files chosen for body text and code
+    font_choice: FontChoice,
+    /// Whether the font settings window is shown
+    show_font_settings: bool,
+    /// Fonts offered in the font settings window, listed when it opens
+    installed_fonts: Vec<PathBuf>,
+    /// Text-to-speech playback of the document
+    speaker: Speaker,
+    /// Source line of the sentence the view last followed while reading aloud
+    spoken_line: Option<usize>,
+    /// Height of the document viewport in the last frame
+    viewport_height: f32,
+    /// External commands listed in the Commands menu
+    command_hooks: Vec<CommandHook>,
+    /// Text most recently copied from the document, used as the command selection
+    last_copied: String,
+    /// Most recently started external command and its output
+    hook_run: Option<HookRun>,
+    /// Named searches offered in the search bar
+    saved_searches: Vec<SavedSearch>,
+    /// Name entered for the search being saved
+    saved_search_name: String,
+    /// Whether the search being saved is limited to the current folder
+    saved_search_in_folder: bool,
+    /// Folder opened in folder mode
+    folder: Option<Vault>,
+    /// Whether the folder picker should be shown
+    show_folder_dialog: bool,
+    /// Quick file switcher, while open
+    file_switcher: Option<FileSwitcher>,
+    /// Heading switcher of the current document, while open
+    heading_switcher: Option<HeadingSwitcher>,
+    /// Tree of the files in the opened folder
+    file_tree: Option<FileTree>,
+    /// Whether the file tree sidebar is shown in folder mode
+    show_file_tree: bool,
+    /// Whether opening a file outside the opened folder opens its parent folder
+    browse_siblings: bool,
+    /// Whether long code lines wrap instead of scrolling horizontally
+    wrap_code: bool,
+    /// Whether `==text==` is rendered as highlighted text
+    highlight_marks: bool,
+    /// Widest an image is shown without a size hint, in percent of the content width
+    image_max_width: f32,
+    /// Tallest an image is shown without a size hint, in points
+    image_max_height: f32,
+    /// Whether the opened folder was opened for browsing a file's siblings
+    folder_from_file: bool,
+    /// Places navigated away from, for Back and Forward
+    history: History,
+    /// Rescans the opened folder for changes
+    folder_watcher: Option<FolderWatcher>,
+    /// Folder of an opened file being scanned in the background for sibling browsing
+    sibling_scan: Option<(PathBuf, mpsc::Receiver<FolderScan>)>,
+    /// Whether the open document was deleted from disk
+    current_file_missing: bool,
+    /// Which documents have been read, in which version
+    read_tracker: ReadTracker,
+    /// Marked positions of the documents
+    bookmarks: Bookmarks,
+    /// Whether the bookmarks panel is shown
+    show_bookmarks: bool,
+    /// Highlights and notes of the current document
+    annotations: Annotations,
+    /// Reads the selected text for new annotations
+    selection_reader: Option<SelectionReader>,
+    /// Annotation to create once the selected text has been read
+    annotation_request: Option<AnnotationKind>,
+    /// Selected text read when a paragraph's context menu was opened
+    selected_quote: Option<String>,
+    /// Whether the annotations panel is shown
+    show_annotations: bool,
+    /// Annotation whose note gets the keyboard focus in the annotations panel
+    focused_annotation: Option<usize>,
+    /// Summary of the opened folder, while its window is open
+    dashboard: Option<Dashboard>,
+    /// System clipboard used for rich text, kept open so the copied HTML stays available
+    /// on X11, where the copying application serves the clipboard
+    clipboard: Option<arboard::Clipboard>,
+    /// Word and character counts of the document
+    text_stats: TextStats,
+    /// Whether the status bar with the document's size is shown
+    show_status_bar: bool,
+    /// Free-form notes on the current document
+    scratchpad: Scratchpad,
+    /// Whether the scratchpad panel is shown
+    show_scratchpad: bool,
+    /// Slides of the document while presenting
+    presentation: Option<Presentation>,
+    /// Socket on which later invocations hand over the files to open
+    instance: Option<Instance>,
+    /// Image viewer opened by clicking an image
+    lightbox: Option<Lightbox>,
+    /// Files passed on the command line or handed over, listed to switch between them
+    opened_files: Vec<PathBuf>,
+    /// Whether the list of opened files is shown
+    show_opened_files: bool,
+    /// Search across the documents of a folder, while its window is open
+    folder_search: Option<FolderSearch>,
+    /// Whether to scroll to the current search result once the document is laid out
+    search_result_pending: bool,
+}
+
+/// A jump scrolling the document view from one offset to another over time.
+#[derive(Debug, Clone, Copy)]
+struct ScrollAnimation {
+    /// Scroll offset when the jump started
+    from: f32,
+    /// Scroll offset of the jump target
+    to: f32,
+    /// Input time when the jump started, in seconds; NaN until the first animated frame
+    start: f64,
 }
 
 /// Represents a header in the table of contents.
@@ -56,8 +309,9 @@ pub struct TocHeader {
     pub level: u8,
     /// Text content of the header
     pub title: String,
-    /// Line number where the header appears (reserved for future use)
-    #[allow(dead_code)]
+    /// Anchor slug of the header, unique within the document
+    pub slug: String,
+    /// Source line (0-based) where the header appears
     pub line_number: usize,
 }
 
@@ -65,16 +319,12 @@ pub struct TocHeader {
 #[derive(Debug, Clone)]
 pub struct SearchResult {
     /// Line number where the match was found
-    #[allow(dead_code)]
     pub line_number: usize,
     /// Full content of the line containing the match
-    #[allow(dead_code)]
     pub line_content: String,
-    /// Character index where the match starts in the line
-    #[allow(dead_code)]
+    /// Byte index where the match starts in the line
     pub match_start: usize,
-    /// Character index where the match ends in the line
-    #[allow(dead_code)]
+    /// Byte index where the match ends in the line
     pub match_end: usize,
 }
 
@@ -84,6 +334,27 @@ impl Default for MarkdownReaderApp {
             markdown_renderer: MarkdownRenderer::new(),
             current_file: None,
             content: String::new(),
+            source: String::new(),
+            apply_filters: true,
+            folder_filters: false,
+            filtered: false,
+            substitute_variables: false,
+            critic_mode: CriticMode::default(),
+            show_comments: false,
+            scroll_anchor: 24.0,
+            center_jump_target: false,
+            scroll_animation_ms: 250,
+            reduce_motion: false,
+            scroll_animation: None,
+            view_settings: ViewSettingsStore::default(),
+            applied_view_settings: ViewSettings::default(),
+            split_pane: None,
+            show_split_dialog: false,
+            sync_mode: SyncMode::default(),
+            synced_offsets: (0.0, 0.0),
+            preprocessor: Preprocessor::default(),
+            preprocess_error: None,
+            preprocessing: false,
             show_open_dialog: false,
             font_size: 14.0,
             wide_mode: false,
@@ -92,10 +363,89 @@ impl Default for MarkdownReaderApp {
             search_results: Vec::new(),
             current_search_index: 0,
             search_case_sensitive: false,
+            search_fuzzy: false,
+            search_scope: SearchScope::All,
+            search_origin: None,
+            show_search_results: false,
             image_cache: HashMap::new(),
             show_toc: false,
             toc_headers: Vec::new(),
+            toc_collapsed: HashSet::new(),
+            toc_highlighted: None,
+            toc_revealed: false,
+            toc_width_changed: false,
             scroll_to_header: None,
+            scroll_offset: 0.0,
+            pending_scroll_offset: None,
+            max_scroll_offset: 0.0,
+            layout: DocumentLayout::default(),
+            show_goto: false,
+            goto_input: String::new(),
+            goto_error: None,
+            reading_stats: ReadingStats::default(),
+            show_stats: false,
+            color_schemes: vec![ColorScheme::zen_dark()],
+            theme_name: ColorScheme::zen_dark().name,
+            startup_theme: None,
+            code_theme: None,
+            code_theme_dark: DEFAULT_DARK_CODE_THEME.to_string(),
+            code_theme_light: DEFAULT_LIGHT_CODE_THEME.to_string(),
+            plantuml_server: Rc::new(RefCell::new(String::new())),
+            plantuml_server_input: String::new(),
+            night_light: NightLight::default(),
+            applied_warmth: 0.0,
+            accessible_font: false,
+            font_error: None,
+            font_choice: FontChoice::default(),
+            show_font_settings: false,
+            installed_fonts: Vec::new(),
+            speaker: Speaker::default(),
+            spoken_line: None,
+            viewport_height: 0.0,
+            command_hooks: Vec::new(),
+            last_copied: String::new(),
+            hook_run: None,
+            saved_searches: Vec::new(),
+            saved_search_name: String::new(),
+            saved_search_in_folder: false,
+            folder: None,
+            show_folder_dialog: false,
+            file_switcher: None,
+            heading_switcher: None,
+            file_tree: None,
+            show_file_tree: true,
+            browse_siblings: false,
+            folder_from_file: false,
+            history: History::default(),
+            wrap_code: true,
+            highlight_marks: false,
+            image_max_width: 100.0,
+            image_max_height: 600.0,
+            folder_watcher: None,
+            sibling_scan: None,
+            current_file_missing: false,
+            read_tracker: ReadTracker::default(),
+            bookmarks: Bookmarks::default(),
+            show_bookmarks: false,
+            annotations: Annotations::default(),
+            selection_reader: None,
+            annotation_request: None,
+            selected_quote: None,
+            show_annotations: false,
+            focused_annotation: None,
+            dashboard: None,
+            folder_search: None,
+            presentation: None,
+            instance: None,
+            lightbox: None,
+            opened_files: Vec::new(),
+            show_opened_files: false,
+            text_stats: TextStats::default(),
+            show_status_bar: true,
+            scratchpad: Scratchpad::default(),
+            show_scratchpad: false,
+            clipboard: None,
+            search_result_pending: false,
         }
     }
 }
@@ -106,17 +456,29 @@ impl MarkdownReaderApp {
     /// Sets up dark theme colors optimized for readability and initializes
     /// the markdown renderer with the default font size.
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
-        // Set up nice visuals for better readability
-        let mut visuals = egui::Visuals::dark();
-        visuals.window_fill = egui::Color32::from_rgb(40, 44, 52);
-        visuals.panel_fill = egui::Color32::from_rgb(40, 44, 52);
-        visuals.extreme_bg_color = egui::Color32::from_rgb(33, 37, 43);
-        visuals.code_bg_color = egui::Color32::from_rgb(33, 37, 43);
-        visuals.override_text_color = Some(egui::Color32::from_rgb(171, 178, 191));
-        cc.egui_ctx.set_visuals(visuals);
-
-        let mut app = Self::default();
-        app.markdown_renderer.set_font_size(app.font_size);
+        let _ = fonts::install(&cc.egui_ctx, false, &FontChoice::default());
+
+        let mut app = Self {
+            view_settings: ViewSettingsStore::load(cc.storage),
+            reading_stats: ReadingStats::load(),
+            command_hooks: hooks::load(),
+            preprocessor: Preprocessor::load(),
+            saved_searches: saved_searches::load(),
+            read_tracker: ReadTracker::load(),
+            bookmarks: Bookmarks::load(),
+            selection_reader: Some(SelectionReader::install(&cc.egui_ctx)),
+            color_schemes: theme::load_schemes(),
+            instance: Instance::listen(&cc.egui_ctx),
+            ..Self::default()
+        };
+        app.apply_preferences(&cc.egui_ctx, Preferences::load(cc.storage));
+        app.apply_view_settings(app.view_settings.defaults);
+        for renderer in plugins::load_command_renderers() {
+            app.markdown_renderer.register_fence_renderer(renderer);
+        }
+        // Registered last so a configured command can take over PlantUML fences
+        app.markdown_renderer
+            .register_fence_renderer(Box::new(PlantUmlRenderer::new(app.plantuml_server.clone())));
         app
     }
 
@@ -125,16 +487,634 @@ impl MarkdownReaderApp {
     /// Reads the file content, clears caches, and regenerates the table of contents.
     /// Returns an error if the file cannot be read.
     pub fn load_file(&mut self, path: PathBuf) -> anyhow::Result<()> {
-        let content = fs::read_to_string(&path)?;
-        self.content = content;
+        let from = self.current_location();
+        let switching = self.current_file.as_ref() != Some(&path);
+        self.open_file(path)?;
+        if let Some(from) = from.filter(|_| switching) {
+            self.history.visit(from);
+        }
+        Ok(())
+    }
+
+    /// Loads a file without recording the navigation in the history.
+    fn open_file(&mut self, path: PathBuf) -> anyhow::Result<()> {
+        self.source = fs::read_to_string(&path)?;
+        if self.browse_siblings {
+            self.browse_siblings_of(&path);
+        }
+        // Resolve note links across the opened folder when the file belongs to it
+        let vault_root = match &self.folder {
+            Some(folder) if path.starts_with(folder.root()) => Some(folder.root().to_path_buf()),
+            _ => path.parent().map(|dir| dir.to_path_buf()),
+        };
+        self.markdown_renderer.set_vault_root(vault_root);
+        self.reading_stats.start_session(path.clone());
+        self.apply_view_settings(self.view_settings.for_document(&path));
+        self.speaker.stop();
+        self.read_tracker.mark_read([path.as_path()]);
+        self.annotations = Annotations::load(&path);
+        self.scratchpad.save();
+        self.scratchpad = Scratchpad::load(&path);
         self.current_file = Some(path);
+        self.current_file_missing = false;
         self.image_cache.clear(); // Clear cache when loading new file
-        self.search_results.clear();
-        self.current_search_index = 0;
-        self.generate_toc(); // Generate TOC when loading new file
+        self.markdown_renderer.forget_images();
+        self.markdown_renderer
+            .set_annotations(self.annotations.items.clone());
+        self.selected_quote = None;
+        self.toc_collapsed.clear();
+        self.refresh_content();
         Ok(())
     }
 
+    /// Applies the view options given on the command line over the remembered ones,
+    /// without remembering them for the document.
+    pub fn apply_startup_options(&mut self, ctx: &Context, options: &StartupOptions) {
+        let mut settings = self.current_view_settings();
+        if let Some(size) = options.font_size {
+            settings.font_size = size;
+        }
+        settings.wide_mode |= options.wide;
+        settings.show_toc |= options.toc;
+        self.apply_view_settings(settings);
+
+        if let Some(name) = &options.theme {
+            match self
+                .color_schemes
+                .iter()
+                .find(|scheme| scheme.name.eq_ignore_ascii_case(name))
+            {
+                Some(scheme) => {
+                    self.startup_theme = Some(scheme.name.clone());
+                    self.apply_theme(ctx);
+                }
+                None => eprintln!("Error: no color scheme named \"{name}\""),
+            }
+        }
+    }
+
+    /// Opens a folder in folder mode and shows the file switcher to pick a document.
+    pub fn open_folder(&mut self, path: PathBuf) {
+        self.index_folder(path);
+        self.folder_from_file = false;
+        if let Some(folder) = &self.folder {
+            self.file_switcher = Some(FileSwitcher::new(folder));
+        }
+    }
+
+    /// Indexes a folder and shows its files in the file tree.
+    fn index_folder(&mut self, path: PathBuf) {
+        self.show_folder(FolderScan::run(path));
+    }
+
+    /// Shows a scanned folder in the file tree.
+    fn show_folder(&mut self, scan: FolderScan) {
+        let FolderScan {
+            vault: folder,
+            modified,
+        } = scan;
+        let mut tree = FileTree::new(&folder);
+        tree.set_modified_times(modified);
+        self.file_tree = Some(tree);
+        self.folder_watcher = Some(FolderWatcher::new(folder.root()));
+        self.folder = Some(folder);
+    }
+
+    /// Starts scanning the folder of a file to show it in the file tree, unless the file
+    /// already belongs to a folder the user opened.
+    fn browse_siblings_of(&mut self, path: &Path) {
+        if !self.may_browse_siblings_of(path) {
+            return;
+        }
+        let Some(parent) = path.parent() else {
+            return;
+        };
+        if self
+            .sibling_scan
+            .as_ref()
+            .is_some_and(|(root, _)| root == parent)
+        {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let root = parent.to_path_buf();
+        std::thread::spawn(move || {
+            let _ = sender.send(FolderScan::run(root));
+        });
+        self.sibling_scan = Some((parent.to_path_buf(), receiver));
+    }
+
+    /// Whether the folder of a file may replace the folder shown in the file tree.
+    fn may_browse_siblings_of(&self, path: &Path) -> bool {
+        let in_folder = self
+            .folder
+            .as_ref()
+            .is_some_and(|folder| path.starts_with(folder.root()));
+        !in_folder && (self.folder.is_none() || self.folder_from_file)
+    }
+
+    /// Shows the folder scanned for sibling browsing once the scan completes, if the
+    /// open document still belongs to it.
+    fn poll_sibling_scan(&mut self, ctx: &Context) {
+        let Some((root, receiver)) = &self.sibling_scan else {
+            return;
+        };
+        let scan = match receiver.try_recv() {
+            Ok(scan) => scan,
+            Err(mpsc::TryRecvError::Empty) => {
+                ctx.request_repaint_after(std::time::Duration::from_millis(100));
+                return;
+            }
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.sibling_scan = None;
+                return;
+            }
+        };
+        let root = root.clone();
+        self.sibling_scan = None;
+        let Some(file) = self.current_file.clone() else {
+            return;
+        };
+        if self.browse_siblings
+            && file.parent() == Some(root.as_path())
+            && self.may_browse_siblings_of(&file)
+        {
+            self.show_folder(scan);
+            self.folder_from_file = true;
+            self.show_file_tree = true;
+        }
+    }
+
+    /// Picks up files created, renamed or deleted in the opened folder.
+    fn watch_folder(&mut self, ctx: &Context) {
+        let Some(FolderScan { vault, modified }) = self
+            .folder_watcher
+            .as_mut()
+            .and_then(|watcher| watcher.poll(ctx))
+        else {
+            return;
+        };
+        let Some(folder) = &self.folder else {
+            return;
+        };
+        if let Some(tree) = &mut self.file_tree {
+            tree.set_modified_times(modified);
+        }
+        if vault.notes() == folder.notes() {
+            return;
+        }
+        let removed: Vec<&PathBuf> = folder
+            .notes()
+            .iter()
+            .filter(|path| vault.notes().binary_search(path).is_err())
+            .collect();
+        let added: Vec<&PathBuf> = vault
+            .notes()
+            .iter()
+            .filter(|path| folder.notes().binary_search(path).is_err())
+            .collect();
+
+        // Follow the open document when it was renamed (a single file disappeared and
+        // another appeared); otherwise note that it is gone
+        if let Some(current) = self.current_file.clone() {
+            if removed.contains(&&current) {
+                if let ([from], [to]) = (removed.as_slice(), added.as_slice()) {
+                    if *from == &current {
+                        self.current_file = Some((*to).clone());
+                    }
+                } else {
+                    self.current_file_missing = true;
+                }
+            } else if added.contains(&&current) {
+                self.current_file_missing = false;
+            }
+        }
+
+        if let Some(tree) = &mut self.file_tree {
+            tree.refresh(&vault);
+        }
+        self.markdown_renderer.set_vault(vault.clone());
+        self.folder = Some(vault);
+    }
+
+    /// Opens the quick file switcher, in folder mode.
+    fn open_file_switcher(&mut self) {
+        if let Some(folder) = &self.folder {
+            self.file_switcher = Some(FileSwitcher::new(folder));
+        }
+    }
+
+    fn show_dashboard(&mut self, ctx: &Context) {
+        let Some(dashboard) = self.dashboard.as_mut() else {
+            return;
+        };
+        let mut open = true;
+        let clicked = dashboard.show(ctx, &mut open);
+        if !open {
+            self.dashboard = None;
+        }
+        if let Some(path) = clicked {
+            if let Err(e) = self.load_file(path) {
+                eprintln!("Error loading file: {e}");
+            }
+        }
+    }
+
+    /// Copies the document to the clipboard as HTML, with the markdown as the plain-text
+    /// alternative, so pasting into mail clients or word processors keeps its formatting.
+    fn copy_as_html(&mut self, ctx: &Context) {
+        let mut html = String::new();
+        pulldown_cmark::html::push_html(
+            &mut html,
+            pulldown_cmark::Parser::new_ext(&self.content, MarkdownRenderer::parser_options()),
+        );
+        let clipboard = match self.clipboard.take() {
+            Some(clipboard) => Ok(clipboard),
+            None => arboard::Clipboard::new(),
+        };
+        let result = clipboard.and_then(|mut clipboard| {
+            clipboard.set_html(&html, Some(&self.content))?;
+            self.clipboard = Some(clipboard);
+            Ok(())
+        });
+        if let Err(e) = result {
+            // Without rich text support, the HTML source is still useful
+            eprintln!("Error copying as rich text: {e}");
+            ctx.output_mut(|o| o.copied_text = html);
+        }
+    }
+
+    /// Shows the word count, character count and estimated reading time of the document.
+    fn show_status_bar(&mut self, ctx: &Context) {
+        let stats = self.text_stats;
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.weak(format!("{} words", stats.words));
+                ui.separator();
+                ui.weak(format!("{} characters", stats.characters));
+                ui.separator();
+                let minutes = stats.reading_minutes();
+                ui.weak(if minutes <= 1 {
+                    "1 min read".to_string()
+                } else {
+                    format!("{minutes} min read")
+                });
+            });
+        });
+    }
+
+    /// Splits the document into slides and shows them full screen.
+    fn start_presentation(&mut self, ctx: &Context) {
+        if self.current_file.is_none() {
+            return;
+        }
+        self.presentation = Some(Presentation::new(&self.content));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(true));
+    }
+
+    /// Shows the first of several files and lists all of them to switch between, since
+    /// only one document is shown at a time.
+    pub fn open_files(&mut self, requests: Vec<OpenRequest>) {
+        for request in &requests {
+            if !self.opened_files.contains(&request.file) {
+                self.opened_files.push(request.file.clone());
+            }
+        }
+        let Some(request) = requests.into_iter().next() else {
+            return;
+        };
+        if let Err(e) = self.load_file(request.file) {
+            eprintln!("Error loading file: {e}");
+        } else if let Some(anchor) = request.anchor {
+            if !self.scroll_to_anchor(&anchor) {
+                eprintln!("Error: no heading matches \"#{anchor}\"");
+            }
+        }
+        self.show_opened_files = self.opened_files.len() > 1;
+    }
+
+    /// Opens the files handed over by later invocations of mdzen with their view flags,
+    /// and raises the window.
+    fn open_requested_files(&mut self, ctx: &Context) {
+        let Some(instance) = &self.instance else {
+            return;
+        };
+        let hand_offs: Vec<HandOff> = std::iter::from_fn(|| instance.next_hand_off()).collect();
+        if hand_offs.is_empty() {
+            return;
+        }
+        if self.presentation.is_some() {
+            self.stop_presentation(ctx);
+        }
+        for hand_off in hand_offs {
+            self.open_files(hand_off.requests);
+            self.apply_startup_options(ctx, &hand_off.options);
+            if let Some([width, height]) = hand_off.options.window_size {
+                ctx.send_viewport_cmd(ViewportCommand::InnerSize(vec2(width, height)));
+            }
+        }
+        ctx.send_viewport_cmd(ViewportCommand::Focus);
+    }
+
+    /// Lists the opened files; clicking one shows it.
+    fn show_opened_files_window(&mut self, ctx: &Context) {
+        let mut open = self.show_opened_files;
+        let mut clicked = None;
+        egui::Window::new("Opened Files")
+            .open(&mut open)
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .max_height(360.0)
+                    .show(ui, |ui| {
+                        for path in &self.opened_files {
+                            let name = path.file_name().unwrap_or_default().to_string_lossy();
+                            let current = self.current_file.as_ref() == Some(path);
+                            if ui
+                                .selectable_label(current, name)
+                                .on_hover_text(path.display().to_string())
+                                .clicked()
+                            {
+                                clicked = Some(path.clone());
+                            }
+                        }
+                    });
+            });
+        self.show_opened_files = open;
+        if let Some(path) = clicked {
+            if let Err(e) = self.load_file(path) {
+                eprintln!("Error loading file: {e}");
+            }
+        }
+    }
+
+    fn stop_presentation(&mut self, ctx: &Context) {
+        self.presentation = None;
+        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(false));
+    }
+
+    /// Draws the current slide, with a font scaled to the window, and handles slide
+    /// navigation.
+    fn show_presentation(&mut self, ctx: &Context) {
+        let Some(presentation) = self.presentation.as_mut() else {
+            return;
+        };
+        let (next, previous, first, last, exit) = ctx.input(|i| {
+            let pressed = |keys: &[egui::Key]| keys.iter().any(|&key| i.key_pressed(key));
+            (
+                pressed(&[
+                    egui::Key::ArrowRight,
+                    egui::Key::ArrowDown,
+                    egui::Key::PageDown,
+                    egui::Key::Space,
+                ]),
+                pressed(&[egui::Key::ArrowLeft, egui::Key::ArrowUp, egui::Key::PageUp]),
+                i.key_pressed(egui::Key::Home),
+                i.key_pressed(egui::Key::End),
+                pressed(&[egui::Key::Escape, egui::Key::F5]),
+            )
+        });
+        if next {
+            presentation.next();
+        }
+        if previous {
+            presentation.previous();
+        }
+        if first {
+            presentation.first();
+        }
+        if last {
+            presentation.last();
+        }
+        if exit {
+            self.stop_presentation(ctx);
+            return;
+        }
+        let slide = presentation.slide().to_string();
+        let position = format!("{} / {}", presentation.current() + 1, presentation.len());
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let rect = ui.max_rect();
+            // Scale the text with the window so slides read from across a room
+            let font_size = (rect.height() / 22.0).clamp(18.0, 64.0);
+            self.markdown_renderer.set_font_size(font_size);
+            let side_padding = rect.width() * 0.1;
+            egui::ScrollArea::vertical()
+                .id_source(("slide", &position))
+                .auto_shrink([false; 2])
+                .show(ui, |ui| {
+                    ui.add_space(rect.height() * 0.06);
+                    ui.horizontal(|ui| {
+                        ui.add_space(side_padding);
+                        ui.vertical(|ui| {
+                            self.markdown_renderer.render(
+                                ui,
+                                &slide,
+                                "",
+                                None,
+                                &mut self.image_cache,
+                                &self.current_file,
+                                &None,
+                                Some(rect.width() - 2.0 * side_padding),
+                            );
+                        });
+                    });
+                });
+            self.markdown_renderer.set_font_size(self.font_size);
+            ui.painter().text(
+                rect.right_bottom() - egui::vec2(12.0, 8.0),
+                egui::Align2::RIGHT_BOTTOM,
+                position,
+                egui::FontId::proportional(14.0),
+                ui.visuals().weak_text_color(),
+            );
+        });
+    }
+
+    /// Opens the folder search window for the opened folder, or the folder of the current
+    /// document, starting with the query of the search bar.
+    fn open_folder_search(&mut self, ctx: &Context) {
+        let root = match (&self.folder, &self.current_file) {
+            (Some(folder), _) => folder.root().to_path_buf(),
+            (None, Some(file)) => match file.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => return,
+            },
+            (None, None) => return,
+        };
+        let mut search = FolderSearch::new(
+            root,
+            self.search_query.clone(),
+            self.search_case_sensitive,
+            self.search_fuzzy,
+        );
+        search.start(ctx);
+        self.folder_search = Some(search);
+    }
+
+    fn show_folder_search(&mut self, ctx: &Context) {
+        let Some(search) = self.folder_search.as_mut() else {
+            return;
+        };
+        let mut open = true;
+        let clicked = search.show(ctx, &mut open);
+        let (query, case_sensitive, fuzzy) =
+            (search.query.clone(), search.case_sensitive, search.fuzzy);
+        if !open {
+            self.folder_search = None;
+        }
+        let Some((path, result)) = clicked else {
+            return;
+        };
+        if let Err(e) = self.load_file(path) {
+            eprintln!("Error loading file: {e}");
+            return;
+        }
+
+        // Continue with the same search in the document, at the clicked match
+        self.search_query = query;
+        self.search_case_sensitive = case_sensitive;
+        self.search_fuzzy = fuzzy;
+        self.markdown_renderer
+            .set_case_sensitive_search(case_sensitive);
+        self.markdown_renderer.set_fuzzy_search(fuzzy);
+        self.show_search = true;
+        self.perform_search();
+        if let Some(index) = self
+            .search_results
+            .iter()
+            .position(|found| {
+                found.line_number == result.line_number && found.match_start == result.match_start
+            })
+            .or_else(|| {
+                self.search_results
+                    .iter()
+                    .position(|found| found.line_number >= result.line_number)
+            })
+        {
+            self.current_search_index = index;
+        }
+        self.search_result_pending = true;
+    }
+
+    fn show_file_switcher(&mut self, ctx: &Context) {
+        let Some(switcher) = self.file_switcher.as_mut() else {
+            return;
+        };
+        match switcher.show(ctx) {
+            SwitcherAction::None => {}
+            SwitcherAction::Close => self.file_switcher = None,
+            SwitcherAction::Open(path) => {
+                self.file_switcher = None;
+                if let Err(e) = self.load_file(path) {
+                    eprintln!("Error loading file: {e}");
+                }
+            }
+        }
+    }
+
+    /// Opens the heading switcher over the headings of the current document.
+    fn open_heading_switcher(&mut self) {
+        if !self.toc_headers.is_empty() {
+            self.heading_switcher = Some(HeadingSwitcher::new(&self.toc_headers));
+        }
+    }
+
+    fn show_heading_switcher(&mut self, ctx: &Context) {
+        let Some(switcher) = self.heading_switcher.as_mut() else {
+            return;
+        };
+        match switcher.show(ctx) {
+            SwitcherAction::None => {}
+            SwitcherAction::Close => self.heading_switcher = None,
+            SwitcherAction::Open(index) => {
+                self.heading_switcher = None;
+                if let Some(header) = self.toc_headers.get(index).cloned() {
+                    self.jump_to_heading(header);
+                }
+            }
+        }
+    }
+
+    /// Scrolls to a heading of the table of contents, remembering the place left.
+    fn jump_to_heading(&mut self, header: TocHeader) {
+        self.remember_location();
+        match self.layout.offset_for_line(header.line_number) {
+            Some(offset) => self.scroll_to(offset),
+            None => self.scroll_to_header = Some(header.slug),
+        }
+    }
+
+    fn current_view_settings(&self) -> ViewSettings {
+        ViewSettings {
+            wide_mode: self.wide_mode,
+            font_size: self.font_size,
+            show_toc: self.show_toc,
+        }
+    }
+
+    fn apply_view_settings(&mut self, settings: ViewSettings) {
+        self.wide_mode = settings.wide_mode;
+        self.font_size = settings.font_size;
+        self.show_toc = settings.show_toc;
+        self.markdown_renderer.set_font_size(self.font_size);
+        self.applied_view_settings = settings;
+    }
+
+    /// Records view settings changed during the frame: for the open document, or as the
+    /// defaults when no document is open.
+    fn store_view_settings(&mut self) {
+        let settings = self.current_view_settings();
+        if settings == self.applied_view_settings {
+            return;
+        }
+        self.applied_view_settings = settings;
+        match &self.current_file {
+            Some(path) => self.view_settings.remember(path, settings),
+            None => self.view_settings.defaults = settings,
+        }
+    }
+
+    /// Rebuilds the displayed content from the file source, running the preprocessor
+    /// and the pre-render filters, and regenerates everything derived from it.
+    fn refresh_content(&mut self) {
+        self.preprocess_error = None;
+        self.preprocessing = false;
+        self.filtered = false;
+        let mut content = self.source.clone();
+        if let Some(path) = &self.current_file {
+            if let Some(converted) = formats::to_markdown(path, &self.source) {
+                content = converted;
+            }
+            if self.preprocessor.applies_to(path) {
+                match self.preprocessor.process(path, &content) {
+                    Preprocessed::Done(Ok(output)) => content = output,
+                    Preprocessed::Done(Err(e)) => self.preprocess_error = Some(e),
+                    Preprocessed::Running => self.preprocessing = true,
+                }
+            }
+            if self.substitute_variables {
+                content = frontmatter::substitute(&content).into_owned();
+            }
+            if self.apply_filters {
+                let filters = Rc::new(Filters::for_document(path, self.folder_filters));
+                if let Cow::Owned(filtered) = filters.apply(&content) {
+                    content = filtered;
+                    self.filtered = true;
+                }
+                self.markdown_renderer.set_filters(Some(filters));
+            } else {
+                self.markdown_renderer.set_filters(None);
+            }
+            content = critic::apply(&content, self.critic_mode).into_owned();
+        }
+        self.content = content;
+        self.text_stats = TextStats::of(&self.content);
+        // Search the new content again so a reload keeps the search going
+        self.perform_search();
+        self.generate_toc(); // Generate TOC when loading new file
+    }
+
     /// Generates the table of contents by parsing markdown headers.
     ///
     /// Scans through the document content and extracts all heading elements
@@ -143,11 +1123,13 @@ impl MarkdownReaderApp {
         use pulldown_cmark::{Event, Parser, Tag, TagEnd};
 
         self.toc_headers.clear();
-        let parser = Parser::new(&self.content);
+        // Parse like the renderer so headings match the ones drawn
+        let parser = Parser::new_ext(&self.content, MarkdownRenderer::parser_options());
         let mut current_header: Option<(u8, String)> = None;
         let mut line_number = 0;
+        let mut slug_counts: HashMap<String, usize> = HashMap::new();
 
-        for event in parser {
+        for (event, range) in parser.into_offset_iter() {
             match event {
                 Event::Start(Tag::Heading { level, .. }) => {
                     let level_num = match level {
@@ -159,6 +1141,7 @@ impl MarkdownReaderApp {
                         pulldown_cmark::HeadingLevel::H6 => 6,
                     };
                     current_header = Some((level_num, String::new()));
+                    line_number = self.content[..range.start].matches('\n').count();
                 }
                 Event::End(TagEnd::Heading(_)) => {
                     if let Some((level, title)) = current_header.take() {
@@ -166,6 +1149,7 @@ impl MarkdownReaderApp {
                             self.toc_headers.push(TocHeader {
                                 level,
                                 title: title.trim().to_string(),
+                                slug: unique_slug(&title, &mut slug_counts),
                                 line_number,
                             });
                         }
@@ -181,9 +1165,6 @@ impl MarkdownReaderApp {
                         title.push_str(&text);
                     }
                 }
-                Event::SoftBreak | Event::HardBreak => {
-                    line_number += 1;
-                }
                 _ => {}
             }
         }
@@ -201,81 +1182,1072 @@ impl MarkdownReaderApp {
             return;
         }
 
-        let query = if self.search_case_sensitive {
-            self.search_query.clone()
-        } else {
-            self.search_query.to_lowercase()
-        };
-
+        let scope = search_scope_ranges(&self.content, self.search_scope);
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(self.content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
         for (line_number, line) in self.content.lines().enumerate() {
-            let search_line = if self.search_case_sensitive {
-                line.to_string()
-            } else {
-                line.to_lowercase()
-            };
-
-            let mut start = 0;
-            while let Some(pos) = search_line[start..].find(&query) {
-                let match_start = start + pos;
-                let match_end = match_start + query.len();
-
+            let offset = line_starts[line_number];
+            for range in search::find_matches(
+                line,
+                &self.search_query,
+                self.search_case_sensitive,
+                self.search_fuzzy,
+            ) {
+                if scope
+                    .as_ref()
+                    .is_some_and(|ranges| !search::in_ranges(ranges, offset + range.start))
+                {
+                    continue;
+                }
                 self.search_results.push(SearchResult {
                     line_number,
                     line_content: line.to_string(),
-                    match_start,
-                    match_end,
+                    match_start: range.start,
+                    match_end: range.end,
                 });
-
-                start = match_end;
             }
         }
+
+        // Start from the reading position rather than the top of the document
+        let start_line = match self.search_origin {
+            Some(origin) => self.layout.line_at(origin),
+            None => self.viewport_line(),
+        };
+        if let Some(line) = start_line {
+            self.current_search_index = self
+                .search_results
+                .iter()
+                .position(|result| result.line_number >= line)
+                .unwrap_or(0);
+        }
     }
 
-    /// Moves to the next search result in the list.
+    /// First source line of the block at the top of the view.
+    fn viewport_line(&self) -> Option<usize> {
+        self.layout.line_at(self.scroll_offset)
+    }
+
+    /// Whether the current search result is within the visible part of the document.
+    fn search_result_visible(&self) -> bool {
+        self.search_results
+            .get(self.current_search_index)
+            .and_then(|result| self.layout.offset_for_line(result.line_number))
+            .is_some_and(|offset| {
+                offset >= self.scroll_offset && offset < self.scroll_offset + self.viewport_height
+            })
+    }
+
+    /// Moves to the next search result.
+    ///
+    /// Steps from the current result while it is in view; after scrolling away, goes to
+    /// the first result below the top of the view instead.
     pub fn next_search_result(&mut self) {
-        if !self.search_results.is_empty() {
-            self.current_search_index = (self.current_search_index + 1) % self.search_results.len();
+        if self.search_results.is_empty() {
+            return;
         }
+        let count = self.search_results.len();
+        self.current_search_index = match self.viewport_line() {
+            Some(line) if !self.search_result_visible() => self
+                .search_results
+                .iter()
+                .position(|result| result.line_number >= line)
+                .unwrap_or(0),
+            _ => (self.current_search_index + 1) % count,
+        };
+        self.scroll_to_search_result();
     }
 
-    /// Moves to the previous search result in the list.
+    /// Moves to the previous search result.
+    ///
+    /// Steps from the current result while it is in view; after scrolling away, goes to
+    /// the last result above the top of the view instead.
     pub fn previous_search_result(&mut self) {
-        if !self.search_results.is_empty() {
-            self.current_search_index = if self.current_search_index == 0 {
-                self.search_results.len() - 1
-            } else {
-                self.current_search_index - 1
-            };
+        if self.search_results.is_empty() {
+            return;
         }
+        let count = self.search_results.len();
+        self.current_search_index = match self.viewport_line() {
+            Some(line) if !self.search_result_visible() => self
+                .search_results
+                .iter()
+                .rposition(|result| result.line_number < line)
+                .unwrap_or(count - 1),
+            _ => (self.current_search_index + count - 1) % count,
+        };
+        self.scroll_to_search_result();
     }
 
-    fn show_menu_bar(&mut self, ctx: &Context) {
-        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
-            egui::menu::bar(ui, |ui| {
-                ui.menu_button("File", |ui| {
-                    if ui.button("Open").clicked() {
-                        self.show_open_dialog = true;
-                        ui.close_menu();
-                    }
-                    if ui.button("Quit").clicked() {
-                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
-                    }
-                });
+    /// Scrolls so the current search result is centered in the view, regardless of the
+    /// jump position, so the text around the match stays visible on both sides.
+    fn scroll_to_search_result(&mut self) {
+        if let Some(offset) = self
+            .search_results
+            .get(self.current_search_index)
+            .and_then(|result| self.layout.offset_for_line(result.line_number))
+        {
+            let target = (offset - self.viewport_height / 2.0).clamp(0.0, self.max_scroll_offset);
+            self.animate_scroll_to(target);
+        }
+    }
 
-                ui.menu_button("View", |ui| {
-                    ui.horizontal(|ui| {
-                        ui.label("Font Size:");
-                        if ui.button("➖").clicked() {
-                            self.font_size = (self.font_size - 2.0).max(8.0);
-                            self.markdown_renderer.set_font_size(self.font_size);
-                        }
-                        ui.label(format!("{:.0}", self.font_size));
-                        if ui.button("➕").clicked() {
-                            self.font_size = (self.font_size + 2.0).min(32.0);
-                            self.markdown_renderer.set_font_size(self.font_size);
-                        }
-                    });
-                    ui.separator();
+    /// Where jump targets are placed, as a distance from the top of the view.
+    fn anchor_position(&self) -> f32 {
+        if self.center_jump_target {
+            self.viewport_height / 2.0
+        } else {
+            self.scroll_anchor
+        }
+    }
+
+    /// Scrolls so a document offset lands at the jump anchor position.
+    fn scroll_to(&mut self, offset: f32) {
+        let target = (offset - self.anchor_position()).clamp(0.0, self.max_scroll_offset);
+        self.animate_scroll_to(target);
+    }
+
+    /// Scrolls the document view to an offset, animating the jump unless animations
+    /// are off.
+    fn animate_scroll_to(&mut self, target: f32) {
+        if self.reduce_motion || self.scroll_animation_ms == 0 {
+            self.scroll_animation = None;
+            self.pending_scroll_offset = Some(target);
+        } else {
+            self.scroll_animation = Some(ScrollAnimation {
+                from: self.scroll_offset,
+                to: target,
+                start: f64::NAN,
+            });
+        }
+    }
+
+    /// Advances the animated jump, if any, by setting this frame's scroll offset.
+    fn step_scroll_animation(&mut self, ctx: &Context) {
+        let Some(animation) = &mut self.scroll_animation else {
+            return;
+        };
+        // Scrolling by hand, or any other scroll request, takes over from the animation
+        let user_scrolled = ctx.input(|i| i.raw_scroll_delta != Vec2::ZERO);
+        if user_scrolled || self.pending_scroll_offset.is_some() {
+            self.scroll_animation = None;
+            return;
+        }
+        let now = ctx.input(|i| i.time);
+        if animation.start.is_nan() {
+            animation.start = now;
+        }
+        let duration = self.scroll_animation_ms as f64 / 1000.0;
+        let progress = ((now - animation.start) / duration).clamp(0.0, 1.0) as f32;
+        // Ease out, so the view slows down as it reaches the target
+        let eased = 1.0 - (1.0 - progress).powi(3);
+        self.pending_scroll_offset = Some(animation.from + (animation.to - animation.from) * eased);
+        if progress < 1.0 {
+            ctx.request_repaint();
+        } else {
+            self.scroll_animation = None;
+        }
+    }
+
+    /// Turns egui's own animations (panels, collapsing headers, ...) off while reduce
+    /// motion is on.
+    fn apply_reduce_motion(&mut self, ctx: &Context) {
+        let animation_time = if self.reduce_motion {
+            0.0
+        } else {
+            Style::default().animation_time
+        };
+        ctx.style_mut(|style| style.animation_time = animation_time);
+        if self.reduce_motion {
+            self.scroll_animation = None;
+        }
+    }
+
+    /// Index of the TOC entry of the section at the top of the viewport.
+    fn current_toc_header(&self) -> Option<usize> {
+        let top = self.scroll_offset + self.anchor_position() + 1.0;
+        self.toc_headers.iter().rposition(|header| {
+            self.layout
+                .offset_for_line(header.line_number)
+                .is_some_and(|offset| offset <= top)
+        })
+    }
+
+    /// Scrolls to the first heading below the top of the viewport.
+    pub fn next_heading(&mut self) {
+        if let Some(&offset) = self
+            .layout
+            .heading_tops
+            .iter()
+            .find(|&&offset| offset > self.scroll_offset + self.anchor_position() + 1.0)
+        {
+            self.scroll_to(offset);
+        }
+    }
+
+    /// Scrolls to the last heading above the top of the viewport.
+    pub fn previous_heading(&mut self) {
+        if let Some(&offset) = self
+            .layout
+            .heading_tops
+            .iter()
+            .rev()
+            .find(|&&offset| offset < self.scroll_offset + self.anchor_position() - 1.0)
+        {
+            self.scroll_to(offset);
+        }
+    }
+
+    /// Finds the heading an anchor points at. Anchors are matched against heading slugs,
+    /// then against the slug of the anchor itself so hand-written anchors like
+    /// `#Getting Started` work too.
+    fn anchor_heading(&self, fragment: &str) -> Option<&TocHeader> {
+        let wanted = slugify(fragment);
+        self.toc_headers
+            .iter()
+            .find(|header| header.slug == fragment)
+            .or_else(|| self.toc_headers.iter().find(|header| header.slug == wanted))
+    }
+
+    /// Scrolls to the heading an anchor points at, once the document is laid out if it
+    /// hasn't been drawn yet. Returns false if no heading matches.
+    pub fn scroll_to_anchor(&mut self, fragment: &str) -> bool {
+        let Some(header) = self.anchor_heading(fragment) else {
+            return false;
+        };
+        let (line, slug) = (header.line_number, header.slug.clone());
+        match self.layout.offset_for_line(line) {
+            Some(offset) => self.scroll_to(offset),
+            None => self.scroll_to_header = Some(slug),
+        }
+        true
+    }
+
+    /// Follows an in-document `#fragment` link, recording the jump in the history.
+    fn jump_to_anchor(&mut self, fragment: &str) {
+        if self.anchor_heading(fragment).is_some() {
+            self.remember_location();
+            self.scroll_to_anchor(fragment);
+        }
+    }
+
+    /// The document and scroll position being shown.
+    fn current_location(&self) -> Option<Location> {
+        Some(Location {
+            file: self.current_file.clone()?,
+            scroll_offset: self.scroll_offset,
+        })
+    }
+
+    /// Records the current position before jumping elsewhere in the document.
+    fn remember_location(&mut self) {
+        if let Some(location) = self.current_location() {
+            self.history.visit(location);
+        }
+    }
+
+    /// Returns to the previous location in the navigation history.
+    pub fn go_back(&mut self) {
+        let Some(from) = self.current_location() else {
+            return;
+        };
+        if let Some(location) = self.history.back(from) {
+            self.show_location(location);
+        }
+    }
+
+    /// Goes forward again after going back.
+    pub fn go_forward(&mut self) {
+        let Some(from) = self.current_location() else {
+            return;
+        };
+        if let Some(location) = self.history.forward(from) {
+            self.show_location(location);
+        }
+    }
+
+    fn show_location(&mut self, location: Location) {
+        if self.current_file.as_ref() != Some(&location.file) {
+            if let Err(e) = self.open_file(location.file) {
+                eprintln!("Error loading file: {e}");
+                return;
+            }
+        }
+        self.pending_scroll_offset = Some(location.scroll_offset);
+    }
+
+    fn open_goto_dialog(&mut self) {
+        self.show_goto = true;
+        self.goto_input.clear();
+        self.goto_error = None;
+    }
+
+    /// Jumps to a 1-based source line number, a percentage of the document (`70%`),
+    /// or a heading given by slug or title.
+    pub fn go_to(&mut self, target: &str) -> Result<(), String> {
+        let target = target.trim();
+        if let Some(percent) = target.strip_suffix('%') {
+            let percent = percent
+                .trim()
+                .parse::<f32>()
+                .ok()
+                .filter(|percent| percent.is_finite())
+                .ok_or_else(|| format!("\"{target}\" is not a valid percentage"))?;
+            self.remember_location();
+            self.animate_scroll_to(self.max_scroll_offset * percent.clamp(0.0, 100.0) / 100.0);
+            return Ok(());
+        }
+
+        let line = if let Ok(line) = target.parse::<usize>() {
+            line.saturating_sub(1)
+        } else {
+            let wanted = slugify(target.trim_start_matches('#'));
+            if wanted.is_empty() {
+                return Err("Enter a line number or heading".to_string());
+            }
+            self.toc_headers
+                .iter()
+                .find(|header| header.slug == wanted)
+                .or_else(|| {
+                    self.toc_headers
+                        .iter()
+                        .find(|header| header.slug.starts_with(&wanted))
+                })
+                .map(|header| header.line_number)
+                .ok_or_else(|| format!("No heading matches \"{target}\""))?
+        };
+
+        let offset = self
+            .layout
+            .offset_for_line(line)
+            .ok_or_else(|| "Nothing to jump to".to_string())?;
+        self.remember_location();
+        self.scroll_to(offset);
+        Ok(())
+    }
+
+    /// Bookmarks the block at the jump anchor position, named after its section.
+    fn add_bookmark(&mut self) {
+        let Some(path) = self.current_file.clone() else {
+            return;
+        };
+        let Some(line) = self
+            .layout
+            .line_at(self.scroll_offset + self.anchor_position())
+        else {
+            return;
+        };
+        let name = match self.current_toc_header() {
+            Some(index) => self.toc_headers[index].title.clone(),
+            None => format!("Line {}", line + 1),
+        };
+        self.bookmarks.add(&path, Bookmark { name, line });
+        self.show_bookmarks = true;
+    }
+
+    /// Shows the bookmarks of the current document, to jump to, rename or remove them.
+    fn show_bookmarks_window(&mut self, ctx: &Context) {
+        let mut open = self.show_bookmarks;
+        egui::Window::new("Bookmarks")
+            .open(&mut open)
+            .default_width(300.0)
+            .show(ctx, |ui| {
+                let Some(path) = self.current_file.clone() else {
+                    ui.weak("No document open");
+                    return;
+                };
+                if ui.button("Add Bookmark (Ctrl+D)").clicked() {
+                    self.add_bookmark();
+                }
+                ui.separator();
+
+                let bookmarks = self.bookmarks.for_document(&path).to_vec();
+                if bookmarks.is_empty() {
+                    ui.weak("No bookmarks in this document");
+                }
+                let mut jump = None;
+                let mut remove = None;
+                for (index, bookmark) in bookmarks.into_iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        if ui
+                            .small_button("↪")
+                            .on_hover_text(format!("Jump to line {}", bookmark.line + 1))
+                            .clicked()
+                        {
+                            jump = Some(bookmark.line);
+                        }
+                        let mut name = bookmark.name;
+                        let response =
+                            ui.add(egui::TextEdit::singleline(&mut name).desired_width(200.0));
+                        if response.changed() {
+                            self.bookmarks.rename(&path, index, name);
+                        }
+                        if response.lost_focus() {
+                            self.bookmarks.save();
+                        }
+                        if ui.small_button("✕").on_hover_text("Remove").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                }
+                if let Some(index) = remove {
+                    self.bookmarks.remove(&path, index);
+                }
+                if let Some(offset) = jump.and_then(|line| self.layout.offset_for_line(line)) {
+                    self.remember_location();
+                    self.scroll_to(offset);
+                }
+            });
+        self.show_bookmarks = open;
+    }
+
+    /// Turns the selected text into a highlight or note, when asked with Ctrl+Shift+H /
+    /// Ctrl+Shift+N or from a paragraph's context menu. Reading the selection takes a
+    /// frame, so this runs before the document is drawn.
+    fn handle_annotation_requests(&mut self, ctx: &Context) {
+        let Some(reader) = &self.selection_reader else {
+            return;
+        };
+        let (highlight, note) = ctx.input(|i| {
+            let chord = i.modifiers.command && i.modifiers.shift;
+            (
+                chord && i.key_pressed(egui::Key::H),
+                chord && i.key_pressed(egui::Key::N),
+            )
+        });
+        if let Some(quote) = reader.take() {
+            self.selected_quote = Some(quote);
+        }
+        let mut reading = false;
+        if highlight || note || self.markdown_renderer.take_selection_wanted() {
+            self.selected_quote = None;
+            reading = reader.request(ctx);
+        }
+        if highlight {
+            self.annotation_request = Some(AnnotationKind::Highlight);
+        } else if note {
+            self.annotation_request = Some(AnnotationKind::Note);
+        }
+        if let Some(kind) = self.markdown_renderer.take_annotation_request() {
+            self.annotation_request = Some(kind);
+        }
+
+        let Some(kind) = self.annotation_request else {
+            return;
+        };
+        match self.selected_quote.take() {
+            Some(quote) if self.current_file.is_some() => {
+                self.annotation_request = None;
+                let index = self.annotations.add(quote, kind);
+                self.markdown_renderer
+                    .set_annotations(self.annotations.items.clone());
+                if kind == AnnotationKind::Note {
+                    self.show_annotations = true;
+                    self.focused_annotation = Some(index);
+                }
+            }
+            // Nothing was selected
+            _ if !reading => self.annotation_request = None,
+            _ => {}
+        }
+    }
+
+    /// Lists the highlights and notes of the current document, to edit the notes or
+    /// remove them.
+    fn show_annotations_window(&mut self, ctx: &Context) {
+        let mut open = self.show_annotations;
+        let focus = self.focused_annotation.take();
+        egui::Window::new("Annotations")
+            .open(&mut open)
+            .default_width(340.0)
+            .show(ctx, |ui| {
+                if self.current_file.is_none() {
+                    ui.weak("No document open");
+                    return;
+                }
+                ui.weak("Select text, then right-click it or press Ctrl+Shift+H to highlight it, Ctrl+Shift+N to add a note");
+                ui.separator();
+                if self.annotations.items.is_empty() {
+                    ui.weak("No highlights in this document");
+                }
+
+                let mut remove = None;
+                let mut changed = false;
+                let mut save = false;
+                egui::ScrollArea::vertical()
+                    .max_height(400.0)
+                    .show(ui, |ui| {
+                        for (index, annotation) in self.annotations.items.iter_mut().enumerate() {
+                            ui.horizontal(|ui| {
+                                if ui.small_button("✕").on_hover_text("Remove").clicked() {
+                                    remove = Some(index);
+                                }
+                                let quote = annotation.quote.trim().replace('\n', " ");
+                                ui.add(
+                                    egui::Label::new(RichText::new(format!("“{quote}”")).italics())
+                                        .truncate(),
+                                )
+                                .on_hover_text(annotation.quote.trim());
+                            });
+                            match &mut annotation.note {
+                                Some(note) => {
+                                    let response = ui.add(
+                                        egui::TextEdit::multiline(note)
+                                            .hint_text("Note")
+                                            .desired_rows(2)
+                                            .desired_width(f32::INFINITY),
+                                    );
+                                    if focus == Some(index) {
+                                        response.request_focus();
+                                        response.scroll_to_me(None);
+                                    }
+                                    changed |= response.changed();
+                                    save |= response.lost_focus();
+                                }
+                                None => {
+                                    if ui.small_button("Add Note").clicked() {
+                                        annotation.note = Some(String::new());
+                                        changed = true;
+                                        save = true;
+                                    }
+                                }
+                            }
+                            ui.separator();
+                        }
+                    });
+                if let Some(index) = remove {
+                    self.annotations.remove(index);
+                    changed = true;
+                } else if save {
+                    self.annotations.save();
+                }
+                if changed {
+                    self.markdown_renderer
+                        .set_annotations(self.annotations.items.clone());
+                }
+            });
+        if !open {
+            // Keep a note being typed when the panel is closed
+            self.annotations.save();
+        }
+        self.show_annotations = open;
+    }
+
+    fn show_goto_dialog(&mut self, ctx: &Context) {
+        egui::Window::new("Go to")
+            .collapsible(false)
+            .resizable(false)
+            .title_bar(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 60.0])
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Go to:");
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.goto_input)
+                            .hint_text("Line, percentage or heading")
+                            .desired_width(240.0),
+                    );
+                    response.request_focus();
+
+                    if response.changed() {
+                        self.goto_error = None;
+                    }
+
+                    if ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        let input = self.goto_input.clone();
+                        match self.go_to(&input) {
+                            Ok(()) => self.show_goto = false,
+                            Err(e) => self.goto_error = Some(e),
+                        }
+                    }
+                });
+
+                if let Some(error) = &self.goto_error {
+                    ui.colored_label(ui.visuals().warn_fg_color, error);
+                } else if self.max_scroll_offset > 0.0 {
+                    ui.weak(format!(
+                        "Currently {:.0}% through the document",
+                        self.scroll_offset / self.max_scroll_offset * 100.0
+                    ));
+                }
+                if self.content != self.source {
+                    ui.weak(
+                        "Line numbers count lines of the rendered document,\nafter conversion, preprocessing and filters",
+                    );
+                }
+            });
+    }
+
+    fn show_commands_menu(&mut self, ui: &mut Ui) {
+        if self.command_hooks.is_empty() {
+            let path = storage::config_dir()
+                .map(|dir| dir.join(hooks::HOOKS_FILE).display().to_string())
+                .unwrap_or_else(|| hooks::HOOKS_FILE.to_owned());
+            ui.label(format!("No commands configured.\nAdd them to {path}"));
+        }
+        for hook in &self.command_hooks {
+            let needs_selection = hooks::uses_selection(hook);
+            let enabled = !needs_selection || !self.last_copied.is_empty();
+            let response = ui
+                .add_enabled(enabled, egui::Button::new(&hook.name))
+                .on_hover_text(&hook.command)
+                .on_disabled_hover_text("Copy a selection (Ctrl+C) first");
+            if response.clicked() {
+                self.hook_run = Some(HookRun::start(
+                    hook,
+                    self.current_file.as_deref(),
+                    &self.last_copied,
+                ));
+                ui.close_menu();
+            }
+        }
+        ui.separator();
+        if ui.button("Reload Commands").clicked() {
+            self.command_hooks = hooks::load();
+            ui.close_menu();
+        }
+    }
+
+    fn show_command_output(&mut self, ctx: &Context) {
+        let Some(run) = self.hook_run.as_mut() else {
+            return;
+        };
+        if run.poll() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+        let mut open = true;
+        egui::Window::new(format!("Output: {}", run.name))
+            .id(egui::Id::new("command_output"))
+            .open(&mut open)
+            .default_width(500.0)
+            .show(ctx, |ui| match run.output() {
+                None => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Running…");
+                    });
+                }
+                Some(Err(error)) => {
+                    ui.colored_label(ui.visuals().warn_fg_color, error);
+                }
+                Some(Ok(output)) => {
+                    if ui.button("Copy").clicked() {
+                        ui.output_mut(|o| o.copied_text = output.clone());
+                    }
+                    egui::ScrollArea::vertical()
+                        .max_height(400.0)
+                        .show(ui, |ui| {
+                            ui.add(
+                                egui::Label::new(egui::RichText::new(output).monospace())
+                                    .selectable(true),
+                            );
+                        });
+                }
+            });
+        if !open {
+            self.hook_run = None;
+        }
+    }
+
+    /// Starts reading the document aloud from the top of the view.
+    fn start_read_aloud(&mut self) {
+        let line = self.layout.line_at(self.scroll_offset).unwrap_or(0);
+        self.speaker.start(speech::sentences(&self.content), line);
+        self.spoken_line = None;
+    }
+
+    /// Shows the read-aloud controls and the sentence being spoken.
+    fn show_read_aloud_bar(&mut self, ctx: &Context) {
+        egui::TopBottomPanel::bottom("read_aloud").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("⏮").on_hover_text("Previous section").clicked() {
+                    self.speaker.previous_section();
+                }
+                let play_label = if self.speaker.is_paused() {
+                    "▶"
+                } else {
+                    "⏸"
+                };
+                if ui
+                    .button(play_label)
+                    .on_hover_text("Play / pause")
+                    .clicked()
+                {
+                    self.speaker.toggle_pause();
+                }
+                if ui.button("⏭").on_hover_text("Next section").clicked() {
+                    self.speaker.next_section();
+                }
+                if ui.button("⏹").on_hover_text("Stop").clicked() {
+                    self.speaker.stop();
+                }
+                ui.separator();
+                if let Some(sentence) = self.speaker.current() {
+                    let text = egui::RichText::new(&sentence.text)
+                        .background_color(ui.visuals().selection.bg_fill);
+                    ui.add(egui::Label::new(text).truncate());
+                } else if let Some(error) = self.speaker.error() {
+                    ui.colored_label(ui.visuals().warn_fg_color, error);
+                    if ui.button("Dismiss").clicked() {
+                        self.speaker = Speaker::default();
+                    }
+                }
+            });
+        });
+    }
+
+    /// Scrolls the sentence being read aloud into view when reading moves to a new block.
+    fn follow_read_aloud(&mut self) {
+        let Some(line) = self.speaker.current().map(|sentence| sentence.line) else {
+            return;
+        };
+        if self.spoken_line == Some(line) {
+            return;
+        }
+        self.spoken_line = Some(line);
+        if let Some(offset) = self.layout.offset_for_line(line) {
+            let visible = self.scroll_offset..self.scroll_offset + self.viewport_height * 0.8;
+            if !visible.contains(&offset) {
+                self.pending_scroll_offset = Some(offset.min(self.max_scroll_offset));
+            }
+        }
+    }
+
+    /// Switches body text to (or back from) the dyslexia-friendly font, widening the
+    /// letter spacing while it is active.
+    fn set_accessible_font(&mut self, ctx: &Context, enabled: bool) {
+        self.font_error = fonts::install(ctx, enabled, &self.font_choice).err();
+        self.accessible_font = enabled && self.font_error.is_none();
+        self.markdown_renderer
+            .set_letter_spacing(if self.accessible_font { 1.5 } else { 0.0 });
+    }
+
+    /// Window for choosing the body and code fonts, previewing the choice live.
+    fn show_font_settings_window(&mut self, ctx: &Context) {
+        let mut open = self.show_font_settings;
+        let mut changed = false;
+        egui::Window::new("Fonts")
+            .open(&mut open)
+            .default_width(360.0)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Add Font File…").clicked() {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Fonts", &["ttf", "otf", "TTF", "OTF"])
+                            .pick_file()
+                        {
+                            if !self.font_choice.custom.contains(&path) {
+                                self.font_choice.custom.push(path.clone());
+                            }
+                            self.font_choice.body = Some(path);
+                            changed = true;
+                        }
+                    }
+                    ui.weak("or copy fonts into the mdzen fonts folder");
+                });
+                let installed: Vec<PathBuf> = self
+                    .font_choice
+                    .custom
+                    .iter()
+                    .chain(&self.installed_fonts)
+                    .cloned()
+                    .collect();
+                egui::Grid::new("font_settings")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        for (label, choice) in [
+                            ("Body", &mut self.font_choice.body),
+                            ("Code", &mut self.font_choice.monospace),
+                        ] {
+                            ui.label(label);
+                            let selected = choice
+                                .as_deref()
+                                .map_or("Built-in".to_string(), fonts::display_name);
+                            egui::ComboBox::from_id_source(label)
+                                .selected_text(selected)
+                                .width(240.0)
+                                .show_ui(ui, |ui| {
+                                    changed |=
+                                        ui.selectable_value(choice, None, "Built-in").changed();
+                                    for font in &installed {
+                                        changed |= ui
+                                            .selectable_value(
+                                                choice,
+                                                Some(font.clone()),
+                                                fonts::display_name(font),
+                                            )
+                                            .changed();
+                                    }
+                                });
+                            ui.end_row();
+                        }
+                    });
+                if self.accessible_font {
+                    ui.weak("The dyslexia-friendly font replaces the body font while enabled.");
+                }
+                if let Some(error) = &self.font_error {
+                    ui.colored_label(ui.visuals().warn_fg_color, error);
+                }
+
+                ui.separator();
+                let size = self.font_size;
+                ui.label(
+                    egui::RichText::new("The quick brown fox jumps over the lazy dog.")
+                        .font(egui::FontId::new(size, fonts::body_family())),
+                );
+                ui.label(
+                    egui::RichText::new("fn main() { println!(\"0O 1lI\"); }")
+                        .font(egui::FontId::monospace(size * 0.9)),
+                );
+            });
+        self.show_font_settings = open;
+        if changed {
+            self.set_accessible_font(ctx, self.accessible_font);
+        }
+    }
+
+    /// Restores the preferences saved by a previous session.
+    fn apply_preferences(&mut self, ctx: &Context, preferences: Preferences) {
+        self.theme_name = preferences.theme;
+        self.code_theme = preferences.code_theme;
+        self.markdown_renderer
+            .set_code_theme(self.code_theme.clone());
+        self.plantuml_server_input = preferences.plantuml_server.clone();
+        *self.plantuml_server.borrow_mut() = preferences.plantuml_server;
+        self.code_theme_dark = preferences.code_theme_dark;
+        self.code_theme_light = preferences.code_theme_light;
+        self.markdown_renderer
+            .set_automatic_code_themes(self.code_theme_dark.clone(), self.code_theme_light.clone());
+        self.search_case_sensitive = preferences.search_case_sensitive;
+        self.markdown_renderer
+            .set_case_sensitive_search(self.search_case_sensitive);
+        self.search_fuzzy = preferences.search_fuzzy;
+        self.markdown_renderer.set_fuzzy_search(self.search_fuzzy);
+        self.night_light = preferences.night_light;
+        self.browse_siblings = preferences.browse_siblings;
+        self.folder_filters = preferences.folder_filters;
+        self.wrap_code = preferences.wrap_code;
+        self.markdown_renderer.set_wrap_code(self.wrap_code);
+        self.highlight_marks = preferences.highlight_marks;
+        self.show_status_bar = preferences.show_status_bar;
+        self.show_scratchpad = preferences.show_scratchpad;
+        self.markdown_renderer
+            .set_highlight_marks(self.highlight_marks);
+        self.image_max_width = preferences.image_max_width;
+        self.image_max_height = preferences.image_max_height;
+        self.scroll_animation_ms = preferences.scroll_animation_ms;
+        self.reduce_motion = preferences.reduce_motion;
+        self.apply_reduce_motion(ctx);
+        self.markdown_renderer
+            .set_image_limits(self.image_max_width, self.image_max_height);
+        self.apply_theme(ctx);
+        self.font_choice = preferences.fonts;
+        if preferences.accessible_font || self.font_choice != FontChoice::default() {
+            self.set_accessible_font(ctx, preferences.accessible_font);
+        }
+    }
+
+    fn current_preferences(&self) -> Preferences {
+        Preferences {
+            theme: self.theme_name.clone(),
+            search_case_sensitive: self.search_case_sensitive,
+            search_fuzzy: self.search_fuzzy,
+            night_light: self.night_light.clone(),
+            accessible_font: self.accessible_font,
+            browse_siblings: self.browse_siblings,
+            folder_filters: self.folder_filters,
+            wrap_code: self.wrap_code,
+            highlight_marks: self.highlight_marks,
+            show_status_bar: self.show_status_bar,
+            show_scratchpad: self.show_scratchpad,
+            code_theme: self.code_theme.clone(),
+            code_theme_dark: self.code_theme_dark.clone(),
+            code_theme_light: self.code_theme_light.clone(),
+            plantuml_server: self.plantuml_server.borrow().clone(),
+            fonts: self.font_choice.clone(),
+            image_max_width: self.image_max_width,
+            image_max_height: self.image_max_height,
+            scroll_animation_ms: self.scroll_animation_ms,
+            reduce_motion: self.reduce_motion,
+        }
+    }
+
+    /// The color scheme shown: the one given on the command line, else the selected one,
+    /// or the first one if it no longer exists.
+    fn color_scheme(&self) -> &ColorScheme {
+        let name = self.startup_theme.as_ref().unwrap_or(&self.theme_name);
+        self.color_schemes
+            .iter()
+            .find(|scheme| scheme.name == *name)
+            .unwrap_or(&self.color_schemes[0])
+    }
+
+    /// Applies the selected color scheme, warmed by the current night-light strength.
+    fn apply_theme(&mut self, ctx: &Context) {
+        let warmth = self.night_light.current_strength();
+        let scheme = self.color_scheme().clone();
+        let mut visuals = scheme.visuals();
+        theme::warm_visuals(&mut visuals, warmth);
+        ctx.set_visuals(visuals);
+        self.markdown_renderer.set_color_scheme(&scheme);
+        self.markdown_renderer.set_warmth(warmth);
+        self.applied_warmth = warmth;
+    }
+
+    /// Re-applies the visuals when the night-light strength changes (settings or schedule).
+    fn apply_night_light(&mut self, ctx: &Context) {
+        if self.night_light.current_strength() != self.applied_warmth {
+            self.apply_theme(ctx);
+        }
+        if self.night_light.enabled && self.night_light.scheduled {
+            // Wake up periodically so the schedule takes effect without user input
+            ctx.request_repaint_after(std::time::Duration::from_secs(60));
+        }
+    }
+
+    fn show_stats_window(&mut self, ctx: &Context) {
+        let mut open = self.show_stats;
+        egui::Window::new("Reading Statistics")
+            .open(&mut open)
+            .default_width(320.0)
+            .resizable(false)
+            .show(ctx, |ui| {
+                match (&self.current_file, self.reading_stats.current()) {
+                    (Some(file), Some(stats)) => {
+                        ui.strong(
+                            file.file_name()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .to_string(),
+                        );
+                        egui::Grid::new("current_stats")
+                            .num_columns(2)
+                            .spacing([20.0, 4.0])
+                            .show(ui, |ui| {
+                                ui.label("This session");
+                                ui.label(format_duration(self.reading_stats.session_seconds()));
+                                ui.end_row();
+                                ui.label("Total");
+                                ui.label(format_duration(stats.total_seconds));
+                                ui.end_row();
+                                ui.label("Sessions");
+                                ui.label(stats.sessions.to_string());
+                                ui.end_row();
+                            });
+                    }
+                    _ => {
+                        ui.weak("No document open");
+                    }
+                }
+
+                ui.separator();
+                ui.label("Most read");
+                egui::Grid::new("most_read")
+                    .num_columns(2)
+                    .spacing([20.0, 4.0])
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (path, stats) in self.reading_stats.most_read().into_iter().take(10) {
+                            ui.label(path.file_name().unwrap_or_default().to_string_lossy())
+                                .on_hover_text(path.display().to_string());
+                            ui.label(format_duration(stats.total_seconds));
+                            ui.end_row();
+                        }
+                    });
+
+                ui.separator();
+                if ui
+                    .checkbox(
+                        &mut self.reading_stats.persist,
+                        "Remember totals across sessions",
+                    )
+                    .changed()
+                {
+                    self.reading_stats.save();
+                }
+                if ui.button("Reset statistics").clicked() {
+                    self.reading_stats.reset();
+                    self.reading_stats.save();
+                }
+            });
+        self.show_stats = open;
+
+        // Keep the session clock ticking while the window is visible
+        ctx.request_repaint_after(std::time::Duration::from_secs(1));
+    }
+
+    fn show_menu_bar(&mut self, ctx: &Context) {
+        egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("File", |ui| {
+                    if ui.button("Open").clicked() {
+                        self.show_open_dialog = true;
+                        ui.close_menu();
+                    }
+                    if ui.button("Open Folder…").clicked() {
+                        self.show_folder_dialog = true;
+                        ui.close_menu();
+                    }
+                    if self.folder.is_some() {
+                        if ui
+                            .add(egui::Button::new("Switch File…").shortcut_text("Ctrl+P"))
+                            .clicked()
+                        {
+                            self.open_file_switcher();
+                            ui.close_menu();
+                        }
+                        if ui.button("Folder Dashboard").clicked() {
+                            self.dashboard = self
+                                .folder
+                                .as_ref()
+                                .map(|folder| Dashboard::open(folder, ctx));
+                            ui.close_menu();
+                        }
+                        if ui.button("Close Folder").clicked() {
+                            self.folder = None;
+                            self.file_tree = None;
+                            self.folder_watcher = None;
+                            self.dashboard = None;
+                            ui.close_menu();
+                        }
+                    }
+                    if self.opened_files.len() > 1 && ui.button("Opened Files…").clicked() {
+                        self.show_opened_files = true;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.current_file.is_some(),
+                            egui::Button::new("Open in Split View…"),
+                        )
+                        .clicked()
+                    {
+                        self.show_split_dialog = true;
+                        ui.close_menu();
+                    }
+                    if self.split_pane.is_some() && ui.button("Close Split View").clicked() {
+                        self.split_pane = None;
+                        ui.close_menu();
+                    }
+                    if ui.button("Quit").clicked() {
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                    }
+                });
+
+                ui.menu_button("View", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Font Size:");
+                        if ui.button("➖").clicked() {
+                            self.font_size = (self.font_size - 2.0).max(8.0);
+                            self.markdown_renderer.set_font_size(self.font_size);
+                        }
+                        ui.label(format!("{:.0}", self.font_size));
+                        if ui.button("➕").clicked() {
+                            self.font_size = (self.font_size + 2.0).min(32.0);
+                            self.markdown_renderer.set_font_size(self.font_size);
+                        }
+                    });
+                    ui.separator();
                     if ui
                         .button(if self.wide_mode {
                             "Normal Width"
@@ -286,44 +2258,659 @@ impl MarkdownReaderApp {
                     {
                         self.wide_mode = !self.wide_mode;
                     }
+                    if self.folder.is_some() {
+                        ui.checkbox(&mut self.show_file_tree, "Show File Tree");
+                    }
+                    if ui
+                        .checkbox(&mut self.browse_siblings, "Browse Sibling Files")
+                        .on_hover_text(
+                            "List the documents of an opened file's folder in the file tree",
+                        )
+                        .changed()
+                        && self.browse_siblings
+                    {
+                        if let Some(path) = self.current_file.clone() {
+                            self.browse_siblings_of(&path);
+                        }
+                    }
+                    ui.menu_button("TOC Options", |ui| {
+                        let settings = &mut self.view_settings;
+                        ui.checkbox(&mut settings.toc_on_right, "On Right");
+                        ui.checkbox(&mut settings.toc_auto_hide, "Auto-Hide")
+                            .on_hover_text("Slide the TOC in when the pointer touches the window edge");
+                        ui.checkbox(&mut settings.toc_numbering, "Number Sections");
+                        let width = ui.add(
+                            egui::Slider::new(&mut settings.toc_width, TOC_WIDTH_RANGE)
+                                .suffix(" pt")
+                                .text("Width"),
+                        );
+                        if width.changed() {
+                            self.toc_width_changed = true;
+                        }
+                    });
                     if ui
                         .button(if self.show_toc {
                             "Hide TOC"
                         } else {
-                            "Show TOC"
-                        })
-                        .clicked()
-                    {
-                        self.show_toc = !self.show_toc;
-                    }
-                });
+                            "Show TOC"
+                        })
+                        .clicked()
+                    {
+                        self.show_toc = !self.show_toc;
+                    }
+                    if let Some(path) = self.current_file.clone() {
+                        if ui.button("Use as Default View").clicked() {
+                            self.view_settings.defaults = self.current_view_settings();
+                            self.view_settings.forget(&path);
+                            ui.close_menu();
+                        }
+                        if ui
+                            .add_enabled(
+                                self.view_settings.has_document(&path),
+                                egui::Button::new("Reset to Default View"),
+                            )
+                            .clicked()
+                        {
+                            self.view_settings.forget(&path);
+                            self.apply_view_settings(self.view_settings.defaults);
+                            ui.close_menu();
+                        }
+                    }
+                    ui.separator();
+                    if ui
+                        .checkbox(&mut self.accessible_font, "Dyslexia-Friendly Font")
+                        .changed()
+                    {
+                        self.set_accessible_font(ui.ctx(), self.accessible_font);
+                    }
+                    if let Some(error) = &self.font_error {
+                        ui.colored_label(ui.visuals().warn_fg_color, error);
+                    }
+                    if ui.button("Fonts…").clicked() {
+                        self.installed_fonts = fonts::installed_fonts();
+                        self.show_font_settings = true;
+                        ui.close_menu();
+                    }
+                    ui.menu_button("Theme", |ui| {
+                        let mut selected = None;
+                        let current = self.color_scheme().name.clone();
+                        for scheme in &self.color_schemes {
+                            if ui
+                                .selectable_label(scheme.name == current, &scheme.name)
+                                .clicked()
+                            {
+                                selected = Some(scheme.name.clone());
+                                ui.close_menu();
+                            }
+                        }
+                        ui.separator();
+                        if ui
+                            .button("Reload Themes")
+                            .on_hover_text("Re-read themes.toml from the config directory")
+                            .clicked()
+                        {
+                            self.color_schemes = theme::load_schemes();
+                            self.apply_theme(ui.ctx());
+                            ui.close_menu();
+                        }
+                        if let Some(name) = selected {
+                            self.theme_name = name;
+                            self.startup_theme = None;
+                            self.apply_theme(ui.ctx());
+                        }
+                    });
+                    ui.menu_button("Code Theme", |ui| {
+                        let mut selected = None;
+                        if ui
+                            .selectable_label(self.code_theme.is_none(), "Automatic")
+                            .on_hover_text("Use a light or dark theme matching the color scheme")
+                            .clicked()
+                        {
+                            selected = Some(None);
+                            ui.close_menu();
+                        }
+                        let mut paired = None;
+                        ui.menu_button("With Dark Schemes", |ui| {
+                            let themes = self.markdown_renderer.code_themes();
+                            if let Some(name) = code_theme_list(ui, themes, &self.code_theme_dark) {
+                                paired = Some((name, self.code_theme_light.clone()));
+                            }
+                        });
+                        ui.menu_button("With Light Schemes", |ui| {
+                            let themes = self.markdown_renderer.code_themes();
+                            if let Some(name) = code_theme_list(ui, themes, &self.code_theme_light)
+                            {
+                                paired = Some((self.code_theme_dark.clone(), name));
+                            }
+                        });
+                        ui.separator();
+                        let themes = self.markdown_renderer.code_themes();
+                        let current = self.code_theme.as_deref().unwrap_or_default();
+                        if let Some(name) = code_theme_list(ui, themes, current) {
+                            selected = Some(Some(name));
+                        }
+                        if let Some(theme) = selected {
+                            self.code_theme = theme;
+                            self.markdown_renderer
+                                .set_code_theme(self.code_theme.clone());
+                        }
+                        if let Some((dark, light)) = paired {
+                            // Picking a pairing switches back to following the color scheme
+                            self.code_theme = None;
+                            self.markdown_renderer.set_code_theme(None);
+                            self.code_theme_dark = dark;
+                            self.code_theme_light = light;
+                            self.markdown_renderer.set_automatic_code_themes(
+                                self.code_theme_dark.clone(),
+                                self.code_theme_light.clone(),
+                            );
+                        }
+                    });
+                    ui.menu_button("Night Light", |ui| {
+                        ui.checkbox(&mut self.night_light.enabled, "Enabled");
+                        ui.add(
+                            egui::Slider::new(&mut self.night_light.strength, 0.0..=1.0)
+                                .text("Warmth"),
+                        );
+                        ui.checkbox(&mut self.night_light.scheduled, "Only between");
+                        ui.add_enabled_ui(self.night_light.scheduled, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::DragValue::new(&mut self.night_light.start_hour)
+                                        .range(0..=23)
+                                        .suffix(":00"),
+                                );
+                                ui.label("and");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.night_light.end_hour)
+                                        .range(0..=23)
+                                        .suffix(":00"),
+                                );
+                            });
+                        });
+                    });
+                    if ui
+                        .checkbox(&mut self.apply_filters, "Apply Filters")
+                        .on_hover_text("Filter scripts from the filters folder of the config directory")
+                        .changed()
+                    {
+                        self.refresh_content();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.apply_filters,
+                            egui::Checkbox::new(&mut self.folder_filters, "Run Folder Filter Scripts"),
+                        )
+                        .on_hover_text(
+                            "Also run the .mdzen-filter.rhai script found next to documents.\nOnly turn this on for folders you trust",
+                        )
+                        .changed()
+                    {
+                        self.refresh_content();
+                    }
+                    if ui
+                        .checkbox(&mut self.substitute_variables, "Substitute Variables")
+                        .on_hover_text("Replace {{ key }} with front matter values")
+                        .changed()
+                    {
+                        self.refresh_content();
+                    }
+                    if ui
+                        .checkbox(&mut self.wrap_code, "Wrap Code Blocks")
+                        .on_hover_text("Scroll long code lines horizontally when unchecked")
+                        .changed()
+                    {
+                        self.markdown_renderer.set_wrap_code(self.wrap_code);
+                    }
+                    if ui
+                        .checkbox(&mut self.highlight_marks, "==Highlight== Syntax")
+                        .on_hover_text("Render ==text== with a highlight color")
+                        .changed()
+                    {
+                        self.markdown_renderer
+                            .set_highlight_marks(self.highlight_marks);
+                    }
+                    ui.menu_button("Image Size", |ui| {
+                        let width = ui.add(
+                            egui::Slider::new(&mut self.image_max_width, 10.0..=100.0)
+                                .suffix("%")
+                                .text("Max width"),
+                        );
+                        let height = ui.add(
+                            egui::Slider::new(&mut self.image_max_height, 100.0..=2000.0)
+                                .suffix(" pt")
+                                .text("Max height"),
+                        );
+                        if width.changed() || height.changed() {
+                            self.markdown_renderer
+                                .set_image_limits(self.image_max_width, self.image_max_height);
+                        }
+                        ui.weak("Images with a width or height given in the document\nuse that size instead");
+                    });
+                    ui.menu_button("PlantUML Server", |ui| {
+                        let response = ui.add(
+                            egui::TextEdit::singleline(&mut self.plantuml_server_input)
+                                .hint_text(plantuml::SERVER_HINT)
+                                .desired_width(280.0),
+                        );
+                        if response.lost_focus() {
+                            let server = self.plantuml_server_input.trim().to_string();
+                            *self.plantuml_server.borrow_mut() = server;
+                        }
+                        ui.weak(
+                            "Renders ```plantuml blocks; their source is sent to this server.\nLeave empty to show the source instead",
+                        );
+                    });
+                    if ui
+                        .checkbox(&mut self.show_comments, "Show HTML Comments")
+                        .changed()
+                    {
+                        self.markdown_renderer.set_show_comments(self.show_comments);
+                    }
+                    ui.checkbox(&mut self.show_status_bar, "Status Bar")
+                        .on_hover_text("Show word count and reading time");
+                    if ui
+                        .checkbox(&mut self.show_scratchpad, "Scratchpad")
+                        .on_hover_text("Show a panel for notes on the document")
+                        .changed()
+                    {
+                        self.scratchpad.save();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.current_file.is_some(),
+                            egui::Button::new("Present").shortcut_text("F5"),
+                        )
+                        .on_hover_text("Show the document as full-screen slides")
+                        .clicked()
+                    {
+                        self.start_presentation(ui.ctx());
+                        ui.close_menu();
+                    }
+                    let mut split = self.split_pane.is_some();
+                    if ui
+                        .add_enabled(
+                            split || self.current_file.is_some(),
+                            egui::Checkbox::new(&mut split, "Split Vertically"),
+                        )
+                        .on_hover_text("Show a second view of the document side by side")
+                        .changed()
+                    {
+                        if split {
+                            self.split_current_document();
+                        } else {
+                            self.split_pane = None;
+                        }
+                    }
+                    ui.add_enabled_ui(self.split_pane.is_some(), |ui| {
+                        ui.menu_button("Sync Scrolling", |ui| {
+                            for (mode, label) in [
+                                (SyncMode::Off, "Off"),
+                                (SyncMode::Proportional, "Proportional"),
+                                (SyncMode::Headings, "By Headings"),
+                            ] {
+                                if ui.radio_value(&mut self.sync_mode, mode, label).changed() {
+                                    // Let the main document lead on the next frame
+                                    self.synced_offsets.0 = f32::INFINITY;
+                                }
+                            }
+                        });
+                    });
+                    ui.menu_button("Jump Position", |ui| {
+                        ui.checkbox(&mut self.center_jump_target, "Center Target");
+                        ui.add_enabled_ui(!self.center_jump_target, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Top offset:");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.scroll_anchor)
+                                        .range(0.0..=400.0)
+                                        .suffix(" px"),
+                                );
+                            });
+                        });
+                        ui.separator();
+                        ui.add_enabled_ui(!self.reduce_motion, |ui| {
+                            ui.add(
+                                egui::Slider::new(&mut self.scroll_animation_ms, 0..=1000)
+                                    .suffix(" ms")
+                                    .text("Scroll animation"),
+                            );
+                        });
+                        if ui
+                            .checkbox(&mut self.reduce_motion, "Reduce Motion")
+                            .changed()
+                        {
+                            self.apply_reduce_motion(ui.ctx());
+                        }
+                    });
+                    ui.menu_button("CriticMarkup", |ui| {
+                        let mut changed = false;
+                        for (mode, label) in [
+                            (CriticMode::Markup, "Show Changes"),
+                            (CriticMode::Accepted, "Accepted"),
+                            (CriticMode::Original, "Original"),
+                        ] {
+                            changed |= ui.radio_value(&mut self.critic_mode, mode, label).changed();
+                        }
+                        if changed {
+                            self.refresh_content();
+                        }
+                    });
+                    if ui.button("Read Aloud").clicked() {
+                        self.start_read_aloud();
+                        ui.close_menu();
+                    }
+                    if ui.button("Reading Statistics").clicked() {
+                        self.show_stats = !self.show_stats;
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Next Heading (Ctrl+Down)").clicked() {
+                        self.next_heading();
+                        ui.close_menu();
+                    }
+                    if ui.button("Previous Heading (Ctrl+Up)").clicked() {
+                        self.previous_heading();
+                        ui.close_menu();
+                    }
+                });
+
+                ui.menu_button("Commands", |ui| {
+                    self.show_commands_menu(ui);
+                });
+
+                ui.menu_button("Edit", |ui| {
+                    if ui.button("Copy as Markdown").clicked() {
+                        ui.output_mut(|o| o.copied_text = self.content.clone());
+                        ui.close_menu();
+                    }
+                    if ui
+                        .button("Copy as Rich Text")
+                        .on_hover_text("Copy as HTML, keeping headings, emphasis, links and code")
+                        .clicked()
+                    {
+                        self.copy_as_html(ui.ctx());
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui.button("Search (Ctrl+F)").clicked() {
+                        self.show_search = !self.show_search;
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.folder.is_some() || self.current_file.is_some(),
+                            egui::Button::new("Search in Folder… (Ctrl+Shift+F)"),
+                        )
+                        .clicked()
+                    {
+                        self.open_folder_search(ui.ctx());
+                        ui.close_menu();
+                    }
+                    if ui.button("Go to… (Ctrl+G)").clicked() {
+                        self.open_goto_dialog();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            !self.toc_headers.is_empty(),
+                            egui::Button::new("Go to Heading… (Ctrl+Shift+O)"),
+                        )
+                        .clicked()
+                    {
+                        self.open_heading_switcher();
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    if ui
+                        .add_enabled(
+                            self.current_file.is_some(),
+                            egui::Button::new("Add Bookmark (Ctrl+D)"),
+                        )
+                        .clicked()
+                    {
+                        self.add_bookmark();
+                        ui.close_menu();
+                    }
+                    if ui.button("Bookmarks…").clicked() {
+                        self.show_bookmarks = !self.show_bookmarks;
+                        ui.close_menu();
+                    }
+                    if ui.button("Annotations…").clicked() {
+                        self.show_annotations = !self.show_annotations;
+                        ui.close_menu();
+                    }
+                });
+
+                ui.separator();
+                if ui
+                    .add_enabled(self.history.can_go_back(), egui::Button::new("⬅"))
+                    .on_hover_text("Back (Alt+Left)")
+                    .clicked()
+                {
+                    self.go_back();
+                }
+                if ui
+                    .add_enabled(self.history.can_go_forward(), egui::Button::new("➡"))
+                    .on_hover_text("Forward (Alt+Right)")
+                    .clicked()
+                {
+                    self.go_forward();
+                }
+            });
+        });
+    }
+
+    fn handle_file_dialog(&mut self) {
+        if self.show_open_dialog {
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter("Markdown", &["md", "markdown"])
+                .add_filter("Tables", formats::TABLE_EXTENSIONS)
+                .add_filter("Org", &["org"])
+                .add_filter("All Files", &["*"])
+                .pick_file()
+            {
+                if let Err(e) = self.load_file(path) {
+                    eprintln!("Error loading file: {e}");
+                }
+            }
+            self.show_open_dialog = false;
+        }
+        if self.show_folder_dialog {
+            if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                self.open_folder(path);
+            }
+            self.show_folder_dialog = false;
+        }
+        if self.show_split_dialog {
+            if let Some(path) = rfd::FileDialog::new()
+                .add_filter("Markdown", &["md", "markdown"])
+                .pick_file()
+            {
+                match Pane::open(path) {
+                    Ok(pane) => self.split_pane = Some(pane),
+                    Err(e) => eprintln!("Error loading file: {e}"),
+                }
+            }
+            self.show_split_dialog = false;
+        }
+    }
+
+    /// Opens the current document in the split view, so two parts of it can be read at once.
+    fn split_current_document(&mut self) {
+        let Some(path) = self.current_file.clone() else {
+            return;
+        };
+        match Pane::open(path) {
+            Ok(pane) => self.split_pane = Some(pane),
+            Err(e) => eprintln!("Error loading file: {e}"),
+        }
+    }
+
+    /// Keeps the split view panes aligned: whichever pane the user scrolled leads.
+    fn sync_split_scroll(&mut self, ctx: &Context) {
+        let Some(pane) = self.split_pane.as_mut() else {
+            return;
+        };
+        if self.sync_mode == SyncMode::Off {
+            return;
+        }
+        let (main_synced, pane_synced) = self.synced_offsets;
+        if (self.scroll_offset - main_synced).abs() > 1.0 {
+            let target = synced_offset(
+                self.sync_mode,
+                &self.layout,
+                self.scroll_offset,
+                self.max_scroll_offset,
+                &pane.layout,
+                pane.max_scroll_offset,
+            );
+            pane.pending_scroll_offset = Some(target);
+            self.synced_offsets = (self.scroll_offset, target);
+            ctx.request_repaint();
+        } else if (pane.scroll_offset - pane_synced).abs() > 1.0 {
+            let target = synced_offset(
+                self.sync_mode,
+                &pane.layout,
+                pane.scroll_offset,
+                pane.max_scroll_offset,
+                &self.layout,
+                self.max_scroll_offset,
+            );
+            self.pending_scroll_offset = Some(target);
+            self.synced_offsets = (target, pane.scroll_offset);
+            ctx.request_repaint();
+        }
+    }
+
+    /// Draws the main document with its header and scroll area.
+    fn show_document(&mut self, ui: &mut Ui) {
+        let Some(file_path) = &self.current_file else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.heading(format!("File: {}", file_path.display()));
+            if self.filtered || self.markdown_renderer.filtered_text() {
+                ui.weak("(filtered)");
+            }
+            if self.current_file_missing {
+                ui.colored_label(ui.visuals().warn_fg_color, "⚠ Deleted from disk");
+            }
+            if let Some(error) = &self.preprocess_error {
+                ui.colored_label(ui.visuals().warn_fg_color, "⚠ Preprocessor failed")
+                    .on_hover_text(error);
+            }
+            if self.preprocessing {
+                ui.spinner();
+                ui.weak("Preprocessing…");
+            }
+        });
+        ui.separator();
+
+        self.step_scroll_animation(ui.ctx());
+        let mut scroll_area = egui::ScrollArea::vertical().auto_shrink([false; 2]);
+        if let Some(offset) = self.pending_scroll_offset.take() {
+            scroll_area = scroll_area.vertical_scroll_offset(offset);
+        }
+
+        let scroll_output = scroll_area.show(ui, |ui| {
+            ui.add_space(10.0);
+
+            // Center the content horizontally with padding on both sides
+            ui.horizontal(|ui| {
+                let total_width = ui.available_width();
+
+                if self.wide_mode || self.split_pane.is_some() {
+                    // Wide mode (and split view): 5% side padding (minimal)
+                    let side_padding = total_width * 0.05;
+                    ui.add_space(side_padding);
+                    let content_width = ui.available_width() - side_padding;
+
+                    ui.vertical(|ui| {
+                        let current_search_result = if !self.search_results.is_empty() {
+                            Some(&self.search_results[self.current_search_index])
+                        } else {
+                            None
+                        };
+                        let content = self.content.clone();
+                        let search_query = self.search_query.clone();
+                        let scroll_to = self.scroll_to_header.clone();
+                        if self
+                            .markdown_renderer
+                            .render(
+                                ui,
+                                &content,
+                                &search_query,
+                                current_search_result,
+                                &mut self.image_cache,
+                                &self.current_file,
+                                &scroll_to,
+                                Some(content_width),
+                            )
+                            .is_some()
+                        {
+                            self.scroll_to_header = None; // Clear the scroll target after use
+                        }
+                    });
+                } else {
+                    // Normal mode: 25% side padding for centered reading column
+                    let side_padding = total_width * 0.25;
+                    ui.add_space(side_padding);
+                    let content_width = ui.available_width() - side_padding;
 
-                ui.menu_button("Edit", |ui| {
-                    if ui.button("Copy as Markdown").clicked() {
-                        ui.output_mut(|o| o.copied_text = self.content.clone());
-                        ui.close_menu();
-                    }
-                    ui.separator();
-                    if ui.button("Search (Ctrl+F)").clicked() {
-                        self.show_search = !self.show_search;
-                        ui.close_menu();
-                    }
-                });
+                    ui.vertical(|ui| {
+                        let current_search_result = if !self.search_results.is_empty() {
+                            Some(&self.search_results[self.current_search_index])
+                        } else {
+                            None
+                        };
+                        let content = self.content.clone();
+                        let search_query = self.search_query.clone();
+                        let scroll_to = self.scroll_to_header.clone();
+                        if self
+                            .markdown_renderer
+                            .render(
+                                ui,
+                                &content,
+                                &search_query,
+                                current_search_result,
+                                &mut self.image_cache,
+                                &self.current_file,
+                                &scroll_to,
+                                Some(content_width),
+                            )
+                            .is_some()
+                        {
+                            self.scroll_to_header = None; // Clear the scroll target after use
+                        }
+                    });
+                }
             });
         });
-    }
 
-    fn handle_file_dialog(&mut self) {
-        if self.show_open_dialog {
-            if let Some(path) = rfd::FileDialog::new()
-                .add_filter("Markdown", &["md", "markdown"])
-                .pick_file()
-            {
-                if let Err(e) = self.load_file(path) {
-                    eprintln!("Error loading file: {e}");
+        // Convert the recorded screen positions into document offsets
+        self.scroll_offset = scroll_output.state.offset.y;
+        self.viewport_height = scroll_output.inner_rect.height();
+        self.max_scroll_offset =
+            (scroll_output.content_size.y - scroll_output.inner_rect.height()).max(0.0);
+        let content_top = scroll_output.inner_rect.top() - scroll_output.state.offset.y;
+        self.layout = self.markdown_renderer.layout().translated(-content_top);
+
+        if let Some(fragment) = self.markdown_renderer.take_anchor_jump() {
+            self.jump_to_anchor(&fragment);
+        }
+        if let Some((path, section)) = self.markdown_renderer.take_clicked_note() {
+            if let Err(e) = self.load_file(path) {
+                eprintln!("Error loading file: {e}");
+            } else if let Some(section) = section {
+                if !self.scroll_to_anchor(&section) {
+                    eprintln!("Error: no heading matches \"#{section}\"");
                 }
             }
-            self.show_open_dialog = false;
+        }
+        if self.search_result_pending && !self.layout.block_tops.is_empty() {
+            self.search_result_pending = false;
+            self.scroll_to_search_result();
         }
     }
 
@@ -333,19 +2920,32 @@ impl MarkdownReaderApp {
                 ui.label("Search:");
                 let response = ui.text_edit_singleline(&mut self.search_query);
 
-                // Auto-focus the search box when opened
-                if self.show_search {
+                // Auto-focus the search box when opened, unless another field (e.g. the
+                // saved search name) has taken the focus
+                if self.show_search && ui.memory(|m| m.focused().is_none_or(|id| id == response.id))
+                {
                     response.request_focus();
                 }
 
-                // Perform search when text changes
+                // Search as you type, jumping to the first match below where the search
+                // began, or back there when nothing matches
                 if response.changed() {
+                    let origin = *self.search_origin.get_or_insert(self.scroll_offset);
                     self.perform_search();
+                    if self.search_results.is_empty() {
+                        self.pending_scroll_offset = Some(origin);
+                    } else {
+                        self.scroll_to_search_result();
+                    }
                 }
 
-                // Handle Enter key to go to next result
+                // Enter goes to the next result, Shift+Enter to the previous one
                 if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
-                    self.next_search_result();
+                    if ui.input(|i| i.modifiers.shift) {
+                        self.previous_search_result();
+                    } else {
+                        self.next_search_result();
+                    }
                 }
 
                 // Handle Escape key to close search
@@ -357,20 +2957,61 @@ impl MarkdownReaderApp {
                 ui.separator();
 
                 // Case sensitivity toggle
-                ui.checkbox(&mut self.search_case_sensitive, "Case sensitive");
+                if ui
+                    .checkbox(&mut self.search_case_sensitive, "Case sensitive")
+                    .changed()
+                {
+                    self.markdown_renderer
+                        .set_case_sensitive_search(self.search_case_sensitive);
+                    self.perform_search();
+                }
+                if ui
+                    .checkbox(&mut self.search_fuzzy, "Fuzzy")
+                    .on_hover_text("Also find words with small typos (shown underlined)")
+                    .changed()
+                {
+                    self.markdown_renderer.set_fuzzy_search(self.search_fuzzy);
+                    self.perform_search();
+                }
+                let scope = self.search_scope;
+                egui::ComboBox::from_id_source("search_scope")
+                    .selected_text(scope.label())
+                    .show_ui(ui, |ui| {
+                        for option in SearchScope::ALL {
+                            ui.selectable_value(&mut self.search_scope, option, option.label());
+                        }
+                    });
+                if self.search_scope != scope {
+                    self.markdown_renderer.set_search_scope(self.search_scope);
+                    self.perform_search();
+                    self.scroll_to_search_result();
+                }
                 if ui.button("🔄").on_hover_text("Refresh search").clicked() {
                     self.perform_search();
                 }
+                ui.menu_button("★", |ui| self.show_saved_searches_menu(ui))
+                    .response
+                    .on_hover_text("Saved searches");
+                ui.toggle_value(&mut self.show_search_results, "☰")
+                    .on_hover_text("List all results");
 
                 ui.separator();
 
                 // Navigation buttons
                 let has_results = !self.search_results.is_empty();
                 ui.add_enabled_ui(has_results, |ui| {
-                    if ui.button("⬆").on_hover_text("Previous result").clicked() {
+                    if ui
+                        .button("⬆")
+                        .on_hover_text("Previous result (Shift+Enter)")
+                        .clicked()
+                    {
                         self.previous_search_result();
                     }
-                    if ui.button("⬇").on_hover_text("Next result").clicked() {
+                    if ui
+                        .button("⬇")
+                        .on_hover_text("Next result (Enter)")
+                        .clicked()
+                    {
                         self.next_search_result();
                     }
                 });
@@ -396,6 +3037,108 @@ impl MarkdownReaderApp {
         });
     }
 
+    /// Lists every search result with its line number and surrounding text; clicking one
+    /// jumps to it.
+    fn show_search_results_panel(&mut self, ctx: &Context) {
+        let mut clicked = None;
+        egui::TopBottomPanel::bottom("search_results")
+            .resizable(true)
+            .default_height(160.0)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        for (index, result) in self.search_results.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.weak(format!("{:>5}", result.line_number + 1));
+                                let current = index == self.current_search_index;
+                                let snippet = result_snippet(result, ui);
+                                if ui.selectable_label(current, snippet).clicked() {
+                                    clicked = Some(index);
+                                }
+                            });
+                        }
+                    });
+            });
+        if let Some(index) = clicked {
+            self.current_search_index = index;
+            self.scroll_to_search_result();
+        }
+    }
+
+    /// Lists the saved searches for the current document and saves the current search.
+    fn show_saved_searches_menu(&mut self, ui: &mut egui::Ui) {
+        let mut run = None;
+        let mut delete = None;
+        for (index, saved) in self.saved_searches.iter().enumerate() {
+            if !saved.applies_to(self.current_file.as_deref()) {
+                continue;
+            }
+            ui.horizontal(|ui| {
+                if ui.button(&saved.name).on_hover_text(&saved.query).clicked() {
+                    run = Some(index);
+                    ui.close_menu();
+                }
+                if saved.folder.is_some() {
+                    ui.weak("(folder)");
+                }
+                if ui.small_button("🗑").on_hover_text("Delete").clicked() {
+                    delete = Some(index);
+                }
+            });
+        }
+        if let Some(saved) = run.map(|index| self.saved_searches[index].clone()) {
+            self.search_query = saved.query;
+            self.search_case_sensitive = saved.case_sensitive;
+            self.markdown_renderer
+                .set_case_sensitive_search(saved.case_sensitive);
+            self.search_fuzzy = saved.fuzzy;
+            self.markdown_renderer.set_fuzzy_search(saved.fuzzy);
+            self.perform_search();
+            self.scroll_to_search_result();
+        }
+        if let Some(index) = delete {
+            self.saved_searches.remove(index);
+            saved_searches::save(&self.saved_searches);
+        }
+        if !self.saved_searches.is_empty() {
+            ui.separator();
+        }
+
+        ui.label("Save current search as:");
+        ui.text_edit_singleline(&mut self.saved_search_name);
+        let folder = self
+            .current_file
+            .as_ref()
+            .and_then(|file| file.parent())
+            .map(|dir| dir.to_path_buf());
+        ui.add_enabled(
+            folder.is_some(),
+            egui::Checkbox::new(&mut self.saved_search_in_folder, "Only in this folder"),
+        );
+        let can_save = !self.saved_search_name.trim().is_empty() && !self.search_query.is_empty();
+        if ui
+            .add_enabled(can_save, egui::Button::new("Save"))
+            .clicked()
+        {
+            let name = self.saved_search_name.trim().to_string();
+            let folder = folder.filter(|_| self.saved_search_in_folder);
+            // Saving under an existing name in the same scope replaces that search
+            self.saved_searches
+                .retain(|saved| saved.name != name || saved.folder != folder);
+            self.saved_searches.push(SavedSearch {
+                name,
+                query: self.search_query.clone(),
+                case_sensitive: self.search_case_sensitive,
+                fuzzy: self.search_fuzzy,
+                folder,
+            });
+            saved_searches::save(&self.saved_searches);
+            self.saved_search_name.clear();
+            ui.close_menu();
+        }
+    }
+
     fn show_drop_zone(&mut self, ui: &mut egui::Ui) {
         ui.vertical_centered(|ui| {
             ui.add_space(50.0);
@@ -428,11 +3171,9 @@ impl MarkdownReaderApp {
                 if let Some(dropped_file) = ui.ctx().input(|i| i.raw.dropped_files.first().cloned())
                 {
                     if let Some(path) = dropped_file.path {
-                        if let Some(extension) = path.extension() {
-                            if extension == "md" || extension == "markdown" || extension == "txt" {
-                                if let Err(e) = self.load_file(path) {
-                                    eprintln!("Error loading dropped file: {e}");
-                                }
+                        if formats::can_open(&path) {
+                            if let Err(e) = self.load_file(path) {
+                                eprintln!("Error loading dropped file: {e}");
                             }
                         }
                     }
@@ -454,7 +3195,51 @@ impl MarkdownReaderApp {
 
 impl eframe::App for MarkdownReaderApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        self.reading_stats.tick(ctx.input(|i| i.focused));
+        self.apply_night_light(ctx);
+        self.watch_folder(ctx);
+        self.poll_sibling_scan(ctx);
+        self.open_requested_files(ctx);
+
+        if let Some(path) = self.preprocessor.poll() {
+            if self.current_file.as_ref() == Some(&path) {
+                self.refresh_content();
+            }
+        }
+        if self.preprocessor.is_running() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+
+        let reading = self.speaker.tick();
+        let spoken = self
+            .speaker
+            .current()
+            .map(|sentence| (sentence.text.clone(), sentence.line));
+        self.markdown_renderer.set_spoken_sentence(spoken);
+        if reading {
+            self.follow_read_aloud();
+            // Keep polling the speech process while reading
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+
+        // Slides take over the whole window while presenting
+        if self.presentation.is_some() {
+            self.show_presentation(ctx);
+            return;
+        }
+
+        // The image viewer keeps Escape to itself
+        if self.lightbox.is_some()
+            && ctx.input_mut(|i| i.consume_key(Modifiers::NONE, egui::Key::Escape))
+        {
+            self.lightbox = None;
+        }
+
         // Handle keyboard shortcuts
+        if ctx.input(|i| i.key_pressed(egui::Key::F5)) {
+            self.start_presentation(ctx);
+        }
+
         if ctx.input(|i| i.key_pressed(egui::Key::T) && i.modifiers.ctrl)
             && ctx.input(|i| i.key_pressed(egui::Key::W))
         {
@@ -462,136 +3247,501 @@ impl eframe::App for MarkdownReaderApp {
         }
 
         if ctx.input(|i| i.key_pressed(egui::Key::F) && i.modifiers.ctrl) {
-            self.show_search = !self.show_search;
+            if ctx.input(|i| i.modifiers.shift) {
+                self.open_folder_search(ctx);
+            } else {
+                self.show_search = !self.show_search;
+            }
+        }
+
+        // Ctrl+O and Ctrl+P open the file switcher in folder mode; otherwise Ctrl+O
+        // shows the file dialog
+        let (switch_key, open_key, heading_key) = ctx.input(|i| {
+            let o = i.modifiers.command && i.key_pressed(egui::Key::O);
+            (
+                i.modifiers.command && i.key_pressed(egui::Key::P),
+                o && !i.modifiers.shift,
+                o && i.modifiers.shift,
+            )
+        });
+        // Ctrl+Shift+O jumps to a heading of the document
+        if heading_key {
+            self.open_heading_switcher();
+        }
+
+        if ctx.input(|i| i.modifiers.command && i.key_pressed(egui::Key::D)) {
+            self.add_bookmark();
+        }
+        self.handle_annotation_requests(ctx);
+        if switch_key || open_key {
+            if self.folder.is_some() {
+                self.open_file_switcher();
+            } else if open_key {
+                self.show_open_dialog = true;
+            }
+        }
+
+        if ctx.input(|i| i.key_pressed(egui::Key::G) && i.modifiers.ctrl) {
+            if self.show_goto {
+                self.show_goto = false;
+            } else {
+                self.open_goto_dialog();
+            }
         }
 
         if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            // Cancelling the search returns to where it began
+            if let Some(origin) = self.search_origin.take().filter(|_| self.show_search) {
+                self.pending_scroll_offset = Some(origin);
+            }
             self.show_search = false;
+            self.show_goto = false;
+        }
+
+        // Alt+Left/Right and the mouse's back/forward buttons walk the history
+        let (back, forward) = ctx.input(|i| {
+            (
+                (i.modifiers.alt && i.key_pressed(egui::Key::ArrowLeft))
+                    || i.pointer.button_pressed(egui::PointerButton::Extra1),
+                (i.modifiers.alt && i.key_pressed(egui::Key::ArrowRight))
+                    || i.pointer.button_pressed(egui::PointerButton::Extra2),
+            )
+        });
+        if back {
+            self.go_back();
+        } else if forward {
+            self.go_forward();
+        }
+
+        if ctx.input(|i| i.key_pressed(egui::Key::ArrowDown) && i.modifiers.ctrl) {
+            self.next_heading();
+        }
+
+        if ctx.input(|i| i.key_pressed(egui::Key::ArrowUp) && i.modifiers.ctrl) {
+            self.previous_heading();
         }
 
         self.show_menu_bar(ctx);
         self.handle_file_dialog();
 
+        if self.show_status_bar && self.current_file.is_some() {
+            self.show_status_bar(ctx);
+        }
+
         // Show search bar
         if self.show_search {
             self.show_search_bar(ctx);
+            if self.show_search_results && !self.search_results.is_empty() {
+                self.show_search_results_panel(ctx);
+            }
+        } else {
+            self.search_origin = None;
         }
 
+        if self.speaker.is_active() || self.speaker.error().is_some() {
+            self.show_read_aloud_bar(ctx);
+        }
+
+        self.show_file_tree_sidebar(ctx);
+
         // Show TOC sidebar
         self.show_toc_sidebar(ctx);
+        self.show_scratchpad_panel(ctx);
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            if let Some(file_path) = &self.current_file {
-                ui.heading(format!("File: {}", file_path.display()));
-                ui.separator();
+        if self.show_goto {
+            self.show_goto_dialog(ctx);
+        }
 
-                egui::ScrollArea::vertical()
-                    .auto_shrink([false; 2])
-                    .show(ui, |ui| {
-                        ui.add_space(10.0);
+        self.show_file_switcher(ctx);
+        self.show_heading_switcher(ctx);
+        self.show_dashboard(ctx);
+        self.show_folder_search(ctx);
 
-                        // Center the content horizontally with padding on both sides
-                        ui.horizontal(|ui| {
-                            let total_width = ui.available_width();
-
-                            if self.wide_mode {
-                                // Wide mode: 5% side padding (minimal)
-                                let side_padding = total_width * 0.05;
-                                ui.add_space(side_padding);
-                                let content_width = ui.available_width() - side_padding;
-
-                                ui.vertical(|ui| {
-                                    let current_search_result = if !self.search_results.is_empty() {
-                                        Some(&self.search_results[self.current_search_index])
-                                    } else {
-                                        None
-                                    };
-                                    let content = self.content.clone();
-                                    let search_query = self.search_query.clone();
-                                    let scroll_to = self.scroll_to_header.clone();
-                                    if self
-                                        .markdown_renderer
-                                        .render(
-                                            ui,
-                                            &content,
-                                            &search_query,
-                                            current_search_result,
-                                            &mut self.image_cache,
-                                            &self.current_file,
-                                            &scroll_to,
-                                            Some(content_width),
-                                        )
-                                        .is_some()
-                                    {
-                                        self.scroll_to_header = None; // Clear the scroll target after use
-                                    }
-                                });
-                            } else {
-                                // Normal mode: 25% side padding for centered reading column
-                                let side_padding = total_width * 0.25;
-                                ui.add_space(side_padding);
-                                let content_width = ui.available_width() - side_padding;
-
-                                ui.vertical(|ui| {
-                                    let current_search_result = if !self.search_results.is_empty() {
-                                        Some(&self.search_results[self.current_search_index])
-                                    } else {
-                                        None
-                                    };
-                                    let content = self.content.clone();
-                                    let search_query = self.search_query.clone();
-                                    let scroll_to = self.scroll_to_header.clone();
-                                    if self
-                                        .markdown_renderer
-                                        .render(
-                                            ui,
-                                            &content,
-                                            &search_query,
-                                            current_search_result,
-                                            &mut self.image_cache,
-                                            &self.current_file,
-                                            &scroll_to,
-                                            Some(content_width),
-                                        )
-                                        .is_some()
-                                    {
-                                        self.scroll_to_header = None; // Clear the scroll target after use
-                                    }
-                                });
-                            }
-                        });
-                    });
-            } else {
+        if self.show_stats {
+            self.show_stats_window(ctx);
+        }
+        if self.show_bookmarks {
+            self.show_bookmarks_window(ctx);
+        }
+        if self.show_annotations {
+            self.show_annotations_window(ctx);
+        }
+        if self.show_font_settings {
+            self.show_font_settings_window(ctx);
+        }
+        if self.show_opened_files {
+            self.show_opened_files_window(ctx);
+        }
+
+        self.show_command_output(ctx);
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if self.current_file.is_none() {
                 self.show_drop_zone(ui);
+            } else if self.split_pane.is_some() {
+                ui.columns(2, |columns| {
+                    self.show_document(&mut columns[0]);
+                    if let Some(pane) = &mut self.split_pane {
+                        if !pane.show(&mut columns[1], &self.markdown_renderer) {
+                            self.split_pane = None;
+                        }
+                    }
+                });
+            } else {
+                self.show_document(ui);
             }
         });
+
+        self.sync_split_scroll(ctx);
+        self.store_view_settings();
+
+        if let Some(index) = self.markdown_renderer.take_clicked_annotation() {
+            self.show_annotations = true;
+            self.focused_annotation = Some(index);
+        }
+        if let Some((url, texture)) = self.markdown_renderer.take_clicked_image() {
+            let bytes = self.markdown_renderer.image_bytes(&url);
+            self.lightbox = Some(Lightbox::new(url, texture, bytes));
+        }
+        if let Some(lightbox) = &mut self.lightbox {
+            if !lightbox.show(ctx) {
+                self.lightbox = None;
+            }
+        }
+
+        // Remember copied text so commands can use it as their {selection}
+        let copied = ctx.output(|o| o.copied_text.clone());
+        if !copied.is_empty() {
+            self.last_copied = copied;
+        }
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.current_preferences().save(storage);
+        self.store_view_settings();
+        self.view_settings.save(storage);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.scratchpad.save();
+        self.reading_stats.save();
     }
 }
 
 impl MarkdownReaderApp {
+    fn show_file_tree_sidebar(&mut self, ctx: &Context) {
+        if !self.show_file_tree {
+            return;
+        }
+        let Some(tree) = self.file_tree.as_mut() else {
+            return;
+        };
+        let mut clicked = None;
+        egui::SidePanel::left("file_tree_panel")
+            .default_width(220.0)
+            .width_range(150.0..=400.0)
+            .show(ctx, |ui| {
+                ui.heading("Files");
+                clicked = tree.show(ui, self.current_file.as_deref(), &mut self.read_tracker);
+            });
+        if let Some(path) = clicked {
+            if let Err(e) = self.load_file(path) {
+                eprintln!("Error loading file: {e}");
+            }
+        }
+    }
+
+    /// Shows the notes on the current document in a panel on the right. They are written
+    /// when the editor loses focus, the panel is hidden or another file is opened.
+    fn show_scratchpad_panel(&mut self, ctx: &Context) {
+        if !self.show_scratchpad || self.current_file.is_none() {
+            return;
+        }
+        egui::SidePanel::right("scratchpad_panel")
+            .default_width(260.0)
+            .width_range(180.0..=500.0)
+            .show(ctx, |ui| {
+                ui.heading("Scratchpad");
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    let response = ui.add_sized(
+                        ui.available_size(),
+                        egui::TextEdit::multiline(&mut self.scratchpad.text)
+                            .hint_text("Notes on this document…"),
+                    );
+                    if response.changed() {
+                        self.scratchpad.edited();
+                    }
+                    if response.lost_focus() {
+                        self.scratchpad.save();
+                    }
+                });
+            });
+    }
+
     fn show_toc_sidebar(&mut self, ctx: &Context) {
-        if self.show_toc && !self.toc_headers.is_empty() {
+        if !self.show_toc || self.toc_headers.is_empty() {
+            return;
+        }
+        if self.view_settings.toc_auto_hide {
+            self.show_toc_overlay(ctx);
+            return;
+        }
+        let panel = if self.view_settings.toc_on_right {
+            egui::SidePanel::right("toc_panel")
+        } else {
             egui::SidePanel::left("toc_panel")
-                .default_width(200.0)
-                .width_range(150.0..=400.0)
-                .show(ctx, |ui| {
-                    ui.heading("Table of Contents");
-                    ui.separator();
+        };
+        // A width set in the menu replaces the one the panel remembers
+        let panel = if std::mem::take(&mut self.toc_width_changed) {
+            panel.exact_width(self.view_settings.toc_width)
+        } else {
+            panel
+                .default_width(self.view_settings.toc_width)
+                .width_range(TOC_WIDTH_RANGE)
+        };
+        let response = panel.show(ctx, |ui| self.show_toc_contents(ui));
 
-                    egui::ScrollArea::vertical()
-                        .auto_shrink([false; 2])
-                        .show(ui, |ui| {
-                            for header in &self.toc_headers {
-                                let indent = (header.level as f32 - 1.0) * 12.0;
-                                ui.horizontal(|ui| {
-                                    ui.add_space(indent);
-                                    if ui.button(&header.title).clicked() {
-                                        self.scroll_to_header = Some(header.title.clone());
-                                    }
-                                });
-                            }
-                        });
+        // Remember the width once the user has finished resizing the panel
+        let width = response.response.rect.width();
+        if (width - self.view_settings.toc_width).abs() > 0.5
+            && !ctx.input(|i| i.pointer.any_down())
+        {
+            self.view_settings.toc_width = width;
+        }
+    }
+
+    /// Shows the TOC in auto-hide mode: it slides in over the document while the pointer
+    /// touches the window edge on its side, and slides out once the pointer moves away.
+    fn show_toc_overlay(&mut self, ctx: &Context) {
+        let area = ctx.available_rect();
+        let width = self.view_settings.toc_width;
+        let on_right = self.view_settings.toc_on_right;
+        match ctx.input(|i| i.pointer.hover_pos()) {
+            Some(pos) => {
+                let from_edge = if on_right {
+                    area.right() - pos.x
+                } else {
+                    pos.x - area.left()
+                };
+                if from_edge <= TOC_REVEAL_MARGIN {
+                    self.toc_revealed = true;
+                } else if from_edge > width {
+                    self.toc_revealed = false;
+                }
+            }
+            None => self.toc_revealed = false,
+        }
+        let shown = ctx.animate_bool_with_time(Id::new("toc_overlay"), self.toc_revealed, 0.15);
+        if shown == 0.0 {
+            return;
+        }
+        let hidden_by = (1.0 - shown) * width;
+        let left = if on_right {
+            area.right() - width + hidden_by
+        } else {
+            area.left() - hidden_by
+        };
+        egui::Area::new(Id::new("toc_overlay"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(pos2(left, area.top()))
+            .show(ctx, |ui| {
+                let frame = egui::Frame::side_top_panel(ui.style());
+                let margin = frame.total_margin().sum();
+                frame.show(ui, |ui| {
+                    ui.set_width(width - margin.x);
+                    ui.set_height(area.height() - margin.y);
+                    self.show_toc_contents(ui);
                 });
+            });
+    }
+
+    /// Draws the TOC entries with their collapse toggles, and jumps to a clicked entry.
+    fn show_toc_contents(&mut self, ui: &mut Ui) {
+        ui.heading("Table of Contents");
+        ui.horizontal(|ui| {
+            if ui.small_button("Expand All").clicked() {
+                self.toc_collapsed.clear();
+            }
+            if ui.small_button("Collapse All").clicked() {
+                self.toc_collapsed = self
+                    .toc_headers
+                    .windows(2)
+                    .filter(|pair| pair[1].level > pair[0].level)
+                    .map(|pair| pair[0].slug.clone())
+                    .collect();
+            }
+        });
+        ui.separator();
+
+        let numbers = if self.view_settings.toc_numbering {
+            toc_numbers(&self.toc_headers)
+        } else {
+            Vec::new()
+        };
+        // Entries outside collapsed sections
+        let mut visible = Vec::new();
+        // Level of the collapsed entry whose subsections are being skipped
+        let mut collapsed_level = None;
+        for (index, header) in self.toc_headers.iter().enumerate() {
+            if collapsed_level.is_some_and(|level| header.level > level) {
+                continue;
+            }
+            collapsed_level = self
+                .toc_collapsed
+                .contains(&header.slug)
+                .then_some(header.level);
+            visible.push(index);
         }
+        // The current section, or the collapsed entry containing it
+        let highlighted = self
+            .current_toc_header()
+            .and_then(|current| visible.iter().rev().find(|&&i| i <= current))
+            .copied();
+        let follow = highlighted != self.toc_highlighted;
+        self.toc_highlighted = highlighted;
+
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                let mut clicked = None;
+                let mut toggled = None;
+                for &index in &visible {
+                    let header = &self.toc_headers[index];
+                    let collapsed = self.toc_collapsed.contains(&header.slug);
+                    let has_children = self
+                        .toc_headers
+                        .get(index + 1)
+                        .is_some_and(|next| next.level > header.level);
+
+                    let indent = (header.level as f32 - 1.0) * 12.0;
+                    ui.horizontal(|ui| {
+                        ui.add_space(indent);
+                        if has_children {
+                            let icon = if collapsed { "▶" } else { "▼" };
+                            if ui
+                                .small_button(icon)
+                                .on_hover_text(if collapsed { "Expand" } else { "Collapse" })
+                                .clicked()
+                            {
+                                toggled = Some(header.slug.clone());
+                            }
+                        } else {
+                            ui.add_space(ui.spacing().interact_size.y);
+                        }
+                        let title = match numbers.get(index) {
+                            Some(number) => format!("{number} {}", header.title),
+                            None => header.title.clone(),
+                        };
+                        let current = highlighted == Some(index);
+                        let response = ui.add(egui::Button::new(title).selected(current));
+                        if current && follow {
+                            response.scroll_to_me(None);
+                        }
+                        if response.clicked() {
+                            clicked = Some(header.clone());
+                        }
+                    });
+                }
+                if let Some(slug) = toggled {
+                    if !self.toc_collapsed.remove(&slug) {
+                        self.toc_collapsed.insert(slug);
+                    }
+                }
+                if let Some(header) = clicked {
+                    self.jump_to_heading(header);
+                }
+            });
     }
 }
+
+/// Widths the TOC panel can be resized to.
+const TOC_WIDTH_RANGE: std::ops::RangeInclusive<f32> = 150.0..=400.0;
+
+/// Distance from the window edge, in points, at which the auto-hidden TOC slides in.
+const TOC_REVEAL_MARGIN: f32 = 4.0;
+
+/// Lists syntax themes as entries of a menu, marking the current one. Returns the
+/// clicked theme.
+fn code_theme_list<'a>(
+    ui: &mut egui::Ui,
+    themes: impl Iterator<Item = &'a str>,
+    current: &str,
+) -> Option<String> {
+    let mut clicked = None;
+    egui::ScrollArea::vertical()
+        .max_height(400.0)
+        .show(ui, |ui| {
+            for name in themes {
+                if ui.selectable_label(name == current, name).clicked() {
+                    clicked = Some(name.to_string());
+                    ui.close_menu();
+                }
+            }
+        });
+    clicked
+}
+
+/// Hierarchical section numbers (1, 1.1, 1.1.2) of the TOC entries. Skipped heading
+/// levels don't add a level of numbering.
+fn toc_numbers(headers: &[TocHeader]) -> Vec<String> {
+    // Level and count of the sections enclosing the current one
+    let mut stack: Vec<(u8, usize)> = Vec::new();
+    headers
+        .iter()
+        .map(|header| {
+            while stack.last().is_some_and(|&(level, _)| level > header.level) {
+                stack.pop();
+            }
+            match stack.last_mut() {
+                Some((level, count)) if *level == header.level => *count += 1,
+                _ => stack.push((header.level, 1)),
+            }
+            stack
+                .iter()
+                .map(|(_, count)| count.to_string())
+                .collect::<Vec<_>>()
+                .join(".")
+        })
+        .collect()
+}
+
+/// Characters of context shown on each side of a match in the search results panel.
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Formats the line of a search result around the match, with the match highlighted.
+pub fn result_snippet(result: &SearchResult, ui: &Ui) -> text::LayoutJob {
+    let line = &result.line_content;
+    let before = &line[..result.match_start];
+    let after = &line[result.match_end..];
+    let before_start = before
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT)
+        .map_or(0, |(index, _)| index);
+    let after_end = after
+        .char_indices()
+        .nth(SNIPPET_CONTEXT)
+        .map_or(after.len(), |(index, _)| index);
+
+    let font_id = TextStyle::Body.resolve(ui.style());
+    let plain = text::TextFormat::simple(font_id.clone(), ui.visuals().text_color());
+    let highlighted = text::TextFormat {
+        background: ui.visuals().selection.bg_fill,
+        ..text::TextFormat::simple(font_id, ui.visuals().warn_fg_color)
+    };
+    let mut job = text::LayoutJob::default();
+    let ellipsis = if before_start > 0 { "…" } else { "" };
+    job.append(
+        &format!("{ellipsis}{}", before[before_start..].trim_start()),
+        0.0,
+        plain.clone(),
+    );
+    job.append(
+        &line[result.match_start..result.match_end],
+        0.0,
+        highlighted,
+    );
+    let ellipsis = if after_end < after.len() { "…" } else { "" };
+    job.append(&format!("{}{ellipsis}", &after[..after_end]), 0.0, plain);
+    job
+}
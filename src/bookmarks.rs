@@ -0,0 +1,82 @@
+//! # Bookmarks Module
+//!
+//! This module stores named positions within documents, keyed by document path, so places
+//! marked while reading can be jumped back to, including in later sessions.
+
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// File in the config directory holding the bookmarks.
+const BOOKMARKS_FILE: &str = "bookmarks.json";
+
+/// A marked position in a document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    /// Name shown in the bookmarks panel
+    pub name: String,
+    /// Source line (0-based) of the marked block
+    pub line: usize,
+}
+
+/// Bookmarks of every document.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bookmarks {
+    /// Bookmarks keyed by document path, in document order
+    documents: HashMap<PathBuf, Vec<Bookmark>>,
+}
+
+impl Bookmarks {
+    /// Loads the bookmarks from the config directory.
+    pub fn load() -> Self {
+        storage::load_json(BOOKMARKS_FILE)
+    }
+
+    /// Writes the bookmarks to the config directory.
+    pub fn save(&self) {
+        if let Err(e) = storage::save_json(BOOKMARKS_FILE, self) {
+            eprintln!("Error saving bookmarks: {e}");
+        }
+    }
+
+    /// Bookmarks of a document, in document order.
+    pub fn for_document(&self, path: &Path) -> &[Bookmark] {
+        self.documents.get(path).map_or(&[], Vec::as_slice)
+    }
+
+    /// Adds a bookmark to a document, unless one already marks the same line.
+    pub fn add(&mut self, path: &Path, bookmark: Bookmark) {
+        let bookmarks = self.documents.entry(path.to_path_buf()).or_default();
+        if let Err(position) = bookmarks.binary_search_by_key(&bookmark.line, |b| b.line) {
+            bookmarks.insert(position, bookmark);
+            self.save();
+        }
+    }
+
+    /// Renames a bookmark of a document. The change is written by the next `save`.
+    pub fn rename(&mut self, path: &Path, index: usize, name: String) {
+        if let Some(bookmark) = self
+            .documents
+            .get_mut(path)
+            .and_then(|bookmarks| bookmarks.get_mut(index))
+        {
+            bookmark.name = name;
+        }
+    }
+
+    /// Removes a bookmark of a document.
+    pub fn remove(&mut self, path: &Path, index: usize) {
+        let Some(bookmarks) = self.documents.get_mut(path) else {
+            return;
+        };
+        if index < bookmarks.len() {
+            bookmarks.remove(index);
+            if bookmarks.is_empty() {
+                self.documents.remove(path);
+            }
+            self.save();
+        }
+    }
+}
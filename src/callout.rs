@@ -0,0 +1,153 @@
+//! # Callout Module
+//!
+//! This module recognises Obsidian-style callout markers (`> [!info] Title`) at the
+//! start of blockquotes and maps them to the icon and accent color used when rendering.
+//! GitHub alerts (`> [!NOTE]`, `> [!TIP]`, `> [!IMPORTANT]`, `> [!WARNING]`,
+//! `> [!CAUTION]`) use the same syntax and are handled as their Obsidian aliases.
+
+use egui::Color32;
+
+/// The visual family of a callout block.
+///
+/// Obsidian accepts several aliases per family (e.g. `summary` and `tldr` both render
+/// as `Abstract`); unknown types fall back to `Note` just like Obsidian does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalloutKind {
+    Note,
+    Abstract,
+    Info,
+    Todo,
+    Tip,
+    Success,
+    Question,
+    Warning,
+    Failure,
+    Danger,
+    Bug,
+    Example,
+    Quote,
+}
+
+/// Whether a callout can be folded, and its initial state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalloutFold {
+    /// Plain `[!type]` callout, always expanded
+    None,
+    /// `[!type]+` callout, foldable and initially expanded
+    Open,
+    /// `[!type]-` callout, foldable and initially collapsed
+    Closed,
+}
+
+/// A parsed callout header line.
+#[derive(Debug, Clone)]
+pub struct Callout {
+    /// Visual family of the callout
+    pub kind: CalloutKind,
+    /// Whether the callout is collapsible
+    pub fold: CalloutFold,
+    /// Title shown in the header (defaults to the capitalised type name)
+    pub title: String,
+}
+
+impl CalloutKind {
+    /// Maps an Obsidian callout type (case-insensitive) to its kind.
+    pub fn from_type(name: &str) -> Self {
+        match name.to_lowercase().as_str() {
+            "abstract" | "summary" | "tldr" => Self::Abstract,
+            "info" => Self::Info,
+            "todo" => Self::Todo,
+            "tip" | "hint" | "important" => Self::Tip,
+            "success" | "check" | "done" => Self::Success,
+            "question" | "help" | "faq" => Self::Question,
+            "warning" | "caution" | "attention" => Self::Warning,
+            "failure" | "fail" | "missing" => Self::Failure,
+            "danger" | "error" => Self::Danger,
+            "bug" => Self::Bug,
+            "example" => Self::Example,
+            "quote" | "cite" => Self::Quote,
+            _ => Self::Note,
+        }
+    }
+
+    /// Icon glyph shown before the callout title.
+    pub fn icon(self) -> &'static str {
+        match self {
+            Self::Note => "✏",
+            Self::Abstract => "📋",
+            Self::Info => "ℹ",
+            Self::Todo => "☑",
+            Self::Tip => "🔥",
+            Self::Success => "✔",
+            Self::Question => "❓",
+            Self::Warning => "⚠",
+            Self::Failure => "✖",
+            Self::Danger => "⚡",
+            Self::Bug => "🐛",
+            Self::Example => "📝",
+            Self::Quote => "❝",
+        }
+    }
+
+    /// Accent color used for the border, icon and title.
+    pub fn color(self) -> Color32 {
+        match self {
+            Self::Note | Self::Info | Self::Todo => Color32::from_rgb(97, 175, 239),
+            Self::Abstract | Self::Tip => Color32::from_rgb(86, 182, 194),
+            Self::Success => Color32::from_rgb(152, 195, 121),
+            Self::Question | Self::Warning => Color32::from_rgb(229, 192, 123),
+            Self::Failure | Self::Danger | Self::Bug => Color32::from_rgb(224, 108, 117),
+            Self::Example => Color32::from_rgb(198, 120, 221),
+            Self::Quote => Color32::from_rgb(130, 137, 151),
+        }
+    }
+}
+
+impl Callout {
+    /// Parses the first line of a blockquote, returning the callout it declares (if any).
+    ///
+    /// Accepts `[!type]`, `[!type]+` and `[!type]-`, optionally followed by a custom title.
+    pub fn parse(line: &str) -> Option<Self> {
+        let rest = line.trim_start().strip_prefix("[!")?;
+        let close = rest.find(']')?;
+        let name = &rest[..close];
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            return None;
+        }
+
+        let mut rest = &rest[close + 1..];
+        let fold = if let Some(r) = rest.strip_prefix('+') {
+            rest = r;
+            CalloutFold::Open
+        } else if let Some(r) = rest.strip_prefix('-') {
+            rest = r;
+            CalloutFold::Closed
+        } else {
+            CalloutFold::None
+        };
+
+        let title = rest.trim();
+        let title = if title.is_empty() {
+            let mut chars = name.chars();
+            match chars.next() {
+                Some(first) => first
+                    .to_uppercase()
+                    .chain(chars.map(|c| c.to_ascii_lowercase()))
+                    .collect(),
+                None => String::new(),
+            }
+        } else {
+            title.to_string()
+        };
+
+        Some(Self {
+            kind: CalloutKind::from_type(name),
+            fold,
+            title,
+        })
+    }
+}
@@ -0,0 +1,124 @@
+//! # Command Line Module
+//!
+//! This module parses the command line: the documents to open (optionally with a
+//! `#section` anchor) and the flags that override the startup view, so mdzen can be
+//! launched pre-configured from scripts and file-manager actions.
+
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+/// Body font sizes accepted by `--font-size`, in points.
+const FONT_SIZES: RangeInclusive<f32> = 8.0..=32.0;
+
+/// Help text printed for `--help`.
+pub const USAGE: &str = "\
+Usage: mdzen [OPTIONS] [FILE[#SECTION]]...
+
+Options:
+      --font-size <SIZE>     Body font size in points (8-32)
+      --wide                 Start in wide mode
+      --theme <NAME>         Color scheme to use, e.g. \"Zen Dark\"
+      --toc                  Show the table of contents
+      --window-size <WxH>    Initial window size in points, e.g. 1200x800
+      --new-window           Open a new window even if mdzen is already running
+  -h, --help                 Print this help
+  -V, --version              Print the version";
+
+/// Flags overriding the view, applied at startup or by the running instance the
+/// documents are handed to.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StartupOptions {
+    /// Body font size overriding the remembered one
+    pub font_size: Option<f32>,
+    /// Whether to start in wide mode
+    pub wide: bool,
+    /// Name of the color scheme to use
+    pub theme: Option<String>,
+    /// Whether to show the table of contents
+    pub toc: bool,
+    /// Initial inner size of the window
+    pub window_size: Option<[f32; 2]>,
+}
+
+/// Options given on the command line.
+#[derive(Debug, Default)]
+pub struct Args {
+    /// Documents to open, possibly with a `#section` suffix
+    pub files: Vec<String>,
+    /// Flags overriding the view
+    pub options: StartupOptions,
+    /// Whether to open a new window instead of handing the file to a running instance
+    pub new_window: bool,
+    /// Whether the help was requested
+    pub help: bool,
+    /// Whether the version was requested
+    pub version: bool,
+}
+
+impl Args {
+    /// Parses the arguments following the program name.
+    ///
+    /// Flag values can be given as `--flag value` or `--flag=value`; `--` ends the flags.
+    pub fn parse(arguments: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = Self::default();
+        let mut arguments = arguments.into_iter();
+        let mut flags_done = false;
+        while let Some(argument) = arguments.next() {
+            if flags_done || !argument.starts_with('-') || argument == "-" {
+                args.files.push(argument);
+                continue;
+            }
+            let (flag, inline_value) = match argument.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (argument.clone(), None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| arguments.next())
+                    .ok_or_else(|| format!("{flag} needs a value"))
+            };
+            match flag.as_str() {
+                "--" => flags_done = true,
+                "--font-size" => {
+                    let value = value()?;
+                    args.options.font_size = Some(
+                        value
+                            .parse::<f32>()
+                            .ok()
+                            .filter(|size| FONT_SIZES.contains(size))
+                            .ok_or_else(|| {
+                                format!("invalid font size \"{value}\" (expected 8-32)")
+                            })?,
+                    );
+                }
+                "--theme" => args.options.theme = Some(value()?),
+                "--window-size" => args.options.window_size = Some(parse_size(&value()?)?),
+                "--wide" => args.options.wide = true,
+                "--toc" => args.options.toc = true,
+                "--new-window" => args.new_window = true,
+                "-h" | "--help" => args.help = true,
+                "-V" | "--version" => args.version = true,
+                _ => return Err(format!("unknown option \"{flag}\"")),
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// Parses a `WIDTHxHEIGHT` window size.
+fn parse_size(value: &str) -> Result<[f32; 2], String> {
+    let invalid = || format!("invalid window size \"{value}\", expected e.g. 1200x800");
+    let (width, height) = value.split_once(['x', 'X']).ok_or_else(invalid)?;
+    let parse = |side: &str| {
+        side.trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|side| side.is_finite() && *side >= 100.0)
+    };
+    match (parse(width), parse(height)) {
+        (Some(width), Some(height)) => Ok([width, height]),
+        _ => Err(invalid()),
+    }
+}
@@ -0,0 +1,124 @@
+//! # CriticMarkup Module
+//!
+//! This module handles CriticMarkup editing annotations — `{++addition++}`,
+//! `{--deletion--}`, `{~~old~>new~~}`, `{==highlight==}` and `{>>comment<<}` — so edited
+//! manuscripts can be reviewed with the changes marked, or read as accepted or original text.
+
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+/// How CriticMarkup annotations are presented.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CriticMode {
+    /// Show insertions, deletions, highlights and comments with review styling
+    #[default]
+    Markup,
+    /// Show the text with all changes accepted and comments removed
+    Accepted,
+    /// Show the text with all changes rejected and comments removed
+    Original,
+}
+
+/// Kind of annotation a piece of text belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CriticKind {
+    Addition,
+    Deletion,
+    Highlight,
+    Comment,
+}
+
+impl CriticKind {
+    fn delimiters(self) -> (&'static str, &'static str) {
+        match self {
+            CriticKind::Addition => ("{++", "++}"),
+            CriticKind::Deletion => ("{--", "--}"),
+            CriticKind::Highlight => ("{==", "==}"),
+            CriticKind::Comment => ("{>>", "<<}"),
+        }
+    }
+}
+
+const KINDS: [CriticKind; 4] = [
+    CriticKind::Addition,
+    CriticKind::Deletion,
+    CriticKind::Highlight,
+    CriticKind::Comment,
+];
+
+/// Whether the markdown contains any CriticMarkup.
+pub fn has_markup(markdown: &str) -> bool {
+    KINDS
+        .iter()
+        .any(|kind| markdown.contains(kind.delimiters().0))
+        || markdown.contains("{~~")
+}
+
+fn substitution_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"(?s)\{~~(.*?)~>(.*?)~~\}").expect("valid regex"))
+}
+
+fn markup_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(r"(?s)\{\+\+(.*?)\+\+\}|\{--(.*?)--\}|\{==(.*?)==\}|\{>>(.*?)<<\}")
+            .expect("valid regex")
+    })
+}
+
+/// Prepares markdown for presentation in the given mode.
+///
+/// Substitutions are rewritten as a deletion followed by an addition, since `~~` would
+/// otherwise be parsed as strikethrough.
+pub fn apply(markdown: &str, mode: CriticMode) -> Cow<'_, str> {
+    if !has_markup(markdown) {
+        return Cow::Borrowed(markdown);
+    }
+    let normalized = substitution_regex().replace_all(markdown, "{--$1--}{++$2++}");
+    if mode == CriticMode::Markup {
+        return normalized;
+    }
+    let resolved = markup_regex()
+        .replace_all(&normalized, |caps: &regex::Captures| {
+            let keep = match mode {
+                CriticMode::Accepted => caps.get(1).or(caps.get(3)),
+                _ => caps.get(2).or(caps.get(3)),
+            };
+            keep.map(|m| m.as_str().to_owned()).unwrap_or_default()
+        })
+        .into_owned();
+    Cow::Owned(resolved)
+}
+
+/// Splits text into runs by annotation, tracking the open annotation in `state` so
+/// annotations may span several text events. Delimiters are dropped.
+pub fn split_marks<'a>(
+    text: &'a str,
+    state: &mut Option<CriticKind>,
+) -> Vec<(&'a str, Option<CriticKind>)> {
+    let mut runs = Vec::new();
+    let mut rest = text;
+    loop {
+        let next = match *state {
+            Some(kind) => rest.find(kind.delimiters().1).map(|pos| (pos, None)),
+            None => KINDS
+                .iter()
+                .filter_map(|&kind| rest.find(kind.delimiters().0).map(|pos| (pos, Some(kind))))
+                .min_by_key(|(pos, _)| *pos),
+        };
+        let Some((pos, new_state)) = next else {
+            if !rest.is_empty() {
+                runs.push((rest, *state));
+            }
+            return runs;
+        };
+        if pos > 0 {
+            runs.push((&rest[..pos], *state));
+        }
+        *state = new_state;
+        // Every delimiter is three characters long
+        rest = &rest[pos + 3..];
+    }
+}
@@ -0,0 +1,271 @@
+//! # Dashboard Module
+//!
+//! This module summarizes the folder opened in folder mode: how many documents and words
+//! it holds, its largest and most recently modified documents, internal links that point
+//! nowhere, and orphaned documents that nothing links to.
+
+use crate::markdown::MarkdownRenderer;
+use crate::read_state::modified_secs;
+use crate::vault::{self, Vault};
+use egui::Context;
+use pulldown_cmark::{Event, LinkType, Parser, Tag};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of entries in the largest and recently modified lists.
+const TOP_ENTRIES: usize = 10;
+
+/// A link that doesn't resolve to an existing file.
+pub struct BrokenLink {
+    /// Document containing the link
+    pub source: PathBuf,
+    /// Link target as written
+    pub target: String,
+}
+
+/// Summary of a folder.
+#[derive(Default)]
+pub struct FolderReport {
+    /// Number of markdown documents
+    pub files: usize,
+    /// Words across all documents
+    pub total_words: usize,
+    /// Documents with the most words, largest first
+    pub largest: Vec<(PathBuf, usize)>,
+    /// Most recently modified documents with their modification time, newest first
+    pub recent: Vec<(PathBuf, u64)>,
+    /// Internal links whose target doesn't exist
+    pub broken_links: Vec<BrokenLink>,
+    /// Documents no other document links to
+    pub orphans: Vec<PathBuf>,
+}
+
+impl FolderReport {
+    /// Reads every document of the folder and builds the summary.
+    pub fn build(folder: &Vault) -> Self {
+        let mut report = FolderReport {
+            files: folder.notes().len(),
+            ..Self::default()
+        };
+        let mut linked: HashSet<PathBuf> = HashSet::new();
+        let mut words = Vec::new();
+        let mut modified = Vec::new();
+
+        for path in folder.notes() {
+            let Ok(content) = fs::read_to_string(path) else {
+                continue;
+            };
+            let mut count = 0;
+            for event in Parser::new_ext(&content, MarkdownRenderer::parser_options()) {
+                match event {
+                    Event::Text(text) | Event::Code(text) => {
+                        count += text.split_whitespace().count();
+                    }
+                    Event::Start(Tag::Link {
+                        link_type,
+                        dest_url,
+                        ..
+                    }) => match resolve_link(folder, path, link_type, &dest_url) {
+                        Some(Ok(target)) => {
+                            let target = canonical(&target);
+                            if target != canonical(path) {
+                                linked.insert(target);
+                            }
+                        }
+                        Some(Err(())) => report.broken_links.push(BrokenLink {
+                            source: path.clone(),
+                            target: dest_url.to_string(),
+                        }),
+                        None => {}
+                    },
+                    _ => {}
+                }
+            }
+            report.total_words += count;
+            words.push((path.clone(), count));
+            if let Some(time) = modified_secs(path) {
+                modified.push((path.clone(), time));
+            }
+        }
+
+        words.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        words.truncate(TOP_ENTRIES);
+        report.largest = words;
+        modified.sort_by_key(|(_, time)| std::cmp::Reverse(*time));
+        modified.truncate(TOP_ENTRIES);
+        report.recent = modified;
+        report.orphans = folder
+            .notes()
+            .iter()
+            .filter(|path| !linked.contains(&canonical(path)))
+            .cloned()
+            .collect();
+        report
+    }
+}
+
+/// Resolves an internal link to a file. Returns None for external links and in-document
+/// anchors, and `Some(Err(()))` if the target doesn't exist.
+fn resolve_link(
+    folder: &Vault,
+    source: &Path,
+    link_type: LinkType,
+    dest: &str,
+) -> Option<Result<PathBuf, ()>> {
+    if matches!(link_type, LinkType::WikiLink { .. }) {
+        let (note, _) = vault::split_target(dest);
+        if note.is_empty() {
+            return None;
+        }
+        return Some(folder.resolve(note, Some(source)).ok_or(()));
+    }
+    if dest.contains("://") || dest.starts_with("mailto:") {
+        return None;
+    }
+    let file = dest
+        .split(['#', '?'])
+        .next()
+        .unwrap_or("")
+        .replace("%20", " ");
+    if file.is_empty() {
+        return None;
+    }
+    let target = match file.strip_prefix('/') {
+        Some(absolute) => folder.root().join(absolute),
+        None => source.parent().unwrap_or(folder.root()).join(&file),
+    };
+    if target.exists() {
+        Some(Ok(target))
+    } else {
+        Some(Err(()))
+    }
+}
+
+/// Normalizes a path (e.g. `dir/../note.md`) so links and indexed paths compare equal.
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// The folder dashboard window; the report is built in the background.
+pub struct Dashboard {
+    /// Folder the report describes
+    root: PathBuf,
+    /// Finished report
+    report: Option<FolderReport>,
+    /// Report being built
+    pending: Option<mpsc::Receiver<FolderReport>>,
+}
+
+impl Dashboard {
+    /// Starts building the report of a folder.
+    pub fn open(folder: &Vault, ctx: &Context) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let root = folder.root().to_path_buf();
+        let folder = folder.clone();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(FolderReport::build(&folder));
+            ctx.request_repaint();
+        });
+        Self {
+            root,
+            report: None,
+            pending: Some(receiver),
+        }
+    }
+
+    /// Draws the dashboard. Returns the document the user clicked, if any; sets `open`
+    /// to false when the window is closed.
+    pub fn show(&mut self, ctx: &Context, open: &mut bool) -> Option<PathBuf> {
+        if let Some(receiver) = &self.pending {
+            if let Ok(report) = receiver.try_recv() {
+                self.report = Some(report);
+                self.pending = None;
+            }
+        }
+
+        let mut clicked = None;
+        let root = &self.root;
+        egui::Window::new("Folder Dashboard")
+            .open(open)
+            .default_width(420.0)
+            .default_height(520.0)
+            .show(ctx, |ui| {
+                ui.weak(root.display().to_string());
+                let Some(report) = &self.report else {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Reading documents…");
+                    });
+                    return;
+                };
+                ui.label(format!(
+                    "{} documents, {} words",
+                    report.files, report.total_words
+                ));
+                ui.separator();
+
+                let mut entry = |ui: &mut egui::Ui, path: &Path, detail: String| {
+                    ui.horizontal(|ui| {
+                        let name = path
+                            .strip_prefix(root)
+                            .unwrap_or(path)
+                            .display()
+                            .to_string();
+                        if ui.link(name).clicked() {
+                            clicked = Some(path.to_path_buf());
+                        }
+                        ui.weak(detail);
+                    });
+                };
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        ui.collapsing("Largest documents", |ui| {
+                            for (path, words) in &report.largest {
+                                entry(ui, path, format!("{words} words"));
+                            }
+                        });
+                        ui.collapsing("Recently modified", |ui| {
+                            for (path, modified) in &report.recent {
+                                entry(ui, path, format_age(*modified));
+                            }
+                        });
+                        ui.collapsing(
+                            format!("Broken links ({})", report.broken_links.len()),
+                            |ui| {
+                                for link in &report.broken_links {
+                                    entry(ui, &link.source, format!("→ {}", link.target));
+                                }
+                            },
+                        );
+                        ui.collapsing(
+                            format!("Orphaned documents ({})", report.orphans.len()),
+                            |ui| {
+                                for path in &report.orphans {
+                                    entry(ui, path, String::new());
+                                }
+                            },
+                        );
+                    });
+            });
+        clicked
+    }
+}
+
+/// Formats how long ago a modification time (seconds since the epoch) was.
+fn format_age(modified: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(modified);
+    match now.saturating_sub(modified) {
+        age if age < 60 => "just now".to_string(),
+        age if age < 3600 => format!("{} min ago", age / 60),
+        age if age < 86_400 => format!("{} h ago", age / 3600),
+        age => format!("{} days ago", age / 86_400),
+    }
+}
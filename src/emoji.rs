@@ -0,0 +1,418 @@
+//! # Emoji Module
+//!
+//! This module replaces GitHub-style emoji shortcodes such as `:rocket:` with the emoji
+//! they stand for. Unknown shortcodes, and colons that aren't part of one (times like
+//! `12:30`), are left as written.
+
+use std::borrow::Cow;
+
+/// Replaces the known shortcodes in a piece of text.
+pub fn replace_shortcodes(text: &str) -> Cow<'_, str> {
+    if !text.contains(':') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut replaced = false;
+    while let Some(start) = rest.find(':') {
+        let after = &rest[start + 1..];
+        let name_len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '+' | '-')))
+            .unwrap_or(after.len());
+        let emoji = (name_len > 0 && after[name_len..].starts_with(':'))
+            .then(|| lookup(&after[..name_len]))
+            .flatten();
+        match emoji {
+            Some(emoji) => {
+                result.push_str(&rest[..start]);
+                result.push_str(emoji);
+                rest = &after[name_len + 1..];
+                replaced = true;
+            }
+            None => {
+                result.push_str(&rest[..=start]);
+                rest = after;
+            }
+        }
+    }
+    if !replaced {
+        return Cow::Borrowed(text);
+    }
+    result.push_str(rest);
+    Cow::Owned(result)
+}
+
+/// Emoji for a shortcode name (without the colons).
+fn lookup(name: &str) -> Option<&'static str> {
+    Some(match name {
+        // Faces
+        "smile" => "😄",
+        "smiley" => "😃",
+        "grinning" => "😀",
+        "grin" => "😁",
+        "laughing" | "satisfied" => "😆",
+        "joy" => "😂",
+        "rofl" => "🤣",
+        "sweat_smile" => "😅",
+        "slightly_smiling_face" => "🙂",
+        "upside_down_face" => "🙃",
+        "wink" => "😉",
+        "blush" => "😊",
+        "innocent" => "😇",
+        "heart_eyes" => "😍",
+        "star_struck" => "🤩",
+        "kissing_heart" => "😘",
+        "yum" => "😋",
+        "stuck_out_tongue" => "😛",
+        "stuck_out_tongue_winking_eye" => "😜",
+        "thinking" => "🤔",
+        "neutral_face" => "😐",
+        "expressionless" => "😑",
+        "no_mouth" => "😶",
+        "smirk" => "😏",
+        "unamused" => "😒",
+        "roll_eyes" => "🙄",
+        "grimacing" => "😬",
+        "relieved" => "😌",
+        "pensive" => "😔",
+        "sleepy" => "😪",
+        "sleeping" => "😴",
+        "mask" => "😷",
+        "nerd_face" => "🤓",
+        "sunglasses" => "😎",
+        "confused" => "😕",
+        "worried" => "😟",
+        "slightly_frowning_face" => "🙁",
+        "open_mouth" => "😮",
+        "astonished" => "😲",
+        "flushed" => "😳",
+        "pleading_face" => "🥺",
+        "cry" => "😢",
+        "sob" => "😭",
+        "scream" => "😱",
+        "confounded" => "😖",
+        "disappointed" => "😞",
+        "sweat" => "😓",
+        "weary" => "😩",
+        "tired_face" => "😫",
+        "yawning_face" => "🥱",
+        "triumph" => "😤",
+        "rage" | "pout" => "😡",
+        "angry" => "😠",
+        "exploding_head" => "🤯",
+        "partying_face" => "🥳",
+        "skull" => "💀",
+        "poop" | "hankey" | "shit" => "💩",
+        "clown_face" => "🤡",
+        "ghost" => "👻",
+        "alien" => "👽",
+        "robot" => "🤖",
+        "see_no_evil" => "🙈",
+        // Hands and people
+        "+1" | "thumbsup" => "👍",
+        "-1" | "thumbsdown" => "👎",
+        "ok_hand" => "👌",
+        "wave" => "👋",
+        "clap" => "👏",
+        "raised_hands" => "🙌",
+        "pray" => "🙏",
+        "handshake" => "🤝",
+        "muscle" => "💪",
+        "point_right" => "👉",
+        "point_left" => "👈",
+        "point_up" => "☝️",
+        "point_down" => "👇",
+        "v" => "✌️",
+        "crossed_fingers" => "🤞",
+        "fist" => "✊",
+        "facepalm" => "🤦",
+        "shrug" => "🤷",
+        "eyes" => "👀",
+        "brain" => "🧠",
+        // Hearts and symbols
+        "heart" => "❤️",
+        "orange_heart" => "🧡",
+        "yellow_heart" => "💛",
+        "green_heart" => "💚",
+        "blue_heart" => "💙",
+        "purple_heart" => "💜",
+        "black_heart" => "🖤",
+        "broken_heart" => "💔",
+        "sparkling_heart" => "💖",
+        "100" => "💯",
+        "boom" | "collision" => "💥",
+        "fire" => "🔥",
+        "sparkles" => "✨",
+        "star" => "⭐",
+        "star2" => "🌟",
+        "zap" => "⚡",
+        "dizzy" => "💫",
+        "tada" => "🎉",
+        "confetti_ball" => "🎊",
+        "balloon" => "🎈",
+        "gift" => "🎁",
+        "trophy" => "🏆",
+        "medal_sports" => "🏅",
+        "1st_place_medal" => "🥇",
+        "white_check_mark" => "✅",
+        "heavy_check_mark" => "✔️",
+        "ballot_box_with_check" => "☑️",
+        "x" => "❌",
+        "negative_squared_cross_mark" => "❎",
+        "heavy_multiplication_x" => "✖️",
+        "heavy_plus_sign" => "➕",
+        "heavy_minus_sign" => "➖",
+        "warning" => "⚠️",
+        "no_entry" => "⛔",
+        "no_entry_sign" => "🚫",
+        "stop_sign" => "🛑",
+        "question" => "❓",
+        "grey_question" => "❔",
+        "exclamation" | "heavy_exclamation_mark" => "❗",
+        "bangbang" => "‼️",
+        "information_source" => "ℹ️",
+        "red_circle" => "🔴",
+        "orange_circle" => "🟠",
+        "yellow_circle" => "🟡",
+        "green_circle" => "🟢",
+        "large_blue_circle" | "blue_circle" => "🔵",
+        "white_circle" => "⚪",
+        "black_circle" => "⚫",
+        "arrow_right" => "➡️",
+        "arrow_left" => "⬅️",
+        "arrow_up" => "⬆️",
+        "arrow_down" => "⬇️",
+        "arrows_counterclockwise" => "🔄",
+        "repeat" => "🔁",
+        "new" => "🆕",
+        "free" => "🆓",
+        "up" => "🆙",
+        "cool" => "🆒",
+        "ok" => "🆗",
+        "sos" => "🆘",
+        "copyright" => "©️",
+        "registered" => "®️",
+        "tm" => "™️",
+        // Objects and tools
+        "rocket" => "🚀",
+        "bug" => "🐛",
+        "memo" | "pencil" => "📝",
+        "pencil2" => "✏️",
+        "book" | "open_book" => "📖",
+        "books" => "📚",
+        "bookmark" => "🔖",
+        "page_facing_up" => "📄",
+        "clipboard" => "📋",
+        "calendar" => "📆",
+        "date" => "📅",
+        "pushpin" => "📌",
+        "round_pushpin" => "📍",
+        "paperclip" => "📎",
+        "link" => "🔗",
+        "lock" => "🔒",
+        "unlock" => "🔓",
+        "key" => "🔑",
+        "mag" => "🔍",
+        "mag_right" => "🔎",
+        "bulb" => "💡",
+        "wrench" => "🔧",
+        "hammer" => "🔨",
+        "hammer_and_wrench" => "🛠️",
+        "gear" => "⚙️",
+        "nut_and_bolt" => "🔩",
+        "package" => "📦",
+        "construction" => "🚧",
+        "rotating_light" => "🚨",
+        "bell" => "🔔",
+        "no_bell" => "🔕",
+        "loudspeaker" => "📢",
+        "mega" => "📣",
+        "speech_balloon" => "💬",
+        "thought_balloon" => "💭",
+        "email" | "e-mail" => "📧",
+        "envelope" => "✉️",
+        "inbox_tray" => "📥",
+        "outbox_tray" => "📤",
+        "computer" => "💻",
+        "desktop_computer" => "🖥️",
+        "keyboard" => "⌨️",
+        "iphone" => "📱",
+        "floppy_disk" => "💾",
+        "cd" => "💿",
+        "chart_with_upwards_trend" => "📈",
+        "chart_with_downwards_trend" => "📉",
+        "bar_chart" => "📊",
+        "file_folder" => "📁",
+        "open_file_folder" => "📂",
+        "wastebasket" => "🗑️",
+        "hourglass" => "⌛",
+        "hourglass_flowing_sand" => "⏳",
+        "alarm_clock" => "⏰",
+        "stopwatch" => "⏱️",
+        "watch" => "⌚",
+        "moneybag" => "💰",
+        "dollar" => "💵",
+        "credit_card" => "💳",
+        "gem" => "💎",
+        "art" => "🎨",
+        "camera" => "📷",
+        "movie_camera" => "🎥",
+        "musical_note" => "🎵",
+        "headphones" => "🎧",
+        "microphone" => "🎤",
+        "video_game" => "🎮",
+        "dart" => "🎯",
+        "game_die" => "🎲",
+        "jigsaw" => "🧩",
+        "test_tube" => "🧪",
+        "microscope" => "🔬",
+        "telescope" => "🔭",
+        "satellite" => "📡",
+        "battery" => "🔋",
+        "electric_plug" => "🔌",
+        "recycle" => "♻️",
+        "triangular_flag_on_post" => "🚩",
+        "checkered_flag" => "🏁",
+        "white_flag" => "🏳️",
+        "label" => "🏷️",
+        "lipstick" => "💄",
+        "ring" => "💍",
+        "crown" => "👑",
+        "tophat" => "🎩",
+        "mortar_board" => "🎓",
+        "shield" => "🛡️",
+        "crossed_swords" => "⚔️",
+        "pill" => "💊",
+        "syringe" => "💉",
+        "broom" => "🧹",
+        "toolbox" => "🧰",
+        "magnet" => "🧲",
+        "bomb" => "💣",
+        // Nature, food and travel
+        "sunny" => "☀️",
+        "cloud" => "☁️",
+        "umbrella" => "☔",
+        "snowflake" => "❄️",
+        "rainbow" => "🌈",
+        "ocean" => "🌊",
+        "earth_americas" => "🌎",
+        "earth_africa" => "🌍",
+        "earth_asia" => "🌏",
+        "globe_with_meridians" => "🌐",
+        "crescent_moon" => "🌙",
+        "seedling" => "🌱",
+        "evergreen_tree" => "🌲",
+        "deciduous_tree" => "🌳",
+        "palm_tree" => "🌴",
+        "cactus" => "🌵",
+        "herb" => "🌿",
+        "four_leaf_clover" => "🍀",
+        "maple_leaf" => "🍁",
+        "fallen_leaf" => "🍂",
+        "rose" => "🌹",
+        "sunflower" => "🌻",
+        "cherry_blossom" => "🌸",
+        "tulip" => "🌷",
+        "mushroom" => "🍄",
+        "dog" => "🐶",
+        "cat" => "🐱",
+        "mouse" => "🐭",
+        "rabbit" => "🐰",
+        "fox_face" => "🦊",
+        "bear" => "🐻",
+        "panda_face" => "🐼",
+        "koala" => "🐨",
+        "tiger" => "🐯",
+        "lion" => "🦁",
+        "cow" => "🐮",
+        "pig" => "🐷",
+        "frog" => "🐸",
+        "monkey" => "🐒",
+        "chicken" => "🐔",
+        "penguin" => "🐧",
+        "bird" => "🐦",
+        "eagle" => "🦅",
+        "owl" => "🦉",
+        "bat" => "🦇",
+        "wolf" => "🐺",
+        "horse" => "🐴",
+        "unicorn" => "🦄",
+        "bee" | "honeybee" => "🐝",
+        "butterfly" => "🦋",
+        "snail" => "🐌",
+        "beetle" => "🐞",
+        "ant" => "🐜",
+        "spider" => "🕷️",
+        "turtle" => "🐢",
+        "snake" => "🐍",
+        "dragon" => "🐉",
+        "t-rex" => "🦖",
+        "whale" => "🐳",
+        "dolphin" => "🐬",
+        "fish" => "🐟",
+        "octopus" => "🐙",
+        "crab" => "🦀",
+        "apple" => "🍎",
+        "green_apple" => "🍏",
+        "lemon" => "🍋",
+        "banana" => "🍌",
+        "watermelon" => "🍉",
+        "grapes" => "🍇",
+        "strawberry" => "🍓",
+        "peach" => "🍑",
+        "cherries" => "🍒",
+        "avocado" => "🥑",
+        "tomato" => "🍅",
+        "hot_pepper" => "🌶️",
+        "corn" => "🌽",
+        "carrot" => "🥕",
+        "bread" => "🍞",
+        "cheese" => "🧀",
+        "egg" => "🥚",
+        "bacon" => "🥓",
+        "hamburger" => "🍔",
+        "fries" => "🍟",
+        "pizza" => "🍕",
+        "hotdog" => "🌭",
+        "taco" => "🌮",
+        "burrito" => "🌯",
+        "sushi" => "🍣",
+        "ramen" => "🍜",
+        "spaghetti" => "🍝",
+        "cookie" => "🍪",
+        "cake" => "🍰",
+        "birthday" => "🎂",
+        "doughnut" => "🍩",
+        "icecream" => "🍦",
+        "chocolate_bar" => "🍫",
+        "candy" => "🍬",
+        "popcorn" => "🍿",
+        "coffee" => "☕",
+        "tea" => "🍵",
+        "beer" => "🍺",
+        "beers" => "🍻",
+        "wine_glass" => "🍷",
+        "cocktail" => "🍸",
+        "champagne" => "🍾",
+        "car" | "red_car" => "🚗",
+        "taxi" => "🚕",
+        "bus" => "🚌",
+        "truck" => "🚚",
+        "bike" => "🚲",
+        "train" => "🚋",
+        "airplane" => "✈️",
+        "ship" => "🚢",
+        "anchor" => "⚓",
+        "house" => "🏠",
+        "office" => "🏢",
+        "hospital" => "🏥",
+        "school" => "🏫",
+        "tent" => "⛺",
+        "mountain" => "⛰️",
+        "volcano" => "🌋",
+        "world_map" => "🗺️",
+        "statue_of_liberty" => "🗽",
+        _ => return None,
+    })
+}
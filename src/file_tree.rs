@@ -0,0 +1,316 @@
+//! # File Tree Module
+//!
+//! This module shows the markdown files of the folder opened in folder mode as a
+//! collapsible tree, with a filter box that narrows the tree by substring or glob
+//! pattern (e.g. `adr-*.md`) and badges on files that are unread or changed since they
+//! were last read.
+
+use crate::read_state::{ReadStatus, ReadTracker};
+use crate::vault::Vault;
+use egui::collapsing_header::CollapsingState;
+use egui::Ui;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// A file or directory of the tree.
+struct Node {
+    /// File or directory name
+    name: String,
+    /// Absolute path
+    path: PathBuf,
+    /// Path relative to the folder, with `/` separators
+    relative: String,
+    /// Entries of a directory, directories first; empty for files
+    children: Vec<Node>,
+    /// Whether the node is a directory
+    is_dir: bool,
+    /// Number of files at or below this node that pass the filter
+    matches: usize,
+}
+
+impl Node {
+    fn insert(&mut self, components: &[String], path: &Path) {
+        let Some((first, rest)) = components.split_first() else {
+            return;
+        };
+        let is_dir = !rest.is_empty();
+        let index = match self
+            .children
+            .iter()
+            .position(|child| child.name == *first && child.is_dir == is_dir)
+        {
+            Some(index) => index,
+            None => {
+                let relative = if self.relative.is_empty() {
+                    first.clone()
+                } else {
+                    format!("{}/{first}", self.relative)
+                };
+                self.children.push(Node {
+                    name: first.clone(),
+                    path: if is_dir {
+                        self.path.join(first)
+                    } else {
+                        path.to_path_buf()
+                    },
+                    relative,
+                    children: Vec::new(),
+                    is_dir,
+                    matches: 0,
+                });
+                self.children.len() - 1
+            }
+        };
+        self.children[index].insert(rest, path);
+    }
+
+    fn sort(&mut self) {
+        self.children.sort_by(|a, b| {
+            b.is_dir
+                .cmp(&a.is_dir)
+                .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+        for child in &mut self.children {
+            child.sort();
+        }
+    }
+
+    /// Recounts the matching files below the node.
+    fn apply_filter(&mut self, filter: Option<&Filter>) -> usize {
+        self.matches = if self.is_dir {
+            self.children
+                .iter_mut()
+                .map(|child| child.apply_filter(filter))
+                .sum()
+        } else {
+            usize::from(filter.is_none_or(|filter| filter.matches(&self.relative, &self.name)))
+        };
+        self.matches
+    }
+}
+
+/// A parsed filter expression.
+enum Filter {
+    /// Case-insensitive substring of the relative path
+    Substring(String),
+    /// Glob pattern (`*` and `?`) matched against the file name, or against the relative
+    /// path if the pattern contains a `/`
+    Glob(Vec<char>),
+}
+
+impl Filter {
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().to_lowercase();
+        if text.is_empty() {
+            None
+        } else if text.contains(['*', '?']) {
+            Some(Filter::Glob(text.chars().collect()))
+        } else {
+            Some(Filter::Substring(text))
+        }
+    }
+
+    fn matches(&self, relative: &str, name: &str) -> bool {
+        match self {
+            Filter::Substring(text) => relative.to_lowercase().contains(text.as_str()),
+            Filter::Glob(pattern) => {
+                let subject = if pattern.contains(&'/') {
+                    relative
+                } else {
+                    name
+                };
+                let subject: Vec<char> = subject.to_lowercase().chars().collect();
+                glob_match(pattern, &subject)
+            }
+        }
+    }
+}
+
+/// Matches text against a pattern where `*` stands for any run of characters and `?`
+/// for any single character.
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// The file tree of the opened folder.
+pub struct FileTree {
+    /// Root directory node
+    root: Node,
+    /// Filter typed by the user
+    filter: String,
+    /// Modification times (seconds since the epoch) of the files
+    modified: HashMap<PathBuf, u64>,
+}
+
+impl FileTree {
+    /// Builds the tree from the files of a folder.
+    pub fn new(folder: &Vault) -> Self {
+        let mut root = Node {
+            name: String::new(),
+            path: folder.root().to_path_buf(),
+            relative: String::new(),
+            children: Vec::new(),
+            is_dir: true,
+            matches: 0,
+        };
+        for path in folder.notes() {
+            let components: Vec<String> = path
+                .strip_prefix(folder.root())
+                .unwrap_or(path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            root.insert(&components, path);
+        }
+        root.sort();
+        root.apply_filter(None);
+        Self {
+            root,
+            filter: String::new(),
+            modified: HashMap::new(),
+        }
+    }
+
+    /// Updates the modification times used to detect files changed since they were read.
+    pub fn set_modified_times(&mut self, modified: HashMap<PathBuf, u64>) {
+        self.modified = modified;
+    }
+
+    /// Rebuilds the tree from a re-indexed folder, keeping the filter.
+    pub fn refresh(&mut self, folder: &Vault) {
+        let filter = std::mem::take(&mut self.filter);
+        let modified = std::mem::take(&mut self.modified);
+        *self = Self::new(folder);
+        self.root.apply_filter(Filter::parse(&filter).as_ref());
+        self.filter = filter;
+        self.modified = modified;
+    }
+
+    /// Draws the filter box and the tree. Returns the file the user clicked, if any.
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        current_file: Option<&Path>,
+        read: &mut ReadTracker,
+    ) -> Option<PathBuf> {
+        let response = ui.add(
+            egui::TextEdit::singleline(&mut self.filter)
+                .hint_text("Filter, e.g. adr-*.md")
+                .desired_width(f32::INFINITY),
+        );
+        if response.changed() {
+            let filter = Filter::parse(&self.filter);
+            self.root.apply_filter(filter.as_ref());
+        }
+        let filtering = !self.filter.trim().is_empty();
+        if filtering {
+            ui.weak(match self.root.matches {
+                1 => "1 matching file".to_string(),
+                count => format!("{count} matching files"),
+            });
+        }
+        let status = |path: &Path| read.status(path, self.modified.get(path).copied());
+        let unread = self
+            .modified
+            .keys()
+            .filter(|path| status(path) != ReadStatus::Read)
+            .count();
+        if unread > 0 && ui.small_button(format!("Mark {unread} as read")).clicked() {
+            let paths: Vec<&Path> = self.modified.keys().map(PathBuf::as_path).collect();
+            read.mark_read(paths);
+        }
+        ui.separator();
+
+        let status = |path: &Path| read.status(path, self.modified.get(path).copied());
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                show_nodes(
+                    ui,
+                    &self.root.children,
+                    filtering,
+                    current_file,
+                    &status,
+                    &mut clicked,
+                );
+            });
+        clicked
+    }
+}
+
+fn show_nodes(
+    ui: &mut Ui,
+    nodes: &[Node],
+    filtering: bool,
+    current_file: Option<&Path>,
+    status: &dyn Fn(&Path) -> ReadStatus,
+    clicked: &mut Option<PathBuf>,
+) {
+    for node in nodes.iter().filter(|node| node.matches > 0) {
+        if !node.is_dir {
+            let is_current = current_file == Some(node.path.as_path());
+            ui.horizontal(|ui| {
+                if ui
+                    .selectable_label(is_current, &node.name)
+                    .on_hover_text(&node.relative)
+                    .clicked()
+                {
+                    *clicked = Some(node.path.clone());
+                }
+                let color = ui.visuals().hyperlink_color;
+                match status(&node.path) {
+                    ReadStatus::Read => {}
+                    ReadStatus::Unread => {
+                        ui.colored_label(color, "●").on_hover_text("Unread");
+                    }
+                    ReadStatus::Updated => {
+                        ui.colored_label(color, "↻")
+                            .on_hover_text("Changed since last read");
+                    }
+                }
+            });
+            continue;
+        }
+
+        let id = ui.make_persistent_id(("file_tree", &node.path));
+        let state = CollapsingState::load_with_default_open(ui.ctx(), id, false);
+        let is_open = state.is_open();
+        state
+            .show_header(ui, |ui| {
+                ui.label(format!("📁 {}", node.name));
+                // Collapsed directories show how many matches they hide
+                if filtering && !is_open {
+                    ui.label(
+                        egui::RichText::new(node.matches.to_string())
+                            .small()
+                            .background_color(ui.visuals().selection.bg_fill),
+                    );
+                }
+            })
+            .body(|ui| show_nodes(ui, &node.children, filtering, current_file, status, clicked));
+    }
+}
@@ -0,0 +1,211 @@
+//! # Filters Module
+//!
+//! This module runs user scripts as pre-render filters on documents — to expand custom
+//! macros, redact content and the like. Scripts are written in [Rhai](https://rhai.rs)
+//! and read from the `filters` folder of the config directory (global, in name order)
+//! and from `.mdzen-filter.rhai` in the document's folder or the nearest parent folder
+//! that has one (per folder). Folder scripts come with the documents, so they only run
+//! once the user opts in to them.
+//!
+//! A script defines either or both of these functions:
+//!
+//! - `document(markdown)` returns the new markdown text of the whole document, before it
+//!   is parsed
+//! - `text(text)` returns the new text of a run of prose in the parsed event stream
+//!   (code and raw HTML are left alone), after the document has been parsed
+//!
+//! Besides Rhai's own string functions, scripts can call `regex_replace(text, pattern,
+//! replacement)` and `redact(text)`, which masks every character with a block.
+
+use crate::storage;
+use regex::Regex;
+use rhai::{Engine, Scope, AST};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Folder of the config directory holding the global filter scripts.
+pub const GLOBAL_FILTERS_DIR: &str = "filters";
+
+/// File holding the filter script of a folder and its subfolders.
+pub const FOLDER_FILTER_FILE: &str = ".mdzen-filter.rhai";
+
+/// Operations a script may run per call, so a runaway loop can't hang the viewer.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Longest string a script may build, in bytes.
+const MAX_STRING_SIZE: usize = 16 * 1024 * 1024;
+
+/// Most elements an array or object map built by a script may hold.
+const MAX_COLLECTION_SIZE: usize = 100_000;
+
+/// Deepest a script may nest function calls.
+const MAX_CALL_LEVELS: usize = 64;
+
+/// Function filtering the whole markdown text.
+const DOCUMENT_FN: &str = "document";
+
+/// Function filtering each run of prose text.
+const TEXT_FN: &str = "text";
+
+/// A compiled filter script.
+struct Script {
+    /// File the script was read from, for error messages
+    path: PathBuf,
+    ast: AST,
+    /// Whether the script defines `document(markdown)`
+    filters_document: bool,
+    /// Whether the script defines `text(text)`
+    filters_text: bool,
+}
+
+/// Compiled filter scripts for a document.
+pub struct Filters {
+    engine: Engine,
+    /// Scripts in the order they are applied (global first, then per folder)
+    scripts: Vec<Script>,
+    /// Results of the text filters, which run on every rendered frame
+    text_cache: RefCell<HashMap<String, String>>,
+    /// Whether a text filter changed any text so far
+    text_changed: Cell<bool>,
+}
+
+impl Filters {
+    /// Loads the global scripts and, if `folder_scripts` is set, the script of the folder
+    /// containing `document`.
+    ///
+    /// Scripts that don't compile are skipped and reported on stderr.
+    pub fn for_document(document: &Path, folder_scripts: bool) -> Self {
+        let mut paths: Vec<PathBuf> = storage::config_dir()
+            .and_then(|dir| fs::read_dir(dir.join(GLOBAL_FILTERS_DIR)).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+            .collect();
+        paths.sort();
+        paths.extend(
+            document
+                .ancestors()
+                .filter(|_| folder_scripts)
+                .skip(1)
+                .map(|dir| dir.join(FOLDER_FILTER_FILE))
+                .find(|path| path.is_file()),
+        );
+
+        let engine = Self::engine();
+        let scripts = paths
+            .into_iter()
+            .filter_map(|path| match engine.compile_file(path.clone()) {
+                Ok(ast) => {
+                    let defines = |name: &str| {
+                        ast.iter_functions()
+                            .any(|function| function.name == name && function.params.len() == 1)
+                    };
+                    Some(Script {
+                        filters_document: defines(DOCUMENT_FN),
+                        filters_text: defines(TEXT_FN),
+                        path,
+                        ast,
+                    })
+                }
+                Err(e) => {
+                    eprintln!("Error in filter script {}: {e}", path.display());
+                    None
+                }
+            })
+            .collect();
+        Self {
+            engine,
+            scripts,
+            text_cache: RefCell::new(HashMap::new()),
+            text_changed: Cell::new(false),
+        }
+    }
+
+    /// Script engine with the helper functions and resource limits.
+    fn engine() -> Engine {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_string_size(MAX_STRING_SIZE)
+            .set_max_array_size(MAX_COLLECTION_SIZE)
+            .set_max_map_size(MAX_COLLECTION_SIZE)
+            .set_max_call_levels(MAX_CALL_LEVELS);
+        engine.register_fn(
+            "regex_replace",
+            |text: &str,
+             pattern: &str,
+             replacement: &str|
+             -> Result<String, Box<rhai::EvalAltResult>> {
+                let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+                Ok(regex.replace_all(text, replacement).into_owned())
+            },
+        );
+        engine.register_fn("redact", |text: &str| {
+            text.chars()
+                .map(|c| if c.is_whitespace() { c } else { '█' })
+                .collect::<String>()
+        });
+        engine
+    }
+
+    /// Whether any script filters prose text, so `apply_to_text` has work to do.
+    pub fn filters_text(&self) -> bool {
+        self.scripts.iter().any(|script| script.filters_text)
+    }
+
+    /// Runs the `document` function of every script on the markdown in order. Returns
+    /// the text borrowed when no script changed it.
+    pub fn apply<'a>(&self, markdown: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(markdown);
+        for script in self.scripts.iter().filter(|script| script.filters_document) {
+            if let Some(filtered) = self.call(script, DOCUMENT_FN, &text) {
+                if filtered != *text {
+                    text = Cow::Owned(filtered);
+                }
+            }
+        }
+        text
+    }
+
+    /// Whether `apply_to_text` changed any text so far.
+    pub fn changed_text(&self) -> bool {
+        self.text_changed.get()
+    }
+
+    /// Runs the `text` function of every script on a run of prose text in order.
+    /// Returns None when no script changed it.
+    pub fn apply_to_text(&self, text: &str) -> Option<String> {
+        if let Some(cached) = self.text_cache.borrow().get(text) {
+            return (cached != text).then(|| cached.clone());
+        }
+        let mut filtered = text.to_string();
+        for script in self.scripts.iter().filter(|script| script.filters_text) {
+            if let Some(result) = self.call(script, TEXT_FN, &filtered) {
+                filtered = result;
+            }
+        }
+        self.text_cache
+            .borrow_mut()
+            .insert(text.to_string(), filtered.clone());
+        let changed = filtered != text;
+        self.text_changed.set(self.text_changed.get() || changed);
+        changed.then_some(filtered)
+    }
+
+    /// Calls a filter function of a script, reporting errors on stderr.
+    fn call(&self, script: &Script, function: &str, text: &str) -> Option<String> {
+        self.engine
+            .call_fn::<String>(
+                &mut Scope::new(),
+                &script.ast,
+                function,
+                (text.to_string(),),
+            )
+            .map_err(|e| eprintln!("Error in filter script {}: {e}", script.path.display()))
+            .ok()
+    }
+}
@@ -0,0 +1,201 @@
+//! # Folder Search Module
+//!
+//! This module searches every markdown file of a folder for a query, in the background,
+//! and lists the matching lines grouped by file with a snippet of each. The opened
+//! folder is searched in folder mode; otherwise the folder of the current document.
+
+use crate::app::{result_snippet, SearchResult};
+use crate::search;
+use crate::vault::Vault;
+use egui::{Context, Key};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+/// Most matches collected before the search stops.
+const MAX_RESULTS: usize = 2000;
+
+/// The matches found in one file.
+pub struct FileMatches {
+    /// File the matches are in
+    pub path: PathBuf,
+    /// Matching lines, in document order
+    pub results: Vec<SearchResult>,
+}
+
+/// Searches the markdown files of a folder. Returns the files with matches, sorted by
+/// path, and whether the search stopped at [`MAX_RESULTS`].
+fn search_folder(
+    root: PathBuf,
+    query: &str,
+    case_sensitive: bool,
+    fuzzy: bool,
+) -> (Vec<FileMatches>, bool) {
+    let mut files = Vec::new();
+    let mut total = 0;
+    for path in Vault::open(root).notes() {
+        let Ok(content) = fs::read_to_string(path) else {
+            continue;
+        };
+        let mut results = Vec::new();
+        for (line_number, line) in content.lines().enumerate() {
+            for range in search::find_matches(line, query, case_sensitive, fuzzy) {
+                results.push(SearchResult {
+                    line_number,
+                    line_content: line.to_string(),
+                    match_start: range.start,
+                    match_end: range.end,
+                });
+            }
+        }
+        if results.is_empty() {
+            continue;
+        }
+        total += results.len();
+        files.push(FileMatches {
+            path: path.clone(),
+            results,
+        });
+        if total >= MAX_RESULTS {
+            return (files, true);
+        }
+    }
+    (files, false)
+}
+
+/// The "Search in Folder" window.
+pub struct FolderSearch {
+    /// Folder being searched
+    root: PathBuf,
+    /// Text typed by the user
+    pub query: String,
+    /// Whether the search is case sensitive
+    pub case_sensitive: bool,
+    /// Whether the search also finds approximate matches
+    pub fuzzy: bool,
+    /// Results of the last finished search, and whether they were truncated
+    results: Option<(Vec<FileMatches>, bool)>,
+    /// Search running in the background
+    pending: Option<mpsc::Receiver<(Vec<FileMatches>, bool)>>,
+}
+
+impl FolderSearch {
+    /// Creates the window for a folder, starting with the given query and options.
+    pub fn new(root: PathBuf, query: String, case_sensitive: bool, fuzzy: bool) -> Self {
+        Self {
+            root,
+            query,
+            case_sensitive,
+            fuzzy,
+            results: None,
+            pending: None,
+        }
+    }
+
+    /// Starts searching the folder for the current query.
+    pub fn start(&mut self, ctx: &Context) {
+        if self.query.is_empty() {
+            self.results = None;
+            self.pending = None;
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let root = self.root.clone();
+        let query = self.query.clone();
+        let (case_sensitive, fuzzy) = (self.case_sensitive, self.fuzzy);
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            let _ = sender.send(search_folder(root, &query, case_sensitive, fuzzy));
+            ctx.request_repaint();
+        });
+        self.pending = Some(receiver);
+    }
+
+    /// Draws the window. Returns the file and match the user clicked, if any; sets
+    /// `open` to false when the window is closed.
+    pub fn show(&mut self, ctx: &Context, open: &mut bool) -> Option<(PathBuf, SearchResult)> {
+        if let Some(receiver) = &self.pending {
+            if let Ok(results) = receiver.try_recv() {
+                self.results = Some(results);
+                self.pending = None;
+            }
+        }
+
+        let mut clicked = None;
+        let mut search = false;
+        egui::Window::new("Search in Folder")
+            .open(open)
+            .default_width(480.0)
+            .default_height(480.0)
+            .show(ctx, |ui| {
+                ui.weak(self.root.display().to_string());
+                ui.horizontal(|ui| {
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.query)
+                            .hint_text("Search all documents")
+                            .desired_width(240.0),
+                    );
+                    if response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter)) {
+                        search = true;
+                    }
+                    search |= ui
+                        .checkbox(&mut self.case_sensitive, "Case sensitive")
+                        .changed();
+                    search |= ui.checkbox(&mut self.fuzzy, "Fuzzy").changed();
+                    search |= ui.button("Search").clicked();
+                });
+                ui.separator();
+
+                if self.pending.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Searching…");
+                    });
+                    return;
+                }
+                let Some((files, truncated)) = &self.results else {
+                    return;
+                };
+                if files.is_empty() {
+                    ui.label("No results");
+                    return;
+                }
+                let total: usize = files.iter().map(|file| file.results.len()).sum();
+                ui.label(if *truncated {
+                    format!("First {total} matches in {} documents", files.len())
+                } else {
+                    format!("{total} matches in {} documents", files.len())
+                });
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        for file in files {
+                            let name = file
+                                .path
+                                .strip_prefix(&self.root)
+                                .unwrap_or(&file.path)
+                                .display()
+                                .to_string();
+                            egui::CollapsingHeader::new(format!("{name} ({})", file.results.len()))
+                                .id_source(&file.path)
+                                .default_open(true)
+                                .show(ui, |ui| {
+                                    for result in &file.results {
+                                        ui.horizontal(|ui| {
+                                            ui.weak(format!("{:>5}", result.line_number + 1));
+                                            let snippet = result_snippet(result, ui);
+                                            if ui.selectable_label(false, snippet).clicked() {
+                                                clicked = Some((file.path.clone(), result.clone()));
+                                            }
+                                        });
+                                    }
+                                });
+                        }
+                    });
+            });
+        if search {
+            self.start(ctx);
+        }
+        clicked
+    }
+}
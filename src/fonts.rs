@@ -0,0 +1,185 @@
+//! # Fonts Module
+//!
+//! This module sets up the font families used for document body text and code, either
+//! egui's built-in fonts or font files installed on the system, and locates an installed
+//! dyslexia-friendly font (OpenDyslexic or Atkinson Hyperlegible) for the accessibility
+//! setting.
+
+use crate::storage;
+use egui::{FontData, FontDefinitions, FontFamily};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Font family used for the body text of documents.
+pub const BODY_FAMILY: &str = "body";
+
+/// File name fragments (lowercase) of the supported dyslexia-friendly fonts, preferred first.
+const ACCESSIBLE_FONTS: &[&str] = &[
+    "opendyslexic",
+    "atkinsonhyperlegible",
+    "atkinson-hyperlegible",
+];
+
+/// Font files chosen for body text and code; None keeps egui's built-in font.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FontChoice {
+    /// Font file used for body text
+    pub body: Option<PathBuf>,
+    /// Font file used for code
+    pub monospace: Option<PathBuf>,
+    /// Font files added by the user, offered alongside the installed fonts
+    pub custom: Vec<PathBuf>,
+}
+
+/// Returns the font family used for document body text.
+pub fn body_family() -> FontFamily {
+    FontFamily::Name(BODY_FAMILY.into())
+}
+
+/// Installs the fonts, using the chosen font files and the dyslexia-friendly font for
+/// body text when requested.
+///
+/// Chosen fonts that can't be read are skipped. Returns an error (and falls back to the
+/// regular font) if no dyslexia-friendly font is installed.
+pub fn install(ctx: &egui::Context, accessible: bool, choice: &FontChoice) -> Result<(), String> {
+    let mut fonts = FontDefinitions::default();
+    let mut body = fonts
+        .families
+        .get(&FontFamily::Proportional)
+        .cloned()
+        .unwrap_or_default();
+
+    // Chosen fonts go first; the built-in ones still cover characters they lack
+    if let Some(data) = choice.body.as_deref().and_then(read_font) {
+        fonts
+            .font_data
+            .insert("chosen-body".to_owned(), FontData::from_owned(data));
+        body.insert(0, "chosen-body".to_owned());
+    }
+    if let Some(data) = choice.monospace.as_deref().and_then(read_font) {
+        fonts
+            .font_data
+            .insert("chosen-monospace".to_owned(), FontData::from_owned(data));
+        fonts
+            .families
+            .entry(FontFamily::Monospace)
+            .or_default()
+            .insert(0, "chosen-monospace".to_owned());
+    }
+
+    let result = if accessible {
+        match find_accessible_font().and_then(|path| std::fs::read(path).ok()) {
+            Some(data) => {
+                fonts
+                    .font_data
+                    .insert("accessible".to_owned(), FontData::from_owned(data));
+                body.insert(0, "accessible".to_owned());
+                Ok(())
+            }
+            None => Err(
+                "No OpenDyslexic or Atkinson Hyperlegible font found. Install one, or copy it \
+                 into the mdzen fonts folder in your config directory."
+                    .to_owned(),
+            ),
+        }
+    } else {
+        Ok(())
+    };
+
+    fonts.families.insert(body_family(), body);
+    ctx.set_fonts(fonts);
+    result
+}
+
+fn read_font(path: &Path) -> Option<Vec<u8>> {
+    std::fs::read(path)
+        .map_err(|e| eprintln!("Error reading font {}: {e}", path.display()))
+        .ok()
+}
+
+/// Name shown for a font file: its file name without the extension.
+pub fn display_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Lists the regular (non-bold, non-italic) fonts in the mdzen config folder and the
+/// usual system font folders, sorted by name.
+pub fn installed_fonts() -> Vec<PathBuf> {
+    let mut fonts: Vec<PathBuf> = font_files()
+        .into_iter()
+        .filter(|path| {
+            let name = file_name_lower(path);
+            !name.contains("bold") && !name.contains("italic") && !name.contains("oblique")
+        })
+        .collect();
+    fonts.sort_by_key(|path| file_name_lower(path));
+    fonts.dedup_by_key(|path| file_name_lower(path));
+    fonts
+}
+
+/// Searches the mdzen config folder and the usual system font folders for a regular
+/// (non-bold, non-italic) TrueType/OpenType dyslexia-friendly font.
+fn find_accessible_font() -> Option<PathBuf> {
+    let candidates = font_files();
+    ACCESSIBLE_FONTS.iter().find_map(|fragment| {
+        candidates
+            .iter()
+            .filter(|path| {
+                let name = file_name_lower(path);
+                name.contains(fragment) && !name.contains("bold") && !name.contains("italic")
+            })
+            .min_by_key(|path| file_name_lower(path).len())
+            .cloned()
+    })
+}
+
+/// Finds the TrueType/OpenType font files in the mdzen config folder and the usual
+/// system font folders.
+fn font_files() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = storage::config_dir()
+        .map(|dir| dir.join("fonts"))
+        .into_iter()
+        .collect();
+    if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+        dirs.push(home.join(".local/share/fonts"));
+        dirs.push(home.join(".fonts"));
+        dirs.push(home.join("Library/Fonts"));
+    }
+    dirs.extend(
+        [
+            "/usr/share/fonts",
+            "/usr/local/share/fonts",
+            "/Library/Fonts",
+            "C:\\Windows\\Fonts",
+        ]
+        .map(PathBuf::from),
+    );
+    if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+        dirs.push(PathBuf::from(local).join("Microsoft\\Windows\\Fonts"));
+    }
+
+    dirs.iter()
+        .filter(|dir| dir.is_dir())
+        .flat_map(|dir| {
+            walkdir::WalkDir::new(dir)
+                .max_depth(4)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.into_path())
+        })
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| matches!(ext.to_lowercase().as_str(), "ttf" | "otf"))
+        })
+        .collect()
+}
+
+fn file_name_lower(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
@@ -0,0 +1,124 @@
+//! # Formats Module
+//!
+//! This module turns documents that aren't markdown into markdown the renderer can show,
+//! so they can be opened like any note: CSV and TSV files become a table, Org-mode files
+//! are translated by the org module, and other text files (source code, configuration,
+//! ...) become a single highlighted code block.
+
+use crate::org;
+use crate::table;
+use crate::vault;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Extensions of the delimiter-separated files shown as a table.
+pub const TABLE_EXTENSIONS: &[&str] = &["csv", "tsv"];
+
+/// Bytes read to tell text files from binary ones.
+const SNIFF_LENGTH: u64 = 8192;
+
+/// Lowercase extension of a path.
+fn extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(str::to_lowercase)
+}
+
+/// Whether a file can be opened: markdown or any other text file. Files whose first bytes
+/// contain a NUL byte are taken for binary.
+pub fn can_open(path: &Path) -> bool {
+    if vault::is_markdown(path) {
+        return true;
+    }
+    let mut head = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(SNIFF_LENGTH).read_to_end(&mut head))
+        .is_ok_and(|_| !head.contains(&0))
+}
+
+/// Converts a document to markdown according to its extension. Returns None for markdown
+/// and plain text, which are shown as they are.
+pub fn to_markdown(path: &Path, source: &str) -> Option<String> {
+    if vault::is_markdown(path) {
+        return None;
+    }
+    match extension(path).as_deref() {
+        Some("txt") => None,
+        Some("csv") => Some(delimited_table(source, ',')),
+        Some("tsv") => Some(delimited_table(source, '\t')),
+        Some("org") => Some(org::to_markdown(source)),
+        Some(ext) => Some(code_block(source, ext)),
+        // Files like `Makefile` or `Dockerfile` are named after their language
+        None => {
+            let name = path.file_name()?.to_string_lossy();
+            Some(code_block(source, &name))
+        }
+    }
+}
+
+/// Wraps a whole file in a fenced code block of the given language, with a fence longer
+/// than any run of backticks in the file.
+fn code_block(source: &str, language: &str) -> String {
+    let longest_run = source.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let newline = if source.ends_with('\n') { "" } else { "\n" };
+    format!("{fence}{language}\n{source}{newline}{fence}\n")
+}
+
+/// Builds a pipe table from delimiter-separated values, taking the first row as the
+/// header. Columns holding only numbers are right-aligned.
+fn delimited_table(source: &str, delimiter: char) -> String {
+    let rows = table::parse_delimited(source, delimiter);
+    let Some((header, body)) = rows.split_first() else {
+        return String::new();
+    };
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let numeric = |column: usize| {
+        let mut cells = body
+            .iter()
+            .filter_map(|row| row.get(column))
+            .map(|cell| cell.trim())
+            .filter(|cell| !cell.is_empty())
+            .peekable();
+        cells.peek().is_some() && cells.all(|cell| cell.parse::<f64>().is_ok())
+    };
+
+    let row = |cells: &[String]| {
+        let mut line = String::from("|");
+        for column in 0..columns {
+            let cell = cells.get(column).map_or("", String::as_str);
+            line.push(' ');
+            line.push_str(&escape_cell(cell.trim()));
+            line.push_str(" |");
+        }
+        line.push('\n');
+        line
+    };
+    let mut markdown = row(header);
+    markdown.push('|');
+    for column in 0..columns {
+        markdown.push_str(if numeric(column) { " ---: |" } else { " --- |" });
+    }
+    markdown.push('\n');
+    for cells in body {
+        markdown.push_str(&row(cells));
+    }
+    markdown
+}
+
+/// Escapes the characters of a cell that markdown would otherwise interpret.
+fn escape_cell(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\n' | '\r' => escaped.push(' '),
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '~' | '$' | '=' | ':' | '&' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,101 @@
+//! # Front Matter Module
+//!
+//! This module reads the YAML front matter at the top of a document and substitutes
+//! `{{ key }}` placeholders in the body with its values, the way static-site generators
+//! do when they build the page.
+
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// Splits off the front matter block, returning its YAML and the byte offset of the body.
+fn split(markdown: &str) -> Option<(&str, usize)> {
+    let rest = markdown
+        .strip_prefix("---\n")
+        .or_else(|| markdown.strip_prefix("---\r\n"))?;
+    let yaml_start = markdown.len() - rest.len();
+    let mut offset = yaml_start;
+    for line in rest.split_inclusive('\n') {
+        if matches!(line.trim_end(), "---" | "...") {
+            return Some((&markdown[yaml_start..offset], offset + line.len()));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+/// The document without its front matter block, if it has one.
+pub fn strip(markdown: &str) -> &str {
+    split(markdown).map_or(markdown, |(_, body_start)| &markdown[body_start..])
+}
+
+/// Parses the front matter into flat `key → value` pairs.
+///
+/// Nested mappings are flattened with dots (`author.name`) and lists of scalars are
+/// joined with commas. Returns None if the document has no valid front matter.
+pub fn parse(markdown: &str) -> Option<(HashMap<String, String>, usize)> {
+    let (yaml, body_start) = split(markdown)?;
+    let document = YamlLoader::load_from_str(yaml).ok()?.into_iter().next()?;
+    let mut values = HashMap::new();
+    flatten(&document, String::new(), &mut values);
+    Some((values, body_start))
+}
+
+fn flatten(yaml: &Yaml, prefix: String, values: &mut HashMap<String, String>) {
+    match yaml {
+        Yaml::Hash(hash) => {
+            for (key, value) in hash {
+                let Some(key) = scalar(key) else { continue };
+                let key = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten(value, key, values);
+            }
+        }
+        Yaml::Array(items) => {
+            let items: Vec<String> = items.iter().filter_map(scalar).collect();
+            values.insert(prefix, items.join(", "));
+        }
+        other => {
+            if let Some(value) = scalar(other) {
+                values.insert(prefix, value);
+            }
+        }
+    }
+}
+
+fn scalar(yaml: &Yaml) -> Option<String> {
+    match yaml {
+        Yaml::String(s) | Yaml::Real(s) => Some(s.clone()),
+        Yaml::Integer(i) => Some(i.to_string()),
+        Yaml::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Replaces `{{ key }}` placeholders in the body with front matter values.
+///
+/// Placeholders without a matching key are left as they are.
+pub fn substitute(markdown: &str) -> Cow<'_, str> {
+    static PLACEHOLDER: OnceLock<Regex> = OnceLock::new();
+    let Some((values, body_start)) = parse(markdown) else {
+        return Cow::Borrowed(markdown);
+    };
+    let placeholder = PLACEHOLDER
+        .get_or_init(|| Regex::new(r"\{\{\s*([\w.-]+)\s*\}\}").expect("valid placeholder regex"));
+
+    let (front_matter, body) = markdown.split_at(body_start);
+    match placeholder.replace_all(body, |caps: &Captures| {
+        values
+            .get(&caps[1])
+            .cloned()
+            .unwrap_or_else(|| caps[0].to_owned())
+    }) {
+        Cow::Borrowed(_) => Cow::Borrowed(markdown),
+        Cow::Owned(body) => Cow::Owned(format!("{front_matter}{body}")),
+    }
+}
@@ -0,0 +1,70 @@
+//! # History Module
+//!
+//! This module keeps browser-style back and forward stacks of the places the reader
+//! navigated away from: opening another document, following a `#fragment` link, or
+//! jumping through the table of contents or the go-to dialog.
+
+use std::path::PathBuf;
+
+/// Maximum number of locations kept in each direction.
+const MAX_ENTRIES: usize = 100;
+
+/// A position in a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    /// Document shown
+    pub file: PathBuf,
+    /// Vertical scroll offset, in points
+    pub scroll_offset: f32,
+}
+
+/// Back and forward navigation stacks.
+#[derive(Debug, Default)]
+pub struct History {
+    /// Locations navigated away from, most recent last
+    back: Vec<Location>,
+    /// Locations gone back from, most recent last
+    forward: Vec<Location>,
+}
+
+impl History {
+    /// Records the location being left by a new navigation, which drops the forward stack.
+    pub fn visit(&mut self, from: Location) {
+        if self.back.last() != Some(&from) {
+            push_bounded(&mut self.back, from);
+        }
+        self.forward.clear();
+    }
+
+    /// Steps back, returning the location to show.
+    pub fn back(&mut self, from: Location) -> Option<Location> {
+        let target = self.back.pop()?;
+        push_bounded(&mut self.forward, from);
+        Some(target)
+    }
+
+    /// Steps forward again, returning the location to show.
+    pub fn forward(&mut self, from: Location) -> Option<Location> {
+        let target = self.forward.pop()?;
+        push_bounded(&mut self.back, from);
+        Some(target)
+    }
+
+    /// Whether there is a location to go back to.
+    pub fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    /// Whether there is a location to go forward to.
+    pub fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+}
+
+/// Pushes a location, dropping the oldest one when the stack is full.
+fn push_bounded(stack: &mut Vec<Location>, location: Location) {
+    if stack.len() == MAX_ENTRIES {
+        stack.remove(0);
+    }
+    stack.push(location);
+}
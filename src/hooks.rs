@@ -0,0 +1,178 @@
+//! # Command Hooks Module
+//!
+//! This module runs user-defined external commands (e.g. "translate selection" or
+//! "open in pandoc") configured in `commands.json` in the config directory. Commands
+//! receive the current file or selection through placeholders and run in the background.
+
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+
+/// File in the config directory listing the command hooks.
+pub const HOOKS_FILE: &str = "commands.json";
+
+/// A user-defined external command.
+///
+/// The command line is split into arguments like a shell would (honouring quotes), but
+/// is not run through a shell. The placeholders `{file}`, `{dir}` and `{selection}`
+/// are replaced inside each argument.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandHook {
+    /// Name shown in the Commands menu
+    pub name: String,
+    /// Command line to run, e.g. `pandoc {file} -o /tmp/out.pdf`
+    pub command: String,
+}
+
+/// Loads the configured command hooks.
+pub fn load() -> Vec<CommandHook> {
+    storage::load_json(HOOKS_FILE)
+}
+
+/// Whether the command uses the `{selection}` placeholder.
+pub fn uses_selection(hook: &CommandHook) -> bool {
+    hook.command.contains("{selection}")
+}
+
+/// A command started from the Commands menu.
+pub struct HookRun {
+    /// Name of the command
+    pub name: String,
+    /// Receives the result once the command has finished
+    receiver: Option<mpsc::Receiver<Result<String, String>>>,
+    /// Combined stdout/stderr, or why the command couldn't be run
+    output: Option<Result<String, String>>,
+}
+
+impl HookRun {
+    /// Starts a command in the background.
+    pub fn start(hook: &CommandHook, file: Option<&Path>, selection: &str) -> Self {
+        let (receiver, output) = match spawn(hook, file, selection) {
+            Ok(receiver) => (Some(receiver), None),
+            Err(e) => (None, Some(Err(e))),
+        };
+        Self {
+            name: hook.name.clone(),
+            receiver,
+            output,
+        }
+    }
+
+    /// Checks whether the command has finished; returns whether it is still running.
+    pub fn poll(&mut self) -> bool {
+        if let Some(receiver) = &self.receiver {
+            match receiver.try_recv() {
+                Ok(result) => {
+                    self.output = Some(result);
+                    self.receiver = None;
+                }
+                Err(mpsc::TryRecvError::Empty) => return true,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.output = Some(Err("Command ended without output".to_owned()));
+                    self.receiver = None;
+                }
+            }
+        }
+        false
+    }
+
+    /// Output of the finished command, if available.
+    pub fn output(&self) -> Option<&Result<String, String>> {
+        self.output.as_ref()
+    }
+}
+
+fn spawn(
+    hook: &CommandHook,
+    file: Option<&Path>,
+    selection: &str,
+) -> Result<mpsc::Receiver<Result<String, String>>, String> {
+    let file_str = file.map(|f| f.display().to_string()).unwrap_or_default();
+    let dir_str = file
+        .and_then(Path::parent)
+        .map(|d| d.display().to_string())
+        .unwrap_or_default();
+    let args: Vec<String> = split_command_line(&hook.command)
+        .into_iter()
+        .map(|arg| {
+            arg.replace("{file}", &file_str)
+                .replace("{dir}", &dir_str)
+                .replace("{selection}", selection)
+        })
+        .collect();
+    let (program, args) = args
+        .split_first()
+        .ok_or_else(|| format!("Command \"{}\" is empty", hook.name))?;
+
+    let mut command = Command::new(program);
+    command.args(args).stdin(Stdio::null());
+    if let Some(dir) = file.and_then(Path::parent) {
+        command.current_dir(dir);
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let result = command
+            .output()
+            .map_err(|e| format!("Error running command: {e}"))
+            .map(|output| {
+                let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if !stderr.trim().is_empty() {
+                    if !text.is_empty() && !text.ends_with('\n') {
+                        text.push('\n');
+                    }
+                    text.push_str(&stderr);
+                }
+                if !output.status.success() {
+                    text.push_str(&format!("\n[{}]", output.status));
+                }
+                text
+            });
+        let _ = sender.send(result);
+    });
+    Ok(receiver)
+}
+
+/// Splits a command line into arguments, honouring single and double quotes and
+/// backslash escapes (outside single quotes).
+pub fn split_command_line(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => current.push(c),
+            (_, '\\') => {
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                in_arg = true;
+            }
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (_, c) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
@@ -0,0 +1,314 @@
+//! # HTML Module
+//!
+//! This module splits the raw HTML found in markdown documents into tags and text, so
+//! the renderer can map a small, safe subset of tags (`<br>`, `<b>`, `<sup>`, `<kbd>`,
+//! `<img>`, ...) to formatting, and reads `<table>` markup into rows of cells. Nothing is
+//! executed or fetched here; tags outside the subset are shown as literal text by the
+//! renderer.
+
+use pulldown_cmark::Alignment;
+use std::borrow::Cow;
+
+/// A piece of raw HTML.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HtmlToken<'a> {
+    /// Text between tags, with character references still encoded
+    Text(&'a str),
+    /// An opening, closing or self-closing tag
+    Tag(HtmlTag<'a>),
+    /// A `<!-- comment -->`
+    Comment(&'a str),
+}
+
+/// A single HTML tag.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtmlTag<'a> {
+    /// Lowercase tag name
+    pub name: String,
+    /// Whether this is a closing tag (`</b>`)
+    pub closing: bool,
+    /// The tag as written, including the angle brackets
+    pub source: &'a str,
+}
+
+impl HtmlTag<'_> {
+    /// Value of an attribute (e.g. `src` of an `<img>`), with character references decoded.
+    pub fn attribute(&self, name: &str) -> Option<String> {
+        let inner = self.source.trim_start_matches('<').trim_end_matches('>');
+        let mut rest = inner.get(self.name.len()..)?;
+        loop {
+            rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+            if rest.is_empty() {
+                return None;
+            }
+            let key_end = rest
+                .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+                .unwrap_or(rest.len());
+            let key = &rest[..key_end];
+            rest = rest[key_end..].trim_start();
+            let value = match rest.strip_prefix('=') {
+                Some(after) => {
+                    let after = after.trim_start();
+                    let (value, remaining) = match after.chars().next() {
+                        Some(quote @ ('"' | '\'')) => {
+                            let body = &after[1..];
+                            let end = body.find(quote).unwrap_or(body.len());
+                            (&body[..end], body.get(end + 1..).unwrap_or(""))
+                        }
+                        _ => {
+                            let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                            (&after[..end], &after[end..])
+                        }
+                    };
+                    rest = remaining;
+                    value
+                }
+                None => "",
+            };
+            if key.eq_ignore_ascii_case(name) {
+                return Some(decode_entities(value).into_owned());
+            }
+        }
+    }
+}
+
+/// A table read from `<table>` markup, with the text of each cell.
+#[derive(Debug, Default)]
+pub struct HtmlTable {
+    /// Cells of the header row; empty if the table has none
+    pub headers: Vec<String>,
+    /// Cells of the body rows
+    pub rows: Vec<Vec<String>>,
+    /// Alignment of each column, from the `align` or `text-align` of its cells
+    pub alignments: Vec<Alignment>,
+}
+
+impl HtmlTable {
+    /// Reads a table from the tokens following its opening `<table>` tag, up to and
+    /// including the matching `</table>`. Nested tables are flattened into the text of
+    /// the enclosing cell.
+    pub fn parse<'a>(tokens: &mut impl Iterator<Item = HtmlToken<'a>>) -> Self {
+        let mut table = Self::default();
+        // Rows as read, each with whether it is a header row: inside `<thead>`, or made of
+        // `<th>` cells only
+        let mut rows: Vec<(Vec<String>, bool)> = Vec::new();
+        let mut cell: Option<String> = None;
+        let mut in_head = false;
+        let mut depth = 0;
+        for token in tokens {
+            let tag = match token {
+                HtmlToken::Text(text) => {
+                    if let Some(cell) = &mut cell {
+                        cell.push_str(&decode_entities(text));
+                    }
+                    continue;
+                }
+                HtmlToken::Comment(_) => continue,
+                HtmlToken::Tag(tag) => tag,
+            };
+            match (tag.name.as_str(), tag.closing) {
+                ("table", false) => depth += 1,
+                ("table", true) if depth == 0 => break,
+                ("table", true) => depth -= 1,
+                _ if depth > 0 => {}
+                ("thead", closing) => in_head = !closing,
+                ("tr", false) => rows.push((Vec::new(), in_head)),
+                ("td" | "th", false) => {
+                    if rows.is_empty() {
+                        rows.push((Vec::new(), in_head));
+                    }
+                    if let Some((cells, header)) = rows.last_mut() {
+                        if cells.is_empty() {
+                            *header |= tag.name == "th";
+                        } else {
+                            *header &= tag.name == "th" || in_head;
+                        }
+                        let column = cells.len();
+                        if table.alignments.len() <= column {
+                            table.alignments.resize(column + 1, Alignment::None);
+                        }
+                        if table.alignments[column] == Alignment::None {
+                            table.alignments[column] = cell_alignment(&tag);
+                        }
+                    }
+                    cell = Some(String::new());
+                }
+                ("td" | "th", true) | ("tr", true) => {
+                    if let (Some(text), Some((cells, _))) = (cell.take(), rows.last_mut()) {
+                        cells.push(text.split_whitespace().collect::<Vec<_>>().join(" "));
+                    }
+                }
+                ("br", _) => {
+                    if let Some(cell) = &mut cell {
+                        cell.push(' ');
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let (Some(text), Some((cells, _))) = (cell, rows.last_mut()) {
+            cells.push(text.split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+
+        let mut rows = rows
+            .into_iter()
+            .filter(|(cells, _)| !cells.is_empty())
+            .peekable();
+        if let Some((cells, _)) = rows.next_if(|(_, header)| *header) {
+            table.headers = cells;
+        }
+        table.rows = rows.map(|(cells, _)| cells).collect();
+        table
+    }
+}
+
+/// Alignment given to a table cell by its `align` attribute or `text-align` style.
+fn cell_alignment(tag: &HtmlTag) -> Alignment {
+    let style = tag
+        .attribute("style")
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let align = tag
+        .attribute("align")
+        .map(|align| align.to_ascii_lowercase())
+        .or_else(|| {
+            style.split(';').find_map(|rule| {
+                let (property, value) = rule.split_once(':')?;
+                (property.trim() == "text-align").then(|| value.trim().to_string())
+            })
+        });
+    match align.as_deref() {
+        Some("left") => Alignment::Left,
+        Some("center") => Alignment::Center,
+        Some("right") => Alignment::Right,
+        _ => Alignment::None,
+    }
+}
+
+/// Splits raw HTML into text, tags and comments.
+pub fn tokenize(html: &str) -> Vec<HtmlToken<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            tokens.push(HtmlToken::Text(rest));
+            break;
+        };
+        if start > 0 {
+            tokens.push(HtmlToken::Text(&rest[..start]));
+        }
+        let candidate = &rest[start..];
+        if let Some(body) = candidate.strip_prefix("<!--") {
+            let end = body.find("-->").unwrap_or(body.len());
+            tokens.push(HtmlToken::Comment(body[..end].trim()));
+            rest = body.get(end + 3..).unwrap_or("");
+            continue;
+        }
+        match parse_tag(candidate) {
+            Some(tag) => {
+                rest = &candidate[tag.source.len()..];
+                tokens.push(HtmlToken::Tag(tag));
+            }
+            None => {
+                // A lone `<` is just text
+                tokens.push(HtmlToken::Text(&candidate[..1]));
+                rest = &candidate[1..];
+            }
+        }
+    }
+    tokens
+}
+
+/// Parses the tag at the start of `text`, if it starts with one.
+pub fn parse_tag(text: &str) -> Option<HtmlTag<'_>> {
+    let body = text.strip_prefix('<')?;
+    let (closing, body) = match body.strip_prefix('/') {
+        Some(body) => (true, body),
+        None => (false, body),
+    };
+    let name_len = body
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(body.len());
+    if name_len == 0 || !body.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    // Find the closing `>`, skipping over quoted attribute values
+    let mut quote = None;
+    let mut end = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => {
+                end = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    Some(HtmlTag {
+        name: body[..name_len].to_ascii_lowercase(),
+        closing,
+        source: &text[..end? + 1],
+    })
+}
+
+/// Decodes the common named and numeric character references (`&amp;`, `&#169;`, ...).
+pub fn decode_entities(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let candidate = &rest[start..];
+        let decoded = candidate
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| {
+                let name = &candidate[1..end];
+                let c = match name {
+                    "amp" => '&',
+                    "lt" => '<',
+                    "gt" => '>',
+                    "quot" => '"',
+                    "apos" => '\'',
+                    "nbsp" => '\u{a0}',
+                    "copy" => '©',
+                    "reg" => '®',
+                    "trade" => '™',
+                    "hellip" => '…',
+                    "mdash" => '—',
+                    "ndash" => '–',
+                    "middot" => '·',
+                    "times" => '×',
+                    "larr" => '←',
+                    "rarr" => '→',
+                    _ => {
+                        let number = name.strip_prefix('#')?;
+                        let code = match number.strip_prefix(['x', 'X']) {
+                            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                            None => number.parse().ok()?,
+                        };
+                        char::from_u32(code)?
+                    }
+                };
+                Some((c, end))
+            });
+        match decoded {
+            Some((c, end)) => {
+                result.push(c);
+                rest = &candidate[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &candidate[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    Cow::Owned(result)
+}
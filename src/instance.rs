@@ -0,0 +1,129 @@
+//! # Instance Module
+//!
+//! This module keeps mdzen to a single window: the first instance listens on a Unix
+//! socket in the user's runtime directory, and later invocations with files hand them
+//! over that socket, along with their view flags, and exit instead of opening another
+//! window. `--new-window` skips the hand-off. Other platforms always open a new window.
+
+use crate::cli::StartupOptions;
+use egui::Context;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::mpsc;
+
+/// A document to open, from the command line or handed over by another invocation.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OpenRequest {
+    /// Absolute path of the document
+    pub file: PathBuf,
+    /// Heading to scroll to
+    pub anchor: Option<String>,
+}
+
+/// What a later invocation hands over to the running instance.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HandOff {
+    /// Documents to open, the first one shown
+    pub requests: Vec<OpenRequest>,
+    /// View flags given along with the documents
+    #[serde(default)]
+    pub options: StartupOptions,
+}
+
+/// The listening side of the running instance.
+pub struct Instance {
+    /// Hand-offs received from other invocations
+    hand_offs: mpsc::Receiver<HandOff>,
+    /// Socket file, removed when the instance exits
+    #[cfg(unix)]
+    socket: PathBuf,
+}
+
+/// Location of the socket shared by the instances of the current user.
+#[cfg(unix)]
+fn socket_path() -> PathBuf {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join("mdzen.sock"),
+        None => {
+            let user = std::env::var("USER").unwrap_or_default();
+            std::env::temp_dir().join(format!("mdzen-{user}.sock"))
+        }
+    }
+}
+
+/// Sends documents and flags to the running instance. Returns false if there is none.
+#[cfg(unix)]
+pub fn hand_off(hand_off: &HandOff) -> bool {
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+
+    let Ok(mut stream) = UnixStream::connect(socket_path()) else {
+        return false;
+    };
+    let Ok(mut message) = serde_json::to_string(hand_off) else {
+        return false;
+    };
+    message.push('\n');
+    stream.write_all(message.as_bytes()).is_ok()
+}
+
+#[cfg(not(unix))]
+pub fn hand_off(_hand_off: &HandOff) -> bool {
+    false
+}
+
+impl Instance {
+    /// Starts listening for requests from later invocations, replacing the socket left
+    /// behind by an instance that didn't exit cleanly.
+    #[cfg(unix)]
+    pub fn listen(ctx: &Context) -> Option<Self> {
+        use std::io::{BufRead, BufReader};
+        use std::os::unix::net::{UnixListener, UnixStream};
+
+        let socket = socket_path();
+        if UnixStream::connect(&socket).is_ok() {
+            // Another instance (started with --new-window) keeps serving requests
+            return None;
+        }
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket)
+            .map_err(|e| eprintln!("Error listening on {}: {e}", socket.display()))
+            .ok()?;
+
+        let (sender, hand_offs) = mpsc::channel();
+        let ctx = ctx.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                    match serde_json::from_str(&line) {
+                        Ok(hand_off) => {
+                            if sender.send(hand_off).is_err() {
+                                return;
+                            }
+                            ctx.request_repaint();
+                        }
+                        Err(e) => eprintln!("Error reading open request: {e}"),
+                    }
+                }
+            }
+        });
+        Some(Self { hand_offs, socket })
+    }
+
+    #[cfg(not(unix))]
+    pub fn listen(_ctx: &Context) -> Option<Self> {
+        None
+    }
+
+    /// Takes the next hand-off received, if any.
+    pub fn next_hand_off(&self) -> Option<HandOff> {
+        self.hand_offs.try_recv().ok()
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Instance {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
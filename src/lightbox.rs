@@ -0,0 +1,186 @@
+//! # Lightbox Module
+//!
+//! This module implements the image viewer opened by clicking an image in the document.
+//! It covers the window with the image fitted to the screen, and can zoom with the
+//! scroll wheel or the toolbar, pan by dragging, and toggle between fitting and actual
+//! size with a double-click. Images can be saved to a file from the viewer or from the
+//! image's context menu.
+
+use egui::{Color32, Context, Rect, Sense, TextureHandle, Vec2};
+use std::fs;
+use std::sync::Arc;
+
+/// Range of zoom factors, relative to the image's actual size.
+const ZOOM_RANGE: std::ops::RangeInclusive<f32> = 0.05..=32.0;
+
+/// Zoom factor applied by the toolbar buttons.
+const ZOOM_STEP: f32 = 1.25;
+
+/// An image shown in the viewer.
+pub struct Lightbox {
+    /// Source of the image, as written in the document
+    url: String,
+    /// Texture of the image
+    texture: TextureHandle,
+    /// Encoded bytes of the image, for saving it
+    bytes: Option<Arc<[u8]>>,
+    /// Zoom factor, or None to fit the image to the window
+    zoom: Option<f32>,
+    /// Offset of the image's center from the window's center, in points
+    pan: Vec2,
+}
+
+impl Lightbox {
+    /// Opens the viewer on an image, fitted to the window.
+    pub fn new(url: String, texture: TextureHandle, bytes: Option<Arc<[u8]>>) -> Self {
+        Self {
+            url,
+            texture,
+            bytes,
+            zoom: None,
+            pan: Vec2::ZERO,
+        }
+    }
+
+    /// Zoom factor that fits the image in a rect, never enlarging it.
+    fn fit_zoom(&self, area: Rect) -> f32 {
+        let size = self.texture.size_vec2();
+        (area.width() / size.x).min(area.height() / size.y).min(1.0)
+    }
+
+    /// Changes the zoom by a factor, keeping the point under `anchor` in place.
+    fn zoom_by(&mut self, factor: f32, current: f32, area: Rect, anchor: egui::Pos2) {
+        let zoom = (current * factor).clamp(*ZOOM_RANGE.start(), *ZOOM_RANGE.end());
+        let factor = zoom / current;
+        let image_center = area.center() + self.pan;
+        self.pan = anchor - (anchor - image_center) * factor - area.center();
+        self.zoom = Some(zoom);
+    }
+
+    /// Draws the viewer over the window. Returns false once it is closed.
+    pub fn show(&mut self, ctx: &Context) -> bool {
+        let mut open = true;
+        let screen = ctx.screen_rect();
+        egui::Area::new(egui::Id::new("lightbox"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(screen.min)
+            .show(ctx, |ui| {
+                ui.set_min_size(screen.size());
+                ui.painter()
+                    .rect_filled(screen, 0.0, Color32::from_black_alpha(230));
+
+                let toolbar = Rect::from_min_size(screen.min, egui::vec2(screen.width(), 36.0));
+                let area =
+                    Rect::from_min_max(egui::pos2(screen.left(), toolbar.bottom()), screen.max)
+                        .shrink(16.0);
+                let fit = self.fit_zoom(area);
+                let zoom = self.zoom.unwrap_or(fit);
+
+                // Wheel zooms around the pointer, dragging pans
+                let response = ui.interact(area, ui.id().with("image"), Sense::click_and_drag());
+                if response.dragged() {
+                    self.pan += response.drag_delta();
+                    self.zoom = Some(zoom);
+                }
+                if response.hovered() {
+                    let scroll = ui.input(|i| i.smooth_scroll_delta.y);
+                    if scroll != 0.0 {
+                        let anchor = ui.input(|i| i.pointer.hover_pos()).unwrap_or(area.center());
+                        self.zoom_by((scroll * 0.002).exp(), zoom, area, anchor);
+                    }
+                }
+                if response.double_clicked() {
+                    if self.zoom.is_some() {
+                        self.zoom = None;
+                        self.pan = Vec2::ZERO;
+                    } else {
+                        let anchor = response.interact_pointer_pos().unwrap_or(area.center());
+                        self.zoom_by(1.0 / zoom, zoom, area, anchor);
+                    }
+                }
+                if response.hovered() || response.dragged() {
+                    ui.ctx().set_cursor_icon(if response.dragged() {
+                        egui::CursorIcon::Grabbing
+                    } else {
+                        egui::CursorIcon::Grab
+                    });
+                }
+
+                let zoom = self.zoom.unwrap_or(fit);
+                let image_rect = Rect::from_center_size(
+                    area.center() + self.pan,
+                    self.texture.size_vec2() * zoom,
+                );
+                ui.painter().with_clip_rect(area.expand(16.0)).image(
+                    self.texture.id(),
+                    image_rect,
+                    Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                    Color32::WHITE,
+                );
+
+                ui.allocate_ui_at_rect(toolbar.shrink2(egui::vec2(8.0, 4.0)), |ui| {
+                    ui.horizontal_centered(|ui| {
+                        if ui.selectable_label(self.zoom.is_none(), "Fit").clicked() {
+                            self.zoom = None;
+                            self.pan = Vec2::ZERO;
+                        }
+                        if ui
+                            .selectable_label(self.zoom == Some(1.0), "100%")
+                            .clicked()
+                        {
+                            self.zoom = Some(1.0);
+                            self.pan = Vec2::ZERO;
+                        }
+                        if ui.button("−").on_hover_text("Zoom out").clicked() {
+                            self.zoom_by(1.0 / ZOOM_STEP, zoom, area, area.center());
+                        }
+                        ui.label(format!("{:.0}%", zoom * 100.0));
+                        if ui.button("+").on_hover_text("Zoom in").clicked() {
+                            self.zoom_by(ZOOM_STEP, zoom, area, area.center());
+                        }
+                        if (self.url.starts_with("http://") || self.url.starts_with("https://"))
+                            && ui.button("Open in Browser").clicked()
+                        {
+                            let _ = webbrowser::open(&self.url);
+                        }
+                        if let Some(bytes) = &self.bytes {
+                            if ui.button("Save As…").clicked() {
+                                save_image(&self.url, bytes);
+                            }
+                        }
+                        ui.weak(&self.url);
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.button("✕").on_hover_text("Close (Esc)").clicked() {
+                                open = false;
+                            }
+                        });
+                    });
+                });
+            });
+        open
+    }
+}
+
+/// Asks for a file name, suggesting the image's own, and writes the image's bytes to it.
+pub fn save_image(url: &str, bytes: &[u8]) {
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("image");
+    // Keep the file openable when the URL has no extension
+    let name = match image::guess_format(bytes) {
+        Ok(format) if !name.contains('.') => {
+            let extension = format.extensions_str().first().copied().unwrap_or("img");
+            format!("{name}.{extension}")
+        }
+        _ => name.to_string(),
+    };
+    let Some(path) = rfd::FileDialog::new().set_file_name(name).save_file() else {
+        return;
+    };
+    if let Err(e) = fs::write(&path, bytes) {
+        eprintln!("Error saving image: {e}");
+    }
+}
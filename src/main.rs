@@ -10,43 +10,136 @@
 //! - File drag-and-drop support
 //! - Wide/normal viewing modes
 
+mod accessibility;
+mod annotations;
 mod app;
+mod bookmarks;
+mod callout;
+mod cli;
+mod critic;
+mod dashboard;
+mod emoji;
+mod file_tree;
+mod filters;
+mod folder_search;
+mod fonts;
+mod formats;
+mod frontmatter;
+mod history;
+mod hooks;
+mod html;
+mod instance;
+mod lightbox;
 mod markdown;
+mod math;
+mod org;
+mod pane;
+mod plantuml;
+mod plugins;
+mod preferences;
+mod preprocess;
+mod presentation;
+mod read_state;
+mod saved_searches;
+mod scratchpad;
+mod search;
+mod speech;
+mod stats;
+mod storage;
+mod switcher;
+mod table;
+mod theme;
+mod vault;
+mod view_settings;
+mod watcher;
 
 use app::MarkdownReaderApp;
+use cli::Args;
 use std::env;
+use std::path::PathBuf;
 
 /// Main entry point for mdzen.
 ///
 /// Sets up the egui application with a native window and initializes the markdown reader.
-/// If a file path is provided as a command line argument, it will be loaded automatically.
+/// Files provided as command line arguments are loaded automatically: the first one is
+/// shown and the others listed to switch to. A `#section` suffix (`notes.md#benchmarks`)
+/// scrolls to that heading. Flags such as
+/// `--wide` or `--font-size 18` override the startup view (see `mdzen --help`).
 fn main() -> Result<(), eframe::Error> {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {e}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", cli::USAGE);
+        return Ok(());
+    }
+    if args.version {
+        println!("mdzen {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+
+    let mut requests = Vec::new();
+    for file in &args.files {
+        let (file_path, anchor) = split_anchor(file);
+        match file_path.canonicalize() {
+            Ok(file) => requests.push(instance::OpenRequest {
+                file,
+                anchor: anchor.map(str::to_string),
+            }),
+            Err(e) => eprintln!("Error opening {}: {e}", file_path.display()),
+        }
+    }
+
+    // A running instance opens the files in its window instead
+    if !args.new_window && !requests.is_empty() {
+        let hand_off = instance::HandOff {
+            requests,
+            options: args.options.clone(),
+        };
+        if instance::hand_off(&hand_off) {
+            return Ok(());
+        }
+        requests = hand_off.requests;
+    }
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([800.0, 600.0])
+            .with_inner_size(args.options.window_size.unwrap_or([800.0, 600.0]))
             .with_title("mdzen")
             .with_icon(eframe::icon_data::from_png_bytes(&[]).unwrap_or_default()),
+        // A size given on the command line wins over the one saved by the last session
+        persist_window: args.options.window_size.is_none(),
         ..Default::default()
     };
 
     eframe::run_native(
         "mdzen",
         options,
-        Box::new(|cc| {
+        Box::new(move |cc| {
             let mut app = MarkdownReaderApp::new(cc);
 
-            // Check if a file was passed as command line argument
-            let args: Vec<String> = env::args().collect();
-            if args.len() > 1 {
-                let file_path = std::path::PathBuf::from(&args[1]);
-                if file_path.exists() {
-                    if let Err(e) = app.load_file(file_path) {
-                        eprintln!("Error loading file: {e}");
-                    }
-                }
-            }
+            // Open the files passed as command line arguments
+            app.open_files(requests);
+            app.apply_startup_options(&cc.egui_ctx, &args.options);
 
             Ok(Box::new(app))
         }),
     )
 }
+
+/// Splits a `file.md#section` argument into the file and the heading anchor. Files whose
+/// name contains `#` are left whole when they exist.
+fn split_anchor(argument: &str) -> (PathBuf, Option<&str>) {
+    let whole = PathBuf::from(argument);
+    if whole.exists() {
+        return (whole, None);
+    }
+    match argument.rsplit_once('#') {
+        Some((file, anchor)) if !anchor.is_empty() => (PathBuf::from(file), Some(anchor)),
+        _ => (whole, None),
+    }
+}
@@ -4,15 +4,39 @@
 //! for parsing and egui for display. It includes syntax highlighting for code blocks,
 //! image loading, search highlighting, and various markdown elements.
 
+use crate::accessibility;
+use crate::annotations::{Annotation, AnnotationKind};
 use crate::app::SearchResult;
+use crate::callout::{Callout, CalloutFold};
+use crate::critic::{self, CriticKind};
+use crate::emoji;
+use crate::filters::Filters;
+use crate::fonts;
+use crate::html::{self, HtmlTable, HtmlTag, HtmlToken};
+use crate::lightbox;
+use crate::math;
+use crate::plugins::{FenceContext, FenceRenderer};
+use crate::search::{self, SearchScope};
+use crate::storage;
+use crate::table::{self, TableCommand, TableState};
+use crate::theme;
+use crate::vault::{self, Vault};
 use egui::text::LayoutJob;
 use egui::*;
-use pulldown_cmark::{CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+use pulldown_cmark::{
+    Alignment, CodeBlockKind, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd,
+};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, SystemTime};
 use syntect::easy::HighlightLines;
-use syntect::highlighting::ThemeSet;
-use syntect::parsing::SyntaxSet;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::{SyntaxDefinition, SyntaxSet};
 use syntect::util::LinesWithEndings;
 
 /// Handles rendering of markdown content with syntax highlighting and search functionality.
@@ -27,6 +51,216 @@ pub struct MarkdownRenderer {
     theme_set: ThemeSet,
     /// Base font size for text rendering
     base_font_size: f32,
+    /// Index of the notes that wikilinks and embeds resolve against
+    vault: Option<Vault>,
+    /// Notes (and sections) currently being rendered, used to detect embed cycles
+    embed_stack: RefCell<Vec<(PathBuf, Option<String>)>>,
+    /// Contents of embedded notes keyed by path, invalidated by modification time
+    embed_cache: RefCell<HashMap<PathBuf, (SystemTime, String)>>,
+    /// Web images currently loading in the background, keyed by URL
+    pending_images: RefCell<HashMap<String, PendingImage>>,
+    /// Encoded bytes of the loaded images keyed by URL, for saving them
+    image_bytes: RefCell<HashMap<String, Arc<[u8]>>>,
+    /// Frames of the loaded animated GIFs keyed by URL
+    animations: RefCell<HashMap<String, Animation>>,
+    /// Positions of the blocks rendered for the main document this frame
+    layout: RefCell<DocumentLayout>,
+    /// Nesting of embeds and previews being rendered; their blocks aren't recorded
+    detached_depth: Cell<usize>,
+    /// Set by `render` so the next `render_events` call records top-level block positions
+    record_blocks: Cell<bool>,
+    /// Night-light strength applied to colors the renderer picks itself
+    warmth: f32,
+    /// Heading colors of the color scheme, from level 1 down
+    heading_colors: Vec<Color32>,
+    /// Whether the color scheme is dark, which selects the syntax highlighting theme
+    dark_scheme: bool,
+    /// Syntax theme picked for code blocks; None follows the color scheme
+    code_theme: Option<String>,
+    /// Syntax themes following dark and light color schemes
+    automatic_code_themes: (String, String),
+    /// Extra spacing between letters of body text, in points
+    letter_spacing: f32,
+    /// Custom renderers for fenced code blocks, consulted in registration order
+    fence_renderers: Vec<Box<dyn FenceRenderer>>,
+    /// Whether `<!-- comments -->` are shown (muted) instead of hidden
+    show_comments: bool,
+    /// Whether search highlighting includes approximate matches
+    fuzzy_search: bool,
+    /// Whether search highlighting is case sensitive
+    case_sensitive_search: bool,
+    /// Part of the document search highlighting is limited to
+    search_scope: SearchScope,
+    /// Top-level block holding the current search result, and which of the block's
+    /// matches it is
+    current_match: Cell<Option<(usize, usize)>>,
+    /// Matches highlighted so far in the block holding the current search result, while
+    /// that block is drawn
+    block_matches: Cell<Option<usize>>,
+    /// Sentence being read aloud, with the source line of its block
+    spoken_sentence: Option<(String, usize)>,
+    /// Top-level block holding the sentence being read aloud
+    spoken_block: Cell<Option<usize>>,
+    /// Whether the block being drawn holds the sentence being read aloud
+    in_spoken_block: Cell<bool>,
+    /// Footnote numbers keyed by label, in order of first reference
+    footnote_numbers: RefCell<HashMap<String, usize>>,
+    /// Plain text of each footnote, keyed by label, shown when hovering its reference
+    footnote_texts: RefCell<HashMap<String, String>>,
+    /// Footnote (`fn:label`) or reference (`fnref:label`) to scroll to when next drawn
+    footnote_jump: RefCell<Option<String>>,
+    /// Target of the last in-document `#fragment` link clicked, until the app takes it
+    anchor_jump: RefCell<Option<String>>,
+    /// Source and texture of the image clicked since the application last asked
+    clicked_image: RefCell<Option<(String, egui::TextureHandle)>>,
+    /// Document (and section) of the last link to another note clicked, until the app
+    /// takes it
+    clicked_note: RefCell<Option<(PathBuf, Option<String>)>>,
+    /// Occurrences of each heading slug drawn so far, to give repeated titles unique slugs
+    heading_slugs: RefCell<HashMap<String, usize>>,
+    /// Measured heights of the main document's top-level blocks, used to skip drawing
+    /// blocks outside the viewport of long documents
+    block_heights: RefCell<BlockHeights>,
+    /// Highlighted code blocks keyed by a hash of their content, language, syntax theme,
+    /// font size and warmth
+    highlight_cache: RefCell<HashMap<u64, LayoutJob>>,
+    /// Languages guessed for code blocks without one, keyed by a hash of their content
+    detected_languages: RefCell<HashMap<u64, Option<String>>>,
+    /// Whether long lines of code blocks wrap instead of scrolling horizontally
+    wrap_code: bool,
+    /// Code blocks toggled away from `wrap_code`, keyed by a hash of content and language
+    code_wrap_overrides: RefCell<HashMap<u64, bool>>,
+    /// Whether `==text==` is rendered as highlighted text
+    highlight_marks: bool,
+    /// Filter scripts run on the prose text of the parsed document
+    filters: Option<Rc<Filters>>,
+    /// Highlights and notes of the current document
+    annotations: Vec<Annotation>,
+    /// Annotation requested from a paragraph's context menu since the last call
+    annotation_request: Cell<Option<AnnotationKind>>,
+    /// Whether a paragraph's context menu was opened, so the selection should be read
+    selection_wanted: Cell<bool>,
+    /// Index of the annotation whose margin note was clicked since the last call
+    clicked_annotation: Cell<Option<usize>>,
+    /// Widest an image is shown without a size hint, as a fraction of the content width
+    image_max_width: f32,
+    /// Tallest an image is shown without a size hint, in points
+    image_max_height: f32,
+}
+
+/// Heights of the top-level blocks of a document laid out at a given width and font size.
+#[derive(Debug, Default)]
+struct BlockHeights {
+    /// Hash of the document and the layout parameters the heights were measured with
+    key: u64,
+    /// Height of every block once it has been drawn
+    heights: Vec<Option<f32>>,
+}
+
+/// Screen positions of rendered blocks, recorded while rendering the main document.
+///
+/// Used by the application to navigate the scroll area (e.g. jump between headings).
+#[derive(Debug, Clone, Default)]
+pub struct DocumentLayout {
+    /// Top edge of every rendered heading, in document order
+    pub heading_tops: Vec<f32>,
+    /// Top edge of every top-level block, in document order
+    pub block_tops: Vec<f32>,
+    /// Source lines (0-based, end exclusive) spanned by every top-level block
+    pub block_lines: Vec<std::ops::Range<usize>>,
+}
+
+impl DocumentLayout {
+    /// Moves every recorded position by `dy`, e.g. to make them relative to the document top.
+    pub fn translated(mut self, dy: f32) -> Self {
+        for top in self
+            .heading_tops
+            .iter_mut()
+            .chain(self.block_tops.iter_mut())
+        {
+            *top += dy;
+        }
+        self
+    }
+
+    /// Returns the first source line (0-based) of the block at a document offset.
+    pub fn line_at(&self, offset: f32) -> Option<usize> {
+        let count = self.block_tops.len().min(self.block_lines.len());
+        let block = self.block_tops[..count]
+            .partition_point(|&top| top <= offset)
+            .saturating_sub(1);
+        self.block_lines.get(block).map(|lines| lines.start)
+    }
+
+    /// Estimates the position of a source line (0-based).
+    ///
+    /// Finds the block containing the line and interpolates within it, so lines deep
+    /// inside long code blocks or lists land close to where they are drawn.
+    pub fn offset_for_line(&self, line: usize) -> Option<f32> {
+        let count = self.block_tops.len().min(self.block_lines.len());
+        if count == 0 {
+            return None;
+        }
+        let block = self.block_lines[..count]
+            .partition_point(|lines| lines.start <= line)
+            .saturating_sub(1);
+        let lines = &self.block_lines[block];
+        let top = self.block_tops[block];
+        let Some(&next_top) = self.block_tops.get(block + 1) else {
+            return Some(top);
+        };
+        let fraction =
+            (line.saturating_sub(lines.start) as f32 / lines.len().max(1) as f32).min(1.0);
+        Some(top + (next_top - top) * fraction)
+    }
+}
+
+/// Narrowest a table column can be made.
+const MIN_COLUMN_WIDTH: f32 = 30.0;
+
+/// Tables with more rows than this render only their visible rows.
+const VIRTUAL_TABLE_ROWS: usize = 200;
+
+/// Documents with more top-level blocks than this only draw the blocks near the viewport.
+const VIRTUAL_DOCUMENT_BLOCKS: usize = 300;
+
+/// Highlighted code blocks kept before the highlight cache is emptied.
+const HIGHLIGHT_CACHE_SIZE: usize = 256;
+
+/// Syntax theme used with dark color schemes unless another is paired with them.
+pub const DEFAULT_DARK_CODE_THEME: &str = "base16-ocean.dark";
+
+/// Syntax theme used with light color schemes unless another is paired with them.
+pub const DEFAULT_LIGHT_CODE_THEME: &str = "base16-ocean.light";
+
+/// Height of the scrollable row area of a virtualized table.
+const VIRTUAL_TABLE_HEIGHT: f32 = 480.0;
+
+/// Cap on the width a table column reserves for its longest word.
+const MAX_MIN_COLUMN_WIDTH: f32 = 160.0;
+
+/// Maximum nesting of `![[embeds]]` inside each other.
+const MAX_EMBED_DEPTH: usize = 6;
+
+/// Number of top-level blocks shown in a link hover preview.
+const PREVIEW_BLOCKS: usize = 5;
+
+/// A link inside a paragraph's layout job, addressed by byte range of the job text.
+struct LinkSpan {
+    /// Destination of the link
+    url: String,
+    /// Byte range of the link text within the layout job
+    range: std::ops::Range<usize>,
+}
+
+/// An item of a list collected for rendering.
+struct ListItem {
+    /// Text of the item
+    text: String,
+    /// Depth of the list the item belongs to
+    nesting_level: usize,
+    /// Checkbox state of a task list item (`- [ ]` / `- [x]`)
+    task: Option<bool>,
 }
 
 /// Tracks the state of the current markdown element being processed.
@@ -40,23 +274,930 @@ struct ElementState {
     is_emphasis: bool,
     /// Whether we're inside strong text
     is_strong: bool,
-    /// Whether we're inside a blockquote
-    is_blockquote: bool,
+    /// Whether we're inside struck-through text
+    is_strikethrough: bool,
+    /// Whether we're inside `<u>` or `<ins>` text
+    is_underline: bool,
+    /// Whether we're inside `<sup>` text
+    is_superscript: bool,
+    /// Whether we're inside `<sub>` text
+    is_subscript: bool,
+    /// Whether we're inside `<code>` or `<kbd>` text
+    is_code: bool,
+    /// Whether we're inside `==highlighted==` or `<mark>` text
+    is_highlight: bool,
+    /// Number of blockquotes enclosing the current text (0 outside quotes)
+    blockquote_depth: usize,
     /// Whether we're inside a link
     is_link: bool,
     /// URL of the current link
     link_url: String,
+    /// Whether we're inside an image, whose text is its alt text
+    is_image: bool,
     /// Text accumulated for the current element
     accumulated_text: String,
+    /// CriticMarkup annotation the current text belongs to
+    critic: Option<CriticKind>,
+}
+
+/// Loads syntect's bundled syntaxes plus the `.sublime-syntax` files found in the
+/// `syntaxes` folder of the mdzen config directory, for languages the bundle lacks.
+fn load_syntax_set() -> SyntaxSet {
+    let defaults = SyntaxSet::load_defaults_newlines();
+    let Some(entries) =
+        storage::config_dir().and_then(|dir| std::fs::read_dir(dir.join("syntaxes")).ok())
+    else {
+        return defaults;
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sublime-syntax"))
+        .collect();
+    if files.is_empty() {
+        return defaults;
+    }
+    files.sort();
+
+    let mut builder = defaults.into_builder();
+    for path in files {
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned());
+        let definition = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| {
+                Ok(SyntaxDefinition::load_from_str(
+                    &content,
+                    true,
+                    name.as_deref(),
+                )?)
+            });
+        match definition {
+            Ok(definition) => builder.add(definition),
+            Err(e) => eprintln!("Error loading syntax {}: {e}", path.display()),
+        }
+    }
+    builder.build()
+}
+
+/// Loads syntect's bundled syntax themes plus the `.tmTheme` files found in the
+/// `syntax-themes` folder of the mdzen config directory, named after their files.
+fn load_theme_set() -> ThemeSet {
+    let mut theme_set = ThemeSet::load_defaults();
+    let Some(entries) =
+        storage::config_dir().and_then(|dir| std::fs::read_dir(dir.join("syntax-themes")).ok())
+    else {
+        return theme_set;
+    };
+    let files = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "tmTheme"));
+    for path in files {
+        let Some(name) = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+        match ThemeSet::get_theme(&path) {
+            Ok(theme) => {
+                theme_set.themes.insert(name, theme);
+            }
+            Err(e) => eprintln!("Error loading syntax theme {}: {e}", path.display()),
+        }
+    }
+    theme_set
+}
+
+/// Applies an inline HTML formatting tag to the element state. Returns false for tags
+/// outside the supported subset.
+fn apply_html_tag(element: &mut ElementState, tag: &HtmlTag) -> bool {
+    let open = !tag.closing;
+    match tag.name.as_str() {
+        "b" | "strong" => element.is_strong = open,
+        "i" | "em" | "cite" | "var" => element.is_emphasis = open,
+        "s" | "del" | "strike" => element.is_strikethrough = open,
+        "u" | "ins" => element.is_underline = open,
+        "sup" => element.is_superscript = open,
+        "sub" => element.is_subscript = open,
+        "code" | "kbd" | "tt" | "samp" => element.is_code = open,
+        "mark" => element.is_highlight = open,
+        "a" => {
+            element.is_link = open;
+            element.link_url = if open {
+                tag.attribute("href").unwrap_or_default()
+            } else {
+                String::new()
+            };
+        }
+        // Known tags without formatting of their own
+        "span" | "abbr" | "small" | "big" | "font" => {}
+        _ => return false,
+    }
+    true
+}
+
+/// HTML tags that start a new paragraph inside an HTML block.
+fn is_html_block_tag(name: &str) -> bool {
+    matches!(
+        name,
+        "p" | "div"
+            | "center"
+            | "section"
+            | "article"
+            | "header"
+            | "footer"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "ul"
+            | "ol"
+            | "li"
+            | "details"
+            | "summary"
+            | "blockquote"
+            | "figure"
+            | "figcaption"
+            | "picture"
+    )
+}
+
+/// Joins runs of consecutive text events, which the parser splits at characters that
+/// might have started inline syntax, so multi-character markers stay in one event.
+fn merge_text_events(events: Vec<Event>) -> Vec<Event> {
+    let mut merged: Vec<Event> = Vec::with_capacity(events.len());
+    for event in events {
+        match (merged.last_mut(), event) {
+            (Some(Event::Text(previous)), Event::Text(text)) => {
+                *previous = CowStr::from(format!("{previous}{text}"));
+            }
+            (_, event) => merged.push(event),
+        }
+    }
+    merged
+}
+
+/// Runs the text filter scripts on the prose text events, leaving code blocks alone.
+fn filter_text_events(events: &mut [Event], filters: &Filters) {
+    let mut in_code_block = false;
+    for event in events.iter_mut() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(text) if !in_code_block => {
+                if let Some(filtered) = filters.apply_to_text(text) {
+                    *text = CowStr::from(filtered);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Turns `==highlighted==` text into `<mark>` inline HTML. Markers are paired within each
+/// block, so a highlight may span emphasis or links; an opening marker must be followed
+/// and a closing marker preceded by a non-space character, which leaves `a == b` alone.
+fn convert_highlight_marks(events: Vec<Event>) -> Vec<Event> {
+    // Byte offsets of paired markers in each text event, with whether they open
+    let mut markers: HashMap<usize, Vec<(usize, bool)>> = HashMap::new();
+    let mut open: Option<(usize, usize)> = None;
+    let mut in_code_block = false;
+    for (index, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(text) if !in_code_block => {
+                let mut pos = 0;
+                while let Some(found) = text[pos..].find("==") {
+                    let at = pos + found;
+                    let run_end = text.len() - text[at..].trim_start_matches('=').len();
+                    pos = run_end;
+                    // Longer runs of `=` aren't markers
+                    if run_end - at != 2 {
+                        continue;
+                    }
+                    let next_is_text = text[run_end..]
+                        .chars()
+                        .next()
+                        .is_some_and(|c| !c.is_whitespace());
+                    let previous_is_space = text[..at]
+                        .chars()
+                        .next_back()
+                        .is_some_and(char::is_whitespace);
+                    match open {
+                        Some((open_index, open_at)) if !previous_is_space => {
+                            markers.entry(open_index).or_default().push((open_at, true));
+                            markers.entry(index).or_default().push((at, false));
+                            open = None;
+                        }
+                        None if next_is_text => open = Some((index, at)),
+                        _ => {}
+                    }
+                }
+            }
+            // Highlights never cross block boundaries
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::Item
+                | TagEnd::TableCell
+                | TagEnd::BlockQuote(_),
+            ) => open = None,
+            _ => {}
+        }
+    }
+    if markers.is_empty() {
+        return events;
+    }
+
+    let mut converted = Vec::with_capacity(events.len() + markers.len() * 2);
+    for (index, event) in events.into_iter().enumerate() {
+        let (Event::Text(text), Some(positions)) = (&event, markers.get_mut(&index)) else {
+            converted.push(event);
+            continue;
+        };
+        positions.sort_unstable();
+        let mut last = 0;
+        for &(at, opening) in positions.iter() {
+            if at > last {
+                converted.push(Event::Text(CowStr::from(text[last..at].to_string())));
+            }
+            let tag = if opening { "<mark>" } else { "</mark>" };
+            converted.push(Event::InlineHtml(CowStr::from(tag)));
+            last = at + 2;
+        }
+        if last < text.len() {
+            converted.push(Event::Text(CowStr::from(text[last..].to_string())));
+        }
+    }
+    converted
+}
+
+/// Turns bare `http://`, `https://` and `www.` URLs in text outside code and links into
+/// links, like GitHub's autolink literals.
+fn convert_autolink_literals(events: Vec<Event>) -> Vec<Event> {
+    let mut converted = Vec::with_capacity(events.len());
+    let mut in_code_block = false;
+    let mut link_depth = 0usize;
+    for event in events {
+        match &event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Start(Tag::Link { .. } | Tag::Image { .. }) => link_depth += 1,
+            Event::End(TagEnd::Link | TagEnd::Image) => {
+                link_depth = link_depth.saturating_sub(1);
+            }
+            Event::Text(text) if !in_code_block && link_depth == 0 => {
+                let urls = find_autolinks(text);
+                if !urls.is_empty() {
+                    let mut last = 0;
+                    for range in urls {
+                        if range.start > last {
+                            let before = text[last..range.start].to_string();
+                            converted.push(Event::Text(CowStr::from(before)));
+                        }
+                        let url = &text[range.clone()];
+                        let dest_url = if url.starts_with("www.") {
+                            format!("http://{url}")
+                        } else {
+                            url.to_string()
+                        };
+                        converted.push(Event::Start(Tag::Link {
+                            link_type: LinkType::Autolink,
+                            dest_url: CowStr::from(dest_url),
+                            title: CowStr::from(""),
+                            id: CowStr::from(""),
+                        }));
+                        converted.push(Event::Text(CowStr::from(url.to_string())));
+                        converted.push(Event::End(TagEnd::Link));
+                        last = range.end;
+                    }
+                    if last < text.len() {
+                        converted.push(Event::Text(CowStr::from(text[last..].to_string())));
+                    }
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        converted.push(event);
+    }
+    converted
+}
+
+/// Finds the autolink literals of a text. A URL starts after whitespace or an opening
+/// delimiter and runs to the next whitespace or `<`, without trailing punctuation or an
+/// unbalanced closing parenthesis.
+fn find_autolinks(text: &str) -> Vec<std::ops::Range<usize>> {
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some((offset, prefix)) = ["https://", "http://", "www."]
+        .into_iter()
+        .filter_map(|prefix| Some((text[pos..].find(prefix)?, prefix)))
+        .min()
+    {
+        let start = pos + offset;
+        let length = text[start..]
+            .find(|c: char| c.is_whitespace() || c == '<')
+            .unwrap_or(text.len() - start);
+        let mut url = &text[start..start + length];
+        loop {
+            let trimmed =
+                url.trim_end_matches(['?', '!', '.', ',', ':', ';', '*', '_', '~', '\'', '"']);
+            let unbalanced = trimmed.ends_with(')')
+                && trimmed.matches(')').count() > trimmed.matches('(').count();
+            let trimmed = if unbalanced {
+                &trimmed[..trimmed.len() - 1]
+            } else {
+                trimmed
+            };
+            if trimmed.len() == url.len() {
+                break;
+            }
+            url = trimmed;
+        }
+
+        let delimited = text[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| c.is_whitespace() || matches!(c, '*' | '_' | '~' | '('));
+        let domain = url[prefix.len()..]
+            .split(['/', '?', '#'])
+            .next()
+            .unwrap_or("");
+        let valid_domain = !domain.is_empty()
+            && (prefix == "www." || domain.contains('.') || domain.starts_with("localhost"))
+            && domain
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '.' | '-' | '_' | ':'));
+        if delimited && valid_domain {
+            found.push(start..start + url.len());
+            pos = start + url.len();
+        } else {
+            pos = start + prefix.len();
+        }
+    }
+    found
+}
+
+/// Extra indentation of each nested blockquote level.
+const BLOCKQUOTE_INDENT: f32 = 16.0;
+
+/// Replaces emoji shortcodes (`:rocket:`) in text events outside code blocks.
+fn replace_emoji_shortcodes(mut events: Vec<Event>) -> Vec<Event> {
+    let mut in_code_block = false;
+    for event in &mut events {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Text(text) if !in_code_block => {
+                if let Cow::Owned(replaced) = emoji::replace_shortcodes(text) {
+                    *text = CowStr::from(replaced);
+                }
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+/// Longest side (in pixels) above which web JPEGs get a fast thumbnail decode first.
+const THUMBNAIL_THRESHOLD: u32 = 1024;
+
+/// Requested longest side of the downscaled thumbnail decode.
+const THUMBNAIL_SIZE: u16 = 256;
+
+/// Largest web image downloaded, in bytes; the download stops once it grows past this.
+const MAX_REMOTE_IMAGE_BYTES: u64 = 32 * 1024 * 1024;
+
+/// A stage of a web image being loaded on a background thread.
+enum ImageStage {
+    /// Quick low-resolution preview, with the size of the full image
+    Thumbnail(egui::ColorImage, Vec2),
+    /// Final full-resolution image, or the error that prevented loading it
+    Full(Result<LoadedImage, String>),
+}
+
+/// A decoded image with its encoded bytes.
+struct LoadedImage {
+    /// The image, or the first frame of an animation
+    image: egui::ColorImage,
+    /// Encoded bytes, as read from disk or downloaded
+    bytes: Arc<[u8]>,
+    /// Frames of an animated GIF with their delays; empty for still images
+    frames: Vec<(egui::ColorImage, Duration)>,
+}
+
+impl LoadedImage {
+    /// Decodes encoded image bytes, with every frame of animated GIFs.
+    fn decode(bytes: Arc<[u8]>) -> Result<Self, String> {
+        Ok(Self {
+            image: decode_image(&bytes)?,
+            frames: decode_animation(&bytes),
+            bytes,
+        })
+    }
+}
+
+/// Frames of an animated GIF, as textures.
+struct Animation {
+    /// Frames in order, with the time each is shown
+    frames: Vec<(egui::TextureHandle, Duration)>,
+    /// Duration of one loop of the animation
+    total: Duration,
+}
+
+/// Shortest frame delay honored; shorter delays are shown this long, as browsers do.
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(20);
+
+/// Delay used for frames whose delay is too short.
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+/// Most frames of an animated GIF decoded; longer animations show their first frame.
+const MAX_ANIMATION_FRAMES: usize = 500;
+
+/// Most pixels decoded across all frames of an animated GIF; larger animations show
+/// their first frame.
+const MAX_ANIMATION_PIXELS: usize = 64 * 1024 * 1024;
+
+/// A web image whose download or decode is still in progress.
+struct PendingImage {
+    /// Receives the stages produced by the loader thread
+    receiver: mpsc::Receiver<ImageStage>,
+    /// Thumbnail texture and full display size, once available
+    thumbnail: Option<(egui::TextureHandle, Vec2)>,
+}
+
+/// Downloads a web image, sending a thumbnail before the full decode for large JPEGs.
+/// The placeholder stays up while the image downloads.
+fn fetch_remote_image(url: &str, sender: &mpsc::Sender<ImageStage>, ctx: &egui::Context) {
+    let result = download_image(url).and_then(|bytes| {
+        if let Some((thumbnail, full_size)) = decode_thumbnail(&bytes) {
+            let _ = sender.send(ImageStage::Thumbnail(thumbnail, full_size));
+            ctx.request_repaint();
+        }
+        LoadedImage::decode(Arc::from(bytes))
+    });
+    let _ = sender.send(ImageStage::Full(result));
+    ctx.request_repaint();
+}
+
+/// Reads a web image, giving up once it is larger than [`MAX_REMOTE_IMAGE_BYTES`].
+fn download_image(url: &str) -> Result<Vec<u8>, String> {
+    use std::io::Read;
+
+    let too_large = || {
+        format!(
+            "Image is larger than {} MB",
+            MAX_REMOTE_IMAGE_BYTES / (1024 * 1024)
+        )
+    };
+    let response = reqwest::blocking::get(url)
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Failed to fetch image: {e}"))?;
+    if response
+        .content_length()
+        .is_some_and(|length| length > MAX_REMOTE_IMAGE_BYTES)
+    {
+        return Err(too_large());
+    }
+    let mut bytes = Vec::new();
+    response
+        .take(MAX_REMOTE_IMAGE_BYTES + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("Failed to read image bytes: {e}"))?;
+    if bytes.len() as u64 > MAX_REMOTE_IMAGE_BYTES {
+        return Err(too_large());
+    }
+    Ok(bytes)
+}
+
+/// Decodes a downscaled version of a large JPEG using the decoder's DCT scaling,
+/// which is much faster than decoding the full image and resizing it.
+fn decode_thumbnail(bytes: &[u8]) -> Option<(egui::ColorImage, Vec2)> {
+    use image::ImageDecoder;
+
+    if image::guess_format(bytes).ok()? != image::ImageFormat::Jpeg {
+        return None;
+    }
+    let mut decoder = image::codecs::jpeg::JpegDecoder::new(std::io::Cursor::new(bytes)).ok()?;
+    let (width, height) = decoder.dimensions();
+    if width.max(height) <= THUMBNAIL_THRESHOLD {
+        return None;
+    }
+    decoder.scale(THUMBNAIL_SIZE, THUMBNAIL_SIZE).ok()?;
+    let image = image::DynamicImage::from_decoder(decoder).ok()?;
+    Some((to_color_image(&image), vec2(width as f32, height as f32)))
+}
+
+/// Decodes every frame of an animated GIF with its delay. Returns no frames for other
+/// images, including single-frame GIFs, and for animations over the frame or pixel caps.
+fn decode_animation(bytes: &[u8]) -> Vec<(egui::ColorImage, Duration)> {
+    use image::AnimationDecoder;
+
+    if image::guess_format(bytes).ok() != Some(image::ImageFormat::Gif) {
+        return Vec::new();
+    }
+    let Ok(decoder) = image::codecs::gif::GifDecoder::new(std::io::Cursor::new(bytes)) else {
+        return Vec::new();
+    };
+    let mut frames = Vec::new();
+    let mut pixels = 0;
+    for frame in decoder.into_frames() {
+        let Ok(frame) = frame else {
+            return Vec::new();
+        };
+        let delay = Duration::from(frame.delay());
+        let delay = if delay < MIN_FRAME_DELAY {
+            DEFAULT_FRAME_DELAY
+        } else {
+            delay
+        };
+        let buffer = frame.into_buffer();
+        let size = [buffer.width() as usize, buffer.height() as usize];
+        pixels += size[0] * size[1];
+        if frames.len() == MAX_ANIMATION_FRAMES || pixels > MAX_ANIMATION_PIXELS {
+            return Vec::new();
+        }
+        let image = egui::ColorImage::from_rgba_unmultiplied(size, buffer.as_raw());
+        frames.push((image, delay));
+    }
+    if frames.len() < 2 {
+        return Vec::new();
+    }
+    frames
+}
+
+/// Decodes encoded image bytes into an egui image.
+fn decode_image(bytes: &[u8]) -> Result<egui::ColorImage, String> {
+    let image =
+        image::load_from_memory(bytes).map_err(|e| format!("Failed to decode image: {e}"))?;
+    Ok(to_color_image(&image))
+}
+
+fn to_color_image(image: &image::DynamicImage) -> egui::ColorImage {
+    let rgba_image = image.to_rgba8();
+    let size = [rgba_image.width() as usize, rgba_image.height() as usize];
+    egui::ColorImage::from_rgba_unmultiplied(size, rgba_image.as_raw())
+}
+
+/// Finds the link under `pointer` in a galley painted at `origin`.
+fn link_at<'a>(
+    galley: &Galley,
+    origin: Pos2,
+    links: &'a [LinkSpan],
+    pointer: Pos2,
+) -> Option<&'a LinkSpan> {
+    let local = pointer - origin;
+    let row_index = galley.cursor_from_pos(local).rcursor.row;
+    let row = galley.rows.get(row_index)?;
+    if !row.rect.expand(1.0).contains(local.to_pos2()) {
+        return None;
+    }
+    // The character whose glyph is under the pointer, rather than the nearest caret
+    // position, which may sit after the link's last character
+    let column = row
+        .glyphs
+        .iter()
+        .position(|glyph| (glyph.pos.x..glyph.max_x()).contains(&local.x))?;
+    let index = galley.rows[..row_index]
+        .iter()
+        .map(|row| row.char_count_including_newline())
+        .sum::<usize>()
+        + column;
+    let byte = galley.text().char_indices().nth(index)?.0;
+    links.iter().find(|link| link.range.contains(&byte))
+}
+
+/// Numbers footnotes in order of their first reference; footnotes that are defined but
+/// never referenced come last.
+fn number_footnotes(events: &[Event]) -> HashMap<String, usize> {
+    let mut numbers = HashMap::new();
+    let references = events.iter().filter_map(|event| match event {
+        Event::FootnoteReference(label) => Some(label),
+        _ => None,
+    });
+    let definitions = events.iter().filter_map(|event| match event {
+        Event::Start(Tag::FootnoteDefinition(label)) => Some(label),
+        _ => None,
+    });
+    for label in references.chain(definitions) {
+        let next = numbers.len() + 1;
+        numbers.entry(label.to_string()).or_insert(next);
+    }
+    numbers
+}
+
+/// Collects the plain text of each footnote definition, keyed by label, with its
+/// paragraphs separated by blank lines.
+fn footnote_texts(events: &[Event]) -> HashMap<String, String> {
+    let mut texts = HashMap::new();
+    let mut current: Option<(String, String)> = None;
+    for event in events {
+        match (event, &mut current) {
+            (Event::Start(Tag::FootnoteDefinition(label)), None) => {
+                current = Some((label.to_string(), String::new()));
+            }
+            (Event::End(TagEnd::FootnoteDefinition), Some(_)) => {
+                if let Some((label, text)) = current.take() {
+                    texts.insert(label, text.trim().to_string());
+                }
+            }
+            (Event::Text(text) | Event::Code(text), Some((_, current))) => {
+                current.push_str(text);
+            }
+            (Event::SoftBreak | Event::HardBreak, Some((_, current))) => current.push(' '),
+            (Event::End(TagEnd::Paragraph), Some((_, current))) => current.push_str("\n\n"),
+            _ => {}
+        }
+    }
+    texts
+}
+
+/// Generates a GitHub-style anchor slug for a heading title.
+///
+/// Lowercases the text, drops punctuation and turns spaces into hyphens, so
+/// `"Getting Started!"` becomes `"getting-started"`.
+pub fn slugify(title: &str) -> String {
+    title
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Byte ranges of the document a search scope covers, sorted and non-overlapping, or
+/// None for the whole document.
+pub fn search_scope_ranges(
+    markdown: &str,
+    scope: SearchScope,
+) -> Option<Vec<std::ops::Range<usize>>> {
+    if scope == SearchScope::All {
+        return None;
+    }
+    let mut headings = Vec::new();
+    let mut code = Vec::new();
+    for (event, range) in
+        Parser::new_ext(markdown, MarkdownRenderer::parser_options()).into_offset_iter()
+    {
+        match event {
+            Event::Start(Tag::Heading { .. }) => headings.push(range),
+            Event::Start(Tag::CodeBlock(_)) | Event::Code(_) => code.push(range),
+            _ => {}
+        }
+    }
+    Some(match scope {
+        SearchScope::Headings => headings,
+        SearchScope::Code => code,
+        _ => {
+            // Prose is what remains around the headings and the code
+            let mut excluded: Vec<_> = headings.into_iter().chain(code).collect();
+            excluded.sort_by_key(|range| range.start);
+            let mut prose = Vec::new();
+            let mut start = 0;
+            for range in excluded {
+                if range.start > start {
+                    prose.push(start..range.start);
+                }
+                start = start.max(range.end);
+            }
+            if start < markdown.len() {
+                prose.push(start..markdown.len());
+            }
+            prose
+        }
+    })
+}
+
+/// Generates the anchor slug of a heading, suffixed with `-1`, `-2`, ... like on GitHub
+/// when an earlier heading of the document has the same slug.
+pub fn unique_slug(title: &str, counts: &mut HashMap<String, usize>) -> String {
+    let slug = slugify(title);
+    let count = counts.entry(slug.clone()).or_insert(0);
+    let unique = if *count == 0 {
+        slug
+    } else {
+        format!("{slug}-{count}")
+    };
+    *count += 1;
+    unique
+}
+
+/// Returns the indices of the events that open a top-level block.
+fn top_level_blocks(events: &[Event]) -> Vec<usize> {
+    let mut blocks = Vec::new();
+    let mut depth = 0usize;
+    for (index, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(_) => {
+                if depth == 0 {
+                    blocks.push(index);
+                }
+                depth += 1;
+            }
+            Event::End(_) => depth = depth.saturating_sub(1),
+            Event::Rule if depth == 0 => blocks.push(index),
+            _ => {}
+        }
+    }
+    blocks
+}
+
+/// Applies `style` to the text of a layout job within a byte range, splitting the
+/// sections the range cuts through.
+fn restyle_range(
+    job: &mut LayoutJob,
+    range: std::ops::Range<usize>,
+    style: impl Fn(&mut TextFormat),
+) {
+    let mut sections = Vec::with_capacity(job.sections.len() + 2);
+    for section in job.sections.drain(..) {
+        let bytes = section.byte_range.clone();
+        let start = range.start.clamp(bytes.start, bytes.end);
+        let end = range.end.clamp(bytes.start, bytes.end);
+        if start == end {
+            sections.push(section);
+            continue;
+        }
+        let mut leading_space = section.leading_space;
+        for (piece, styled) in [
+            (bytes.start..start, false),
+            (start..end, true),
+            (end..bytes.end, false),
+        ] {
+            if piece.is_empty() {
+                continue;
+            }
+            let mut format = section.format.clone();
+            if styled {
+                style(&mut format);
+            }
+            sections.push(egui::text::LayoutSection {
+                leading_space,
+                byte_range: piece,
+                format,
+            });
+            leading_space = 0.0;
+        }
+    }
+    job.sections = sections;
+}
+
+/// Returns whether a link target points at an image rather than a note.
+fn is_image_url(url: &str) -> bool {
+    let path = url.split(['#', '?']).next().unwrap_or(url);
+    std::path::Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            matches!(
+                ext.to_lowercase().as_str(),
+                "png" | "jpg" | "jpeg" | "gif" | "bmp" | "webp" | "svg" | "ico" | "tiff"
+            )
+        })
+}
+
+/// A requested image width or height.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SizeHint {
+    /// Size in points
+    Points(f32),
+    /// Percentage of the content width (or of the image's height, for heights)
+    Percent(f32),
+}
+
+impl SizeHint {
+    /// Parses `300`, `300px` or `50%`.
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (number, percent) = match value.strip_suffix('%') {
+            Some(number) => (number, true),
+            None => (value.strip_suffix("px").unwrap_or(value), false),
+        };
+        let number = number
+            .trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|n| n.is_finite() && *n > 0.0)?;
+        Some(if percent {
+            Self::Percent(number)
+        } else {
+            Self::Points(number)
+        })
+    }
+
+    /// Size in points, given the size a percentage refers to.
+    fn resolve(self, reference: f32) -> f32 {
+        match self {
+            Self::Points(points) => points,
+            Self::Percent(percent) => reference * percent / 100.0,
+        }
+    }
+}
+
+/// Display size requested for an image, by `<img>` attributes or a `{width=50%}` block
+/// after a markdown image.
+#[derive(Debug, Clone, Copy, Default)]
+struct ImageHints {
+    /// Requested width
+    width: Option<SizeHint>,
+    /// Requested height
+    height: Option<SizeHint>,
+}
+
+impl ImageHints {
+    /// Reads the `width` and `height` attributes of an `<img>` tag.
+    fn from_tag(tag: &HtmlTag) -> Self {
+        Self {
+            width: tag.attribute("width").and_then(|w| SizeHint::parse(&w)),
+            height: tag.attribute("height").and_then(|h| SizeHint::parse(&h)),
+        }
+    }
+
+    /// Reads an attribute block such as `{width=50% height=200}` at the start of the text
+    /// following an image. Returns the hints and the length of the block, or None when the
+    /// text doesn't start with a block giving a size.
+    fn from_attribute_block(text: &str) -> Option<(Self, usize)> {
+        let end = text.strip_prefix('{')?.find('}')? + 2;
+        let mut hints = Self::default();
+        for attribute in text[1..end - 1].split_whitespace() {
+            let Some((key, value)) = attribute.split_once('=') else {
+                continue;
+            };
+            let value = value.trim_matches(['"', '\'']);
+            match key {
+                "width" => hints.width = SizeHint::parse(value),
+                "height" => hints.height = SizeHint::parse(value),
+                _ => {}
+            }
+        }
+        (hints.width.is_some() || hints.height.is_some()).then_some((hints, end))
+    }
 }
 
 impl MarkdownRenderer {
     /// Creates a new markdown renderer with default syntax highlighting setup.
     pub fn new() -> Self {
         Self {
-            syntax_set: SyntaxSet::load_defaults_newlines(),
-            theme_set: ThemeSet::load_defaults(),
+            syntax_set: load_syntax_set(),
+            theme_set: load_theme_set(),
             base_font_size: 14.0,
+            vault: None,
+            embed_stack: RefCell::new(Vec::new()),
+            embed_cache: RefCell::new(HashMap::new()),
+            pending_images: RefCell::new(HashMap::new()),
+            image_bytes: RefCell::new(HashMap::new()),
+            animations: RefCell::new(HashMap::new()),
+            layout: RefCell::new(DocumentLayout::default()),
+            detached_depth: Cell::new(0),
+            record_blocks: Cell::new(false),
+            case_sensitive_search: false,
+            search_scope: SearchScope::All,
+            current_match: Cell::new(None),
+            block_matches: Cell::new(None),
+            spoken_sentence: None,
+            spoken_block: Cell::new(None),
+            in_spoken_block: Cell::new(false),
+            warmth: 0.0,
+            heading_colors: Vec::new(),
+            dark_scheme: true,
+            code_theme: None,
+            automatic_code_themes: (
+                DEFAULT_DARK_CODE_THEME.to_string(),
+                DEFAULT_LIGHT_CODE_THEME.to_string(),
+            ),
+            letter_spacing: 0.0,
+            fence_renderers: Vec::new(),
+            show_comments: false,
+            fuzzy_search: false,
+            footnote_numbers: RefCell::new(HashMap::new()),
+            footnote_texts: RefCell::new(HashMap::new()),
+            footnote_jump: RefCell::new(None),
+            anchor_jump: RefCell::new(None),
+            clicked_image: RefCell::new(None),
+            clicked_note: RefCell::new(None),
+            heading_slugs: RefCell::new(HashMap::new()),
+            block_heights: RefCell::new(BlockHeights::default()),
+            highlight_cache: RefCell::new(HashMap::new()),
+            detected_languages: RefCell::new(HashMap::new()),
+            wrap_code: true,
+            code_wrap_overrides: RefCell::new(HashMap::new()),
+            highlight_marks: false,
+            filters: None,
+            image_max_width: 1.0,
+            image_max_height: 600.0,
+            annotations: Vec::new(),
+            annotation_request: Cell::new(None),
+            selection_wanted: Cell::new(false),
+            clicked_annotation: Cell::new(None),
         }
     }
 
@@ -65,64 +1206,351 @@ impl MarkdownRenderer {
         self.base_font_size = size;
     }
 
+    /// Registers a custom renderer for fenced code blocks.
+    ///
+    /// Renderers registered earlier take precedence when several handle a language.
+    pub fn register_fence_renderer(&mut self, renderer: Box<dyn FenceRenderer>) {
+        self.fence_renderers.push(renderer);
+    }
+
+    /// Sets whether HTML comments are shown in a muted style instead of hidden.
+    pub fn set_show_comments(&mut self, show: bool) {
+        self.show_comments = show;
+    }
+
+    /// Sets whether long code lines wrap by default instead of scrolling horizontally.
+    pub fn set_wrap_code(&mut self, wrap: bool) {
+        self.wrap_code = wrap;
+        self.code_wrap_overrides.borrow_mut().clear();
+    }
+
+    /// Sets the largest size images are shown at without a size hint: a percentage of the
+    /// content width and a height in points.
+    pub fn set_image_limits(&mut self, max_width_percent: f32, max_height: f32) {
+        self.image_max_width = (max_width_percent / 100.0).clamp(0.1, 1.0);
+        self.image_max_height = max_height.max(50.0);
+    }
+
+    /// Sets the highlights and notes drawn over the document.
+    pub fn set_annotations(&mut self, annotations: Vec<Annotation>) {
+        self.annotations = annotations;
+    }
+
+    /// Takes the annotation requested from a paragraph's context menu since the last call.
+    pub fn take_annotation_request(&self) -> Option<AnnotationKind> {
+        self.annotation_request.take()
+    }
+
+    /// Takes whether a paragraph's context menu was opened since the last call.
+    pub fn take_selection_wanted(&self) -> bool {
+        self.selection_wanted.take()
+    }
+
+    /// Takes the index of the annotation whose margin note was clicked since the last call.
+    pub fn take_clicked_annotation(&self) -> Option<usize> {
+        self.clicked_annotation.take()
+    }
+
+    /// Sets whether the `==highlight==` extension is enabled.
+    pub fn set_highlight_marks(&mut self, enabled: bool) {
+        self.highlight_marks = enabled;
+    }
+
+    /// Sets the filter scripts run on the prose text of documents; None runs none.
+    pub fn set_filters(&mut self, filters: Option<Rc<Filters>>) {
+        self.filters = filters;
+    }
+
+    /// Whether the filter scripts changed prose text of the documents rendered so far.
+    pub fn filtered_text(&self) -> bool {
+        self.filters
+            .as_ref()
+            .is_some_and(|filters| filters.changed_text())
+    }
+
+    /// Applies the event passes (text filters, emoji shortcodes, autolink literals,
+    /// enabled extensions) to parsed events.
+    fn prepare_events<'a>(&self, events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+        let mut events = merge_text_events(events);
+        if let Some(filters) = self
+            .filters
+            .as_ref()
+            .filter(|filters| filters.filters_text())
+        {
+            filter_text_events(&mut events, filters);
+        }
+        let events = convert_autolink_literals(replace_emoji_shortcodes(events));
+        if self.highlight_marks {
+            convert_highlight_marks(events)
+        } else {
+            events
+        }
+    }
+
+    /// Sets whether search highlighting includes approximate (typo-tolerant) matches.
+    pub fn set_fuzzy_search(&mut self, fuzzy: bool) {
+        self.fuzzy_search = fuzzy;
+    }
+
+    /// Sets whether search highlighting is case sensitive.
+    pub fn set_case_sensitive_search(&mut self, case_sensitive: bool) {
+        self.case_sensitive_search = case_sensitive;
+    }
+
+    /// Sets the part of the document search highlighting is limited to.
+    pub fn set_search_scope(&mut self, scope: SearchScope) {
+        self.search_scope = scope;
+    }
+
+    /// Sets the extra spacing between letters of body text, in points.
+    pub fn set_letter_spacing(&mut self, spacing: f32) {
+        self.letter_spacing = spacing;
+    }
+
+    /// Sets the night-light strength used for syntax highlighting and callout colors.
+    pub fn set_warmth(&mut self, warmth: f32) {
+        self.warmth = warmth;
+    }
+
+    /// Uses the heading colors and code highlighting matching a color scheme.
+    pub fn set_color_scheme(&mut self, scheme: &theme::ColorScheme) {
+        self.heading_colors = scheme.headings.clone();
+        self.dark_scheme = scheme.is_dark();
+    }
+
+    /// Picks the syntax theme of code blocks; None uses the one matching the color scheme.
+    pub fn set_code_theme(&mut self, theme: Option<String>) {
+        self.code_theme = theme;
+    }
+
+    /// Sets the syntax themes followed automatically by dark and light color schemes.
+    pub fn set_automatic_code_themes(&mut self, dark: String, light: String) {
+        self.automatic_code_themes = (dark, light);
+    }
+
+    /// Names of the available syntax themes, sorted.
+    pub fn code_themes(&self) -> impl Iterator<Item = &str> {
+        self.theme_set.themes.keys().map(String::as_str)
+    }
+
+    /// Syntax theme used for code blocks.
+    fn syntax_theme(&self) -> &Theme {
+        let (paired, fallback) = if self.dark_scheme {
+            (&self.automatic_code_themes.0, DEFAULT_DARK_CODE_THEME)
+        } else {
+            (&self.automatic_code_themes.1, DEFAULT_LIGHT_CODE_THEME)
+        };
+        let themes = &self.theme_set.themes;
+        self.code_theme
+            .as_ref()
+            .or(Some(paired))
+            .and_then(|name| themes.get(name))
+            .unwrap_or(&themes[fallback])
+    }
+
+    /// Color of a heading of the given level (1-6).
+    fn heading_color(&self, level: u8, ui: &Ui) -> Color32 {
+        match self
+            .heading_colors
+            .get(usize::from(level).saturating_sub(1))
+        {
+            Some(&color) => theme::warm_color(color, self.warmth),
+            None => ui.visuals().text_color(),
+        }
+    }
+
     /// Loads an image from a URL or file path, using the cache to avoid reloading.
     ///
     /// Supports both local files (relative to the current markdown file) and web URLs.
-    /// Returns None if the image cannot be loaded.
+    /// Web images are fetched in the background; while they load, a low-resolution
+    /// thumbnail may be returned instead. Returns the texture together with the size the
+    /// full image should be displayed at, or None if nothing can be shown yet.
     pub fn load_image(
         &self,
         ctx: &egui::Context,
         url: &str,
         image_cache: &mut HashMap<String, Result<egui::TextureHandle, String>>,
         current_file: &Option<PathBuf>,
-    ) -> Option<egui::TextureHandle> {
+    ) -> Option<(egui::TextureHandle, Vec2)> {
         if let Some(cached_result) = image_cache.get(url) {
-            return cached_result.as_ref().ok().cloned();
+            return cached_result.as_ref().ok().map(|texture| {
+                let frame = self.animation_frame(ctx, url).unwrap_or(texture.clone());
+                (frame, texture.size_vec2())
+            });
         }
 
-        // Try to load image
+        if url.starts_with("http://") || url.starts_with("https://") {
+            return self.poll_remote_image(ctx, url, image_cache);
+        }
+
+        // Local images are loaded synchronously
         let load_result = self.try_load_image(ctx, url, current_file);
-        let texture_handle = load_result.as_ref().ok().cloned();
+        let texture_handle = load_result
+            .as_ref()
+            .ok()
+            .map(|texture| (texture.clone(), texture.size_vec2()));
         image_cache.insert(url.to_string(), load_result);
         texture_handle
     }
 
+    /// Starts or advances the background load of a web image.
+    ///
+    /// Returns the thumbnail (sized like the full image) until the full-resolution
+    /// texture arrives, at which point it is moved into the image cache.
+    fn poll_remote_image(
+        &self,
+        ctx: &egui::Context,
+        url: &str,
+        image_cache: &mut HashMap<String, Result<egui::TextureHandle, String>>,
+    ) -> Option<(egui::TextureHandle, Vec2)> {
+        let mut pending_images = self.pending_images.borrow_mut();
+        let pending = pending_images.entry(url.to_string()).or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel();
+            let ctx = ctx.clone();
+            let url = url.to_string();
+            std::thread::spawn(move || fetch_remote_image(&url, &sender, &ctx));
+            PendingImage {
+                receiver,
+                thumbnail: None,
+            }
+        });
+
+        let mut finished = None;
+        loop {
+            match pending.receiver.try_recv() {
+                Ok(ImageStage::Thumbnail(image, full_size)) => {
+                    let texture = ctx.load_texture(
+                        format!("{url}#thumbnail"),
+                        image,
+                        egui::TextureOptions::default(),
+                    );
+                    pending.thumbnail = Some((texture, full_size));
+                }
+                Ok(ImageStage::Full(result)) => {
+                    finished = Some(result);
+                    break;
+                }
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    finished = Some(Err("Image loader stopped unexpectedly".to_string()));
+                    break;
+                }
+            }
+        }
+
+        let Some(result) = finished else {
+            return pending.thumbnail.clone();
+        };
+        pending_images.remove(url);
+        let result = result.map(|loaded| self.store_loaded_image(ctx, url, loaded));
+        let texture_handle = result
+            .as_ref()
+            .ok()
+            .map(|texture| (texture.clone(), texture.size_vec2()));
+        image_cache.insert(url.to_string(), result);
+        texture_handle
+    }
+
     fn try_load_image(
         &self,
         ctx: &egui::Context,
         url: &str,
         current_file: &Option<PathBuf>,
     ) -> Result<egui::TextureHandle, String> {
-        let image_data = if url.starts_with("http://") || url.starts_with("https://") {
-            // Load from URL
-            reqwest::blocking::get(url)
-                .map_err(|e| format!("Failed to fetch image: {e}"))?
-                .bytes()
-                .map_err(|e| format!("Failed to read image bytes: {e}"))?
-                .to_vec()
+        // Load from local file
+        let image_path = if let Some(current_file) = current_file {
+            current_file
+                .parent()
+                .unwrap_or(std::path::Path::new("."))
+                .join(url)
         } else {
-            // Load from local file
-            let image_path = if let Some(current_file) = current_file {
-                current_file
-                    .parent()
-                    .unwrap_or(std::path::Path::new("."))
-                    .join(url)
-            } else {
-                std::path::PathBuf::from(url)
-            };
-
-            std::fs::read(&image_path).map_err(|e| format!("Failed to read local image: {e}"))?
+            std::path::PathBuf::from(url)
         };
 
-        let image = image::load_from_memory(&image_data)
-            .map_err(|e| format!("Failed to decode image: {e}"))?;
+        let image_data =
+            std::fs::read(&image_path).map_err(|e| format!("Failed to read local image: {e}"))?;
+        let loaded = LoadedImage::decode(Arc::from(image_data))?;
+        Ok(self.store_loaded_image(ctx, url, loaded))
+    }
+
+    /// Uploads a decoded image (and the frames of an animation) as textures and keeps
+    /// its bytes. Returns the texture of the image or first frame.
+    fn store_loaded_image(
+        &self,
+        ctx: &egui::Context,
+        url: &str,
+        loaded: LoadedImage,
+    ) -> egui::TextureHandle {
+        self.image_bytes
+            .borrow_mut()
+            .insert(url.to_string(), loaded.bytes);
+        if !loaded.frames.is_empty() {
+            let frames: Vec<_> = loaded
+                .frames
+                .into_iter()
+                .enumerate()
+                .map(|(index, (image, delay))| {
+                    let name = format!("{url}#frame{index}");
+                    let texture = ctx.load_texture(name, image, egui::TextureOptions::default());
+                    (texture, delay)
+                })
+                .collect();
+            let total = frames.iter().map(|(_, delay)| *delay).sum();
+            self.animations
+                .borrow_mut()
+                .insert(url.to_string(), Animation { frames, total });
+        }
+        ctx.load_texture(url, loaded.image, egui::TextureOptions::default())
+    }
+
+    /// Texture of the frame an animated GIF is at, scheduling a repaint for the next one.
+    fn animation_frame(&self, ctx: &egui::Context, url: &str) -> Option<egui::TextureHandle> {
+        let animations = self.animations.borrow();
+        let animation = animations.get(url)?;
+        let mut elapsed = ctx.input(|i| i.time) % animation.total.as_secs_f64();
+        for (texture, delay) in &animation.frames {
+            let delay = delay.as_secs_f64();
+            if elapsed < delay {
+                ctx.request_repaint_after(Duration::from_secs_f64(delay - elapsed));
+                return Some(texture.clone());
+            }
+            elapsed -= delay;
+        }
+        animation.frames.last().map(|(texture, _)| texture.clone())
+    }
+
+    /// Returns the block positions recorded during the last call to [`Self::render`].
+    pub fn layout(&self) -> DocumentLayout {
+        self.layout.borrow().clone()
+    }
+
+    /// Sets the folder used to resolve `[[wikilinks]]` and `![[embeds]]`.
+    ///
+    /// The folder is only re-indexed when it differs from the current one.
+    pub fn set_vault_root(&mut self, root: Option<PathBuf>) {
+        if self.vault.as_ref().map(|v| v.root()) == root.as_deref() {
+            return;
+        }
+        self.vault = root.map(Vault::open);
+        self.embed_cache.borrow_mut().clear();
+    }
 
-        let rgba_image = image.to_rgba8();
-        let size = [rgba_image.width() as usize, rgba_image.height() as usize];
-        let pixels = rgba_image.into_raw();
+    /// Replaces the folder index used to resolve links, e.g. after files changed.
+    pub fn set_vault(&mut self, vault: Vault) {
+        self.vault = Some(vault);
+        self.embed_cache.borrow_mut().clear();
+    }
 
-        let color_image = egui::ColorImage::from_rgba_unmultiplied(size, &pixels);
-        Ok(ctx.load_texture(url, color_image, egui::TextureOptions::default()))
+    /// Markdown extensions enabled for every document the renderer parses.
+    pub fn parser_options() -> Options {
+        let mut options = Options::empty();
+        options.insert(Options::ENABLE_TABLES);
+        options.insert(Options::ENABLE_STRIKETHROUGH);
+        options.insert(Options::ENABLE_WIKILINKS);
+        options.insert(Options::ENABLE_TASKLISTS);
+        options.insert(Options::ENABLE_FOOTNOTES);
+        options.insert(Options::ENABLE_MATH);
+        options
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -137,21 +1565,130 @@ impl MarkdownRenderer {
         scroll_to_header: &Option<String>,
         content_width: Option<f32>,
     ) -> Option<String> {
-        let mut options = Options::empty();
-        options.insert(Options::ENABLE_TABLES);
-        options.insert(Options::ENABLE_STRIKETHROUGH);
-        let parser = Parser::new_ext(markdown, options);
-        let events = parser.collect::<Vec<_>>();
+        let parser = Parser::new_ext(markdown, Self::parser_options());
+        let (events, ranges): (Vec<_>, Vec<_>) = parser.into_offset_iter().unzip();
 
-        self.render_events(
-            ui,
-            events,
-            search_query,
-            current_search_result,
-            image_cache,
-            current_file,
-            scroll_to_header,
-            content_width,
+        // Map each top-level block to the source lines it spans. This is done before the
+        // event passes, which add and remove inline events (so indices no longer match
+        // `ranges`) but leave the top-level blocks as they are.
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(markdown.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let line_of = |offset: usize| line_starts.partition_point(|&start| start <= offset) - 1;
+        let block_lines: Vec<_> = top_level_blocks(&events)
+            .into_iter()
+            .map(|i| line_of(ranges[i].start)..line_of(ranges[i].end.saturating_sub(1)) + 1)
+            .collect();
+        let events = self.prepare_events(events);
+
+        // Heights measured for another document or layout no longer apply
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        markdown.hash(&mut hasher);
+        content_width.map(f32::to_bits).hash(&mut hasher);
+        self.base_font_size.to_bits().hash(&mut hasher);
+        self.letter_spacing.to_bits().hash(&mut hasher);
+        let key = hasher.finish();
+        let mut block_heights = self.block_heights.borrow_mut();
+        if block_heights.key != key || block_heights.heights.len() != block_lines.len() {
+            *block_heights = BlockHeights {
+                key,
+                heights: vec![None; block_lines.len()],
+            };
+        }
+        drop(block_heights);
+
+        // Locate the current search result as the n-th match of its top-level block, so it
+        // can be told apart from the other matches while that block is drawn
+        let current_match = current_search_result.and_then(|result| {
+            let block = block_lines
+                .iter()
+                .position(|lines| lines.contains(&result.line_number))?;
+            let count = |line: &str| {
+                search::find_matches(
+                    line,
+                    search_query,
+                    self.case_sensitive_search,
+                    self.fuzzy_search,
+                )
+            };
+            // Matches outside the search scope aren't highlighted
+            let scope = search_scope_ranges(markdown, self.search_scope);
+            let in_scope = |line: usize, offset: usize| {
+                scope
+                    .as_ref()
+                    .is_none_or(|ranges| search::in_ranges(ranges, line_starts[line] + offset))
+            };
+            let before: usize = markdown
+                .lines()
+                .enumerate()
+                .skip(block_lines[block].start)
+                .take(result.line_number - block_lines[block].start)
+                .map(|(line, text)| {
+                    count(text)
+                        .iter()
+                        .filter(|range| in_scope(line, range.start))
+                        .count()
+                })
+                .sum();
+            let in_line = count(&result.line_content)
+                .iter()
+                .filter(|range| {
+                    range.start < result.match_start && in_scope(result.line_number, range.start)
+                })
+                .count();
+            Some((block, before + in_line))
+        });
+        self.current_match.set(current_match);
+        self.block_matches.set(None);
+        self.spoken_block.set(
+            self.spoken_sentence
+                .as_ref()
+                .and_then(|(_, line)| block_lines.iter().position(|lines| lines.contains(line))),
+        );
+        self.in_spoken_block.set(false);
+
+        // The document being rendered is the root of any embed chain
+        let mut embed_stack = self.embed_stack.borrow_mut();
+        embed_stack.clear();
+        if let Some(path) = current_file {
+            embed_stack.push((path.clone(), None));
+        }
+        drop(embed_stack);
+        *self.layout.borrow_mut() = DocumentLayout {
+            block_lines,
+            ..Default::default()
+        };
+        self.record_blocks.set(true);
+        *self.footnote_numbers.borrow_mut() = number_footnotes(&events);
+        *self.footnote_texts.borrow_mut() = footnote_texts(&events);
+        self.heading_slugs.borrow_mut().clear();
+
+        let ctx = ui.ctx().clone();
+        let name = current_file
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned());
+        accessibility::group(
+            &ctx,
+            ui.id().with("document"),
+            accesskit::Role::Document,
+            |builder| {
+                if let Some(name) = name {
+                    builder.set_name(name);
+                }
+            },
+            || {
+                self.render_events(
+                    ui,
+                    events,
+                    search_query,
+                    current_search_result,
+                    image_cache,
+                    current_file,
+                    scroll_to_header,
+                    content_width,
+                )
+            },
         )
     }
 
@@ -159,7 +1696,7 @@ impl MarkdownRenderer {
     fn render_events(
         &self,
         ui: &mut Ui,
-        events: Vec<Event>,
+        mut events: Vec<Event>,
         search_query: &str,
         current_search_result: Option<&SearchResult>,
         image_cache: &mut HashMap<String, Result<egui::TextureHandle, String>>,
@@ -176,18 +1713,60 @@ impl MarkdownRenderer {
         let mut code_block_content = String::new();
         let mut code_block_lang = String::new();
         let mut paragraph_has_content = false;
-        let mut in_blockquote = false;
-        let mut paragraph_links: Vec<(String, String)> = Vec::new();
-        let mut list_stack: Vec<(bool, Vec<(String, usize)>)> = Vec::new(); // (is_ordered, items_with_level)
+        let mut paragraph_links: Vec<LinkSpan> = Vec::new();
+        let mut list_stack: Vec<(bool, Vec<ListItem>)> = Vec::new(); // (is_ordered, items)
         let mut current_list_item = String::new();
+        let mut current_task: Option<bool> = None;
         let mut current_nesting_level = 0;
         let mut in_table = false;
         let mut table_headers: Vec<String> = Vec::new();
         let mut table_rows: Vec<Vec<String>> = Vec::new();
+        let mut table_alignments: Vec<Alignment> = Vec::new();
         let mut current_table_row: Vec<String> = Vec::new();
         let mut current_table_cell = String::new();
+        let mut html_block = String::new();
+        let mut footnote_definitions: Vec<(String, Vec<Event>)> = Vec::new();
+
+        // Only the outermost call for the main document records block positions
+        let record_blocks = self.record_blocks.replace(false) && self.detached_depth.get() == 0;
+        let block_starts = if record_blocks {
+            top_level_blocks(&events)
+        } else {
+            Vec::new()
+        };
+        let mut next_block = 0;
+        // Long documents skip blocks far from the viewport, unless a jump needs them drawn
+        let virtualize = block_starts.len() > VIRTUAL_DOCUMENT_BLOCKS
+            && scroll_to_header.is_none()
+            && self.footnote_jump.borrow().is_none();
+        // Block being drawn and where it started, to measure its height
+        let mut measuring: Option<(usize, f32)> = None;
 
-        for event in events {
+        let mut index = 0;
+        while index < events.len() {
+            if block_starts.get(next_block) == Some(&index) {
+                let top = ui.cursor().top();
+                self.record_block_height(measuring.take(), top);
+                self.layout.borrow_mut().block_tops.push(top);
+                let block = next_block;
+                next_block += 1;
+                let holds_current = self
+                    .current_match
+                    .get()
+                    .is_some_and(|(current, _)| current == block);
+                self.block_matches.set(holds_current.then_some(0));
+                self.in_spoken_block
+                    .set(self.spoken_block.get() == Some(block));
+                if virtualize {
+                    if let Some(end) = self.skip_block(ui, &events, index, block, top) {
+                        index = end;
+                        continue;
+                    }
+                    measuring = Some((block, top));
+                }
+            }
+            let event = events[index].clone();
+            index += 1;
             // Debug: print events to see what we're getting
             // println!("Event: {:?}", event);
             match event {
@@ -198,13 +1777,20 @@ impl MarkdownRenderer {
                 }
                 Event::End(TagEnd::Paragraph) => {
                     if paragraph_has_content {
-                        if in_blockquote {
-                            self.render_blockquote(ui, current_paragraph.clone(), content_width);
+                        if current_element.blockquote_depth > 0 {
+                            self.render_blockquote(
+                                ui,
+                                current_paragraph.clone(),
+                                current_element.blockquote_depth,
+                                content_width,
+                            );
                         } else {
                             self.render_paragraph_with_links(
                                 ui,
                                 current_paragraph.clone(),
                                 &paragraph_links,
+                                image_cache,
+                                current_file,
                                 content_width,
                             );
                         }
@@ -229,8 +1815,11 @@ impl MarkdownRenderer {
                 }
                 Event::End(TagEnd::Heading(_)) => {
                     if !current_element.accumulated_text.is_empty() {
-                        let should_scroll =
-                            scroll_to_header.as_ref() == Some(&current_element.accumulated_text);
+                        let slug = unique_slug(
+                            &current_element.accumulated_text,
+                            &mut self.heading_slugs.borrow_mut(),
+                        );
+                        let should_scroll = scroll_to_header.as_ref() == Some(&slug);
                         self.render_heading(
                             ui,
                             &current_element.accumulated_text,
@@ -253,15 +1842,145 @@ impl MarkdownRenderer {
                 }
                 Event::End(TagEnd::CodeBlock) => {
                     in_code_block = false;
-                    self.render_code_block(
+                    if !self.render_custom_fence(
                         ui,
                         &code_block_content,
                         &code_block_lang,
+                        current_file,
                         content_width,
-                    );
+                    ) {
+                        self.render_code_block(
+                            ui,
+                            &code_block_content,
+                            &code_block_lang,
+                            content_width,
+                        );
+                    }
                     code_block_content.clear();
                     ui.add_space(8.0);
                 }
+                Event::Start(Tag::HtmlBlock) => {
+                    html_block.clear();
+                }
+                Event::Html(html) => {
+                    html_block.push_str(&html);
+                }
+                Event::End(TagEnd::HtmlBlock) => {
+                    self.render_html_block(
+                        ui,
+                        &html_block,
+                        image_cache,
+                        current_file,
+                        content_width,
+                    );
+                }
+                Event::InlineHtml(html) => {
+                    let plain_text = if current_element.is_heading {
+                        Some(&mut current_element.accumulated_text)
+                    } else if in_table {
+                        Some(&mut current_table_cell)
+                    } else if !list_stack.is_empty() {
+                        Some(&mut current_list_item)
+                    } else {
+                        None
+                    };
+                    // Headings, tables and lists are drawn from plain text: keep line
+                    // breaks and text, drop formatting
+                    if let Some(plain_text) = plain_text {
+                        for token in html::tokenize(&html) {
+                            match token {
+                                HtmlToken::Text(text) => {
+                                    plain_text.push_str(&html::decode_entities(text))
+                                }
+                                HtmlToken::Tag(tag) if tag.name == "br" => {
+                                    plain_text.push(if in_table { ' ' } else { '\n' })
+                                }
+                                HtmlToken::Tag(tag) if tag.name == "img" => {
+                                    plain_text.push_str(&tag.attribute("alt").unwrap_or_default())
+                                }
+                                HtmlToken::Tag(tag) => {
+                                    let mut ignored = ElementState::default();
+                                    if !apply_html_tag(&mut ignored, &tag) {
+                                        plain_text.push_str(tag.source);
+                                    }
+                                }
+                                HtmlToken::Comment(_) => {}
+                            }
+                        }
+                        continue;
+                    }
+
+                    for token in html::tokenize(&html) {
+                        match token {
+                            HtmlToken::Comment(comment) => {
+                                if self.show_comments {
+                                    current_paragraph.append(
+                                        comment,
+                                        4.0,
+                                        TextFormat {
+                                            font_id: FontId::proportional(
+                                                self.base_font_size * 0.9,
+                                            ),
+                                            color: ui.visuals().weak_text_color(),
+                                            background: ui.visuals().faint_bg_color,
+                                            italics: true,
+                                            ..Default::default()
+                                        },
+                                    );
+                                    paragraph_has_content = true;
+                                }
+                            }
+                            HtmlToken::Text(text) => {
+                                if let Some(link_info) = self.append_text(
+                                    &mut current_paragraph,
+                                    &CowStr::from(html::decode_entities(text).into_owned()),
+                                    &current_element,
+                                    ui,
+                                    search_query,
+                                    current_search_result,
+                                ) {
+                                    paragraph_links.push(link_info);
+                                }
+                                paragraph_has_content = true;
+                            }
+                            HtmlToken::Tag(tag) if tag.name == "br" => {
+                                current_paragraph.append("\n", 0.0, TextFormat::default());
+                            }
+                            HtmlToken::Tag(tag) if tag.name == "img" => {
+                                // Like markdown images, drawn as their own block
+                                if let Some(src) = tag.attribute("src") {
+                                    let alt = tag.attribute("alt").unwrap_or_default();
+                                    self.render_image(
+                                        ui,
+                                        &src,
+                                        &alt,
+                                        ImageHints::from_tag(&tag),
+                                        image_cache,
+                                        current_file,
+                                        content_width,
+                                    );
+                                    ui.add_space(8.0);
+                                }
+                            }
+                            HtmlToken::Tag(tag) => {
+                                if !apply_html_tag(&mut current_element, &tag) {
+                                    self.append_literal_html(
+                                        &mut current_paragraph,
+                                        tag.source,
+                                        ui,
+                                    );
+                                    paragraph_has_content = true;
+                                }
+                            }
+                        }
+                    }
+                }
+                Event::Start(Tag::Strikethrough) => {
+                    current_element.is_strikethrough = true;
+                }
+                Event::End(TagEnd::Strikethrough) => {
+                    current_element.is_strikethrough = false;
+                }
                 Event::Start(Tag::Emphasis) => {
                     current_element.is_emphasis = true;
                 }
@@ -288,6 +2007,53 @@ impl MarkdownRenderer {
                         paragraph_has_content = true;
                     }
                 }
+                Event::InlineMath(tex) => {
+                    if current_element.is_heading {
+                        current_element
+                            .accumulated_text
+                            .push_str(&math::to_text(&tex));
+                    } else if in_table {
+                        current_table_cell.push_str(&math::to_text(&tex));
+                    } else if !list_stack.is_empty() {
+                        current_list_item.push_str(&math::to_text(&tex));
+                    } else {
+                        math::append(
+                            &mut current_paragraph,
+                            &tex,
+                            self.base_font_size,
+                            ui.visuals().text_color(),
+                        );
+                        paragraph_has_content = true;
+                    }
+                }
+                Event::DisplayMath(tex) => {
+                    let alone = !paragraph_has_content
+                        && matches!(events.get(index), Some(Event::End(TagEnd::Paragraph)));
+                    if current_element.is_heading {
+                        current_element
+                            .accumulated_text
+                            .push_str(&math::to_text(&tex));
+                    } else if in_table {
+                        current_table_cell.push_str(&math::to_text(&tex));
+                    } else if !list_stack.is_empty() {
+                        current_list_item.push_str(&math::to_text(&tex));
+                    } else if alone && current_element.blockquote_depth == 0 {
+                        self.render_display_math(ui, &tex, content_width);
+                    } else {
+                        // Math sharing a paragraph with text goes on its own line
+                        if paragraph_has_content {
+                            current_paragraph.append("\n", 0.0, TextFormat::default());
+                        }
+                        math::append(
+                            &mut current_paragraph,
+                            &tex,
+                            self.base_font_size * 1.1,
+                            ui.visuals().text_color(),
+                        );
+                        current_paragraph.append("\n", 0.0, TextFormat::default());
+                        paragraph_has_content = true;
+                    }
+                }
                 Event::Text(text) => {
                     if in_code_block {
                         code_block_content.push_str(&text);
@@ -297,68 +2063,86 @@ impl MarkdownRenderer {
                         current_table_cell.push_str(&text);
                     } else if !list_stack.is_empty() {
                         current_list_item.push_str(&text);
-                    } else if !current_element.link_url.is_empty()
+                    } else if current_element.is_image
                         && current_element.accumulated_text.is_empty()
                     {
                         // This is alt text for an image
                         current_element.accumulated_text.push_str(&text);
                     } else {
-                        if let Some(link_info) = self.append_text(
-                            &mut current_paragraph,
-                            &text,
-                            &current_element,
-                            ui,
-                            search_query,
-                            current_search_result,
-                        ) {
-                            paragraph_links.push(link_info);
+                        let mut critic = current_element.critic;
+                        for (run, kind) in critic::split_marks(&text, &mut critic) {
+                            current_element.critic = kind;
+                            if let Some(link_info) = self.append_text(
+                                &mut current_paragraph,
+                                &CowStr::from(run),
+                                &current_element,
+                                ui,
+                                search_query,
+                                current_search_result,
+                            ) {
+                                paragraph_links.push(link_info);
+                            }
                         }
+                        current_element.critic = critic;
                         paragraph_has_content = true;
                     }
                 }
-                Event::SoftBreak => {
-                    if !in_code_block {
-                        if current_element.is_heading {
-                            current_element.accumulated_text.push(' ');
-                        } else if !list_stack.is_empty() {
-                            current_list_item.push(' ');
-                        } else if let Some(link_info) = self.append_text(
-                            &mut current_paragraph,
-                            &CowStr::from(" "),
-                            &current_element,
-                            ui,
-                            search_query,
-                            current_search_result,
-                        ) {
-                            paragraph_links.push(link_info);
-                        }
+                Event::SoftBreak if !in_code_block => {
+                    if current_element.is_heading {
+                        current_element.accumulated_text.push(' ');
+                    } else if !list_stack.is_empty() {
+                        current_list_item.push(' ');
+                    } else if let Some(link_info) = self.append_text(
+                        &mut current_paragraph,
+                        &CowStr::from(" "),
+                        &current_element,
+                        ui,
+                        search_query,
+                        current_search_result,
+                    ) {
+                        paragraph_links.push(link_info);
                     }
                 }
-                Event::HardBreak => {
-                    if !in_code_block {
-                        if current_element.is_heading {
-                            current_element.accumulated_text.push('\n');
-                        } else if !list_stack.is_empty() {
-                            current_list_item.push('\n');
-                        } else if let Some(link_info) = self.append_text(
-                            &mut current_paragraph,
-                            &CowStr::from("\n"),
-                            &current_element,
+                Event::HardBreak if !in_code_block => {
+                    if current_element.is_heading {
+                        current_element.accumulated_text.push('\n');
+                    } else if !list_stack.is_empty() {
+                        current_list_item.push('\n');
+                    } else if let Some(link_info) = self.append_text(
+                        &mut current_paragraph,
+                        &CowStr::from("\n"),
+                        &current_element,
+                        ui,
+                        search_query,
+                        current_search_result,
+                    ) {
+                        paragraph_links.push(link_info);
+                    }
+                }
+                Event::Start(Tag::BlockQuote(_)) => {
+                    let end = Self::find_matching_end(&events, index);
+                    if let Some((callout, body)) = Self::split_callout(&events[index..end]) {
+                        self.render_callout(
                             ui,
+                            &callout,
+                            body,
+                            index,
                             search_query,
                             current_search_result,
-                        ) {
-                            paragraph_links.push(link_info);
-                        }
+                            image_cache,
+                            current_file,
+                            scroll_to_header,
+                            content_width,
+                        );
+                        ui.add_space(8.0);
+                        index = end + 1;
+                    } else {
+                        current_element.blockquote_depth += 1;
                     }
                 }
-                Event::Start(Tag::BlockQuote(_)) => {
-                    in_blockquote = true;
-                    current_element.is_blockquote = true;
-                }
                 Event::End(TagEnd::BlockQuote(_)) => {
-                    in_blockquote = false;
-                    current_element.is_blockquote = false;
+                    current_element.blockquote_depth =
+                        current_element.blockquote_depth.saturating_sub(1);
                 }
                 Event::Start(Tag::Link { dest_url, .. }) => {
                     current_element.is_link = true;
@@ -382,23 +2166,39 @@ impl MarkdownRenderer {
                 }
                 Event::Start(Tag::Item) => {
                     current_list_item.clear();
+                    current_task = None;
                 }
-                Event::End(TagEnd::Item) => {
-                    if !list_stack.is_empty() && !current_list_item.is_empty() {
-                        if let Some((_, ref mut items)) = list_stack.last_mut() {
-                            items.push((current_list_item.clone(), current_nesting_level));
-                        }
-                        current_list_item.clear();
+                Event::TaskListMarker(checked) => {
+                    current_task = Some(checked);
+                }
+                Event::End(TagEnd::Item)
+                    if !list_stack.is_empty() && !current_list_item.is_empty() =>
+                {
+                    if let Some((_, ref mut items)) = list_stack.last_mut() {
+                        items.push(ListItem {
+                            text: current_list_item.clone(),
+                            nesting_level: current_nesting_level,
+                            task: current_task.take(),
+                        });
                     }
+                    current_list_item.clear();
                 }
-                Event::Start(Tag::Table(_)) => {
+                Event::Start(Tag::Table(alignments)) => {
                     in_table = true;
                     table_headers.clear();
                     table_rows.clear();
+                    table_alignments = alignments;
                 }
                 Event::End(TagEnd::Table) => {
                     if in_table {
-                        self.render_table(ui, &table_headers, &table_rows, content_width);
+                        self.render_table(
+                            ui,
+                            index,
+                            &table_headers,
+                            &table_rows,
+                            &table_alignments,
+                            content_width,
+                        );
                         ui.add_space(8.0);
                     }
                     in_table = false;
@@ -406,63 +2206,192 @@ impl MarkdownRenderer {
                 Event::Start(Tag::TableHead) => {
                     current_table_row.clear();
                 }
-                Event::End(TagEnd::TableHead) => {
-                    if in_table {
-                        table_headers = current_table_row.clone();
-                        current_table_row.clear();
-                    }
+                Event::End(TagEnd::TableHead) if in_table => {
+                    table_headers = current_table_row.clone();
+                    current_table_row.clear();
                 }
                 Event::Start(Tag::TableRow) => {
                     current_table_row.clear();
                 }
-                Event::End(TagEnd::TableRow) => {
-                    if in_table && !current_table_row.is_empty() {
-                        table_rows.push(current_table_row.clone());
-                        current_table_row.clear();
-                    }
+                Event::End(TagEnd::TableRow) if in_table && !current_table_row.is_empty() => {
+                    table_rows.push(current_table_row.clone());
+                    current_table_row.clear();
                 }
                 Event::Start(Tag::TableCell) => {
                     current_table_cell.clear();
                 }
-                Event::End(TagEnd::TableCell) => {
-                    if in_table {
-                        current_table_row.push(current_table_cell.clone());
-                        current_table_cell.clear();
-                    }
+                Event::End(TagEnd::TableCell) if in_table => {
+                    current_table_row.push(current_table_cell.clone());
+                    current_table_cell.clear();
+                }
+                Event::Start(Tag::Image {
+                    link_type: LinkType::WikiLink { .. },
+                    dest_url,
+                    ..
+                }) if !is_image_url(&dest_url) => {
+                    // ![[note]] / ![[note#Section]] embeds another note's content
+                    self.render_embed(
+                        ui,
+                        &dest_url,
+                        search_query,
+                        image_cache,
+                        current_file,
+                        content_width,
+                    );
+                    ui.add_space(8.0);
+                    index = Self::find_matching_end(&events, index) + 1;
                 }
                 Event::Start(Tag::Image {
                     dest_url, title: _, ..
                 }) => {
                     // Image start - we'll get the alt text from the Text event and handle End event
                     current_element.link_url = dest_url.to_string();
+                    current_element.is_image = true;
                     current_element.accumulated_text.clear();
                 }
                 Event::End(TagEnd::Image) => {
+                    // A `{width=50%}` block right after the image sets its size
+                    let mut hints = ImageHints::default();
+                    if let Some(Event::Text(text)) = events.get(index) {
+                        if let Some((block_hints, length)) = ImageHints::from_attribute_block(text)
+                        {
+                            hints = block_hints;
+                            let rest = text[length..].to_string();
+                            if rest.is_empty() {
+                                index += 1;
+                            } else {
+                                events[index] = Event::Text(rest.into());
+                            }
+                        }
+                    }
                     // Render image with accumulated alt text
                     self.render_image(
                         ui,
                         &current_element.link_url,
                         &current_element.accumulated_text,
+                        hints,
                         image_cache,
                         current_file,
                         content_width,
                     );
                     ui.add_space(8.0);
                     current_element.link_url.clear();
+                    current_element.is_image = false;
                     current_element.accumulated_text.clear();
                 }
                 Event::Rule => {
                     ui.separator();
                     ui.add_space(8.0);
                 }
+                Event::FootnoteReference(label) => {
+                    let marker = self.footnote_marker(&label);
+                    if current_element.is_heading {
+                        current_element
+                            .accumulated_text
+                            .push_str(&format!("[{marker}]"));
+                    } else if in_table {
+                        current_table_cell.push_str(&format!("[{marker}]"));
+                    } else if !list_stack.is_empty() {
+                        current_list_item.push_str(&format!("[{marker}]"));
+                    } else {
+                        let start = current_paragraph.text.len();
+                        current_paragraph.append(
+                            &marker,
+                            1.0,
+                            TextFormat {
+                                font_id: FontId::proportional(self.base_font_size * 0.7),
+                                color: ui.visuals().hyperlink_color,
+                                valign: egui::Align::TOP,
+                                ..Default::default()
+                            },
+                        );
+                        paragraph_links.push(LinkSpan {
+                            url: format!("#fn:{label}"),
+                            range: start..current_paragraph.text.len(),
+                        });
+                        paragraph_has_content = true;
+                    }
+                }
+                Event::Start(Tag::FootnoteDefinition(label)) => {
+                    // Definitions are collected and rendered at the end of the document
+                    let end = Self::find_matching_end(&events, index);
+                    footnote_definitions.push((label.to_string(), events[index..end].to_vec()));
+                    index = end + 1;
+                }
                 _ => {}
             }
         }
+        self.record_block_height(measuring, ui.cursor().top());
+        if record_blocks {
+            self.block_matches.set(None);
+        }
+
+        if !footnote_definitions.is_empty() {
+            self.render_footnotes(
+                ui,
+                footnote_definitions,
+                search_query,
+                current_search_result,
+                image_cache,
+                current_file,
+                scroll_to_header,
+                content_width,
+            );
+        }
 
         // Return the scroll target if we found it
         scroll_to_header.clone()
     }
 
+    /// Leaves room for a top-level block instead of drawing it if it is far from the
+    /// viewport. Returns the index of the event after the block when it was skipped.
+    fn skip_block(
+        &self,
+        ui: &mut Ui,
+        events: &[Event],
+        index: usize,
+        block: usize,
+        top: f32,
+    ) -> Option<usize> {
+        let end = match &events[index] {
+            // Definitions are collected for the footnote list at the end of the document
+            Event::Start(Tag::FootnoteDefinition(_)) => return None,
+            Event::Start(_) => Self::find_matching_end(events, index + 1) + 1,
+            _ => index + 1,
+        };
+        let height = self.block_heights.borrow().heights.get(block).copied()?;
+        // Blocks never drawn get an estimate from their number of source lines
+        let height = height.unwrap_or_else(|| {
+            let lines = self
+                .layout
+                .borrow()
+                .block_lines
+                .get(block)
+                .map_or(1, |lines| lines.len());
+            lines as f32 * self.base_font_size * 1.5 + 8.0
+        });
+
+        let clip = ui.clip_rect();
+        let margin = clip.height();
+        if top < clip.bottom() + margin && top + height > clip.top() - margin {
+            return None;
+        }
+        if matches!(events[index], Event::Start(Tag::Heading { .. })) {
+            self.layout.borrow_mut().heading_tops.push(top);
+        }
+        ui.add_space(height);
+        Some(end)
+    }
+
+    /// Stores the height of a drawn block, given where it started and where it ended.
+    fn record_block_height(&self, measuring: Option<(usize, f32)>, bottom: f32) {
+        if let Some((block, top)) = measuring {
+            if let Some(height) = self.block_heights.borrow_mut().heights.get_mut(block) {
+                *height = Some(bottom - top);
+            }
+        }
+    }
+
     fn render_heading(
         &self,
         ui: &mut Ui,
@@ -489,19 +2418,27 @@ impl MarkdownRenderer {
         job.halign = egui::Align::LEFT; // Force left alignment
         job.justify = false; // Disable text justification
 
-        if !search_query.is_empty() {
-            self.append_heading_with_search_highlight(&mut job, text, font_size, ui, search_query);
+        if !search_query.is_empty() && self.search_scope.covers_headings() {
+            self.append_heading_with_search_highlight(
+                &mut job,
+                text,
+                font_size,
+                self.heading_color(level, ui),
+                ui,
+                search_query,
+            );
         } else {
             job.append(
                 text,
                 0.0,
                 TextFormat {
                     font_id: FontId::proportional(font_size),
-                    color: ui.visuals().text_color(),
+                    color: self.heading_color(level, ui),
                     ..Default::default()
                 },
             );
         }
+        self.mark_spoken(&mut job, ui);
 
         let response = ui
             .horizontal(|ui| {
@@ -513,11 +2450,57 @@ impl MarkdownRenderer {
                 .inner
             })
             .inner;
+        accessibility::heading(&response, level);
 
         // If this is the header we want to scroll to, do it now
         if should_scroll {
             response.scroll_to_me(Some(egui::Align::TOP));
         }
+
+        if self.detached_depth.get() == 0 {
+            self.layout
+                .borrow_mut()
+                .heading_tops
+                .push(response.rect.top());
+        }
+    }
+
+    /// Finds the search matches in a piece of rendered text.
+    fn search_matches(&self, text: &str, search_query: &str) -> Vec<std::ops::Range<usize>> {
+        search::find_matches(
+            text,
+            search_query,
+            self.case_sensitive_search,
+            self.fuzzy_search,
+        )
+    }
+
+    /// Underlines a highlighted search match if it is only an approximate match, so fuzzy
+    /// matches can be told apart from exact ones.
+    fn mark_fuzzy_match(&self, format: &mut TextFormat, matched: &str, search_query: &str) {
+        if self.fuzzy_search
+            && !search::is_exact_match(matched, search_query, self.case_sensitive_search)
+        {
+            format.underline = Stroke::new(1.5, format.color);
+        }
+    }
+
+    /// Text and background colors of the next highlighted search match. The current
+    /// result is drawn in the warning color with dark text so it stands out from the
+    /// other matches.
+    fn search_match_colors(&self, ui: &Ui) -> (Color32, Color32) {
+        let current = match self.block_matches.get() {
+            Some(seen) if self.detached_depth.get() == 0 => {
+                self.block_matches.set(Some(seen + 1));
+                self.current_match.get().map(|(_, index)| index) == Some(seen)
+            }
+            _ => false,
+        };
+        if current {
+            (Color32::BLACK, ui.visuals().warn_fg_color)
+        } else {
+            (ui.visuals().warn_fg_color, ui.visuals().selection.bg_fill)
+        }
     }
 
     fn append_heading_with_search_highlight(
@@ -525,18 +2508,15 @@ impl MarkdownRenderer {
         job: &mut LayoutJob,
         text: &str,
         font_size: f32,
+        color: Color32,
         ui: &Ui,
         search_query: &str,
     ) {
-        let text_lower = text.to_lowercase();
-        let query_lower = search_query.to_lowercase();
-
         let mut last_end = 0;
-        let mut start_pos = 0;
 
-        while let Some(pos) = text_lower[start_pos..].find(&query_lower) {
-            let match_start = start_pos + pos;
-            let match_end = match_start + search_query.len();
+        for range in self.search_matches(text, search_query) {
+            let (match_start, match_end) = (range.start, range.end);
+            let (match_color, match_background) = self.search_match_colors(ui);
 
             // Add text before the match
             if match_start > last_end {
@@ -546,7 +2526,7 @@ impl MarkdownRenderer {
                     0.0,
                     TextFormat {
                         font_id: FontId::proportional(font_size),
-                        color: ui.visuals().text_color(),
+                        color,
                         ..Default::default()
                     },
                 );
@@ -554,19 +2534,16 @@ impl MarkdownRenderer {
 
             // Add the highlighted match
             let match_text = &text[match_start..match_end];
-            job.append(
-                match_text,
-                0.0,
-                TextFormat {
-                    font_id: FontId::proportional(font_size),
-                    color: ui.visuals().warn_fg_color,
-                    background: ui.visuals().selection.bg_fill,
-                    ..Default::default()
-                },
-            );
+            let mut format = TextFormat {
+                font_id: FontId::proportional(font_size),
+                color: match_color,
+                background: match_background,
+                ..Default::default()
+            };
+            self.mark_fuzzy_match(&mut format, match_text, search_query);
+            job.append(match_text, 0.0, format);
 
             last_end = match_end;
-            start_pos = match_end;
         }
 
         // Add remaining text after the last match
@@ -577,7 +2554,7 @@ impl MarkdownRenderer {
                 0.0,
                 TextFormat {
                     font_id: FontId::proportional(font_size),
-                    color: ui.visuals().text_color(),
+                    color,
                     ..Default::default()
                 },
             );
@@ -592,7 +2569,8 @@ impl MarkdownRenderer {
         ui: &Ui,
         search_query: &str,
         _current_search_result: Option<&SearchResult>,
-    ) -> Option<(String, String)> {
+    ) -> Option<LinkSpan> {
+        let start = job.text.len();
         let font_size = if element.is_strong {
             self.base_font_size * 1.1 // Slightly larger for bold effect
         } else {
@@ -600,7 +2578,7 @@ impl MarkdownRenderer {
         };
 
         // Enhanced search highlighting
-        if !search_query.is_empty() {
+        if !search_query.is_empty() && self.search_scope.covers_prose() {
             self.append_text_with_search_highlight(job, text, element, ui, search_query, font_size);
         } else {
             // No search - render normally
@@ -611,7 +2589,8 @@ impl MarkdownRenderer {
             };
 
             let mut format = TextFormat {
-                font_id: FontId::proportional(font_size),
+                font_id: FontId::new(font_size, fonts::body_family()),
+                extra_letter_spacing: self.letter_spacing,
                 color,
                 background: Color32::TRANSPARENT,
                 underline: if element.is_link {
@@ -622,16 +2601,18 @@ impl MarkdownRenderer {
                 ..Default::default()
             };
 
-            if element.is_emphasis {
-                format.italics = true;
-            }
+            Self::apply_inline_style(&mut format, element, ui, false);
+            Self::apply_critic_style(&mut format, element.critic, ui);
 
             job.append(text, 0.0, format);
         }
 
         // Return link info if this is a link
         if element.is_link && !element.link_url.is_empty() {
-            Some((element.link_url.clone(), text.to_string()))
+            Some(LinkSpan {
+                url: element.link_url.clone(),
+                range: start..job.text.len(),
+            })
         } else {
             None
         }
@@ -647,15 +2628,10 @@ impl MarkdownRenderer {
         font_size: f32,
     ) {
         let text_str = text.to_string();
-        let text_lower = text_str.to_lowercase();
-        let query_lower = search_query.to_lowercase();
-
         let mut last_end = 0;
-        let mut start_pos = 0;
 
-        while let Some(pos) = text_lower[start_pos..].find(&query_lower) {
-            let match_start = start_pos + pos;
-            let match_end = match_start + search_query.len();
+        for range in self.search_matches(&text_str, search_query) {
+            let (match_start, match_end) = (range.start, range.end);
 
             // Add text before the match
             if match_start > last_end {
@@ -666,9 +2642,11 @@ impl MarkdownRenderer {
             // Add the highlighted match
             let match_text = &text_str[match_start..match_end];
             self.append_text_segment(job, match_text, element, ui, font_size, true);
+            if let Some(section) = job.sections.last_mut() {
+                self.mark_fuzzy_match(&mut section.format, match_text, search_query);
+            }
 
             last_end = match_end;
-            start_pos = match_end;
         }
 
         // Add remaining text after the last match
@@ -687,22 +2665,17 @@ impl MarkdownRenderer {
         font_size: f32,
         is_search_match: bool,
     ) {
-        let color = if is_search_match {
-            ui.visuals().warn_fg_color
+        let (color, background) = if is_search_match {
+            self.search_match_colors(ui)
         } else if element.is_link {
-            ui.visuals().hyperlink_color
-        } else {
-            ui.visuals().text_color()
-        };
-
-        let background = if is_search_match {
-            ui.visuals().selection.bg_fill
+            (ui.visuals().hyperlink_color, Color32::TRANSPARENT)
         } else {
-            Color32::TRANSPARENT
+            (ui.visuals().text_color(), Color32::TRANSPARENT)
         };
 
         let mut format = TextFormat {
-            font_id: FontId::proportional(font_size),
+            font_id: FontId::new(font_size, fonts::body_family()),
+            extra_letter_spacing: self.letter_spacing,
             color,
             background,
             underline: if element.is_link {
@@ -713,18 +2686,223 @@ impl MarkdownRenderer {
             ..Default::default()
         };
 
+        Self::apply_inline_style(&mut format, element, ui, is_search_match);
+        Self::apply_critic_style(&mut format, element.critic, ui);
+
+        job.append(text, 0.0, format);
+    }
+
+    /// Applies emphasis, strikethrough and the inline HTML styles to a text format.
+    fn apply_inline_style(
+        format: &mut TextFormat,
+        element: &ElementState,
+        ui: &Ui,
+        is_search_match: bool,
+    ) {
         if element.is_emphasis {
             format.italics = true;
         }
+        if element.is_strikethrough {
+            format.strikethrough = Stroke::new(1.0, format.color);
+        }
+        if element.is_underline && !element.is_link {
+            format.underline = Stroke::new(1.0, format.color);
+        }
+        if element.is_code {
+            format.font_id = FontId::monospace(format.font_id.size * 0.9);
+            if !is_search_match {
+                format.background = ui.visuals().code_bg_color;
+            }
+        }
+        if element.is_highlight && !is_search_match {
+            format.background = Color32::from_rgba_unmultiplied(229, 192, 123, 90);
+        }
+        if element.is_superscript || element.is_subscript {
+            format.font_id.size *= 0.7;
+            format.valign = if element.is_superscript {
+                egui::Align::TOP
+            } else {
+                egui::Align::BOTTOM
+            };
+        }
+    }
 
-        job.append(text, 0.0, format);
+    /// Appends an unsupported HTML tag as dimmed literal text.
+    fn append_literal_html(&self, job: &mut LayoutJob, source: &str, ui: &Ui) {
+        job.append(
+            source,
+            0.0,
+            TextFormat {
+                font_id: FontId::monospace(self.base_font_size * 0.85),
+                color: ui.visuals().weak_text_color(),
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Draws an HTML block, mapping the supported tags to formatting. Block-level tags
+    /// start new paragraphs; unsupported tags are shown as dimmed literal text.
+    fn render_html_block(
+        &self,
+        ui: &mut Ui,
+        html_source: &str,
+        image_cache: &mut HashMap<String, Result<egui::TextureHandle, String>>,
+        current_file: &Option<PathBuf>,
+        content_width: Option<f32>,
+    ) {
+        let mut job = LayoutJob::default();
+        let mut links: Vec<LinkSpan> = Vec::new();
+        let mut element = ElementState::default();
+        let flush = |ui: &mut Ui,
+                     job: &mut LayoutJob,
+                     links: &mut Vec<LinkSpan>,
+                     image_cache: &mut HashMap<_, _>| {
+            if !job.text.trim().is_empty() {
+                self.render_paragraph_with_links(
+                    ui,
+                    std::mem::take(job),
+                    links,
+                    image_cache,
+                    current_file,
+                    content_width,
+                );
+                ui.add_space(8.0);
+            }
+            *job = LayoutJob::default();
+            links.clear();
+        };
+
+        let mut tables = 0;
+        let mut tokens = html::tokenize(html_source).into_iter();
+        while let Some(token) = tokens.next() {
+            match token {
+                HtmlToken::Comment(comment) => {
+                    if self.show_comments && !comment.is_empty() {
+                        flush(ui, &mut job, &mut links, image_cache);
+                        self.render_comment_block(ui, comment, content_width);
+                    }
+                }
+                HtmlToken::Text(text) => {
+                    // Whitespace in HTML collapses to single spaces
+                    let text = html::decode_entities(text);
+                    let mut collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                    if text.starts_with(char::is_whitespace) && !job.text.is_empty() {
+                        collapsed.insert(0, ' ');
+                    }
+                    if text.ends_with(char::is_whitespace) && !collapsed.is_empty() {
+                        collapsed.push(' ');
+                    }
+                    if collapsed.is_empty() {
+                        continue;
+                    }
+                    if let Some(link) =
+                        self.append_text(&mut job, &CowStr::from(collapsed), &element, ui, "", None)
+                    {
+                        links.push(link);
+                    }
+                }
+                HtmlToken::Tag(tag) => match tag.name.as_str() {
+                    "br" => job.append("\n", 0.0, TextFormat::default()),
+                    "hr" => {
+                        flush(ui, &mut job, &mut links, image_cache);
+                        ui.separator();
+                        ui.add_space(8.0);
+                    }
+                    "img" if !tag.closing => {
+                        flush(ui, &mut job, &mut links, image_cache);
+                        if let Some(src) = tag.attribute("src") {
+                            let alt = tag.attribute("alt").unwrap_or_default();
+                            self.render_image(
+                                ui,
+                                &src,
+                                &alt,
+                                ImageHints::from_tag(&tag),
+                                image_cache,
+                                current_file,
+                                content_width,
+                            );
+                            ui.add_space(8.0);
+                        }
+                    }
+                    "table" if !tag.closing => {
+                        flush(ui, &mut job, &mut links, image_cache);
+                        let table = HtmlTable::parse(&mut tokens);
+                        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                        (html_source, tables).hash(&mut hasher);
+                        tables += 1;
+                        self.render_table(
+                            ui,
+                            hasher.finish() as usize,
+                            &table.headers,
+                            &table.rows,
+                            &table.alignments,
+                            content_width,
+                        );
+                        ui.add_space(8.0);
+                    }
+                    name if is_html_block_tag(name) => {
+                        flush(ui, &mut job, &mut links, image_cache);
+                        element.is_strong = !tag.closing && name.starts_with('h');
+                        if name == "li" && !tag.closing {
+                            job.append(
+                                "• ",
+                                0.0,
+                                TextFormat {
+                                    font_id: FontId::proportional(self.base_font_size),
+                                    color: ui.visuals().text_color(),
+                                    ..Default::default()
+                                },
+                            );
+                        }
+                    }
+                    _ => {
+                        if !apply_html_tag(&mut element, &tag) {
+                            self.append_literal_html(&mut job, tag.source, ui);
+                        }
+                    }
+                },
+            }
+        }
+        flush(ui, &mut job, &mut links, image_cache);
+    }
+
+    /// Styles text inside a CriticMarkup annotation.
+    fn apply_critic_style(format: &mut TextFormat, kind: Option<CriticKind>, ui: &Ui) {
+        let Some(kind) = kind else {
+            return;
+        };
+        match kind {
+            CriticKind::Addition => {
+                format.color = Color32::from_rgb(152, 195, 121);
+                format.underline = Stroke::new(1.0, format.color);
+            }
+            CriticKind::Deletion => {
+                format.color = Color32::from_rgb(224, 108, 117);
+                format.strikethrough = Stroke::new(1.0, format.color);
+            }
+            CriticKind::Highlight => {
+                if format.background == Color32::TRANSPARENT {
+                    format.background = Color32::from_rgba_unmultiplied(229, 192, 123, 60);
+                }
+            }
+            CriticKind::Comment => {
+                format.color = ui.visuals().weak_text_color();
+                format.italics = true;
+                format.font_id.size *= 0.9;
+                if format.background == Color32::TRANSPARENT {
+                    format.background = ui.visuals().faint_bg_color;
+                }
+            }
+        }
     }
 
     fn render_paragraph_with_links(
         &self,
         ui: &mut Ui,
         mut job: LayoutJob,
-        links: &[(String, String)],
+        links: &[LinkSpan],
+        image_cache: &mut HashMap<String, Result<egui::TextureHandle, String>>,
+        current_file: &Option<PathBuf>,
         content_width: Option<f32>,
     ) {
         // Force proper wrapping by using content width constraint
@@ -733,6 +2911,11 @@ impl MarkdownRenderer {
         job.wrap.break_anywhere = false;
         job.wrap.overflow_character = Some('…');
         job.halign = egui::Align::LEFT;
+        let noted = self.annotate(&mut job, ui);
+        self.mark_spoken(&mut job, ui);
+
+        // Lay out the galley ourselves so hovered links can be hit-tested against it
+        let galley = ui.fonts(|fonts| fonts.layout_job(job));
 
         // Force left alignment by using horizontal layout
         let response = ui
@@ -740,114 +2923,467 @@ impl MarkdownRenderer {
                 ui.allocate_ui_with_layout(
                     [max_width, 0.0].into(),
                     egui::Layout::left_to_right(egui::Align::TOP),
-                    |ui| ui.add(egui::Label::new(job).wrap()),
+                    |ui| ui.add(egui::Label::new(galley.clone())),
                 )
                 .inner
             })
             .inner;
+        accessibility::links(
+            &response,
+            &galley,
+            links
+                .iter()
+                .map(|link| (link.url.as_str(), link.range.clone())),
+        );
+        if self.detached_depth.get() == 0 {
+            self.annotation_menu(&response);
+            self.render_note_markers(ui, &response, &galley, &noted, max_width);
+        }
+
+        if !links.is_empty() && response.hovered() {
+            let hovered_link = ui
+                .ctx()
+                .pointer_hover_pos()
+                .and_then(|pos| link_at(&galley, response.rect.min, links, pos));
+            if let Some(link) = hovered_link {
+                // Show pointer cursor only over the link text itself
+                ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+
+                // Peek at links without navigating away
+                let tooltip_id = response.id.with(&link.url);
+                if let Some(label) = link.url.strip_prefix("#fn:") {
+                    // Read a footnote without jumping to the end of the document
+                    let text = self.footnote_texts.borrow().get(label).cloned();
+                    if let Some(text) = text {
+                        let marker = self.footnote_marker(label);
+                        egui::show_tooltip_at_pointer(ui.ctx(), ui.layer_id(), tooltip_id, |ui| {
+                            ui.set_max_width(360.0);
+                            ui.label(format!("{marker}. {text}"));
+                        });
+                    }
+                } else if let Some((path, section)) =
+                    self.resolve_internal_link(&link.url, current_file)
+                {
+                    egui::show_tooltip_at_pointer(ui.ctx(), ui.layer_id(), tooltip_id, |ui| {
+                        self.render_link_preview(ui, &path, section, image_cache)
+                    });
+                } else if !link.url.starts_with("#fnref:") {
+                    egui::show_tooltip_at_pointer(ui.ctx(), ui.layer_id(), tooltip_id, |ui| {
+                        ui.set_max_width(480.0);
+                        ui.add(egui::Label::new(RichText::new(&link.url).monospace()).wrap());
+                    });
+                }
+            }
+        }
+
+        // Scroll back to the reference of the footnote the reader came from
+        let jump = self.footnote_jump.borrow().clone();
+        if let Some(label) = jump.as_deref().and_then(|jump| jump.strip_prefix("fnref:")) {
+            let target = format!("#fn:{label}");
+            if links.iter().any(|link| link.url == target) {
+                ui.scroll_to_rect(response.rect, Some(egui::Align::Center));
+                *self.footnote_jump.borrow_mut() = None;
+            }
+        }
+
+        // Handle link clicks
+        let clicked_link = response
+            .interact_pointer_pos()
+            .filter(|_| response.clicked())
+            .and_then(|pos| link_at(&galley, response.rect.min, links, pos));
+        let clicked_fragment = clicked_link.and_then(|link| link.url.strip_prefix('#'));
+        if let Some(label) = clicked_fragment.and_then(|fragment| fragment.strip_prefix("fn:")) {
+            *self.footnote_jump.borrow_mut() = Some(format!("fn:{label}"));
+            ui.ctx().request_repaint();
+        } else if let Some(fragment) = clicked_fragment {
+            *self.anchor_jump.borrow_mut() = Some(fragment.replace("%20", " "));
+            ui.ctx().request_repaint();
+        } else if let Some(LinkSpan { url, .. }) = clicked_link {
+            if url.starts_with("http://") || url.starts_with("https://") {
+                let _ = webbrowser::open(url);
+            } else if let Some(target) = self.resolve_internal_link(url, current_file) {
+                *self.clicked_note.borrow_mut() = Some(target);
+                ui.ctx().request_repaint();
+            }
+        }
+    }
+
+    /// Sets the sentence being read aloud and the source line of its block, to be
+    /// highlighted in the document; None highlights nothing.
+    pub fn set_spoken_sentence(&mut self, sentence: Option<(String, usize)>) {
+        self.spoken_sentence = sentence;
+    }
+
+    /// Highlights the sentence being read aloud in a text of the block holding it.
+    fn mark_spoken(&self, job: &mut LayoutJob, ui: &Ui) {
+        if self.detached_depth.get() > 0 || !self.in_spoken_block.get() {
+            return;
+        }
+        let Some((sentence, _)) = &self.spoken_sentence else {
+            return;
+        };
+        if let Some(start) = job.text.find(sentence.as_str()) {
+            let background = ui.visuals().selection.bg_fill.gamma_multiply(0.5);
+            restyle_range(job, start..start + sentence.len(), |format| {
+                format.background = background;
+            });
+        }
+    }
+
+    /// Highlights the annotated passages of a paragraph of the main document. Returns the
+    /// annotations with a note found in it, with the byte range of their first match.
+    fn annotate(&self, job: &mut LayoutJob, ui: &Ui) -> Vec<(usize, std::ops::Range<usize>)> {
+        let mut noted = Vec::new();
+        if self.detached_depth.get() > 0 {
+            return noted;
+        }
+        let background = Color32::from_rgba_unmultiplied(229, 192, 123, 90);
+        let underline = Stroke::new(1.0, ui.visuals().warn_fg_color);
+        for (index, annotation) in self.annotations.iter().enumerate() {
+            let mut first = None;
+            // A selection spanning paragraphs was copied with a line per paragraph
+            for line in annotation.quote.lines().map(str::trim) {
+                if line.is_empty() {
+                    continue;
+                }
+                let ranges: Vec<_> = job
+                    .text
+                    .match_indices(line)
+                    .map(|(start, text)| start..start + text.len())
+                    .collect();
+                for range in ranges {
+                    first.get_or_insert(range.clone());
+                    restyle_range(job, range, |format| {
+                        // Search matches stay visible over highlights
+                        if format.background == Color32::TRANSPARENT {
+                            format.background = background;
+                        }
+                        if annotation.note.is_some() {
+                            format.underline = underline;
+                        }
+                    });
+                }
+            }
+            if let (Some(range), Some(_)) = (first, &annotation.note) {
+                noted.push((index, range));
+            }
+        }
+        noted
+    }
+
+    /// Offers to highlight or annotate the selected text from a paragraph's context menu.
+    fn annotation_menu(&self, response: &Response) {
+        // The selection is read on the next frame, while it is still there
+        if response.secondary_clicked() {
+            self.selection_wanted.set(true);
+        }
+        response.context_menu(|ui| {
+            if ui.button("Highlight Selection").clicked() {
+                self.annotation_request.set(Some(AnnotationKind::Highlight));
+                ui.close_menu();
+            }
+            if ui.button("Add Note to Selection…").clicked() {
+                self.annotation_request.set(Some(AnnotationKind::Note));
+                ui.close_menu();
+            }
+        });
+    }
+
+    /// Draws a marker in the right margin next to each annotated passage with a note,
+    /// showing the note on hover.
+    fn render_note_markers(
+        &self,
+        ui: &Ui,
+        response: &Response,
+        galley: &Galley,
+        noted: &[(usize, std::ops::Range<usize>)],
+        max_width: f32,
+    ) {
+        let x = (response.rect.left() + max_width + 6.0).min(ui.clip_rect().right() - 16.0);
+        for (index, range) in noted {
+            let Some(note) = self.annotations[*index].note.as_deref() else {
+                continue;
+            };
+            let chars = galley.text()[..range.start].chars().count();
+            let row = galley.pos_from_ccursor(egui::text::CCursor::new(chars));
+            let rect = egui::Rect::from_min_size(
+                egui::pos2(x, response.rect.top() + row.top()),
+                egui::vec2(14.0, row.height()),
+            );
+            let marker = ui
+                .interact(rect, response.id.with(("note", *index)), Sense::click())
+                .on_hover_cursor(egui::CursorIcon::PointingHand)
+                .on_hover_text(if note.is_empty() { "Empty note" } else { note });
+            ui.painter().text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                "✎",
+                FontId::proportional(self.base_font_size * 0.9),
+                ui.visuals().warn_fg_color,
+            );
+            if marker.clicked() {
+                self.clicked_annotation.set(Some(*index));
+            }
+        }
+    }
+
+    /// Takes the target of the in-document `#fragment` link clicked since the last call.
+    pub fn take_anchor_jump(&self) -> Option<String> {
+        self.anchor_jump.borrow_mut().take()
+    }
+
+    /// Forgets the bytes and animation frames of the loaded images; called when the
+    /// application clears its image cache.
+    pub fn forget_images(&self) {
+        self.image_bytes.borrow_mut().clear();
+        self.animations.borrow_mut().clear();
+    }
+
+    /// Encoded bytes of a loaded image, as read from disk or downloaded.
+    pub fn image_bytes(&self, url: &str) -> Option<Arc<[u8]>> {
+        self.image_bytes.borrow().get(url).cloned()
+    }
+
+    /// Takes the document and section of the link to another note clicked since the
+    /// last call.
+    pub fn take_clicked_note(&self) -> Option<(PathBuf, Option<String>)> {
+        self.clicked_note.borrow_mut().take()
+    }
+
+    /// Takes the source and texture of the image clicked since the last call.
+    pub fn take_clicked_image(&self) -> Option<(String, egui::TextureHandle)> {
+        self.clicked_image.borrow_mut().take()
+    }
+
+    /// Number shown for a footnote, or its label if it has none.
+    fn footnote_marker(&self, label: &str) -> String {
+        self.footnote_numbers
+            .borrow()
+            .get(label)
+            .map_or_else(|| label.to_string(), usize::to_string)
+    }
+
+    /// Renders footnote definitions as a numbered list below the document.
+    ///
+    /// Clicking a footnote's number scrolls back to where it is referenced.
+    #[allow(clippy::too_many_arguments)]
+    fn render_footnotes(
+        &self,
+        ui: &mut Ui,
+        mut definitions: Vec<(String, Vec<Event>)>,
+        search_query: &str,
+        current_search_result: Option<&SearchResult>,
+        image_cache: &mut HashMap<String, Result<egui::TextureHandle, String>>,
+        current_file: &Option<PathBuf>,
+        scroll_to_header: &Option<String>,
+        content_width: Option<f32>,
+    ) {
+        definitions.sort_by_key(|(label, _)| {
+            self.footnote_numbers
+                .borrow()
+                .get(label)
+                .copied()
+                .unwrap_or(usize::MAX)
+        });
+        let width = content_width.unwrap_or(ui.available_width());
+        ui.add_space(16.0);
+        ui.separator();
+        ui.add_space(8.0);
+
+        for (label, body) in definitions {
+            let response = ui
+                .horizontal_top(|ui| {
+                    let marker = ui
+                        .link(format!("{}.", self.footnote_marker(&label)))
+                        .on_hover_text("Back to reference");
+                    if marker.clicked() {
+                        *self.footnote_jump.borrow_mut() = Some(format!("fnref:{label}"));
+                        ui.ctx().request_repaint();
+                    }
+                    ui.vertical(|ui| {
+                        self.render_events(
+                            ui,
+                            body,
+                            search_query,
+                            current_search_result,
+                            image_cache,
+                            current_file,
+                            scroll_to_header,
+                            Some(width - 40.0),
+                        );
+                    });
+                })
+                .response;
 
-        // Show pointer cursor when hovering over paragraphs with links
-        if !links.is_empty() && response.hovered() {
-            ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+            let target = format!("fn:{label}");
+            if self.footnote_jump.borrow().as_deref() == Some(target.as_str()) {
+                ui.scroll_to_rect(response.rect, Some(egui::Align::Center));
+                *self.footnote_jump.borrow_mut() = None;
+            }
         }
+    }
 
-        // Handle link clicks
-        if response.clicked() {
-            if let Some((url, _text)) = links.first() {
-                if url.starts_with("http://") || url.starts_with("https://") {
-                    let _ = webbrowser::open(url);
-                }
+    /// Resolves a link target to a markdown note (and optional section) if it is internal.
+    ///
+    /// Web URLs and in-document `#fragment` links are not considered internal.
+    fn resolve_internal_link(
+        &self,
+        url: &str,
+        current_file: &Option<PathBuf>,
+    ) -> Option<(PathBuf, Option<String>)> {
+        if url.starts_with('#') || url.contains("://") || url.starts_with("mailto:") {
+            return None;
+        }
+        let (note, section) = vault::split_target(url);
+        let note = note.replace("%20", " ");
+        let path = self.resolve_note(&note, current_file)?;
+        Some((path, section.map(str::to_string)))
+    }
+
+    /// Resolves a note name or relative path through the vault (or the current folder).
+    fn resolve_note(&self, note: &str, current_file: &Option<PathBuf>) -> Option<PathBuf> {
+        match &self.vault {
+            Some(vault) => vault.resolve(note, current_file.as_deref()),
+            None => {
+                let dir = current_file.as_ref()?.parent()?;
+                [dir.join(note), dir.join(format!("{note}.md"))]
+                    .into_iter()
+                    .find(|path| path.is_file() && vault::is_markdown(path))
             }
         }
     }
 
-    fn render_nested_list(
+    /// Renders the first few blocks of a note inside a hover popup.
+    fn render_link_preview(
         &self,
         ui: &mut Ui,
-        items: &[(String, usize)],
-        is_ordered: bool,
-        content_width: Option<f32>,
+        path: &PathBuf,
+        section: Option<String>,
+        image_cache: &mut HashMap<String, Result<egui::TextureHandle, String>>,
     ) {
-        let max_width = content_width.unwrap_or(ui.available_width());
+        let width = 360.0;
+        ui.set_max_width(width);
+        ui.label(
+            RichText::new(format!(
+                "📄 {}",
+                path.file_stem().unwrap_or_default().to_string_lossy()
+            ))
+            .size(self.base_font_size * 0.9)
+            .color(ui.visuals().weak_text_color()),
+        );
+        ui.separator();
 
-        for (index, (item, nesting_level)) in items.iter().enumerate() {
-            ui.horizontal(|ui| {
-                // Dynamic indentation based on nesting level
-                let base_indent = 20.0;
-                let indent_per_level = 30.0;
-                let total_indent = base_indent + (indent_per_level * (*nesting_level as f32));
-                ui.add_space(total_indent);
+        let Some(content) = self.load_note(path) else {
+            ui.label(RichText::new("Note could not be read").italics());
+            return;
+        };
+        let markdown = match &section {
+            Some(section) => vault::extract_section(&content, section).unwrap_or(&content),
+            None => content.as_str(),
+        };
 
-                if is_ordered {
-                    ui.label(format!("{}.", index + 1));
-                } else {
-                    ui.label("•");
+        // Keep only the first few top-level blocks
+        let mut events = Vec::new();
+        let mut depth = 0usize;
+        let mut blocks = 0;
+        for event in Parser::new_ext(markdown, Self::parser_options()) {
+            match &event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        blocks += 1;
+                    }
                 }
-
-                ui.add_space(8.0);
-
-                // Create a label with proper text wrapping
-                let available_width = max_width - total_indent - 40.0; // Account for indentation, bullet, and spacing
-                let mut job = LayoutJob::default();
-                job.wrap.max_width = available_width;
-                job.wrap.break_anywhere = false;
-                job.halign = egui::Align::LEFT;
-                job.append(
-                    item.trim(),
-                    0.0,
-                    TextFormat {
-                        font_id: FontId::proportional(self.base_font_size),
-                        color: ui.visuals().text_color(),
-                        ..Default::default()
-                    },
-                );
-
-                ui.horizontal(|ui| {
-                    ui.allocate_ui_with_layout(
-                        [available_width, 0.0].into(),
-                        egui::Layout::left_to_right(egui::Align::TOP),
-                        |ui| ui.add(egui::Label::new(job).wrap()),
-                    );
-                });
-            });
-            ui.add_space(4.0);
+                Event::Rule if depth == 0 => blocks += 1,
+                _ => {}
+            }
+            events.push(event);
+            if blocks >= PREVIEW_BLOCKS {
+                break;
+            }
         }
+
+        let events = self.prepare_events(events);
+        self.detached_depth.set(self.detached_depth.get() + 1);
+        self.render_events(
+            ui,
+            events,
+            "",
+            None,
+            image_cache,
+            &Some(path.clone()),
+            &None,
+            Some(width),
+        );
+        self.detached_depth.set(self.detached_depth.get() - 1);
     }
 
-    fn render_table(
+    fn render_nested_list(
         &self,
         ui: &mut Ui,
-        headers: &[String],
-        rows: &[Vec<String>],
+        items: &[ListItem],
+        is_ordered: bool,
         content_width: Option<f32>,
     ) {
-        if headers.is_empty() && rows.is_empty() {
-            return;
-        }
+        let max_width = content_width.unwrap_or(ui.available_width());
+        let ctx = ui.ctx().clone();
+        let list_id = ui.next_auto_id().with("list");
 
-        egui::Frame::none()
-            .stroke(egui::Stroke::new(1.0, ui.visuals().weak_text_color()))
-            .inner_margin(egui::Margin::same(8.0))
-            .show(ui, |ui| {
-                egui::Grid::new("table")
-                    .num_columns(
-                        headers
-                            .len()
-                            .max(rows.iter().map(|r| r.len()).max().unwrap_or(0)),
-                    )
-                    .spacing([10.0, 4.0])
-                    .striped(true)
-                    .show(ui, |ui| {
-                        // Render headers
-                        if !headers.is_empty() {
-                            let available_width = content_width.unwrap_or(ui.available_width());
-                            for header in headers {
+        accessibility::group(
+            &ctx,
+            list_id,
+            accesskit::Role::List,
+            |_| {},
+            || {
+                for (index, list_item) in items.iter().enumerate() {
+                    let (item, nesting_level) = (&list_item.text, &list_item.nesting_level);
+                    let item_id = list_id.with(index);
+                    let describe = |builder: &mut accesskit::NodeBuilder| {
+                        builder.set_hierarchical_level(nesting_level + 1);
+                        if let Some(checked) = list_item.task {
+                            builder.set_checked(if checked {
+                                accesskit::Checked::True
+                            } else {
+                                accesskit::Checked::False
+                            });
+                        }
+                    };
+                    accessibility::group(
+                        &ctx,
+                        item_id,
+                        accesskit::Role::ListItem,
+                        describe,
+                        || {
+                            ui.horizontal(|ui| {
+                                // Dynamic indentation based on nesting level
+                                let base_indent = 20.0;
+                                let indent_per_level = 30.0;
+                                let total_indent =
+                                    base_indent + (indent_per_level * (*nesting_level as f32));
+                                ui.add_space(total_indent);
+
+                                if is_ordered {
+                                    ui.label(format!("{}.", index + 1));
+                                }
+                                match list_item.task {
+                                    Some(checked) => self.paint_task_checkbox(ui, checked),
+                                    None if !is_ordered => {
+                                        ui.label("•");
+                                    }
+                                    None => {}
+                                }
+
+                                ui.add_space(8.0);
+
+                                // Create a label with proper text wrapping
+                                let available_width = max_width - total_indent - 40.0; // Account for indentation, bullet, and spacing
                                 let mut job = LayoutJob::default();
-                                job.wrap.max_width = available_width / headers.len() as f32;
+                                job.wrap.max_width = available_width;
                                 job.wrap.break_anywhere = false;
                                 job.halign = egui::Align::LEFT;
                                 job.append(
-                                    header.trim(),
+                                    item.trim(),
                                     0.0,
                                     TextFormat {
                                         font_id: FontId::proportional(self.base_font_size),
@@ -855,80 +3391,632 @@ impl MarkdownRenderer {
                                         ..Default::default()
                                     },
                                 );
+                                self.mark_spoken(&mut job, ui);
+
                                 ui.horizontal(|ui| {
                                     ui.allocate_ui_with_layout(
-                                        [available_width / headers.len() as f32, 0.0].into(),
+                                        [available_width, 0.0].into(),
                                         egui::Layout::left_to_right(egui::Align::TOP),
                                         |ui| ui.add(egui::Label::new(job).wrap()),
                                     );
                                 });
-                            }
-                            ui.end_row();
+                            });
+                        },
+                    );
+                    ui.add_space(4.0);
+                }
+            },
+        );
+    }
+
+    /// Draws the checkbox of a task list item (`- [ ]` / `- [x]`).
+    fn paint_task_checkbox(&self, ui: &mut Ui, checked: bool) {
+        let size = self.base_font_size * 0.9;
+        let (rect, _) = ui.allocate_exact_size(Vec2::splat(size), Sense::hover());
+        let rect = rect.translate(Vec2::new(0.0, size * 0.15));
+        let visuals = ui.visuals();
+        let stroke = Stroke::new(1.5, visuals.text_color());
+        if checked {
+            ui.painter().rect(
+                rect,
+                2.0,
+                visuals.selection.bg_fill,
+                Stroke::new(1.5, visuals.selection.bg_fill),
+            );
+            let check = [
+                rect.left_top() + Vec2::new(size * 0.2, size * 0.5),
+                rect.left_top() + Vec2::new(size * 0.42, size * 0.72),
+                rect.left_top() + Vec2::new(size * 0.8, size * 0.28),
+            ];
+            ui.painter()
+                .line_segment([check[0], check[1]], Stroke::new(2.0, Color32::WHITE));
+            ui.painter()
+                .line_segment([check[1], check[2]], Stroke::new(2.0, Color32::WHITE));
+        } else {
+            ui.painter().rect_stroke(rect, 2.0, stroke);
+        }
+    }
+
+    fn render_table(
+        &self,
+        ui: &mut Ui,
+        id_source: usize,
+        headers: &[String],
+        rows: &[Vec<String>],
+        alignments: &[Alignment],
+        content_width: Option<f32>,
+    ) {
+        if headers.is_empty() && rows.is_empty() {
+            return;
+        }
+
+        let num_columns = headers
+            .len()
+            .max(rows.iter().map(|r| r.len()).max().unwrap_or(0));
+        let spacing = egui::vec2(10.0, 4.0);
+        let margin = 8.0;
+        let available_width = content_width.unwrap_or(ui.available_width())
+            - 2.0 * margin
+            - spacing.x * (num_columns.saturating_sub(1)) as f32;
+        let format = TextFormat {
+            font_id: FontId::proportional(self.base_font_size),
+            color: ui.visuals().text_color(),
+            ..Default::default()
+        };
+
+        // Widths dragged by the user override the automatic layout until reset
+        let id = ui.make_persistent_id(("table", id_source));
+        let state_id = id.with("state");
+        let mut state: TableState = ui.data(|d| d.get_temp(state_id)).unwrap_or_default();
+
+        // Rows sorted by a clicked header, kept until the table or the order changes
+        let sorted;
+        let rows = match state.sort {
+            Some(order) => {
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                (rows, order).hash(&mut hasher);
+                let key = hasher.finish();
+                let cache_id = id.with("sorted");
+                sorted = match ui.data(|d| d.get_temp::<(u64, Arc<Vec<Vec<String>>>)>(cache_id)) {
+                    Some((cached_key, rows)) if cached_key == key => rows,
+                    _ => {
+                        let rows = Arc::new(table::sorted_rows(rows, order));
+                        ui.data_mut(|d| d.insert_temp(cache_id, (key, rows.clone())));
+                        rows
+                    }
+                };
+                sorted.as_slice()
+            }
+            None => rows,
+        };
+        // Header cells show which column the rows are sorted by
+        let header_texts: Vec<String> = if headers.is_empty() {
+            Vec::new()
+        } else {
+            (0..num_columns)
+                .map(|col| {
+                    let text = headers.get(col).map_or("", |h| h.trim());
+                    match state.sort {
+                        Some(order) if order.column == col => {
+                            format!("{text} {}", if order.descending { "⏷" } else { "⏶" })
                         }
+                        _ => text.to_string(),
+                    }
+                })
+                .collect()
+        };
+        let widths = ui
+            .data(|d| d.get_temp::<Vec<f32>>(id))
+            .filter(|widths| widths.len() == num_columns)
+            .unwrap_or_else(|| {
+                // Measuring a sample of rows keeps generated reports with thousands of rows fast
+                let cells = std::iter::once(headers)
+                    .chain(rows.iter().take(VIRTUAL_TABLE_ROWS).map(Vec::as_slice));
+                Self::auto_column_widths(ui, cells, num_columns, available_width, &format)
+            });
+        // Huge tables only render the visible rows, which requires one line per row
+        let virtualized = rows.len() > VIRTUAL_TABLE_ROWS;
 
-                        // Render rows
-                        for row in rows {
-                            let max_cols = headers.len().max(row.len());
-                            let available_width = content_width.unwrap_or(ui.available_width());
-                            for col in 0..max_cols {
-                                let cell_text = row.get(col).map(|s| s.trim()).unwrap_or("");
-                                let mut job = LayoutJob::default();
-                                job.wrap.max_width = available_width / max_cols as f32;
-                                job.wrap.break_anywhere = false;
-                                job.halign = egui::Align::LEFT;
-                                job.append(
-                                    cell_text,
-                                    0.0,
-                                    TextFormat {
-                                        font_id: FontId::proportional(self.base_font_size),
-                                        color: ui.visuals().text_color(),
-                                        ..Default::default()
-                                    },
-                                );
-                                ui.horizontal(|ui| {
-                                    ui.allocate_ui_with_layout(
-                                        [available_width / max_cols as f32, 0.0].into(),
-                                        egui::Layout::left_to_right(egui::Align::TOP),
-                                        |ui| ui.add(egui::Label::new(job).wrap()),
+        // Cells follow the column's `:---:` alignment marker
+        let cell = |ui: &mut Ui, text: &str, col: usize, role: accesskit::Role| {
+            let width = widths[col];
+            let align = match alignments.get(col) {
+                Some(Alignment::Center) => egui::Align::Center,
+                Some(Alignment::Right) => egui::Align::RIGHT,
+                _ => egui::Align::LEFT,
+            };
+            let mut job = LayoutJob::single_section(text.trim().to_string(), format.clone());
+            job.wrap.max_width = width;
+            job.wrap.break_anywhere = virtualized;
+            if virtualized {
+                job.wrap.max_rows = 1;
+            }
+            job.halign = align;
+            ui.allocate_ui_with_layout([width, 0.0].into(), egui::Layout::top_down(align), |ui| {
+                ui.set_min_width(width);
+                let response = ui.add(egui::Label::new(job).wrap());
+                accessibility::set_role(&response, role);
+            })
+            .response
+            .rect
+        };
+
+        // Screen rects of the body cells drawn this frame, as (row, column, rect)
+        let mut cell_rects: Vec<(usize, usize, egui::Rect)> = Vec::new();
+        // Screen rects of the header cells, as (column, rect)
+        let mut header_rects: Vec<(usize, egui::Rect)> = Vec::new();
+
+        let ctx = ui.ctx().clone();
+        let row_count = rows.len() + usize::from(!headers.is_empty());
+        let describe = |builder: &mut accesskit::NodeBuilder| {
+            builder.set_table_row_count(row_count);
+            builder.set_table_column_count(num_columns);
+        };
+        let frame = accessibility::group(
+            &ctx,
+            id.with("table"),
+            accesskit::Role::Table,
+            describe,
+            || {
+                egui::Frame::none()
+                    .stroke(egui::Stroke::new(1.0, ui.visuals().weak_text_color()))
+                    .inner_margin(egui::Margin::same(margin))
+                    .show(ui, |ui| {
+                        if virtualized {
+                            return self.render_virtual_rows(
+                                ui,
+                                id,
+                                &header_texts,
+                                rows,
+                                &widths,
+                                spacing,
+                                cell,
+                                &mut state,
+                                &mut cell_rects,
+                                &mut header_rects,
+                            );
+                        }
+                        egui::Grid::new(id)
+                            .num_columns(num_columns)
+                            .min_col_width(0.0)
+                            .spacing(spacing)
+                            .striped(true)
+                            .show(ui, |ui| {
+                                if !headers.is_empty() {
+                                    accessibility::group(
+                                        &ctx,
+                                        id.with("head"),
+                                        accesskit::Role::Row,
+                                        |_| {},
+                                        || {
+                                            for (col, text) in header_texts.iter().enumerate() {
+                                                let rect = cell(
+                                                    ui,
+                                                    text,
+                                                    col,
+                                                    accesskit::Role::ColumnHeader,
+                                                );
+                                                header_rects.push((col, rect));
+                                            }
+                                        },
                                     );
-                                });
-                            }
-                            ui.end_row();
+                                    ui.end_row();
+                                }
+                                for (index, row) in rows.iter().enumerate() {
+                                    accessibility::group(
+                                        &ctx,
+                                        id.with(("row", index)),
+                                        accesskit::Role::Row,
+                                        |_| {},
+                                        || {
+                                            for col in 0..num_columns {
+                                                let text = row.get(col).map_or("", |c| c.as_str());
+                                                let rect =
+                                                    cell(ui, text, col, accesskit::Role::Cell);
+                                                cell_rects.push((index, col, rect));
+                                            }
+                                        },
+                                    );
+                                    ui.end_row();
+                                }
+                            })
+                            .response
+                            .rect
+                    })
+            },
+        );
+
+        let grid_rect = frame.inner;
+        self.handle_table_interaction(
+            ui,
+            id,
+            grid_rect,
+            headers,
+            rows,
+            num_columns,
+            &mut state,
+            &cell_rects,
+            &header_rects,
+        );
+        ui.data_mut(|d| d.insert_temp(state_id, state));
+
+        // Column dividers can be dragged to resize; double-click restores automatic widths
+        let mut new_widths = widths.clone();
+        let mut reset = false;
+        let mut x = grid_rect.left();
+        for col in 0..num_columns.saturating_sub(1) {
+            x += widths[col] + spacing.x / 2.0;
+            let divider = egui::Rect::from_x_y_ranges(x - 3.0..=x + 3.0, grid_rect.y_range());
+            let response = ui
+                .interact(divider, id.with(("divider", col)), Sense::click_and_drag())
+                .on_hover_cursor(egui::CursorIcon::ResizeHorizontal);
+            if response.dragged() {
+                let delta = response
+                    .drag_delta()
+                    .x
+                    .max(MIN_COLUMN_WIDTH - new_widths[col])
+                    .min(new_widths[col + 1] - MIN_COLUMN_WIDTH);
+                new_widths[col] += delta;
+                new_widths[col + 1] -= delta;
+            }
+            if response.double_clicked() {
+                reset = true;
+            }
+            if response.hovered() || response.dragged() {
+                ui.painter().vline(
+                    x,
+                    grid_rect.y_range(),
+                    Stroke::new(1.0, ui.visuals().selection.stroke.color),
+                );
+            }
+            x += spacing.x / 2.0;
+        }
+        if reset {
+            ui.data_mut(|d| d.remove::<Vec<f32>>(id));
+        } else if new_widths != widths {
+            ui.data_mut(|d| d.insert_temp(id, new_widths));
+        }
+    }
+
+    /// Draws a huge table with a sticky header row above a scroll area that only lays out
+    /// the visible rows. Returns the rect covered by the table.
+    #[allow(clippy::too_many_arguments)]
+    fn render_virtual_rows(
+        &self,
+        ui: &mut Ui,
+        id: egui::Id,
+        headers: &[String],
+        rows: &[Vec<String>],
+        widths: &[f32],
+        spacing: Vec2,
+        cell: impl Fn(&mut Ui, &str, usize, accesskit::Role) -> egui::Rect,
+        state: &mut TableState,
+        cell_rects: &mut Vec<(usize, usize, egui::Rect)>,
+        header_rects: &mut Vec<(usize, egui::Rect)>,
+    ) -> egui::Rect {
+        let top = ui.cursor().top();
+        ui.spacing_mut().item_spacing = spacing;
+        let ctx = ui.ctx().clone();
+        if !headers.is_empty() {
+            accessibility::group(
+                &ctx,
+                id.with("head"),
+                accesskit::Role::Row,
+                |_| {},
+                || {
+                    ui.horizontal(|ui| {
+                        for col in 0..widths.len() {
+                            let text = headers.get(col).map_or("", |h| h.as_str());
+                            let rect = cell(ui, text, col, accesskit::Role::ColumnHeader);
+                            header_rects.push((col, rect));
                         }
-                    });
+                    })
+                },
+            );
+            ui.separator();
+        }
+
+        let row_height = ui.fonts(|f| f.row_height(&FontId::proportional(self.base_font_size)));
+        let output = egui::ScrollArea::vertical()
+            .id_source(id.with("rows"))
+            .max_height(VIRTUAL_TABLE_HEIGHT)
+            .auto_shrink([false, true])
+            .show_rows(ui, row_height, rows.len(), |ui, visible| {
+                // Rows outside the visible range aren't laid out, so scroll to a focused row
+                // by its computed position
+                if let Some((row, _)) = state.focus.filter(|_| state.scroll_to_focus) {
+                    let top = ui.cursor().top()
+                        + (row as f32 - visible.start as f32) * (row_height + spacing.y);
+                    let rect =
+                        egui::Rect::from_x_y_ranges(ui.cursor().x_range(), top..=top + row_height);
+                    ui.scroll_to_rect(rect, None);
+                    state.scroll_to_focus = false;
+                }
+                for index in visible {
+                    // Reserve the stripe's place below the row's text before laying it out
+                    let stripe = ui.painter().add(egui::Shape::Noop);
+                    let row_id = id.with(("row", index));
+                    let row_rect = accessibility::group(
+                        &ctx,
+                        row_id,
+                        accesskit::Role::Row,
+                        |_| {},
+                        || {
+                            ui.horizontal(|ui| {
+                                for col in 0..widths.len() {
+                                    let text = rows[index].get(col).map_or("", |c| c.as_str());
+                                    let rect = cell(ui, text, col, accesskit::Role::Cell);
+                                    cell_rects.push((index, col, rect));
+                                }
+                            })
+                            .response
+                            .rect
+                        },
+                    );
+                    if index % 2 == 1 {
+                        ui.painter().set(
+                            stripe,
+                            egui::Shape::rect_filled(
+                                row_rect.expand2(egui::vec2(0.0, spacing.y / 2.0)),
+                                0.0,
+                                ui.visuals().faint_bg_color,
+                            ),
+                        );
+                    }
+                }
+            });
+        let width = widths.iter().sum::<f32>() + spacing.x * widths.len().saturating_sub(1) as f32;
+        egui::Rect::from_min_size(
+            egui::pos2(output.inner_rect.left(), top),
+            egui::vec2(width, output.inner_rect.bottom() - top),
+        )
+    }
+
+    /// Handles clicks, the context menu and keyboard navigation of a table, and draws
+    /// the row selection and focused cell.
+    #[allow(clippy::too_many_arguments)]
+    fn handle_table_interaction(
+        &self,
+        ui: &mut Ui,
+        id: egui::Id,
+        rect: egui::Rect,
+        headers: &[String],
+        rows: &[Vec<String>],
+        num_columns: usize,
+        state: &mut TableState,
+        cell_rects: &[(usize, usize, egui::Rect)],
+        header_rects: &[(usize, egui::Rect)],
+    ) {
+        let response = ui.interact(rect, id.with("cells"), Sense::click());
+        let header_at = |pointer: egui::Pos2| {
+            header_rects
+                .iter()
+                .find(|(_, cell)| cell.contains(pointer))
+                .map(|&(col, _)| col)
+        };
+        if response.hover_pos().and_then(header_at).is_some() {
+            ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+        }
+        if response.clicked() || response.secondary_clicked() {
+            response.request_focus();
+            if let Some(pointer) = response.interact_pointer_pos() {
+                // Clicking a header sorts the rows by its column
+                if let Some(col) = header_at(pointer).filter(|_| response.clicked()) {
+                    state.toggle_sort(col);
+                    ui.ctx().request_repaint();
+                } else if let Some(&(row, col, _)) = cell_rects
+                    .iter()
+                    .find(|(_, _, cell)| cell.contains(pointer))
+                {
+                    if response.clicked() {
+                        state.click(row, col, ui.input(|i| i.modifiers));
+                    }
+                }
+            }
+        }
+
+        let copy_rows = |selected_only: bool, state: &TableState| {
+            if selected_only {
+                table::to_tsv(
+                    headers,
+                    state.selected.iter().filter_map(|&row| rows.get(row)),
+                )
+            } else {
+                table::to_tsv(headers, rows)
+            }
+        };
+        response.context_menu(|ui| {
+            if ui.button("Copy Table as TSV").clicked() {
+                ui.output_mut(|o| o.copied_text = copy_rows(false, state));
+                ui.close_menu();
+            }
+            if ui
+                .add_enabled(
+                    !state.selected.is_empty(),
+                    egui::Button::new("Copy Selected Rows as TSV"),
+                )
+                .clicked()
+            {
+                ui.output_mut(|o| o.copied_text = copy_rows(true, state));
+                ui.close_menu();
+            }
+            ui.separator();
+            if ui.button("Copy Table as CSV").clicked() {
+                ui.output_mut(|o| o.copied_text = table::to_csv(headers, rows));
+                ui.close_menu();
+            }
+            if ui.button("Save Table as CSV…").clicked() {
+                ui.close_menu();
+                table::export_csv(headers, rows);
+            }
+        });
+
+        if response.has_focus() {
+            ui.memory_mut(|m| {
+                m.set_focus_lock_filter(
+                    response.id,
+                    egui::EventFilter {
+                        horizontal_arrows: true,
+                        vertical_arrows: true,
+                        escape: true,
+                        ..Default::default()
+                    },
+                )
             });
+            match ui.input(|i| state.handle_input(i, rows.len(), num_columns)) {
+                Some(TableCommand::Copy) => {
+                    let text = copy_rows(!state.selected.is_empty(), state);
+                    ui.output_mut(|o| o.copied_text = text);
+                }
+                Some(TableCommand::Leave) => response.surrender_focus(),
+                None => {}
+            }
+        }
+
+        // Selected rows and the focused cell
+        let selection = ui.visuals().selection;
+        for &(row, col, cell) in cell_rects {
+            if state.selected.contains(&row) {
+                ui.painter().rect_filled(
+                    cell.expand(2.0),
+                    0.0,
+                    selection.bg_fill.gamma_multiply(0.3),
+                );
+            }
+            if response.has_focus() && state.focus == Some((row, col)) {
+                ui.painter()
+                    .rect_stroke(cell.expand(2.0), 2.0, selection.stroke);
+                if state.scroll_to_focus {
+                    ui.scroll_to_rect(cell, None);
+                    state.scroll_to_focus = false;
+                }
+            }
+        }
+    }
+
+    /// Computes content-aware column widths.
+    ///
+    /// Columns get their natural (unwrapped) width when everything fits. Otherwise each
+    /// column keeps at least its longest word (capped, so one long URL can't starve the
+    /// rest) and the remaining space is shared in proportion to how much each column
+    /// would still like to grow, so long cells wrap while short columns stay compact.
+    fn auto_column_widths<'a>(
+        ui: &Ui,
+        cells: impl Iterator<Item = &'a [String]>,
+        num_columns: usize,
+        available_width: f32,
+        format: &TextFormat,
+    ) -> Vec<f32> {
+        let mut natural = vec![MIN_COLUMN_WIDTH; num_columns];
+        let mut minimum = vec![MIN_COLUMN_WIDTH; num_columns];
+        ui.fonts(|fonts| {
+            let measure = |text: &str| {
+                fonts
+                    .layout_no_wrap(text.to_string(), format.font_id.clone(), format.color)
+                    .size()
+                    .x
+                    .ceil()
+            };
+            for row in cells {
+                for (col, text) in row.iter().enumerate().take(num_columns) {
+                    let text = text.trim();
+                    natural[col] = natural[col].max(measure(text));
+                    let longest_word = text
+                        .split_whitespace()
+                        .map(measure)
+                        .fold(0.0, f32::max)
+                        .min(MAX_MIN_COLUMN_WIDTH);
+                    minimum[col] = minimum[col].max(longest_word);
+                }
+            }
+        });
+
+        let total_natural: f32 = natural.iter().sum();
+        if total_natural <= available_width {
+            return natural;
+        }
+        let total_minimum: f32 = minimum.iter().sum();
+        if total_minimum >= available_width {
+            // Not even the minimums fit; scale them down evenly
+            let scale = available_width / total_minimum;
+            return minimum
+                .iter()
+                .map(|w| (w * scale).max(MIN_COLUMN_WIDTH))
+                .collect();
+        }
+        let extra = available_width - total_minimum;
+        let wanted = total_natural - total_minimum;
+        natural
+            .iter()
+            .zip(&minimum)
+            .map(|(natural, minimum)| minimum + (natural - minimum) / wanted * extra)
+            .collect()
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn render_image(
         &self,
         ui: &mut Ui,
         url: &str,
         title: &str,
+        hints: ImageHints,
         image_cache: &mut HashMap<String, Result<egui::TextureHandle, String>>,
         current_file: &Option<PathBuf>,
         content_width: Option<f32>,
     ) {
-        if let Some(texture) = self.load_image(ui.ctx(), url, image_cache, current_file) {
+        if let Some((texture, image_size)) =
+            self.load_image(ui.ctx(), url, image_cache, current_file)
+        {
             // Successfully loaded image - render it
             let available_width = content_width.unwrap_or(ui.available_width());
             let max_width = available_width - 20.0; // Leave margin for proper centering
-            let max_height = 600.0; // Reasonable max height
 
-            let image_size = texture.size_vec2();
-            let scale_factor = (max_width / image_size.x)
-                .min(max_height / image_size.y)
-                .min(1.0);
-            let display_size = image_size * scale_factor;
+            // Size hints win over the configured limits, keeping the aspect ratio when
+            // only one side is given
+            let width = hints.width.map(|w| w.resolve(max_width));
+            let height = hints.height.map(|h| h.resolve(image_size.y));
+            let display_size = match (width, height) {
+                (Some(width), Some(height)) => Vec2::new(width, height),
+                (Some(width), None) => Vec2::new(width, width * image_size.y / image_size.x),
+                (None, Some(height)) => Vec2::new(height * image_size.x / image_size.y, height),
+                (None, None) => {
+                    image_size
+                        * (max_width * self.image_max_width / image_size.x)
+                            .min(self.image_max_height / image_size.y)
+                            .min(1.0)
+                }
+            };
+            // Never wider than the content
+            let display_size = display_size * (max_width / display_size.x).min(1.0);
 
             // Left-align the image but constrain to available width
             ui.vertical(|ui| {
-                let response = ui.add(egui::Image::new(&texture).max_size(display_size));
+                let response = ui.add(
+                    egui::Image::new(&texture)
+                        .fit_to_exact_size(display_size)
+                        .sense(Sense::click()),
+                );
 
-                // Make image clickable to open in browser
-                if response.clicked() && (url.starts_with("http://") || url.starts_with("https://"))
-                {
-                    let _ = webbrowser::open(url);
+                // Clicking an image opens it in the zoomable viewer
+                if response.clicked() {
+                    *self.clicked_image.borrow_mut() = Some((url.to_string(), texture.clone()));
                 }
+                response.context_menu(|ui| {
+                    if ui.button("View Image").clicked() {
+                        *self.clicked_image.borrow_mut() = Some((url.to_string(), texture.clone()));
+                        ui.close_menu();
+                    }
+                    let bytes = self.image_bytes(url);
+                    if ui
+                        .add_enabled(bytes.is_some(), egui::Button::new("Save Image As…"))
+                        .clicked()
+                    {
+                        if let Some(bytes) = bytes {
+                            lightbox::save_image(url, &bytes);
+                        }
+                        ui.close_menu();
+                    }
+                });
 
                 if response.hovered() {
                     ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
@@ -951,30 +4039,158 @@ impl MarkdownRenderer {
                             ..Default::default()
                         },
                     );
-                    ui.horizontal(|ui| {
-                        ui.allocate_ui_with_layout(
-                            [display_size.x, 0.0].into(),
-                            egui::Layout::left_to_right(egui::Align::TOP),
-                            |ui| ui.add(egui::Label::new(job).wrap()),
-                        );
-                    });
+                    ui.horizontal(|ui| {
+                        ui.allocate_ui_with_layout(
+                            [display_size.x, 0.0].into(),
+                            egui::Layout::left_to_right(egui::Align::TOP),
+                            |ui| ui.add(egui::Label::new(job).wrap()),
+                        );
+                    });
+                }
+            });
+        } else if self.pending_images.borrow().contains_key(url) {
+            self.render_image_loading(ui, url, content_width);
+        } else {
+            // Failed to load image - show placeholder, offering to try again
+            let error = image_cache
+                .get(url)
+                .and_then(|result| result.as_ref().err())
+                .cloned();
+            if self.render_image_placeholder(ui, url, title, error.as_deref(), content_width) {
+                image_cache.remove(url);
+                ui.ctx().request_repaint();
+            }
+        }
+    }
+
+    /// Reads a note for embedding, reusing the cached text while its mtime is unchanged.
+    fn load_note(&self, path: &PathBuf) -> Option<String> {
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok()?;
+        let mut cache = self.embed_cache.borrow_mut();
+        if let Some((cached_modified, content)) = cache.get(path) {
+            if *cached_modified == modified {
+                return Some(content.clone());
+            }
+        }
+        let content = std::fs::read_to_string(path).ok()?;
+        cache.insert(path.clone(), (modified, content.clone()));
+        Some(content)
+    }
+
+    fn render_embed(
+        &self,
+        ui: &mut Ui,
+        target: &str,
+        search_query: &str,
+        image_cache: &mut HashMap<String, Result<egui::TextureHandle, String>>,
+        current_file: &Option<PathBuf>,
+        content_width: Option<f32>,
+    ) {
+        let (note, section) = vault::split_target(target);
+        let path = if note.is_empty() {
+            current_file.clone()
+        } else {
+            self.resolve_note(note, current_file)
+        };
+        let inner_width = content_width.unwrap_or(ui.available_width()) - 32.0; // Account for embed margins
+
+        egui::Frame::none()
+            .fill(ui.visuals().faint_bg_color)
+            .stroke(egui::Stroke::new(1.0, ui.visuals().weak_text_color()))
+            .rounding(4.0)
+            .inner_margin(egui::Margin::same(12.0))
+            .outer_margin(egui::Margin::same(4.0))
+            .show(ui, |ui| {
+                ui.set_max_width(inner_width);
+                ui.label(
+                    RichText::new(format!("📄 {target}"))
+                        .size(self.base_font_size * 0.9)
+                        .color(ui.visuals().weak_text_color()),
+                );
+                ui.add_space(6.0);
+
+                let notice = |ui: &mut Ui, text: &str| {
+                    ui.label(
+                        RichText::new(text)
+                            .italics()
+                            .color(ui.visuals().weak_text_color()),
+                    );
+                };
+
+                let Some(path) = path else {
+                    notice(ui, "Note not found");
+                    return;
+                };
+                let key = (path.clone(), section.map(str::to_string));
+                let depth = self.embed_stack.borrow().len();
+                if self.embed_stack.borrow().contains(&key) {
+                    notice(ui, "⟳ Circular embed skipped");
+                    return;
                 }
+                if depth > MAX_EMBED_DEPTH {
+                    notice(ui, "Embed depth limit reached");
+                    return;
+                }
+                let Some(content) = self.load_note(&path) else {
+                    notice(ui, "Note could not be read");
+                    return;
+                };
+                let markdown = match section {
+                    Some(section) => match vault::extract_section(&content, section) {
+                        Some(markdown) => markdown,
+                        None => {
+                            notice(ui, "Section not found");
+                            return;
+                        }
+                    },
+                    None => content.as_str(),
+                };
+
+                let events = self
+                    .prepare_events(Parser::new_ext(markdown, Self::parser_options()).collect());
+                self.embed_stack.borrow_mut().push(key);
+                self.detached_depth.set(self.detached_depth.get() + 1);
+                self.render_events(
+                    ui,
+                    events,
+                    search_query,
+                    None,
+                    image_cache,
+                    &Some(path),
+                    &None,
+                    Some(inner_width),
+                );
+                self.detached_depth.set(self.detached_depth.get() - 1);
+                self.embed_stack.borrow_mut().pop();
             });
-        } else {
-            // Failed to load image - show placeholder
-            self.render_image_placeholder(ui, url, title, content_width);
-        }
     }
 
+    /// Draws a spinner in place of a web image that is still downloading, sized like a
+    /// typical image so the text below doesn't jump much when it arrives.
+    fn render_image_loading(&self, ui: &mut Ui, url: &str, content_width: Option<f32>) {
+        let max_width = content_width.unwrap_or(ui.available_width());
+        let size = vec2(max_width.min(400.0), 200.0);
+        let (rect, response) = ui.allocate_exact_size(size, Sense::hover());
+        ui.painter()
+            .rect_filled(rect, 4.0, ui.visuals().faint_bg_color);
+        let spinner = Rect::from_center_size(rect.center(), vec2(24.0, 24.0));
+        ui.put(spinner, egui::Spinner::new().size(24.0));
+        response.on_hover_text(format!("Loading {url}"));
+    }
+
+    /// Draws the placeholder of an image that couldn't be loaded, with the error and a
+    /// button to try again. Returns true when the button is clicked.
     fn render_image_placeholder(
         &self,
         ui: &mut Ui,
         url: &str,
         title: &str,
+        error: Option<&str>,
         content_width: Option<f32>,
-    ) {
+    ) -> bool {
         let max_width = content_width.unwrap_or(ui.available_width());
         let frame_width = max_width.min(400.0); // Limit placeholder width
+        let mut retry = false;
 
         egui::Frame::none()
             .fill(ui.visuals().faint_bg_color)
@@ -1045,22 +4261,71 @@ impl MarkdownRenderer {
                             ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
                         }
                     }
+
+                    if let Some(error) = error {
+                        ui.add_space(4.0);
+                        ui.horizontal_wrapped(|ui| {
+                            ui.colored_label(ui.visuals().warn_fg_color, error);
+                        });
+                        retry = ui.button("Retry").clicked();
+                    }
                 })
             });
+        retry
     }
 
-    fn render_blockquote(&self, ui: &mut Ui, mut job: LayoutJob, content_width: Option<f32>) {
-        // Set word wrap for the blockquote
-        let max_width = content_width.unwrap_or(ui.available_width()) - 40.0; // Account for blockquote margins
-        job.wrap.max_width = max_width;
+    /// Draws a paragraph inside `depth` nested quote frames.
+    fn render_blockquote(
+        &self,
+        ui: &mut Ui,
+        mut job: LayoutJob,
+        depth: usize,
+        content_width: Option<f32>,
+    ) {
+        // Set word wrap for the blockquote, accounting for the margins of every level
+        let max_width = content_width.unwrap_or(ui.available_width())
+            - 40.0
+            - BLOCKQUOTE_INDENT * (depth.saturating_sub(1)) as f32;
+        job.wrap.max_width = max_width.max(50.0);
         job.wrap.break_anywhere = false; // Break at word boundaries
         job.halign = egui::Align::LEFT;
+        // Notes are only marked in the margin of plain paragraphs
+        self.annotate(&mut job, ui);
+        self.mark_spoken(&mut job, ui);
+        Self::blockquote_frame(ui, 1, depth, job);
+    }
+
+    /// Draws the frame of one quote level and, inside it, the deeper levels.
+    fn blockquote_frame(ui: &mut Ui, level: usize, depth: usize, job: LayoutJob) {
+        // Each level is indented further and gets a lighter border than its parent
+        let shade = 1.0 / (1.0 + 0.35 * (level - 1) as f32);
+        let (inner_margin, outer_margin) = if level == depth {
+            (egui::Margin::same(12.0), egui::Margin::same(4.0))
+        } else {
+            (
+                egui::Margin {
+                    left: BLOCKQUOTE_INDENT,
+                    right: 4.0,
+                    top: 4.0,
+                    bottom: 4.0,
+                },
+                egui::Margin::same(if level == 1 { 4.0 } else { 0.0 }),
+            )
+        };
         egui::Frame::none()
             .fill(ui.visuals().faint_bg_color)
-            .inner_margin(egui::Margin::same(12.0))
-            .outer_margin(egui::Margin::same(4.0))
-            .stroke(egui::Stroke::new(4.0, ui.visuals().weak_text_color()))
+            .inner_margin(inner_margin)
+            .outer_margin(outer_margin)
+            .stroke(egui::Stroke::new(
+                4.0,
+                ui.visuals().weak_text_color().gamma_multiply(shade),
+            ))
             .show(ui, |ui| {
+                if level < depth {
+                    Self::blockquote_frame(ui, level + 1, depth, job);
+                    return;
+                }
+                let max_width = job.wrap.max_width;
                 ui.horizontal(|ui| {
                     ui.allocate_ui_with_layout(
                         [max_width, 0.0].into(),
@@ -1071,6 +4336,152 @@ impl MarkdownRenderer {
             });
     }
 
+    /// Returns the index of the `End` event closing the container opened just before `start`.
+    fn find_matching_end(events: &[Event], start: usize) -> usize {
+        let mut depth = 1;
+        for (offset, event) in events[start..].iter().enumerate() {
+            match event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return start + offset;
+                    }
+                }
+                _ => {}
+            }
+        }
+        events.len()
+    }
+
+    /// Checks whether a blockquote body starts with a `[!type]` callout marker.
+    ///
+    /// Returns the parsed callout and the remaining body events with the marker line removed.
+    fn split_callout<'a>(events: &[Event<'a>]) -> Option<(Callout, Vec<Event<'a>>)> {
+        if !matches!(events.first(), Some(Event::Start(Tag::Paragraph))) {
+            return None;
+        }
+
+        let mut line = String::new();
+        let mut rest = events.len();
+        let mut paragraph_ended = false;
+        for (i, event) in events.iter().enumerate().skip(1) {
+            match event {
+                Event::Text(text) | Event::Code(text) => line.push_str(text),
+                Event::SoftBreak | Event::HardBreak => {
+                    rest = i + 1;
+                    break;
+                }
+                Event::End(TagEnd::Paragraph) => {
+                    rest = i + 1;
+                    paragraph_ended = true;
+                    break;
+                }
+                _ => {}
+            }
+        }
+
+        let callout = Callout::parse(&line)?;
+        let mut body = Vec::new();
+        if !paragraph_ended {
+            body.push(Event::Start(Tag::Paragraph));
+        }
+        body.extend_from_slice(&events[rest.min(events.len())..]);
+        Some((callout, body))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render_callout(
+        &self,
+        ui: &mut Ui,
+        callout: &Callout,
+        body: Vec<Event>,
+        id_source: usize,
+        search_query: &str,
+        current_search_result: Option<&SearchResult>,
+        image_cache: &mut HashMap<String, Result<egui::TextureHandle, String>>,
+        current_file: &Option<PathBuf>,
+        scroll_to_header: &Option<String>,
+        content_width: Option<f32>,
+    ) {
+        let color = theme::warm_color(callout.kind.color(), self.warmth);
+        let inner_width = content_width.unwrap_or(ui.available_width()) - 32.0; // Account for callout margins
+
+        let mut header = LayoutJob::default();
+        header.append(
+            callout.kind.icon(),
+            0.0,
+            TextFormat {
+                font_id: FontId::proportional(self.base_font_size * 1.1),
+                color,
+                ..Default::default()
+            },
+        );
+        header.append(
+            &callout.title,
+            8.0,
+            TextFormat {
+                font_id: FontId::proportional(self.base_font_size * 1.1),
+                color,
+                ..Default::default()
+            },
+        );
+        header.wrap.max_width = inner_width;
+
+        egui::Frame::none()
+            .fill(color.gamma_multiply(0.08))
+            .stroke(egui::Stroke::new(1.0, color.gamma_multiply(0.6)))
+            .rounding(4.0)
+            .inner_margin(egui::Margin::same(12.0))
+            .outer_margin(egui::Margin::same(4.0))
+            .show(ui, |ui| {
+                ui.set_max_width(inner_width);
+                let render_body = |ui: &mut Ui| {
+                    ui.add_space(6.0);
+                    self.render_events(
+                        ui,
+                        body,
+                        search_query,
+                        current_search_result,
+                        image_cache,
+                        current_file,
+                        scroll_to_header,
+                        Some(inner_width),
+                    );
+                };
+
+                if callout.fold == CalloutFold::None {
+                    ui.add(egui::Label::new(header).wrap());
+                    render_body(ui);
+                    return;
+                }
+
+                // Foldable callouts toggle when the header is clicked
+                let id = ui.make_persistent_id(("callout", id_source, &callout.title));
+                let mut state = egui::collapsing_header::CollapsingState::load_with_default_open(
+                    ui.ctx(),
+                    id,
+                    callout.fold == CalloutFold::Open,
+                );
+                let response = ui
+                    .horizontal(|ui| {
+                        let label = ui.add(egui::Label::new(header).sense(Sense::click()));
+                        ui.label(
+                            RichText::new(if state.is_open() { "⏷" } else { "⏵" }).color(color),
+                        );
+                        label
+                    })
+                    .inner;
+                if response.hovered() {
+                    ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+                }
+                if response.clicked() {
+                    state.toggle(ui);
+                }
+                state.show_body_unindented(ui, render_body);
+            });
+    }
+
     fn append_inline_code(
         &self,
         job: &mut LayoutJob,
@@ -1079,7 +4490,7 @@ impl MarkdownRenderer {
         search_query: &str,
         _current_search_result: Option<&SearchResult>,
     ) {
-        if !search_query.is_empty() {
+        if !search_query.is_empty() && self.search_scope.covers_code() {
             self.append_inline_code_with_search_highlight(job, text, ui, search_query);
         } else {
             // No search - render normally
@@ -1104,15 +4515,11 @@ impl MarkdownRenderer {
         search_query: &str,
     ) {
         let text_str = text.to_string();
-        let text_lower = text_str.to_lowercase();
-        let query_lower = search_query.to_lowercase();
-
         let mut last_end = 0;
-        let mut start_pos = 0;
 
-        while let Some(pos) = text_lower[start_pos..].find(&query_lower) {
-            let match_start = start_pos + pos;
-            let match_end = match_start + search_query.len();
+        for range in self.search_matches(&text_str, search_query) {
+            let (match_start, match_end) = (range.start, range.end);
+            let (match_color, match_background) = self.search_match_colors(ui);
 
             // Add text before the match
             if match_start > last_end {
@@ -1131,19 +4538,16 @@ impl MarkdownRenderer {
 
             // Add the highlighted match
             let match_text = &text_str[match_start..match_end];
-            job.append(
-                match_text,
-                0.0,
-                TextFormat {
-                    font_id: FontId::monospace(self.base_font_size * 0.9),
-                    color: ui.visuals().warn_fg_color,
-                    background: ui.visuals().selection.bg_fill,
-                    ..Default::default()
-                },
-            );
+            let mut format = TextFormat {
+                font_id: FontId::monospace(self.base_font_size * 0.9),
+                color: match_color,
+                background: match_background,
+                ..Default::default()
+            };
+            self.mark_fuzzy_match(&mut format, match_text, search_query);
+            job.append(match_text, 0.0, format);
 
             last_end = match_end;
-            start_pos = match_end;
         }
 
         // Add remaining text after the last match
@@ -1162,6 +4566,76 @@ impl MarkdownRenderer {
         }
     }
 
+    /// Draws the text of a block-level HTML comment in a muted frame.
+    /// Draws a `$$...$$` block on its own, centered in the content width.
+    fn render_display_math(&self, ui: &mut Ui, tex: &str, content_width: Option<f32>) {
+        let max_width = content_width.unwrap_or(ui.available_width());
+        let mut job = LayoutJob::default();
+        math::append(
+            &mut job,
+            tex,
+            self.base_font_size * 1.2,
+            ui.visuals().text_color(),
+        );
+        job.wrap.max_width = max_width;
+        ui.allocate_ui_with_layout(
+            [max_width, 0.0].into(),
+            egui::Layout::top_down(egui::Align::Center),
+            |ui| ui.add(egui::Label::new(job).wrap()),
+        );
+        ui.add_space(8.0);
+    }
+
+    fn render_comment_block(&self, ui: &mut Ui, comment: &str, content_width: Option<f32>) {
+        let max_width = content_width.unwrap_or(ui.available_width());
+        egui::Frame::none()
+            .fill(ui.visuals().faint_bg_color)
+            .inner_margin(6.0)
+            .show(ui, |ui| {
+                ui.set_max_width(max_width);
+                ui.add(
+                    egui::Label::new(
+                        RichText::new(comment)
+                            .size(self.base_font_size * 0.9)
+                            .italics()
+                            .color(ui.visuals().weak_text_color()),
+                    )
+                    .wrap(),
+                );
+            });
+        ui.add_space(8.0);
+    }
+
+    /// Draws a fenced block with a registered fence renderer, if one handles its language.
+    /// Returns false if the block should be drawn as regular code instead.
+    fn render_custom_fence(
+        &self,
+        ui: &mut Ui,
+        content: &str,
+        info: &str,
+        current_file: &Option<PathBuf>,
+        content_width: Option<f32>,
+    ) -> bool {
+        let language = info.split_whitespace().next().unwrap_or_default();
+        if language.is_empty() {
+            return false;
+        }
+        let Some(renderer) = self
+            .fence_renderers
+            .iter()
+            .find(|renderer| renderer.handles(language))
+        else {
+            return false;
+        };
+        let context = FenceContext {
+            language,
+            font_size: self.base_font_size,
+            max_width: content_width.unwrap_or(ui.available_width()),
+            current_file: current_file.as_deref(),
+        };
+        renderer.render(ui, content, &context)
+    }
+
     fn render_code_block(
         &self,
         ui: &mut Ui,
@@ -1169,68 +4643,298 @@ impl MarkdownRenderer {
         language: &str,
         content_width: Option<f32>,
     ) {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        (content, language).hash(&mut hasher);
+        let block_key = hasher.finish();
+        let wrap = self
+            .code_wrap_overrides
+            .borrow()
+            .get(&block_key)
+            .copied()
+            .unwrap_or(self.wrap_code);
+
+        // Blocks without a language are highlighted when it can be guessed from the code
+        let detected = if language.is_empty() {
+            self.detected_language(content, block_key)
+        } else {
+            None
+        };
+        let syntax_language = detected.as_deref().unwrap_or(language);
+        let label = match &detected {
+            Some(name) => format!("{name} (detected)"),
+            None => language.to_string(),
+        };
+
+        let mut job = if syntax_language.is_empty() {
+            // Plain text code block
+            LayoutJob::single_section(
+                content.to_string(),
+                TextFormat {
+                    font_id: FontId::monospace(self.base_font_size * 0.9),
+                    color: ui.visuals().text_color(),
+                    ..Default::default()
+                },
+            )
+        } else {
+            // Syntax highlighted code block
+            self.highlighted_code(content, syntax_language)
+        };
+        job.halign = egui::Align::LEFT;
+
         let max_width = content_width.unwrap_or(ui.available_width());
-        egui::Frame::none()
-            .fill(ui.visuals().code_bg_color)
-            .inner_margin(8.0)
-            .show(ui, |ui| {
-                if language.is_empty() {
-                    // Plain text code block
-                    let mut job = LayoutJob::single_section(
-                        content.to_string(),
-                        TextFormat {
-                            font_id: FontId::monospace(self.base_font_size * 0.9),
-                            color: ui.visuals().text_color(),
-                            ..Default::default()
-                        },
-                    );
-                    job.wrap.max_width = max_width;
-                    job.wrap.break_anywhere = false; // Allow breaking long lines
-                    job.halign = egui::Align::LEFT;
-                    ui.horizontal(|ui| {
-                        ui.allocate_ui_with_layout(
-                            [max_width, 0.0].into(),
-                            egui::Layout::left_to_right(egui::Align::TOP),
-                            |ui| ui.add(egui::Label::new(job).wrap()),
-                        );
-                    });
-                } else {
-                    // Syntax highlighted code block
-                    self.render_highlighted_code(ui, content, language, content_width);
-                }
+        let ctx = ui.ctx().clone();
+        let describe = |builder: &mut accesskit::NodeBuilder| {
+            builder.set_name(if language.is_empty() {
+                "Code block".to_string()
+            } else {
+                format!("{language} code block")
             });
+        };
+        let code_id = ui.next_auto_id().with("code");
+        accessibility::group(&ctx, code_id, accesskit::Role::Pre, describe, || {
+            egui::Frame::none()
+                .fill(ui.visuals().code_bg_color)
+                .inner_margin(8.0)
+                .show(ui, |ui| {
+                    self.code_block_header(ui, content, &label, max_width, block_key, wrap);
+                    if matches!(language, "diff" | "patch") {
+                        self.render_diff_lines(ui, content, max_width, block_key, wrap);
+                    } else if wrap {
+                        job.wrap.max_width = max_width;
+                        job.wrap.break_anywhere = false; // Allow breaking long lines
+                        ui.horizontal(|ui| {
+                            ui.allocate_ui_with_layout(
+                                [max_width, 0.0].into(),
+                                egui::Layout::left_to_right(egui::Align::TOP),
+                                |ui| ui.add(egui::Label::new(job).wrap()),
+                            );
+                        });
+                    } else {
+                        // Long lines keep their alignment and scroll sideways
+                        ui.set_width(max_width);
+                        egui::ScrollArea::horizontal()
+                            .id_source(("code_block", block_key))
+                            .show(ui, |ui| ui.add(egui::Label::new(job).extend()));
+                    }
+                })
+        });
+    }
+
+    /// Draws the lines of a diff on full-width backgrounds, green for added lines and red
+    /// for removed ones, with the +/- markers in a gutter of their own.
+    fn render_diff_lines(
+        &self,
+        ui: &mut Ui,
+        content: &str,
+        width: f32,
+        block_key: u64,
+        wrap: bool,
+    ) {
+        let font = FontId::monospace(self.base_font_size * 0.9);
+        let added = theme::warm_color(Color32::from_rgb(152, 195, 121), self.warmth);
+        let removed = theme::warm_color(Color32::from_rgb(224, 108, 117), self.warmth);
+        let draw_lines = |ui: &mut Ui| {
+            ui.spacing_mut().item_spacing.y = 0.0;
+            for line in content.lines() {
+                let (marker, text, color) = match line.as_bytes().first() {
+                    Some(b'+') if !line.starts_with("+++") => ("+", &line[1..], Some(added)),
+                    Some(b'-') if !line.starts_with("---") => ("-", &line[1..], Some(removed)),
+                    Some(b' ') => (" ", &line[1..], None),
+                    _ => (" ", line, None),
+                };
+                let text_color = if line.starts_with("@@") {
+                    ui.visuals().hyperlink_color
+                } else if color.is_none() && !line.starts_with(' ') && !line.is_empty() {
+                    // File headers and "\ No newline at end of file"
+                    ui.visuals().weak_text_color()
+                } else {
+                    ui.visuals().text_color()
+                };
+                egui::Frame::none()
+                    .fill(color.map_or(Color32::TRANSPARENT, |color| color.gamma_multiply(0.2)))
+                    .show(ui, |ui| {
+                        ui.set_min_width(width);
+                        ui.horizontal_top(|ui| {
+                            ui.spacing_mut().item_spacing.x = 6.0;
+                            ui.label(
+                                RichText::new(marker)
+                                    .font(font.clone())
+                                    .color(color.unwrap_or(text_color)),
+                            );
+                            let label = egui::Label::new(
+                                RichText::new(text).font(font.clone()).color(text_color),
+                            );
+                            ui.add(if wrap { label.wrap() } else { label.extend() });
+                        });
+                    });
+            }
+        };
+        if wrap {
+            ui.set_max_width(width);
+            draw_lines(ui);
+        } else {
+            // Long lines keep their alignment and scroll sideways
+            ui.set_width(width);
+            egui::ScrollArea::horizontal()
+                .id_source(("diff_block", block_key))
+                .show(ui, draw_lines);
+        }
+    }
+
+    /// Returns the syntax name guessed for a code block without a language, from the cache
+    /// when possible.
+    fn detected_language(&self, content: &str, block_key: u64) -> Option<String> {
+        if let Some(name) = self.detected_languages.borrow().get(&block_key) {
+            return name.clone();
+        }
+        let name = self.detect_language(content);
+        let mut cache = self.detected_languages.borrow_mut();
+        if cache.len() >= HIGHLIGHT_CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(block_key, name.clone());
+        name
+    }
+
+    /// Guesses the language of a code block from a shebang or other telltale first line,
+    /// falling back to a few patterns of common snippets.
+    fn detect_language(&self, content: &str) -> Option<String> {
+        let first_line = content.lines().find(|line| !line.trim().is_empty())?;
+        if let Some(syntax) = self.syntax_set.find_syntax_by_first_line(first_line) {
+            return Some(syntax.name.clone());
+        }
+
+        let trimmed = content.trim();
+        let starts_line = |prefixes: &[&str]| {
+            content.lines().any(|line| {
+                let line = line.trim_start();
+                prefixes.iter().any(|prefix| line.starts_with(prefix))
+            })
+        };
+        let name = if (trimmed.starts_with('{') || trimmed.starts_with('['))
+            && serde_json::from_str::<serde_json::Value>(trimmed).is_ok()
+        {
+            "JSON"
+        } else if trimmed.starts_with('<') && trimmed.ends_with('>') {
+            "HTML"
+        } else if starts_line(&[
+            "fn ",
+            "pub fn ",
+            "impl ",
+            "use std::",
+            "let mut ",
+            "#[derive",
+        ]) {
+            "Rust"
+        } else if starts_line(&["def ", "import ", "from ", "class "]) && trimmed.contains(':') {
+            "Python"
+        } else if starts_line(&[
+            "$ ", "sudo ", "cd ", "git ", "cargo ", "npm ", "echo ", "export ",
+        ]) {
+            "Bourne Again Shell (bash)"
+        } else {
+            return None;
+        };
+        Some(name.to_string())
     }
 
-    fn render_highlighted_code(
+    /// Draws the header strip of a code block: the language as written in the fence (or
+    /// as detected), and buttons to copy the code and to toggle wrapping of long lines.
+    fn code_block_header(
         &self,
         ui: &mut Ui,
         content: &str,
         language: &str,
-        content_width: Option<f32>,
+        width: f32,
+        block_key: u64,
+        wrap: bool,
     ) {
+        let font = FontId::proportional(self.base_font_size * 0.8);
+        ui.allocate_ui_with_layout(
+            [width, 0.0].into(),
+            egui::Layout::left_to_right(egui::Align::Center),
+            |ui| {
+                ui.set_width(width);
+                ui.label(
+                    RichText::new(language)
+                        .font(font.clone())
+                        .color(ui.visuals().weak_text_color()),
+                );
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let wrap_label = if wrap {
+                        "Scroll long lines"
+                    } else {
+                        "Wrap long lines"
+                    };
+                    let button = egui::Button::new(
+                        RichText::new(if wrap { "↔" } else { "↩" }).font(font.clone()),
+                    )
+                    .frame(false);
+                    let response = ui.add(button).on_hover_text(wrap_label);
+                    response
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::Button, true, wrap_label));
+                    if response.clicked() {
+                        self.code_wrap_overrides
+                            .borrow_mut()
+                            .insert(block_key, !wrap);
+                    }
+
+                    let button = egui::Button::new(RichText::new("📋").font(font)).frame(false);
+                    let response = ui.add(button).on_hover_text("Copy code");
+                    response
+                        .widget_info(|| WidgetInfo::labeled(WidgetType::Button, true, "Copy code"));
+                    if response.clicked() {
+                        ui.output_mut(|o| o.copied_text = content.to_string());
+                    }
+                });
+            },
+        );
+        ui.add_space(4.0);
+    }
+
+    /// Returns the highlighted text of a code block, from the cache when possible.
+    fn highlighted_code(&self, content: &str, language: &str) -> LayoutJob {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        (content, language, self.dark_scheme).hash(&mut hasher);
+        (&self.code_theme, &self.automatic_code_themes).hash(&mut hasher);
+        self.base_font_size.to_bits().hash(&mut hasher);
+        self.warmth.to_bits().hash(&mut hasher);
+        let key = hasher.finish();
+
+        if let Some(job) = self.highlight_cache.borrow().get(&key) {
+            return job.clone();
+        }
+        let job = self.highlight_code(content, language);
+        let mut cache = self.highlight_cache.borrow_mut();
+        if cache.len() >= HIGHLIGHT_CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(key, job.clone());
+        job
+    }
+
+    /// Runs syntect over a code block, producing colored text without wrapping settings.
+    fn highlight_code(&self, content: &str, language: &str) -> LayoutJob {
         let syntax = self
             .syntax_set
             .find_syntax_by_extension(language)
             .or_else(|| self.syntax_set.find_syntax_by_name(language))
             .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text());
 
-        let theme = &self.theme_set.themes["base16-ocean.dark"];
+        let theme = self.syntax_theme();
         let mut highlighter = HighlightLines::new(syntax, theme);
 
-        let max_width = content_width.unwrap_or(ui.available_width());
         let mut job = LayoutJob::default();
-        job.wrap.max_width = max_width;
-        job.wrap.break_anywhere = false; // Allow breaking long lines
-        job.halign = egui::Align::LEFT;
-
         for line in LinesWithEndings::from(content) {
             let ranges = highlighter
                 .highlight_line(line, &self.syntax_set)
                 .unwrap_or_else(|_| vec![(syntect::highlighting::Style::default(), line)]);
 
             for (style, text) in ranges {
-                let color =
-                    Color32::from_rgb(style.foreground.r, style.foreground.g, style.foreground.b);
+                let color = theme::warm_color(
+                    Color32::from_rgb(style.foreground.r, style.foreground.g, style.foreground.b),
+                    self.warmth,
+                );
                 job.append(
                     text,
                     0.0,
@@ -1242,13 +4946,149 @@ impl MarkdownRenderer {
                 );
             }
         }
+        job
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders a document in a headless frame and returns its layout.
+    fn render_layout(markdown: &str, highlight_marks: bool) -> DocumentLayout {
+        let mut renderer = MarkdownRenderer::new();
+        renderer.set_highlight_marks(highlight_marks);
+        let ctx = egui::Context::default();
+        crate::fonts::install(&ctx, false, &crate::fonts::FontChoice::default())
+            .expect("built-in fonts");
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                renderer.render(
+                    ui,
+                    markdown,
+                    "",
+                    None,
+                    &mut HashMap::new(),
+                    &None,
+                    &None,
+                    None,
+                );
+            });
+        });
+        renderer.layout()
+    }
+
+    #[test]
+    fn block_lines_with_bare_urls() {
+        let layout = render_layout(
+            "https://a.com https://b.com https://c.com https://d.com\n\nend\n",
+            false,
+        );
+        assert_eq!(layout.block_lines, vec![0..1, 2..3]);
+    }
 
-        ui.horizontal(|ui| {
-            ui.allocate_ui_with_layout(
-                [max_width, 0.0].into(),
-                egui::Layout::left_to_right(egui::Align::TOP),
-                |ui| ui.add(egui::Label::new(job).wrap()),
+    #[test]
+    fn block_lines_with_emoji_shortcodes() {
+        let layout = render_layout(
+            "Hi :smile: and *text* [a] b\n\n# Title :tada:\n\n> :wave:\n> more\n\nend\n",
+            false,
+        );
+        assert_eq!(layout.block_lines, vec![0..1, 2..3, 4..6, 7..8]);
+    }
+
+    #[test]
+    fn block_lines_with_highlight_marks() {
+        let layout = render_layout(
+            "a ==marked *text*== b ==c==\n\n# ==Title==\n\n> ==d==\n> e\n\nend\n",
+            true,
+        );
+        assert_eq!(layout.block_lines, vec![0..1, 2..3, 4..6, 7..8]);
+    }
+
+    /// Renders a document in headless frames, drags a selection across all of it and
+    /// returns the text Ctrl+C copies.
+    fn copy_selection(markdown: &str) -> String {
+        let renderer = MarkdownRenderer::new();
+        let ctx = egui::Context::default();
+        crate::fonts::install(&ctx, false, &crate::fonts::FontChoice::default())
+            .expect("built-in fonts");
+        let screen = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(800.0, 600.0));
+        let (start, end) = (egui::pos2(9.0, 20.0), egui::pos2(790.0, 590.0));
+        let button = |pos, pressed| egui::Event::PointerButton {
+            pos,
+            button: egui::PointerButton::Primary,
+            pressed,
+            modifiers: egui::Modifiers::NONE,
+        };
+        let frames = [
+            vec![],
+            vec![egui::Event::PointerMoved(start)],
+            vec![button(start, true)],
+            vec![egui::Event::PointerMoved(end)],
+            vec![button(end, false)],
+            vec![egui::Event::Copy],
+        ];
+        frames
+            .into_iter()
+            .map(|events| {
+                let input = egui::RawInput {
+                    screen_rect: Some(screen),
+                    events,
+                    ..Default::default()
+                };
+                ctx.run(input, |ctx| {
+                    egui::CentralPanel::default().show(ctx, |ui| {
+                        renderer.render(
+                            ui,
+                            markdown,
+                            "",
+                            None,
+                            &mut HashMap::new(),
+                            &None,
+                            &None,
+                            None,
+                        );
+                    });
+                })
+                .platform_output
+                .copied_text
+            })
+            .collect()
+    }
+
+    #[test]
+    fn copy_selection_with_links() {
+        let copied = copy_selection("See [the docs](https://example.com) first.\n\nend\n");
+        assert_eq!(copied, "See the docs first.\n\nend");
+    }
+
+    #[test]
+    fn link_at_stops_at_the_last_character() {
+        let ctx = egui::Context::default();
+        crate::fonts::install(&ctx, false, &crate::fonts::FontChoice::default())
+            .expect("built-in fonts");
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            let job = LayoutJob::simple(
+                "see link now".to_string(),
+                FontId::proportional(14.0),
+                Color32::WHITE,
+                500.0,
             );
+            let galley = ctx.fonts(|fonts| fonts.layout_job(job));
+            let links = [LinkSpan {
+                url: "#x".to_string(),
+                range: 4..8,
+            }];
+            let row = &galley.rows[0];
+            let hit = |column: usize| {
+                let glyph = &row.glyphs[column];
+                let pos = egui::pos2(glyph.pos.x + 0.5, row.rect.center().y);
+                link_at(&galley, Pos2::ZERO, &links, pos).is_some()
+            };
+            assert!(!hit(3));
+            assert!(hit(4));
+            assert!(hit(7));
+            assert!(!hit(8));
         });
     }
 }
@@ -0,0 +1,404 @@
+//! # Math Module
+//!
+//! This module typesets `$...$` and `$$...$$` TeX math as styled text: commands become
+//! their Unicode symbols, `^` and `_` become raised and lowered smaller text, fractions,
+//! roots and `\mathbb` letters are spelled with Unicode, and variables are set in italics.
+//! It covers the notation common in technical notes rather than all of TeX.
+
+use egui::text::{LayoutJob, TextFormat};
+use egui::{Align, Color32, FontId};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Appends typeset math to a layout job.
+pub fn append(job: &mut LayoutJob, tex: &str, font_size: f32, color: Color32) {
+    let mut writer = Writer { job, color };
+    writer.write(tex, font_size, Align::Center);
+}
+
+/// Converts math to plain Unicode text, for places that can't show styled text.
+pub fn to_text(tex: &str) -> String {
+    let mut job = LayoutJob::default();
+    append(&mut job, tex, 14.0, Color32::WHITE);
+    job.text
+}
+
+struct Writer<'a> {
+    job: &'a mut LayoutJob,
+    color: Color32,
+}
+
+impl Writer<'_> {
+    fn push(&mut self, text: &str, size: f32, valign: Align, italics: bool) {
+        self.job.append(
+            text,
+            0.0,
+            TextFormat {
+                font_id: FontId::proportional(size),
+                color: self.color,
+                italics,
+                valign,
+                ..Default::default()
+            },
+        );
+    }
+
+    fn write(&mut self, tex: &str, size: f32, valign: Align) {
+        let mut chars = tex.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' => {
+                    let group = read_group(&mut chars);
+                    self.write(&group, size, valign);
+                }
+                '}' => {}
+                '^' | '_' => {
+                    let argument = read_argument(&mut chars);
+                    let script_align = if c == '^' { Align::TOP } else { Align::BOTTOM };
+                    self.write(&argument, size * 0.7, script_align);
+                }
+                '\\' => self.command(&mut chars, size, valign),
+                c if c.is_whitespace() => {}
+                '=' | '+' | '<' | '>' => self.push(&format!(" {c} "), size, valign, false),
+                '-' => self.push(" − ", size, valign, false),
+                c if c.is_alphabetic() => self.push(&c.to_string(), size, valign, true),
+                c => self.push(&c.to_string(), size, valign, false),
+            }
+        }
+    }
+
+    fn command(&mut self, chars: &mut Peekable<Chars>, size: f32, valign: Align) {
+        let mut name = String::new();
+        while let Some(&c) = chars.peek() {
+            if !c.is_ascii_alphabetic() {
+                break;
+            }
+            name.push(c);
+            chars.next();
+        }
+        if name.is_empty() {
+            // Single-character commands like \, \{ or \\
+            match chars.next() {
+                Some('\\') => self.push("\n", size, valign, false),
+                Some(',' | ':' | ';' | ' ') => self.push("\u{2009}", size, valign, false),
+                Some(c) => self.push(&c.to_string(), size, valign, false),
+                None => {}
+            }
+            return;
+        }
+
+        match name.as_str() {
+            "frac" | "dfrac" | "tfrac" => {
+                let numerator = read_argument(chars);
+                let denominator = read_argument(chars);
+                self.write_wrapped(&numerator, size, valign);
+                self.push("⁄", size, valign, false);
+                self.write_wrapped(&denominator, size, valign);
+            }
+            "sqrt" => {
+                let argument = read_argument(chars);
+                self.push("√", size, valign, false);
+                self.write_wrapped(&argument, size, valign);
+            }
+            "text" | "mathrm" | "operatorname" | "textrm" => {
+                let argument = read_argument(chars);
+                self.push(&argument, size, valign, false);
+            }
+            "mathbf" | "boldsymbol" | "mathit" => {
+                let argument = read_argument(chars);
+                self.write(&argument, size, valign);
+            }
+            "mathbb" => {
+                let argument = read_argument(chars);
+                let letters: String = argument.chars().map(double_struck).collect();
+                self.push(&letters, size, valign, false);
+            }
+            "left" | "right" | "big" | "Big" | "bigg" | "Bigg" | "displaystyle" => {}
+            "quad" => self.push("\u{2003}", size, valign, false),
+            "qquad" => self.push("\u{2003}\u{2003}", size, valign, false),
+            _ => match symbol(&name) {
+                Some(symbol) if is_relation(symbol) => {
+                    self.push(&format!(" {symbol} "), size, valign, false)
+                }
+                Some(symbol) => self.push(symbol, size, valign, false),
+                None if is_function(&name) => self.push(&name, size, valign, false),
+                None => self.push(&format!("\\{name}"), size, valign, false),
+            },
+        }
+    }
+
+    /// Writes an argument, in parentheses if it is longer than a single symbol.
+    fn write_wrapped(&mut self, tex: &str, size: f32, valign: Align) {
+        let simple = tex.chars().filter(|c| !c.is_whitespace()).count() <= 1
+            || (tex.starts_with('\\') && tex[1..].chars().all(|c| c.is_ascii_alphabetic()));
+        if simple {
+            self.write(tex, size, valign);
+        } else {
+            self.push("(", size, valign, false);
+            self.write(tex, size, valign);
+            self.push(")", size, valign, false);
+        }
+    }
+}
+
+/// Reads the contents of a `{...}` group whose opening brace was consumed.
+fn read_group(chars: &mut Peekable<Chars>) -> String {
+    let mut depth = 1;
+    let mut group = String::new();
+    for c in chars.by_ref() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+        group.push(c);
+    }
+    group
+}
+
+/// Reads the argument of a command or script: a group, a command, or one character.
+fn read_argument(chars: &mut Peekable<Chars>) -> String {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+    match chars.next() {
+        Some('{') => read_group(chars),
+        Some('\\') => {
+            let mut command = String::from('\\');
+            while let Some(&c) = chars.peek() {
+                if !c.is_ascii_alphabetic() {
+                    break;
+                }
+                command.push(c);
+                chars.next();
+            }
+            if command.len() == 1 {
+                command.extend(chars.next());
+            }
+            command
+        }
+        Some(c) => c.to_string(),
+        None => String::new(),
+    }
+}
+
+fn is_relation(symbol: &str) -> bool {
+    matches!(
+        symbol,
+        "≤" | "≥"
+            | "≠"
+            | "≈"
+            | "≡"
+            | "∼"
+            | "≅"
+            | "∝"
+            | "→"
+            | "←"
+            | "↔"
+            | "⇒"
+            | "⇐"
+            | "⇔"
+            | "↦"
+            | "∈"
+            | "∉"
+            | "⊂"
+            | "⊆"
+            | "⊃"
+            | "⊇"
+            | "×"
+            | "·"
+            | "±"
+            | "∓"
+            | "÷"
+            | "∘"
+            | "∧"
+            | "∨"
+            | "∩"
+            | "∪"
+            | "⊕"
+            | "⊗"
+            | "≪"
+            | "≫"
+            | "∣"
+    )
+}
+
+fn is_function(name: &str) -> bool {
+    matches!(
+        name,
+        "sin"
+            | "cos"
+            | "tan"
+            | "cot"
+            | "sec"
+            | "csc"
+            | "arcsin"
+            | "arccos"
+            | "arctan"
+            | "sinh"
+            | "cosh"
+            | "tanh"
+            | "log"
+            | "ln"
+            | "lg"
+            | "exp"
+            | "lim"
+            | "liminf"
+            | "limsup"
+            | "max"
+            | "min"
+            | "sup"
+            | "inf"
+            | "det"
+            | "dim"
+            | "deg"
+            | "gcd"
+            | "arg"
+            | "ker"
+            | "Pr"
+            | "mod"
+    )
+}
+
+fn symbol(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "delta" => "δ",
+        "epsilon" => "ϵ",
+        "varepsilon" => "ε",
+        "zeta" => "ζ",
+        "eta" => "η",
+        "theta" => "θ",
+        "vartheta" => "ϑ",
+        "iota" => "ι",
+        "kappa" => "κ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "nu" => "ν",
+        "xi" => "ξ",
+        "pi" => "π",
+        "varpi" => "ϖ",
+        "rho" => "ρ",
+        "varrho" => "ϱ",
+        "sigma" => "σ",
+        "varsigma" => "ς",
+        "tau" => "τ",
+        "upsilon" => "υ",
+        "phi" => "ϕ",
+        "varphi" => "φ",
+        "chi" => "χ",
+        "psi" => "ψ",
+        "omega" => "ω",
+        "Gamma" => "Γ",
+        "Delta" => "Δ",
+        "Theta" => "Θ",
+        "Lambda" => "Λ",
+        "Xi" => "Ξ",
+        "Pi" => "Π",
+        "Sigma" => "Σ",
+        "Upsilon" => "Υ",
+        "Phi" => "Φ",
+        "Psi" => "Ψ",
+        "Omega" => "Ω",
+        "sum" => "∑",
+        "prod" => "∏",
+        "coprod" => "∐",
+        "int" => "∫",
+        "iint" => "∬",
+        "iiint" => "∭",
+        "oint" => "∮",
+        "infty" => "∞",
+        "partial" => "∂",
+        "nabla" => "∇",
+        "forall" => "∀",
+        "exists" => "∃",
+        "nexists" => "∄",
+        "emptyset" | "varnothing" => "∅",
+        "neg" | "lnot" => "¬",
+        "le" | "leq" => "≤",
+        "ge" | "geq" => "≥",
+        "ne" | "neq" => "≠",
+        "approx" => "≈",
+        "equiv" => "≡",
+        "sim" => "∼",
+        "cong" => "≅",
+        "propto" => "∝",
+        "ll" => "≪",
+        "gg" => "≫",
+        "to" | "rightarrow" => "→",
+        "leftarrow" | "gets" => "←",
+        "leftrightarrow" => "↔",
+        "Rightarrow" | "implies" => "⇒",
+        "Leftarrow" => "⇐",
+        "Leftrightarrow" | "iff" => "⇔",
+        "mapsto" => "↦",
+        "in" => "∈",
+        "notin" => "∉",
+        "ni" => "∋",
+        "subset" => "⊂",
+        "subseteq" => "⊆",
+        "supset" => "⊃",
+        "supseteq" => "⊇",
+        "cap" => "∩",
+        "cup" => "∪",
+        "wedge" | "land" => "∧",
+        "vee" | "lor" => "∨",
+        "oplus" => "⊕",
+        "otimes" => "⊗",
+        "times" => "×",
+        "cdot" => "·",
+        "div" => "÷",
+        "pm" => "±",
+        "mp" => "∓",
+        "circ" => "∘",
+        "mid" => "∣",
+        "cdots" => "⋯",
+        "ldots" | "dots" => "…",
+        "vdots" => "⋮",
+        "ddots" => "⋱",
+        "prime" => "′",
+        "degree" => "°",
+        "hbar" => "ℏ",
+        "ell" => "ℓ",
+        "Re" => "ℜ",
+        "Im" => "ℑ",
+        "aleph" => "ℵ",
+        "angle" => "∠",
+        "perp" => "⊥",
+        "parallel" => "∥",
+        "langle" => "⟨",
+        "rangle" => "⟩",
+        "lceil" => "⌈",
+        "rceil" => "⌉",
+        "lfloor" => "⌊",
+        "rfloor" => "⌋",
+        "lbrace" => "{",
+        "rbrace" => "}",
+        "vert" => "|",
+        "Vert" => "‖",
+        _ => return None,
+    })
+}
+
+/// Maps a letter to its double-struck form (`\mathbb{R}` → ℝ).
+fn double_struck(c: char) -> char {
+    match c {
+        'C' => 'ℂ',
+        'H' => 'ℍ',
+        'N' => 'ℕ',
+        'P' => 'ℙ',
+        'Q' => 'ℚ',
+        'R' => 'ℝ',
+        'Z' => 'ℤ',
+        'A'..='Z' => char::from_u32(0x1D538 + (c as u32 - 'A' as u32)).unwrap_or(c),
+        'a'..='z' => char::from_u32(0x1D552 + (c as u32 - 'a' as u32)).unwrap_or(c),
+        c => c,
+    }
+}
@@ -0,0 +1,332 @@
+//! # Org Module
+//!
+//! This module converts Org-mode documents to markdown so they render like any note.
+//! It covers the parts notes are mostly made of: headings, lists and checkboxes, source,
+//! example and quote blocks, tables, links and inline emphasis. Keywords other than
+//! `#+TITLE`, comments and drawers (`:PROPERTIES:` ... `:END:`) are left out.
+
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+/// Kind of `#+BEGIN_...` block being converted.
+enum Block {
+    /// Source or example block, copied verbatim into a fence
+    Code,
+    /// Quote block, turned into a blockquote
+    Quote,
+    /// Any other block (center, verse, ...), whose lines are kept as text
+    Other,
+}
+
+/// Converts an Org document to markdown.
+pub fn to_markdown(source: &str) -> String {
+    let mut markdown = String::new();
+    let mut block = None;
+    let mut in_drawer = false;
+    let mut table: Vec<&str> = Vec::new();
+
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if !matches!(block, Some(Block::Code)) && trimmed.starts_with('|') {
+            table.push(trimmed);
+            continue;
+        }
+        push_table(&mut markdown, &mut table);
+
+        let lower = trimmed.to_ascii_lowercase();
+        if let Some(kind) = &block {
+            if lower.starts_with("#+end_") {
+                if matches!(kind, Block::Code) {
+                    markdown.push_str("```\n");
+                }
+                markdown.push('\n');
+                block = None;
+                continue;
+            }
+            match kind {
+                // Org escapes lines starting with `*` or `#+` in blocks with a comma
+                Block::Code => {
+                    let unescaped = match line.trim_start().strip_prefix(',') {
+                        Some(rest) if rest.starts_with('*') || rest.starts_with("#+") => {
+                            line.replacen(',', "", 1)
+                        }
+                        _ => line.to_string(),
+                    };
+                    markdown.push_str(&unescaped);
+                }
+                Block::Quote => {
+                    markdown.push_str("> ");
+                    markdown.push_str(&inline(trimmed));
+                }
+                Block::Other => markdown.push_str(&inline(trimmed)),
+            }
+            markdown.push('\n');
+            continue;
+        }
+
+        if let Some(rest) = lower.strip_prefix("#+begin_") {
+            let name = rest.split_whitespace().next().unwrap_or_default();
+            block = Some(match name {
+                "src" => {
+                    // The language is the first argument: `#+BEGIN_SRC rust :results output`
+                    let language = trimmed.split_whitespace().nth(1).unwrap_or_default();
+                    markdown.push_str(&format!("```{language}\n"));
+                    Block::Code
+                }
+                "example" => {
+                    markdown.push_str("```\n");
+                    Block::Code
+                }
+                "quote" => Block::Quote,
+                _ => Block::Other,
+            });
+            continue;
+        }
+        if lower.starts_with("#+title:") {
+            markdown.push_str(&format!("# {}\n\n", inline(trimmed[8..].trim())));
+            continue;
+        }
+        // Other keywords and comments
+        if trimmed.starts_with("#+") || trimmed == "#" || trimmed.starts_with("# ") {
+            continue;
+        }
+        if in_drawer {
+            in_drawer = !trimmed.eq_ignore_ascii_case(":END:");
+            continue;
+        }
+        if is_drawer_start(trimmed) {
+            in_drawer = true;
+            continue;
+        }
+
+        if trimmed.len() >= 5 && trimmed.chars().all(|c| c == '-') {
+            markdown.push_str("\n---\n");
+        } else if let Some(heading) = heading(line) {
+            markdown.push_str(&heading);
+        } else if let Some(item) = list_item(line) {
+            markdown.push_str(&item);
+        } else {
+            markdown.push_str(&inline(line));
+        }
+        markdown.push('\n');
+    }
+    push_table(&mut markdown, &mut table);
+    if matches!(block, Some(Block::Code)) {
+        markdown.push_str("```\n");
+    }
+    markdown
+}
+
+/// Whether a line opens a drawer such as `:PROPERTIES:` or `:LOGBOOK:`.
+fn is_drawer_start(line: &str) -> bool {
+    line.len() > 2
+        && line.starts_with(':')
+        && line.ends_with(':')
+        && line[1..line.len() - 1]
+            .chars()
+            .all(|c| c.is_ascii_alphabetic() || c == '-' || c == '_')
+}
+
+/// Converts a `** Heading :tag:` line to a markdown heading, dropping the tags.
+fn heading(line: &str) -> Option<String> {
+    let level = line.chars().take_while(|&c| c == '*').count();
+    if level == 0 {
+        return None;
+    }
+    let title = line[level..].strip_prefix(' ')?;
+    let title = tags_regex().replace(title.trim(), "");
+    Some(format!(
+        "\n{} {}\n",
+        "#".repeat(level.min(6)),
+        inline(title.trim())
+    ))
+}
+
+/// Converts a list item (`- `, `+ `, indented `* `, `1. `, `1) `) to a markdown item,
+/// keeping its indentation and checkbox.
+fn list_item(line: &str) -> Option<String> {
+    let body = line.trim_start();
+    let indent = &line[..line.len() - body.len()];
+    let (marker, rest) = if let Some(rest) = body
+        .strip_prefix("- ")
+        .or_else(|| body.strip_prefix("+ "))
+        .or_else(|| body.strip_prefix("* ").filter(|_| !indent.is_empty()))
+    {
+        ("-".to_string(), rest)
+    } else {
+        let digits = body.chars().take_while(char::is_ascii_digit).count();
+        let rest = body[digits..]
+            .strip_prefix(". ")
+            .or_else(|| body[digits..].strip_prefix(") "))
+            .filter(|_| digits > 0)?;
+        (format!("{}.", &body[..digits]), rest)
+    };
+    let rest = match rest.get(..4) {
+        Some("[X] ") => format!("[x] {}", inline(&rest[4..])),
+        Some("[-] ") => format!("[ ] {}", inline(&rest[4..])),
+        _ => inline(rest),
+    };
+    Some(format!("{indent}{marker} {rest}"))
+}
+
+/// Writes the collected rows of an Org table as a pipe table. Horizontal rules are
+/// dropped and the first row becomes the header, which markdown tables require.
+fn push_table(markdown: &mut String, rows: &mut Vec<&str>) {
+    let cells: Vec<Vec<String>> = rows
+        .drain(..)
+        .filter(|row| !row.starts_with("|-"))
+        .map(|row| {
+            let row = row.trim_start_matches('|');
+            let row = row.strip_suffix('|').unwrap_or(row);
+            row.split('|').map(|cell| inline(cell.trim())).collect()
+        })
+        .collect();
+    let Some(columns) = cells.iter().map(Vec::len).max() else {
+        return;
+    };
+    if !markdown.is_empty() && !markdown.ends_with("\n\n") {
+        markdown.push('\n');
+    }
+    for (index, row) in cells.iter().enumerate() {
+        markdown.push('|');
+        for column in 0..columns {
+            markdown.push(' ');
+            markdown.push_str(row.get(column).map_or("", String::as_str));
+            markdown.push_str(" |");
+        }
+        markdown.push('\n');
+        if index == 0 {
+            markdown.push_str(&"| --- ".repeat(columns));
+            markdown.push_str("|\n");
+        }
+    }
+    markdown.push('\n');
+}
+
+/// Converts the links, code and emphasis of a line of Org text.
+fn inline(text: &str) -> String {
+    let mut result = String::new();
+    let mut last = 0;
+    for captures in span_regex().captures_iter(text) {
+        let span = captures.get(0).expect("whole match");
+        let before = text[..span.start()].chars().next_back();
+        let after = text[span.end()..].chars().next();
+        let code = captures.name("code");
+        // Verbatim spans must stand apart from the surrounding words, like emphasis
+        if code.is_some() && !(opens_markup(before) && closes_markup(after)) {
+            continue;
+        }
+        result.push_str(&emphasis(&text[last..span.start()]));
+        match code {
+            Some(code) => {
+                let code = &code.as_str()[1..code.len() - 1];
+                let ticks = if code.contains('`') { "``" } else { "`" };
+                result.push_str(&format!("{ticks}{code}{ticks}"));
+            }
+            None => result.push_str(&link(&captures)),
+        }
+        last = span.end();
+    }
+    result.push_str(&emphasis(&text[last..]));
+    result
+}
+
+/// Whether the character before an emphasis or verbatim marker allows it to open.
+fn opens_markup(before: Option<char>) -> bool {
+    before.is_none_or(|c| c.is_whitespace() || "-({'\"".contains(c))
+}
+
+/// Whether the character after an emphasis or verbatim marker allows it to close.
+fn closes_markup(after: Option<char>) -> bool {
+    after.is_none_or(|c| c.is_whitespace() || "-.,;:!?')}\"".contains(c))
+}
+
+/// Converts `[[target][description]]` or `[[target]]` to a markdown link, or to an
+/// image for a bare link to a picture.
+fn link(captures: &Captures) -> String {
+    let target = &captures["target"];
+    let target = target.strip_prefix("file:").unwrap_or(target);
+    let text = target.strip_prefix('*').unwrap_or(target).to_string();
+    // `[[*Heading]]` links to a heading of the document
+    let target = match target.strip_prefix('*') {
+        Some(heading) => format!("#{}", crate::markdown::slugify(heading)),
+        None => target.to_string(),
+    };
+    let target = if target.contains(' ') {
+        format!("<{target}>")
+    } else {
+        target
+    };
+    match captures.name("description") {
+        Some(description) => format!("[{}]({target})", emphasis(description.as_str())),
+        None if is_image(&target) => format!("![]({target})"),
+        None => format!("[{text}]({target})"),
+    }
+}
+
+fn is_image(target: &str) -> bool {
+    let target = target.to_ascii_lowercase();
+    [".png", ".jpg", ".jpeg", ".gif", ".svg", ".webp"]
+        .iter()
+        .any(|extension| target.ends_with(extension))
+}
+
+/// Converts `*bold*`, `/italic/`, `+strike-through+` and `_underline_` to markdown.
+fn emphasis(text: &str) -> String {
+    let mut text = text.to_string();
+    for (marker, open, close) in [
+        ('*', "**", "**"),
+        ('/', "*", "*"),
+        ('+', "~~", "~~"),
+        ('_', "<u>", "</u>"),
+    ] {
+        text = emphasis_regex(marker)
+            .replace_all(&text, |captures: &Captures| {
+                format!(
+                    "{}{open}{}{close}{}",
+                    &captures[1], &captures[2], &captures[3]
+                )
+            })
+            .into_owned();
+    }
+    text
+}
+
+/// Links and verbatim (`=code=`, `~code~`) spans.
+fn span_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(
+            r"\[\[(?P<target>[^\]]+)\](?:\[(?P<description>[^\]]+)\])?\]|(?P<code>=[^=\s](?:[^=]*[^=\s])?=|~[^~\s](?:[^~]*[^~\s])?~)",
+        )
+        .expect("valid regex")
+    })
+}
+
+/// Trailing heading tags (`:work:urgent:`).
+fn tags_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"\s+:[\w@#%:]+:$").expect("valid regex"))
+}
+
+/// Emphasis around a marker, with the characters allowed before and after it.
+fn emphasis_regex(marker: char) -> &'static Regex {
+    static REGEXES: OnceLock<Vec<(char, Regex)>> = OnceLock::new();
+    let regexes = REGEXES.get_or_init(|| {
+        ['*', '/', '+', '_']
+            .into_iter()
+            .map(|marker| {
+                let m = regex::escape(&marker.to_string());
+                let pattern = format!(
+                    r#"(^|[\s\-({{'"]){m}([^\s{m}](?:[^{m}]*[^\s{m}])?){m}($|[\s\-.,;:!?')}}"])"#
+                );
+                (marker, Regex::new(&pattern).expect("valid regex"))
+            })
+            .collect()
+    });
+    &regexes
+        .iter()
+        .find(|(candidate, _)| *candidate == marker)
+        .expect("known marker")
+        .1
+}
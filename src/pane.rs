@@ -0,0 +1,222 @@
+//! # Pane Module
+//!
+//! This module implements the secondary document pane of the split view, which shows a
+//! second document next to the main one with its own scroll position and search.
+
+use crate::markdown::{DocumentLayout, MarkdownRenderer};
+use egui::Ui;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// How the scroll positions of the two panes are kept aligned.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Panes scroll independently
+    #[default]
+    Off,
+    /// Panes keep the same relative position
+    Proportional,
+    /// Panes keep the same position relative to their n-th heading
+    Headings,
+}
+
+/// Computes the offset of a pane that keeps it aligned with another pane.
+pub fn synced_offset(
+    mode: SyncMode,
+    from: &DocumentLayout,
+    from_offset: f32,
+    from_max: f32,
+    to: &DocumentLayout,
+    to_max: f32,
+) -> f32 {
+    let proportional = if from_max > 0.0 {
+        from_offset / from_max * to_max
+    } else {
+        0.0
+    };
+    if mode != SyncMode::Headings || from.heading_tops.is_empty() || to.heading_tops.is_empty() {
+        return proportional.clamp(0.0, to_max);
+    }
+
+    // Locate the position between two headings (or the document edges) and map it onto
+    // the same span in the other document
+    let span = |tops: &[f32], index: usize, max: f32| {
+        let start = if index == 0 { 0.0 } else { tops[index - 1] };
+        let end = tops.get(index).copied().unwrap_or(max.max(start));
+        (start, end)
+    };
+    let index = from.heading_tops.partition_point(|&top| top <= from_offset);
+    if index > to.heading_tops.len() {
+        return proportional.clamp(0.0, to_max);
+    }
+    let (from_start, from_end) = span(&from.heading_tops, index, from_max);
+    let (to_start, to_end) = span(&to.heading_tops, index, to_max);
+    let fraction = if from_end > from_start {
+        ((from_offset - from_start) / (from_end - from_start)).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (to_start + (to_end - to_start) * fraction).clamp(0.0, to_max)
+}
+
+/// A document shown in the secondary pane of the split view.
+pub struct Pane {
+    /// Path of the document
+    pub file: PathBuf,
+    /// Markdown content of the document
+    content: String,
+    /// Text searched for within this pane
+    search_query: String,
+    /// Source lines (0-based) containing a match of the search query
+    search_lines: Vec<usize>,
+    /// Index of the current entry in `search_lines`
+    search_index: usize,
+    /// Images loaded for this document
+    image_cache: HashMap<String, Result<egui::TextureHandle, String>>,
+    /// Scroll position of the last frame
+    pub scroll_offset: f32,
+    /// Largest scroll offset of the last frame
+    pub max_scroll_offset: f32,
+    /// Scroll offset to apply in the next frame
+    pub pending_scroll_offset: Option<f32>,
+    /// Block and heading positions of the last frame, relative to the document top
+    pub layout: DocumentLayout,
+}
+
+impl Pane {
+    /// Opens a document in a new pane.
+    pub fn open(file: PathBuf) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(&file)?;
+        Ok(Self {
+            file,
+            content,
+            search_query: String::new(),
+            search_lines: Vec::new(),
+            search_index: 0,
+            image_cache: HashMap::new(),
+            scroll_offset: 0.0,
+            max_scroll_offset: 0.0,
+            pending_scroll_offset: None,
+            layout: DocumentLayout::default(),
+        })
+    }
+
+    fn perform_search(&mut self) {
+        let query = self.search_query.to_lowercase();
+        self.search_index = 0;
+        self.search_lines = if query.is_empty() {
+            Vec::new()
+        } else {
+            self.content
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| line.to_lowercase().contains(&query))
+                .map(|(number, _)| number)
+                .collect()
+        };
+        self.scroll_to_search_result();
+    }
+
+    fn step_search(&mut self, forward: bool) {
+        let count = self.search_lines.len();
+        if count == 0 {
+            return;
+        }
+        self.search_index = if forward {
+            (self.search_index + 1) % count
+        } else {
+            (self.search_index + count - 1) % count
+        };
+        self.scroll_to_search_result();
+    }
+
+    fn scroll_to_search_result(&mut self) {
+        if let Some(offset) = self
+            .search_lines
+            .get(self.search_index)
+            .and_then(|&line| self.layout.offset_for_line(line))
+        {
+            self.pending_scroll_offset = Some(offset.min(self.max_scroll_offset));
+        }
+    }
+
+    /// Draws the pane. Returns false if the user closed it.
+    pub fn show(&mut self, ui: &mut Ui, renderer: &MarkdownRenderer) -> bool {
+        let mut open = true;
+        ui.horizontal(|ui| {
+            let name = self
+                .file
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            ui.heading(name)
+                .on_hover_text(self.file.display().to_string());
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("✖").on_hover_text("Close pane").clicked() {
+                    open = false;
+                }
+                if ui.button("⬇").on_hover_text("Next result").clicked() {
+                    self.step_search(true);
+                }
+                if ui.button("⬆").on_hover_text("Previous result").clicked() {
+                    self.step_search(false);
+                }
+                if !self.search_query.is_empty() {
+                    ui.label(format!(
+                        "{}/{}",
+                        (self.search_index + 1).min(self.search_lines.len()),
+                        self.search_lines.len()
+                    ));
+                }
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.search_query)
+                        .hint_text("Search this pane")
+                        .desired_width(140.0),
+                );
+                if response.changed() {
+                    self.perform_search();
+                }
+                if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                    self.step_search(true);
+                }
+            });
+        });
+        ui.separator();
+
+        let mut scroll_area = egui::ScrollArea::vertical()
+            .id_source("secondary_pane")
+            .auto_shrink([false; 2]);
+        if let Some(offset) = self.pending_scroll_offset.take() {
+            scroll_area = scroll_area.vertical_scroll_offset(offset);
+        }
+        let current_file = Some(self.file.clone());
+        let scroll_output = scroll_area.show(ui, |ui| {
+            ui.add_space(10.0);
+            ui.horizontal(|ui| {
+                let side_padding = ui.available_width() * 0.05;
+                ui.add_space(side_padding);
+                let content_width = ui.available_width() - side_padding;
+                ui.vertical(|ui| {
+                    renderer.render(
+                        ui,
+                        &self.content,
+                        &self.search_query,
+                        None,
+                        &mut self.image_cache,
+                        &current_file,
+                        &None,
+                        Some(content_width),
+                    );
+                });
+            });
+        });
+
+        self.scroll_offset = scroll_output.state.offset.y;
+        self.max_scroll_offset =
+            (scroll_output.content_size.y - scroll_output.inner_rect.height()).max(0.0);
+        let content_top = scroll_output.inner_rect.top() - scroll_output.state.offset.y;
+        self.layout = renderer.layout().translated(-content_top);
+        open
+    }
+}
@@ -0,0 +1,140 @@
+//! # PlantUML Module
+//!
+//! This module renders `plantuml` fences by sending their source to a PlantUML server
+//! and showing the PNG it returns. The source travels in the URL, hex-encoded (PlantUML's
+//! `~h` form), so no compression is needed. No server is set by default, so documents
+//! stay local until the user picks one; until then, or when the server can't be
+//! reached, the block falls back to showing its source.
+
+use crate::plugins::{FenceContext, FenceRenderer};
+use egui::{ColorImage, TextureHandle, TextureOptions, Ui};
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use std::sync::mpsc;
+
+/// Example server address shown in the empty server field.
+pub const SERVER_HINT: &str = "http://localhost:8080/plantuml";
+
+/// Diagram of one block.
+enum Diagram {
+    /// The download is still running
+    Pending(mpsc::Receiver<Result<Vec<u8>, String>>),
+    /// Rendered diagram
+    Image(TextureHandle),
+    /// The server couldn't render the diagram
+    Error(String),
+}
+
+/// Fence renderer drawing `plantuml` / `puml` blocks with a PlantUML server.
+pub struct PlantUmlRenderer {
+    /// Base URL of the server, shared with the preferences so changes apply at once
+    server: Rc<RefCell<String>>,
+    /// Diagrams keyed by a hash of the server and the block's source
+    cache: RefCell<HashMap<u64, Diagram>>,
+}
+
+impl PlantUmlRenderer {
+    /// Creates a renderer using the given server.
+    pub fn new(server: Rc<RefCell<String>>) -> Self {
+        Self {
+            server,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn fetch(url: String, ctx: egui::Context) -> mpsc::Receiver<Result<Vec<u8>, String>> {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let result = reqwest::blocking::get(&url)
+                .and_then(|response| response.error_for_status())
+                .and_then(|response| response.bytes())
+                .map(|bytes| bytes.to_vec())
+                .map_err(|e| format!("Failed to fetch PlantUML diagram: {e}"));
+            let _ = sender.send(result);
+            ctx.request_repaint();
+        });
+        receiver
+    }
+
+    fn finish(ui: &Ui, key: u64, bytes: &[u8]) -> Diagram {
+        match image::load_from_memory(bytes) {
+            Ok(image) => {
+                let image = image.to_rgba8();
+                let size = [image.width() as usize, image.height() as usize];
+                let color_image = ColorImage::from_rgba_unmultiplied(size, &image);
+                Diagram::Image(ui.ctx().load_texture(
+                    format!("plantuml_{key}"),
+                    color_image,
+                    TextureOptions::default(),
+                ))
+            }
+            Err(e) => Diagram::Error(format!("Error decoding PlantUML diagram: {e}")),
+        }
+    }
+}
+
+impl FenceRenderer for PlantUmlRenderer {
+    fn handles(&self, language: &str) -> bool {
+        language.eq_ignore_ascii_case("plantuml") || language.eq_ignore_ascii_case("puml")
+    }
+
+    fn render(&self, ui: &mut Ui, code: &str, context: &FenceContext) -> bool {
+        let server = self.server.borrow();
+        if server.is_empty() {
+            return false;
+        }
+        let mut hasher = DefaultHasher::new();
+        (server.as_str(), code).hash(&mut hasher);
+        let key = hasher.finish();
+
+        let mut cache = self.cache.borrow_mut();
+        let diagram = cache.entry(key).or_insert_with(|| {
+            Diagram::Pending(Self::fetch(diagram_url(&server, code), ui.ctx().clone()))
+        });
+
+        if let Diagram::Pending(receiver) = diagram {
+            match receiver.try_recv() {
+                Ok(Ok(bytes)) => *diagram = Self::finish(ui, key, &bytes),
+                Ok(Err(e)) => *diagram = Diagram::Error(e),
+                Err(mpsc::TryRecvError::Empty) => {}
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    *diagram =
+                        Diagram::Error("PlantUML download ended without a diagram".to_owned())
+                }
+            }
+        }
+
+        match diagram {
+            Diagram::Pending(_) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.weak(format!("Rendering {} block…", context.language));
+                });
+                true
+            }
+            Diagram::Image(texture) => {
+                let size = texture.size_vec2();
+                let scale = (context.max_width / size.x).min(1.0);
+                ui.add(egui::Image::new(&*texture).fit_to_exact_size(size * scale));
+                true
+            }
+            Diagram::Error(e) => {
+                ui.colored_label(ui.visuals().warn_fg_color, e.as_str());
+                false
+            }
+        }
+    }
+}
+
+/// URL of the PNG rendering of a diagram.
+fn diagram_url(server: &str, code: &str) -> String {
+    let mut url = format!("{}/png/~h", server.trim_end_matches('/'));
+    for byte in code.as_bytes() {
+        let _ = write!(url, "{byte:02x}");
+    }
+    url
+}
@@ -0,0 +1,236 @@
+//! # Plugins Module
+//!
+//! This module defines the extension point for custom code-fence renderers: a fenced block
+//! whose language is claimed by a registered [`FenceRenderer`] is drawn by that renderer
+//! instead of as highlighted code. Besides renderers registered in code at startup,
+//! renderers backed by external commands (e.g. Graphviz for `dot` fences) are discovered
+//! from `fences.json` in the config directory.
+
+use crate::hooks;
+use crate::storage;
+use egui::{ColorImage, TextureHandle, TextureOptions, Ui};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+
+/// File in the config directory listing command-backed fence renderers.
+pub const FENCES_FILE: &str = "fences.json";
+
+/// Information about the fenced block being rendered.
+pub struct FenceContext<'a> {
+    /// Fence language (first word of the info string)
+    pub language: &'a str,
+    /// Base font size of the document
+    pub font_size: f32,
+    /// Width available to the block
+    pub max_width: f32,
+    /// Markdown file the block belongs to
+    pub current_file: Option<&'a Path>,
+}
+
+/// A custom renderer for fenced code blocks of particular languages.
+pub trait FenceRenderer {
+    /// Whether this renderer handles fences of the given language.
+    fn handles(&self, language: &str) -> bool;
+
+    /// Draws the block. Returns false to fall back to the regular code block.
+    fn render(&self, ui: &mut Ui, code: &str, context: &FenceContext) -> bool;
+}
+
+/// How the output of a command-backed fence renderer is displayed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FenceOutput {
+    /// Output is an image (PNG, JPEG, ...)
+    #[default]
+    Image,
+    /// Output is plain text, shown monospaced
+    Text,
+}
+
+/// Configuration of a command-backed fence renderer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandFenceConfig {
+    /// Fence languages handled by the command
+    pub languages: Vec<String>,
+    /// Command line; the block's code is written to its stdin
+    pub command: String,
+    /// How the command's stdout is displayed
+    #[serde(default)]
+    pub output: FenceOutput,
+}
+
+/// Result of running the command for one block.
+enum CommandResult {
+    /// The command is still running
+    Pending(mpsc::Receiver<Result<Vec<u8>, String>>),
+    /// Rendered image
+    Image(TextureHandle),
+    /// Rendered text
+    Text(String),
+    /// The command failed
+    Error(String),
+}
+
+/// Fence renderer that pipes the block through an external command.
+pub struct CommandFenceRenderer {
+    /// Configuration the renderer was created from
+    config: CommandFenceConfig,
+    /// Results keyed by a hash of the block's code
+    cache: RefCell<HashMap<u64, CommandResult>>,
+}
+
+impl CommandFenceRenderer {
+    /// Creates a renderer from its configuration.
+    pub fn new(config: CommandFenceConfig) -> Self {
+        Self {
+            config,
+            cache: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn spawn(&self, code: &str, dir: Option<&Path>) -> mpsc::Receiver<Result<Vec<u8>, String>> {
+        let (sender, receiver) = mpsc::channel();
+        let args = hooks::split_command_line(&self.config.command);
+        let code = code.to_owned();
+        let dir = dir.map(Path::to_path_buf);
+        std::thread::spawn(move || {
+            let result = (|| {
+                let (program, args) = args
+                    .split_first()
+                    .ok_or_else(|| "Fence command is empty".to_owned())?;
+                let mut command = Command::new(program);
+                command
+                    .args(args)
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped());
+                if let Some(dir) = dir {
+                    command.current_dir(dir);
+                }
+                let mut child = command
+                    .spawn()
+                    .map_err(|e| format!("Error running {program}: {e}"))?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    // Feed stdin from another thread so a command writing output before it
+                    // has read all of its input can't deadlock on full pipes
+                    std::thread::spawn(move || {
+                        let _ = stdin.write_all(code.as_bytes());
+                    });
+                }
+                let output = child
+                    .wait_with_output()
+                    .map_err(|e| format!("Error running {program}: {e}"))?;
+                if output.status.success() {
+                    Ok(output.stdout)
+                } else {
+                    Err(format!(
+                        "{program} failed ({}): {}",
+                        output.status,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ))
+                }
+            })();
+            let _ = sender.send(result);
+        });
+        receiver
+    }
+
+    fn finish(&self, ui: &Ui, key: u64, bytes: Vec<u8>) -> CommandResult {
+        match self.config.output {
+            FenceOutput::Text => CommandResult::Text(String::from_utf8_lossy(&bytes).into_owned()),
+            FenceOutput::Image => match image::load_from_memory(&bytes) {
+                Ok(image) => {
+                    let image = image.to_rgba8();
+                    let size = [image.width() as usize, image.height() as usize];
+                    let color_image = ColorImage::from_rgba_unmultiplied(size, &image);
+                    CommandResult::Image(ui.ctx().load_texture(
+                        format!("fence_{key}"),
+                        color_image,
+                        TextureOptions::default(),
+                    ))
+                }
+                Err(e) => CommandResult::Error(format!("Error decoding command output: {e}")),
+            },
+        }
+    }
+}
+
+impl FenceRenderer for CommandFenceRenderer {
+    fn handles(&self, language: &str) -> bool {
+        self.config
+            .languages
+            .iter()
+            .any(|handled| handled.eq_ignore_ascii_case(language))
+    }
+
+    fn render(&self, ui: &mut Ui, code: &str, context: &FenceContext) -> bool {
+        let mut hasher = DefaultHasher::new();
+        code.hash(&mut hasher);
+        let key = hasher.finish();
+
+        let mut cache = self.cache.borrow_mut();
+        let dir = context.current_file.and_then(Path::parent);
+        let result = cache
+            .entry(key)
+            .or_insert_with(|| CommandResult::Pending(self.spawn(code, dir)));
+
+        if let CommandResult::Pending(receiver) = result {
+            match receiver.try_recv() {
+                Ok(Ok(bytes)) => *result = self.finish(ui, key, bytes),
+                Ok(Err(e)) => *result = CommandResult::Error(e),
+                Err(mpsc::TryRecvError::Empty) => {}
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    *result = CommandResult::Error("Fence command ended without output".to_owned())
+                }
+            }
+        }
+
+        match result {
+            CommandResult::Pending(_) => {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.weak(format!("Rendering {} block…", context.language));
+                });
+                ui.ctx()
+                    .request_repaint_after(std::time::Duration::from_millis(100));
+                true
+            }
+            CommandResult::Image(texture) => {
+                let size = texture.size_vec2();
+                let scale = (context.max_width / size.x).min(1.0);
+                ui.add(egui::Image::new(&*texture).fit_to_exact_size(size * scale));
+                true
+            }
+            CommandResult::Text(text) => {
+                ui.add(
+                    egui::Label::new(
+                        egui::RichText::new(text.as_str())
+                            .monospace()
+                            .size(context.font_size * 0.9),
+                    )
+                    .wrap(),
+                );
+                true
+            }
+            CommandResult::Error(e) => {
+                ui.colored_label(ui.visuals().warn_fg_color, e.as_str());
+                false
+            }
+        }
+    }
+}
+
+/// Loads the command-backed fence renderers configured in `fences.json`.
+pub fn load_command_renderers() -> Vec<Box<dyn FenceRenderer>> {
+    storage::load_json::<Vec<CommandFenceConfig>>(FENCES_FILE)
+        .into_iter()
+        .map(|config| Box::new(CommandFenceRenderer::new(config)) as Box<dyn FenceRenderer>)
+        .collect()
+}
@@ -0,0 +1,104 @@
+//! # Preferences Module
+//!
+//! This module stores the global preferences that aren't tied to a document (color
+//! theme, code themes, search options, night light, the reading fonts, sibling browsing,
+//! folder filter scripts, code wrapping, opt-in syntax, image size limits, scroll animation and the panels
+//! shown) in eframe's storage so they survive restarts. eframe saves them periodically
+//! and on exit.
+//! Font size, wide mode and TOC visibility are kept by the view settings, in the same
+//! storage.
+
+use crate::fonts::FontChoice;
+use crate::markdown::{DEFAULT_DARK_CODE_THEME, DEFAULT_LIGHT_CODE_THEME};
+use crate::theme::{ColorScheme, NightLight};
+use serde::{Deserialize, Serialize};
+
+/// Key of the preferences in eframe's storage.
+const STORAGE_KEY: &str = "preferences";
+
+/// Preferences restored at startup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// Name of the selected color scheme
+    pub theme: String,
+    /// Whether search is case sensitive
+    pub search_case_sensitive: bool,
+    /// Whether search includes approximate matches
+    pub search_fuzzy: bool,
+    /// Night-light settings
+    pub night_light: NightLight,
+    /// Whether the dyslexia-friendly font is enabled
+    pub accessible_font: bool,
+    /// Whether opening a file lists the documents of its folder in the file tree
+    pub browse_siblings: bool,
+    /// Whether the filter scripts found next to documents run
+    pub folder_filters: bool,
+    /// Whether long code lines wrap instead of scrolling horizontally
+    pub wrap_code: bool,
+    /// Whether `==text==` is rendered as highlighted text
+    pub highlight_marks: bool,
+    /// Whether the status bar with word count and reading time is shown
+    pub show_status_bar: bool,
+    /// Whether the scratchpad panel with notes on the document is shown
+    pub show_scratchpad: bool,
+    /// Syntax theme of code blocks; None follows the color scheme
+    pub code_theme: Option<String>,
+    /// Syntax theme following dark color schemes
+    pub code_theme_dark: String,
+    /// Syntax theme following light color schemes
+    pub code_theme_light: String,
+    /// Base URL of the PlantUML server rendering `plantuml` fences, empty for none
+    pub plantuml_server: String,
+    /// Font files chosen for body text and code
+    pub fonts: FontChoice,
+    /// Widest an image is shown without a size hint, in percent of the content width
+    pub image_max_width: f32,
+    /// Tallest an image is shown without a size hint, in points
+    pub image_max_height: f32,
+    /// How long a jump scrolls to its target, in milliseconds; 0 jumps at once
+    pub scroll_animation_ms: u32,
+    /// Whether animations are turned off, including animated jumps
+    pub reduce_motion: bool,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            theme: ColorScheme::zen_dark().name,
+            search_case_sensitive: false,
+            search_fuzzy: false,
+            night_light: NightLight::default(),
+            accessible_font: false,
+            browse_siblings: false,
+            folder_filters: false,
+            wrap_code: true,
+            highlight_marks: false,
+            show_status_bar: true,
+            show_scratchpad: false,
+            code_theme: None,
+            code_theme_dark: DEFAULT_DARK_CODE_THEME.to_string(),
+            code_theme_light: DEFAULT_LIGHT_CODE_THEME.to_string(),
+            plantuml_server: String::new(),
+            fonts: FontChoice::default(),
+            image_max_width: 100.0,
+            image_max_height: 600.0,
+            scroll_animation_ms: 250,
+            reduce_motion: false,
+        }
+    }
+}
+
+impl Preferences {
+    /// Loads the preferences from eframe's storage.
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        storage
+            .and_then(|storage| eframe::get_value(storage, STORAGE_KEY))
+            .unwrap_or_default()
+    }
+
+    /// Writes the preferences to eframe's storage.
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, STORAGE_KEY, self);
+    }
+}
@@ -0,0 +1,188 @@
+//! # Preprocessor Module
+//!
+//! This module pipes documents through an external preprocessor command (e.g. `pandoc`
+//! converting a custom dialect to CommonMark, or a templating tool) before rendering.
+//! The command is configured in `preprocessor.json` in the config directory. It runs in
+//! the background so a slow command can't freeze the window; its output is cached per
+//! file and reused until the file's modification time changes.
+
+use crate::hooks;
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::SystemTime;
+
+/// File in the config directory holding the preprocessor configuration.
+pub const PREPROCESSOR_FILE: &str = "preprocessor.json";
+
+/// Preprocessor configuration.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PreprocessorConfig {
+    /// Command line run with the document on stdin; `{file}` and `{dir}` are substituted
+    pub command: Option<String>,
+    /// File extensions the preprocessor applies to (all files when empty)
+    #[serde(default)]
+    pub extensions: Vec<String>,
+}
+
+/// Preprocessed markdown of a file, or the lack of it yet.
+pub enum Preprocessed {
+    /// Output of the command, or why it failed
+    Done(Result<String, String>),
+    /// The command is running in the background; `poll` tells when it has finished
+    Running,
+}
+
+/// Preprocessor command running in the background.
+#[derive(Debug)]
+struct PendingRun {
+    /// File being preprocessed
+    path: PathBuf,
+    /// Modification time of the file when the run started
+    modified: Option<SystemTime>,
+    /// Receives the output once the command has finished
+    receiver: mpsc::Receiver<Result<String, String>>,
+}
+
+/// Runs the configured preprocessor, caching its output per file.
+#[derive(Debug, Default)]
+pub struct Preprocessor {
+    /// Loaded configuration
+    config: PreprocessorConfig,
+    /// Output (or failure) keyed by path, valid for the recorded modification time
+    cache: HashMap<PathBuf, (Option<SystemTime>, Result<String, String>)>,
+    /// Run started by the last `process` call that missed the cache
+    pending: Option<PendingRun>,
+}
+
+impl Preprocessor {
+    /// Loads the preprocessor configuration from the config directory.
+    pub fn load() -> Self {
+        Self {
+            config: storage::load_json(PREPROCESSOR_FILE),
+            cache: HashMap::new(),
+            pending: None,
+        }
+    }
+
+    /// Whether a preprocessor command applies to the given file.
+    pub fn applies_to(&self, path: &Path) -> bool {
+        self.config.command.is_some()
+            && (self.config.extensions.is_empty()
+                || path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| {
+                        self.config
+                            .extensions
+                            .iter()
+                            .any(|wanted| wanted.trim_start_matches('.').eq_ignore_ascii_case(ext))
+                    }))
+    }
+
+    /// Returns the preprocessed markdown for a file if the cached run is still valid,
+    /// and otherwise starts the command in the background.
+    pub fn process(&mut self, path: &Path, source: &str) -> Preprocessed {
+        let Some(command_line) = self.config.command.clone() else {
+            return Preprocessed::Done(Ok(source.to_owned()));
+        };
+        let modified = path.metadata().and_then(|m| m.modified()).ok();
+        if let Some((cached_time, result)) = self.cache.get(path) {
+            if *cached_time == modified {
+                return Preprocessed::Done(result.clone());
+            }
+        }
+        if self
+            .pending
+            .as_ref()
+            .is_some_and(|run| run.path == path && run.modified == modified)
+        {
+            return Preprocessed::Running;
+        }
+
+        // A run for another file or an older version is dropped along with its receiver
+        let (sender, receiver) = mpsc::channel();
+        let run_path = path.to_path_buf();
+        let source = source.to_owned();
+        std::thread::spawn(move || {
+            let _ = sender.send(run(&command_line, &run_path, &source));
+        });
+        self.pending = Some(PendingRun {
+            path: path.to_path_buf(),
+            modified,
+            receiver,
+        });
+        Preprocessed::Running
+    }
+
+    /// Whether a command is running in the background.
+    pub fn is_running(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Checks whether the background run has finished. Returns the file it was for,
+    /// whose output `process` now returns.
+    pub fn poll(&mut self) -> Option<PathBuf> {
+        let result = match self.pending.as_ref()?.receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                Err("Preprocessor stopped without output".to_owned())
+            }
+        };
+        let run = self.pending.take()?;
+        self.cache.insert(run.path.clone(), (run.modified, result));
+        Some(run.path)
+    }
+}
+
+fn run(command_line: &str, path: &Path, source: &str) -> Result<String, String> {
+    let file = path.display().to_string();
+    let dir = path
+        .parent()
+        .map(|d| d.display().to_string())
+        .unwrap_or_default();
+    let args: Vec<String> = hooks::split_command_line(command_line)
+        .into_iter()
+        .map(|arg| arg.replace("{file}", &file).replace("{dir}", &dir))
+        .collect();
+    let (program, args) = args
+        .split_first()
+        .ok_or_else(|| "Preprocessor command is empty".to_owned())?;
+
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if let Some(dir) = path.parent() {
+        command.current_dir(dir);
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Error running preprocessor {program}: {e}"))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let source = source.to_owned();
+        // Feed stdin from another thread so a large document can't deadlock on full pipes
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(source.as_bytes());
+        });
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Error running preprocessor {program}: {e}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Preprocessor {program} failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|_| "Preprocessor output is not valid UTF-8".to_owned())
+}
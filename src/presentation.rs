@@ -0,0 +1,118 @@
+//! # Presentation Module
+//!
+//! This module splits a document into slides for presentation mode. Slides are separated
+//! by top-level `---` rules; documents without rules are split before every level-1 and
+//! level-2 heading instead. Front matter is left out of the slides.
+
+use crate::frontmatter;
+use crate::markdown::MarkdownRenderer;
+use pulldown_cmark::{Event, HeadingLevel, Parser, Tag};
+
+/// A document being presented.
+pub struct Presentation {
+    /// Markdown source of every slide
+    slides: Vec<String>,
+    /// Index of the slide shown
+    current: usize,
+}
+
+impl Presentation {
+    /// Splits a document into slides, starting at the first one.
+    pub fn new(markdown: &str) -> Self {
+        Self {
+            slides: split_slides(frontmatter::strip(markdown)),
+            current: 0,
+        }
+    }
+
+    /// Markdown source of the slide shown.
+    pub fn slide(&self) -> &str {
+        &self.slides[self.current]
+    }
+
+    /// Index of the slide shown.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Number of slides.
+    pub fn len(&self) -> usize {
+        self.slides.len()
+    }
+
+    /// Moves to the next slide, staying on the last one.
+    pub fn next(&mut self) {
+        self.current = (self.current + 1).min(self.slides.len() - 1);
+    }
+
+    /// Moves to the previous slide, staying on the first one.
+    pub fn previous(&mut self) {
+        self.current = self.current.saturating_sub(1);
+    }
+
+    /// Moves to the first slide.
+    pub fn first(&mut self) {
+        self.current = 0;
+    }
+
+    /// Moves to the last slide.
+    pub fn last(&mut self) {
+        self.current = self.slides.len() - 1;
+    }
+}
+
+/// Splits a document at its top-level rules, or before its level-1 and level-2 headings
+/// when it has none. Always returns at least one slide.
+fn split_slides(markdown: &str) -> Vec<String> {
+    let mut rules = Vec::new();
+    let mut headings = Vec::new();
+    let mut depth = 0usize;
+    for (event, range) in
+        Parser::new_ext(markdown, MarkdownRenderer::parser_options()).into_offset_iter()
+    {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) if depth == 0 => {
+                if matches!(level, HeadingLevel::H1 | HeadingLevel::H2) {
+                    headings.push(range.start..range.start);
+                }
+                depth += 1;
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth = depth.saturating_sub(1),
+            Event::Rule if depth == 0 => rules.push(range),
+            _ => {}
+        }
+    }
+
+    let breaks = if rules.is_empty() { headings } else { rules };
+    let mut slides = Vec::new();
+    let mut start = 0;
+    for range in breaks {
+        slides.push(&markdown[start..range.start]);
+        start = range.end;
+    }
+    slides.push(&markdown[start..]);
+
+    let slides: Vec<String> = slides
+        .into_iter()
+        .filter(|slide| !slide.trim().is_empty())
+        .map(str::to_string)
+        .collect();
+    if slides.is_empty() {
+        vec![String::new()]
+    } else {
+        slides
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn front_matter_is_not_a_slide() {
+        let presentation = Presentation::new("---\ntitle: Talk\n---\n# One\n\n---\n\n# Two\n");
+        assert_eq!(presentation.len(), 2);
+        assert_eq!(presentation.slide(), "# One\n\n");
+    }
+}
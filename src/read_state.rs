@@ -0,0 +1,75 @@
+//! # Read State Module
+//!
+//! This module remembers which documents have been opened, and the modification time
+//! they had then, so folder mode can mark files that were never read or that changed
+//! since they were last read (e.g. after a `git pull`).
+
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// File in the config directory holding the read state.
+const READ_STATE_FILE: &str = "read_state.json";
+
+/// Whether a document has been read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadStatus {
+    /// Opened since its last change
+    Read,
+    /// Never opened
+    Unread,
+    /// Changed on disk since it was last opened
+    Updated,
+}
+
+/// Modification times of documents at the time they were last opened.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadTracker {
+    /// Modification time (seconds since the epoch) keyed by document path
+    documents: HashMap<PathBuf, u64>,
+}
+
+impl ReadTracker {
+    /// Loads the read state from the config directory.
+    pub fn load() -> Self {
+        storage::load_json(READ_STATE_FILE)
+    }
+
+    fn save(&self) {
+        if let Err(e) = storage::save_json(READ_STATE_FILE, self) {
+            eprintln!("Error saving read state: {e}");
+        }
+    }
+
+    /// Records that documents were read in their current version.
+    pub fn mark_read<'a>(&mut self, paths: impl IntoIterator<Item = &'a Path>) {
+        for path in paths {
+            if let Some(modified) = modified_secs(path) {
+                self.documents.insert(path.to_path_buf(), modified);
+            }
+        }
+        self.save();
+    }
+
+    /// Status of a document with the given modification time.
+    pub fn status(&self, path: &Path, modified: Option<u64>) -> ReadStatus {
+        match (self.documents.get(path), modified) {
+            (None, _) => ReadStatus::Unread,
+            (Some(read), Some(modified)) if modified > *read => ReadStatus::Updated,
+            _ => ReadStatus::Read,
+        }
+    }
+}
+
+/// Modification time of a file in seconds since the epoch.
+pub fn modified_secs(path: &Path) -> Option<u64> {
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    modified
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|time| time.as_secs())
+}
@@ -0,0 +1,53 @@
+//! # Saved Searches Module
+//!
+//! This module stores named search queries together with their options, either globally
+//! or for the documents of one folder, so frequent searches can be re-run from the search
+//! bar.
+
+use crate::storage;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// File in the config directory holding the saved searches.
+const SAVED_SEARCHES_FILE: &str = "saved_searches.json";
+
+/// A named search query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSearch {
+    /// Name shown in the saved searches menu
+    pub name: String,
+    /// Text searched for
+    pub query: String,
+    /// Whether the search is case sensitive
+    #[serde(default)]
+    pub case_sensitive: bool,
+    /// Whether the search also finds approximate matches
+    #[serde(default)]
+    pub fuzzy: bool,
+    /// Folder the search is limited to; None for searches available everywhere
+    #[serde(default)]
+    pub folder: Option<PathBuf>,
+}
+
+impl SavedSearch {
+    /// Whether the search is offered for a document.
+    pub fn applies_to(&self, file: Option<&Path>) -> bool {
+        match (&self.folder, file) {
+            (None, _) => true,
+            (Some(folder), Some(file)) => file.starts_with(folder),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+/// Loads the saved searches from the config directory.
+pub fn load() -> Vec<SavedSearch> {
+    storage::load_json(SAVED_SEARCHES_FILE)
+}
+
+/// Writes the saved searches to the config directory.
+pub fn save(searches: &[SavedSearch]) {
+    if let Err(e) = storage::save_json(SAVED_SEARCHES_FILE, &searches) {
+        eprintln!("Error saving searches: {e}");
+    }
+}
@@ -0,0 +1,63 @@
+//! # Scratchpad Module
+//!
+//! This module keeps the free-form notes jotted down while reading a document. They are
+//! plain Markdown stored in a hidden sidecar file next to the document
+//! (`.notes.md.scratch.md` for `notes.md`), so they can be opened with any editor too.
+
+use crate::storage;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extension of the sidecar file holding a document's scratchpad.
+const SIDECAR_EXTENSION: &str = "scratch.md";
+
+/// Scratchpad of one document.
+#[derive(Debug, Default)]
+pub struct Scratchpad {
+    /// Sidecar file the notes are stored in
+    path: Option<PathBuf>,
+    /// Text of the notes
+    pub text: String,
+    /// Whether the text changed since it was last written
+    dirty: bool,
+}
+
+impl Scratchpad {
+    /// Loads the scratchpad of a document from its sidecar file, if it has one.
+    pub fn load(document: &Path) -> Self {
+        let path = storage::sidecar_path(document, SIDECAR_EXTENSION);
+        Self {
+            text: fs::read_to_string(&path).unwrap_or_default(),
+            path: Some(path),
+            dirty: false,
+        }
+    }
+
+    /// Marks the text as changed, to be written by the next `save`.
+    pub fn edited(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Writes the text to the sidecar file if it changed, removing the file once the
+    /// text is empty.
+    pub fn save(&mut self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if !self.dirty {
+            return;
+        }
+        let result = if self.text.trim().is_empty() {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            }
+        } else {
+            fs::write(path, &self.text)
+        };
+        match result {
+            Ok(()) => self.dirty = false,
+            Err(e) => eprintln!("Error saving scratchpad: {e}"),
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! # Search Module
+//!
+//! This module finds occurrences of a search query in a line of text, either as exact
+//! substrings or, in fuzzy mode, additionally as runs of words within a small edit
+//! distance of the query, so a search for "recieve" still finds "receive". Searches can
+//! be limited to the headings, the code or the prose of a document.
+
+use std::ops::Range;
+
+/// Part of a document a search is limited to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchScope {
+    /// The whole document
+    #[default]
+    All,
+    /// Heading text
+    Headings,
+    /// Code blocks and inline code
+    Code,
+    /// Everything except headings and code
+    Prose,
+}
+
+impl SearchScope {
+    /// Every scope, in the order offered in the search bar.
+    pub const ALL: [SearchScope; 4] = [Self::All, Self::Headings, Self::Code, Self::Prose];
+
+    /// Name shown in the search bar.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::All => "Everything",
+            Self::Headings => "Headings",
+            Self::Code => "Code",
+            Self::Prose => "Prose",
+        }
+    }
+
+    /// Whether matches in heading text are included.
+    pub fn covers_headings(self) -> bool {
+        matches!(self, Self::All | Self::Headings)
+    }
+
+    /// Whether matches in code are included.
+    pub fn covers_code(self) -> bool {
+        matches!(self, Self::All | Self::Code)
+    }
+
+    /// Whether matches in body text are included.
+    pub fn covers_prose(self) -> bool {
+        matches!(self, Self::All | Self::Prose)
+    }
+}
+
+/// Whether a byte offset lies in one of the sorted, non-overlapping ranges.
+pub fn in_ranges(ranges: &[Range<usize>], offset: usize) -> bool {
+    let index = ranges.partition_point(|range| range.end <= offset);
+    ranges
+        .get(index)
+        .is_some_and(|range| range.contains(&offset))
+}
+
+/// Finds the byte ranges of the query in the text, ordered by position.
+pub fn find_matches(
+    text: &str,
+    query: &str,
+    case_sensitive: bool,
+    fuzzy: bool,
+) -> Vec<Range<usize>> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+    let fold = |s: &str| {
+        if case_sensitive {
+            s.to_owned()
+        } else {
+            s.to_lowercase()
+        }
+    };
+    let haystack = fold(text);
+    let needle = fold(query);
+
+    let mut matches = Vec::new();
+    let mut start = 0;
+    while let Some(pos) = haystack[start..].find(&needle) {
+        let match_start = start + pos;
+        let match_end = match_start + needle.len();
+        matches.push(match_start..match_end);
+        start = match_end;
+    }
+    if !fuzzy {
+        return matches;
+    }
+
+    // Compare the query against every run of as many consecutive words
+    let target: Vec<char> = needle
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .collect();
+    let word_count = needle.split_whitespace().count();
+    let max_distance = allowed_distance(target.len());
+    if word_count == 0 || max_distance == 0 {
+        return matches;
+    }
+    let words = word_spans(text);
+    for window in words.windows(word_count) {
+        let range = window[0].start..window[word_count - 1].end;
+        if matches
+            .iter()
+            .any(|m| m.start < range.end && range.start < m.end)
+        {
+            continue;
+        }
+        let candidate: Vec<char> = window
+            .iter()
+            .map(|word| fold(&text[word.clone()]))
+            .collect::<Vec<_>>()
+            .join(" ")
+            .chars()
+            .collect();
+        if candidate.len().abs_diff(target.len()) <= max_distance
+            && edit_distance(&candidate, &target) <= max_distance
+        {
+            matches.push(range);
+        }
+    }
+    matches.sort_by_key(|m| m.start);
+    matches
+}
+
+/// Whether a match found by `find_matches` is the query itself rather than an
+/// approximate match.
+pub fn is_exact_match(matched: &str, query: &str, case_sensitive: bool) -> bool {
+    if case_sensitive {
+        matched == query
+    } else {
+        matched.to_lowercase() == query.to_lowercase()
+    }
+}
+
+/// Number of typos tolerated for a query of the given length; short queries must match
+/// exactly, since almost any short word is within one edit of them.
+fn allowed_distance(length: usize) -> usize {
+    match length {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+/// Byte ranges of the words (runs of alphanumeric characters and apostrophes) in the text.
+fn word_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices() {
+        let is_word = c.is_alphanumeric() || c == '\'';
+        match (start, is_word) {
+            (None, true) => start = Some(index),
+            (Some(word_start), false) => {
+                spans.push(word_start..index);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(word_start) = start {
+        spans.push(word_start..text.len());
+    }
+    spans
+}
+
+/// Edit distance counting insertions, deletions, substitutions and transpositions of
+/// adjacent characters (optimal string alignment).
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut before_previous: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before_previous[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut before_previous, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Scores how well a query matches a candidate when its characters appear in order,
+/// though not necessarily next to each other (case-insensitive).
+///
+/// Consecutive characters and characters at the start of words score higher, so "rdme"
+/// ranks "README.md" above "docs/random/meeting.md". Returns None if some character of
+/// the query doesn't appear in order.
+pub fn subsequence_score(candidate: &str, query: &str) -> Option<i64> {
+    let mut score = 0;
+    let mut previous: Option<char> = None;
+    let mut previous_matched = false;
+    let mut wanted = query.chars().flat_map(char::to_lowercase).peekable();
+    for c in candidate.chars() {
+        let Some(&next) = wanted.peek() else { break };
+        let matched = c.to_lowercase().eq(std::iter::once(next));
+        if matched {
+            score += 1;
+            if previous_matched {
+                score += 5;
+            }
+            if previous.is_none_or(|p| !p.is_alphanumeric()) {
+                score += 3;
+            }
+            wanted.next();
+        }
+        previous_matched = matched;
+        previous = Some(c);
+    }
+    if wanted.peek().is_some() {
+        return None;
+    }
+    // Prefer shorter candidates among equally good matches
+    Some(score * 100 - candidate.chars().count() as i64)
+}
@@ -0,0 +1,317 @@
+//! # Speech Module
+//!
+//! This module reads documents aloud using the platform's text-to-speech command
+//! (`say` on macOS, System.Speech via PowerShell on Windows, speech-dispatcher or eSpeak
+//! elsewhere), one sentence at a time so the reader can follow along, pause and skip.
+
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use std::process::{Child, Command, Stdio};
+
+/// A sentence of the document, as spoken.
+#[derive(Debug, Clone)]
+pub struct Sentence {
+    /// Plain text of the sentence, without markdown syntax
+    pub text: String,
+    /// Source line (0-based) of the block the sentence belongs to
+    pub line: usize,
+    /// Whether the sentence is a heading, i.e. starts a new section
+    pub is_heading: bool,
+}
+
+/// Splits markdown into speakable sentences, skipping code blocks.
+pub fn sentences(markdown: &str) -> Vec<Sentence> {
+    let line_of = |offset: usize| markdown[..offset].matches('\n').count();
+    let mut sentences = Vec::new();
+    let mut block: Option<(String, usize, bool)> = None;
+    let mut in_code_block = false;
+
+    for (event, range) in Parser::new(markdown).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+            Event::End(TagEnd::CodeBlock) => in_code_block = false,
+            Event::Start(Tag::Heading { .. }) => {
+                block = Some((String::new(), line_of(range.start), true));
+            }
+            Event::Start(Tag::Item) => {
+                // Nested list items interrupt the text of their parent item
+                flush(&mut sentences, block.take());
+                block = Some((String::new(), line_of(range.start), false));
+            }
+            Event::Start(Tag::Paragraph | Tag::TableCell) if block.is_none() => {
+                block = Some((String::new(), line_of(range.start), false));
+            }
+            Event::Text(text) | Event::Code(text) if !in_code_block => {
+                if let Some((buffer, _, _)) = block.as_mut() {
+                    buffer.push_str(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some((buffer, _, _)) = block.as_mut() {
+                    buffer.push(' ');
+                }
+            }
+            Event::End(
+                TagEnd::Heading(_) | TagEnd::Paragraph | TagEnd::TableCell | TagEnd::Item,
+            ) => flush(&mut sentences, block.take()),
+            _ => {}
+        }
+    }
+    sentences
+}
+
+/// Turns the text collected for a block into sentences.
+fn flush(sentences: &mut Vec<Sentence>, block: Option<(String, usize, bool)>) {
+    let Some((buffer, line, is_heading)) = block else {
+        return;
+    };
+    if is_heading {
+        push_sentence(sentences, &buffer, line, true);
+    } else {
+        for text in split_sentences(&buffer) {
+            push_sentence(sentences, text, line, false);
+        }
+    }
+}
+
+fn push_sentence(sentences: &mut Vec<Sentence>, text: &str, line: usize, is_heading: bool) {
+    let text = text.trim();
+    if !text.is_empty() {
+        sentences.push(Sentence {
+            text: text.to_owned(),
+            line,
+            is_heading,
+        });
+    }
+}
+
+/// Splits text after `.`, `!` or `?` followed by whitespace.
+fn split_sentences(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        if matches!(c, '.' | '!' | '?')
+            && chars.peek().is_some_and(|(_, next)| next.is_whitespace())
+        {
+            let end = index + c.len_utf8();
+            parts.push(&text[start..end]);
+            start = end;
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// Plays sentences through the platform speech command.
+#[derive(Debug, Default)]
+pub struct Speaker {
+    /// Sentences of the document being read
+    sentences: Vec<Sentence>,
+    /// Index of the sentence being (or about to be) spoken
+    index: usize,
+    /// Speech process for the current sentence
+    child: Option<Child>,
+    /// Whether reading is active (playing or paused)
+    active: bool,
+    /// Whether reading is paused
+    paused: bool,
+    /// Error from the last attempt to start the speech command
+    error: Option<String>,
+}
+
+impl Speaker {
+    /// Starts reading `sentences` from the first sentence at or after `line`.
+    pub fn start(&mut self, sentences: Vec<Sentence>, line: usize) {
+        self.stop();
+        self.index = sentences
+            .iter()
+            .position(|sentence| sentence.line >= line)
+            .unwrap_or(0);
+        self.sentences = sentences;
+        self.active = !self.sentences.is_empty();
+        self.paused = false;
+        self.error = None;
+        self.speak_current();
+    }
+
+    /// Stops reading.
+    pub fn stop(&mut self) {
+        self.kill();
+        self.active = false;
+        self.paused = false;
+    }
+
+    /// Pauses or resumes reading; resuming restarts the current sentence.
+    pub fn toggle_pause(&mut self) {
+        if !self.active {
+            return;
+        }
+        self.paused = !self.paused;
+        if self.paused {
+            self.kill();
+        } else {
+            self.speak_current();
+        }
+    }
+
+    /// Jumps to the start of the next section.
+    pub fn next_section(&mut self) {
+        if let Some(offset) = self.sentences[(self.index + 1).min(self.sentences.len())..]
+            .iter()
+            .position(|sentence| sentence.is_heading)
+        {
+            self.jump_to(self.index + 1 + offset);
+        }
+    }
+
+    /// Jumps to the start of the current section, or the previous one if already there.
+    pub fn previous_section(&mut self) {
+        let target = self.sentences[..self.index]
+            .iter()
+            .rposition(|sentence| sentence.is_heading)
+            .unwrap_or(0);
+        self.jump_to(target);
+    }
+
+    /// Advances to the next sentence once the current one has been spoken.
+    /// Call once per frame; returns whether reading is still active.
+    pub fn tick(&mut self) -> bool {
+        if !self.active || self.paused {
+            return self.active;
+        }
+        let finished = match self.child.as_mut() {
+            Some(child) => !matches!(child.try_wait(), Ok(None)),
+            None => true,
+        };
+        if finished {
+            self.child = None;
+            if self.index + 1 < self.sentences.len() {
+                self.index += 1;
+                self.speak_current();
+            } else {
+                self.active = false;
+            }
+        }
+        self.active
+    }
+
+    /// Whether reading is active (playing or paused).
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Whether reading is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// The sentence being read, if any.
+    pub fn current(&self) -> Option<&Sentence> {
+        self.active
+            .then(|| self.sentences.get(self.index))
+            .flatten()
+    }
+
+    /// Error from the last attempt to start the speech command.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    fn jump_to(&mut self, index: usize) {
+        if !self.active || index >= self.sentences.len() {
+            return;
+        }
+        self.kill();
+        self.index = index;
+        if !self.paused {
+            self.speak_current();
+        }
+    }
+
+    fn kill(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+
+    fn speak_current(&mut self) {
+        let Some(sentence) = self.sentences.get(self.index) else {
+            return;
+        };
+        match spawn_speech(&sentence.text) {
+            Ok(child) => self.child = Some(child),
+            Err(e) => {
+                self.error = Some(e);
+                self.active = false;
+            }
+        }
+    }
+}
+
+impl Drop for Speaker {
+    fn drop(&mut self) {
+        self.kill();
+    }
+}
+
+/// Speech commands to try, in order, as (program, arguments). The sentence is passed
+/// as the last argument after `--`, so a sentence starting with `-` isn't taken for an
+/// option, or on stdin when [`PIPE_TEXT`] is set.
+#[cfg(target_os = "macos")]
+const SPEECH_COMMANDS: &[(&str, &[&str])] = &[("say", &[])];
+
+#[cfg(windows)]
+const SPEECH_COMMANDS: &[(&str, &[&str])] = &[(
+    "powershell",
+    &[
+        "-NoProfile",
+        "-Command",
+        "Add-Type -AssemblyName System.Speech; \
+         (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak([Console]::In.ReadToEnd())",
+    ],
+)];
+
+#[cfg(not(any(target_os = "macos", windows)))]
+const SPEECH_COMMANDS: &[(&str, &[&str])] = &[
+    ("spd-say", &["--wait"]),
+    ("espeak-ng", &[]),
+    ("espeak", &[]),
+];
+
+/// Whether the speech command reads the text from stdin instead of its arguments. `say`
+/// reads stdin when given no text.
+const PIPE_TEXT: bool = cfg!(any(windows, target_os = "macos"));
+
+fn spawn_speech(text: &str) -> Result<Child, String> {
+    use std::io::Write;
+
+    for (program, args) in SPEECH_COMMANDS {
+        let mut command = Command::new(program);
+        command.args(*args);
+        if PIPE_TEXT {
+            command.stdin(Stdio::piped());
+        } else {
+            command.arg("--").arg(text).stdin(Stdio::null());
+        }
+        let result = command.stdout(Stdio::null()).stderr(Stdio::null()).spawn();
+        match result {
+            Ok(mut child) => {
+                if let Some(mut stdin) = child.stdin.take() {
+                    let _ = stdin.write_all(text.as_bytes());
+                }
+                return Ok(child);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Error starting {program}: {e}")),
+        }
+    }
+    let names: Vec<&str> = SPEECH_COMMANDS
+        .iter()
+        .map(|(program, _)| *program)
+        .collect();
+    Err(format!(
+        "No text-to-speech command found (tried {})",
+        names.join(", ")
+    ))
+}
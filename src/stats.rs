@@ -0,0 +1,209 @@
+//! # Reading Statistics Module
+//!
+//! This module tracks how long each document is read — counting only time while the
+//! window is focused — and optionally keeps the totals across sessions. It also counts
+//! the words and characters of a document to estimate how long it takes to read.
+
+use crate::markdown::MarkdownRenderer;
+use crate::storage;
+use pulldown_cmark::{Event, Parser};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// File in the config directory holding the persisted totals.
+const STATS_FILE: &str = "reading_stats.json";
+
+/// Average silent reading speed used for reading time estimates.
+const WORDS_PER_MINUTE: usize = 230;
+
+/// Gaps between frames longer than this are treated as the reader being away.
+const MAX_FRAME_GAP: Duration = Duration::from_secs(5 * 60);
+
+/// How often accumulated totals are written to disk while reading.
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Accumulated reading statistics for a single document.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DocumentStats {
+    /// Total reading time in seconds across all sessions
+    pub total_seconds: f64,
+    /// Number of times the document was opened
+    pub sessions: u32,
+    /// Unix timestamp (seconds) of the last time the document was opened
+    pub last_read: u64,
+}
+
+/// Reading time tracker for all documents.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReadingStats {
+    /// Whether totals are saved to disk between sessions
+    pub persist: bool,
+    /// Totals keyed by document path
+    pub documents: HashMap<PathBuf, DocumentStats>,
+    /// Document currently being read
+    #[serde(skip)]
+    current: Option<PathBuf>,
+    /// Reading time of the current document since it was opened
+    #[serde(skip)]
+    session_seconds: f64,
+    /// Time of the previous tick
+    #[serde(skip)]
+    last_tick: Option<Instant>,
+    /// Whether the window was focused at the previous tick
+    #[serde(skip)]
+    was_focused: bool,
+    /// Time of the last save to disk
+    #[serde(skip)]
+    last_save: Option<Instant>,
+}
+
+impl Default for ReadingStats {
+    fn default() -> Self {
+        Self {
+            persist: true,
+            documents: HashMap::new(),
+            current: None,
+            session_seconds: 0.0,
+            last_tick: None,
+            was_focused: false,
+            last_save: None,
+        }
+    }
+}
+
+impl ReadingStats {
+    /// Loads persisted totals from the config directory.
+    pub fn load() -> Self {
+        storage::load_json(STATS_FILE)
+    }
+
+    /// Writes the totals to disk (only the opt-out flag when persistence is disabled).
+    pub fn save(&mut self) {
+        self.last_save = Some(Instant::now());
+        let result = if self.persist {
+            storage::save_json(STATS_FILE, self)
+        } else {
+            storage::save_json(
+                STATS_FILE,
+                &Self {
+                    persist: false,
+                    ..Default::default()
+                },
+            )
+        };
+        if let Err(e) = result {
+            eprintln!("Error saving reading statistics: {e}");
+        }
+    }
+
+    /// Starts a reading session for a newly opened document.
+    pub fn start_session(&mut self, path: PathBuf) {
+        let stats = self.documents.entry(path.clone()).or_default();
+        stats.sessions += 1;
+        stats.last_read = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        self.current = Some(path);
+        self.session_seconds = 0.0;
+    }
+
+    /// Advances the clock, crediting the time since the last tick to the current
+    /// document if the window was focused throughout. Call once per frame.
+    pub fn tick(&mut self, focused: bool) {
+        let now = Instant::now();
+        if let (Some(last_tick), Some(path)) = (self.last_tick, &self.current) {
+            let elapsed = now - last_tick;
+            if self.was_focused && elapsed <= MAX_FRAME_GAP {
+                let seconds = elapsed.as_secs_f64();
+                self.session_seconds += seconds;
+                self.documents
+                    .entry(path.clone())
+                    .or_default()
+                    .total_seconds += seconds;
+            }
+        }
+        self.last_tick = Some(now);
+        self.was_focused = focused;
+
+        if self.persist
+            && self.current.is_some()
+            && self
+                .last_save
+                .is_none_or(|last_save| now - last_save >= SAVE_INTERVAL)
+        {
+            self.save();
+        }
+    }
+
+    /// Reading time of the current document in this session, in seconds.
+    pub fn session_seconds(&self) -> f64 {
+        self.session_seconds
+    }
+
+    /// Totals for the current document, if one is open.
+    pub fn current(&self) -> Option<&DocumentStats> {
+        self.current
+            .as_ref()
+            .and_then(|path| self.documents.get(path))
+    }
+
+    /// Documents sorted by total reading time, longest first.
+    pub fn most_read(&self) -> Vec<(&PathBuf, &DocumentStats)> {
+        let mut documents: Vec<_> = self.documents.iter().collect();
+        documents.sort_by(|a, b| b.1.total_seconds.total_cmp(&a.1.total_seconds));
+        documents
+    }
+
+    /// Forgets all totals, keeping the current session running.
+    pub fn reset(&mut self) {
+        self.documents.clear();
+        self.session_seconds = 0.0;
+        if let Some(path) = self.current.clone() {
+            self.start_session(path);
+        }
+    }
+}
+
+/// Size of a document's text, ignoring markdown syntax.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextStats {
+    /// Number of words
+    pub words: usize,
+    /// Number of characters, including spaces
+    pub characters: usize,
+}
+
+impl TextStats {
+    /// Counts the words and characters of the text and inline code of a document.
+    pub fn of(markdown: &str) -> Self {
+        let mut stats = Self::default();
+        for event in Parser::new_ext(markdown, MarkdownRenderer::parser_options()) {
+            if let Event::Text(text) | Event::Code(text) = event {
+                stats.words += text.split_whitespace().count();
+                stats.characters += text.chars().count();
+            }
+        }
+        stats
+    }
+
+    /// Estimated reading time in whole minutes, rounded up.
+    pub fn reading_minutes(&self) -> usize {
+        self.words.div_ceil(WORDS_PER_MINUTE)
+    }
+}
+
+/// Formats a duration in seconds as a compact human-readable string (e.g. `1h 05m`).
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}h {minutes:02}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
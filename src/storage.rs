@@ -0,0 +1,56 @@
+//! # Storage Module
+//!
+//! This module locates mdzen's per-user configuration directory and reads/writes the
+//! small JSON files used to remember state (statistics, preferences, ...) across sessions,
+//! and names the sidecar files kept next to documents.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Returns the mdzen configuration directory.
+///
+/// Uses `$XDG_CONFIG_HOME/mdzen` when set, otherwise `~/.config/mdzen`
+/// (`%APPDATA%\mdzen` on Windows). Returns None if no home directory is known.
+pub fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        return Some(PathBuf::from(dir).join("mdzen"));
+    }
+    if cfg!(windows) {
+        if let Some(dir) = std::env::var_os("APPDATA") {
+            return Some(PathBuf::from(dir).join("mdzen"));
+        }
+    }
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join("mdzen"))
+}
+
+/// Loads a JSON state file from the config directory.
+///
+/// Missing or unreadable files yield the default value so a corrupt file never
+/// prevents mdzen from starting.
+pub fn load_json<T: DeserializeOwned + Default>(name: &str) -> T {
+    config_dir()
+        .and_then(|dir| fs::read_to_string(dir.join(name)).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// Writes a JSON state file to the config directory, creating the directory if needed.
+pub fn save_json<T: Serialize>(name: &str, value: &T) -> anyhow::Result<()> {
+    let dir = config_dir().ok_or_else(|| anyhow::anyhow!("No config directory available"))?;
+    fs::create_dir_all(&dir)?;
+    let content = serde_json::to_string_pretty(value)?;
+    // Write to a temporary file first so a crash never leaves a truncated file behind
+    let temp_path = dir.join(format!("{name}.tmp"));
+    fs::write(&temp_path, content)?;
+    fs::rename(temp_path, dir.join(name))?;
+    Ok(())
+}
+
+/// Path of a hidden file next to a document holding data about it, e.g.
+/// `.notes.md.annotations.json` for `notes.md`.
+pub fn sidecar_path(document: &Path, extension: &str) -> PathBuf {
+    let name = document.file_name().unwrap_or_default().to_string_lossy();
+    document.with_file_name(format!(".{name}.{extension}"))
+}
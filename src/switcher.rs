@@ -0,0 +1,342 @@
+//! # Switcher Module
+//!
+//! This module implements the keyboard-driven switcher overlays: the quick file switcher
+//! of folder mode, which fuzzy-matches the paths and titles of all markdown files in the
+//! opened folder and opens the chosen one, and the heading switcher, which fuzzy-matches
+//! the headings of the current document and jumps to the chosen one.
+
+use crate::app::TocHeader;
+use crate::search::subsequence_score;
+use crate::vault::Vault;
+use egui::{Context, Key, Modifiers};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Number of lines searched for a document's title.
+const TITLE_SEARCH_LINES: usize = 40;
+
+/// Most matches listed at once.
+const MAX_LISTED: usize = 50;
+
+/// A file offered by the switcher.
+struct Entry {
+    /// Absolute path of the file
+    path: PathBuf,
+    /// Path relative to the opened folder
+    relative: String,
+    /// Text of the first level-1 heading, if any
+    title: Option<String>,
+}
+
+/// What the user did in a switcher this frame.
+pub enum SwitcherAction<T> {
+    /// Still choosing
+    None,
+    /// Chose an entry
+    Open(T),
+    /// Dismissed the switcher
+    Close,
+}
+
+impl<T> SwitcherAction<T> {
+    fn map<U>(self, f: impl FnOnce(T) -> U) -> SwitcherAction<U> {
+        match self {
+            Self::None => SwitcherAction::None,
+            Self::Open(entry) => SwitcherAction::Open(f(entry)),
+            Self::Close => SwitcherAction::Close,
+        }
+    }
+}
+
+/// State of an open file switcher.
+pub struct FileSwitcher {
+    /// Files of the opened folder
+    entries: Vec<Entry>,
+    /// Query, matches and selection
+    picker: Picker,
+}
+
+impl FileSwitcher {
+    /// Creates a switcher over the files of a folder.
+    pub fn new(folder: &Vault) -> Self {
+        let entries = folder
+            .notes()
+            .iter()
+            .map(|path| Entry {
+                relative: path
+                    .strip_prefix(folder.root())
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .into_owned(),
+                title: read_title(path),
+                path: path.clone(),
+            })
+            .collect();
+        let mut switcher = Self {
+            entries,
+            picker: Picker::default(),
+        };
+        switcher.update_matches();
+        switcher
+    }
+
+    fn update_matches(&mut self) {
+        let query: String = self.picker.query.split_whitespace().collect();
+        let mut scored: Vec<(i64, usize)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                let file_name = entry.relative.rsplit(['/', '\\']).next().unwrap_or("");
+                // Matches within the file name or title count more than matches that are
+                // spread over the directories
+                let score = [
+                    subsequence_score(file_name, &query).map(|score| score + 1000),
+                    entry
+                        .title
+                        .as_deref()
+                        .and_then(|title| subsequence_score(title, &query))
+                        .map(|score| score + 1000),
+                    subsequence_score(&entry.relative, &query),
+                ]
+                .into_iter()
+                .flatten()
+                .max()?;
+                Some((score, index))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        self.picker.set_matches(scored);
+    }
+
+    /// Draws the switcher.
+    pub fn show(&mut self, ctx: &Context) -> SwitcherAction<PathBuf> {
+        let entries = &self.entries;
+        let action = self.picker.show(
+            ctx,
+            "Switch File",
+            "Find a file by name, path or title",
+            "No matching files",
+            |ui, index, selected| {
+                let entry = &entries[index];
+                let label = entry.title.as_deref().unwrap_or(&entry.relative);
+                let response = ui
+                    .selectable_label(selected, label)
+                    .on_hover_text(entry.path.display().to_string());
+                if entry.title.is_some() {
+                    ui.weak(&entry.relative);
+                }
+                response
+            },
+        );
+        if self.picker.query_changed {
+            self.update_matches();
+        }
+        action.map(|index| self.entries[index].path.clone())
+    }
+}
+
+/// A heading offered by the heading switcher.
+struct HeadingEntry {
+    /// Index of the heading in the table of contents
+    index: usize,
+    /// Text of the heading
+    title: String,
+    /// Titles of the enclosing headings, outermost first
+    parents: String,
+}
+
+/// State of an open heading switcher, which jumps to a heading of the current document.
+pub struct HeadingSwitcher {
+    /// Headings of the document
+    entries: Vec<HeadingEntry>,
+    /// Query, matches and selection
+    picker: Picker,
+}
+
+impl HeadingSwitcher {
+    /// Creates a switcher over the headings of a table of contents.
+    pub fn new(headers: &[TocHeader]) -> Self {
+        // Titles and levels of the headings enclosing the current one
+        let mut enclosing: Vec<(u8, &str)> = Vec::new();
+        let entries = headers
+            .iter()
+            .enumerate()
+            .map(|(index, header)| {
+                enclosing.retain(|&(level, _)| level < header.level);
+                let parents = enclosing
+                    .iter()
+                    .map(|(_, title)| *title)
+                    .collect::<Vec<_>>()
+                    .join(" › ");
+                enclosing.push((header.level, &header.title));
+                HeadingEntry {
+                    index,
+                    title: header.title.clone(),
+                    parents,
+                }
+            })
+            .collect();
+        let mut switcher = Self {
+            entries,
+            picker: Picker::default(),
+        };
+        switcher.update_matches();
+        switcher
+    }
+
+    fn update_matches(&mut self) {
+        let query: String = self.picker.query.split_whitespace().collect();
+        let mut scored: Vec<(i64, usize)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                // An empty query keeps the document order
+                let score = if query.is_empty() {
+                    0
+                } else {
+                    subsequence_score(&entry.title, &query)?
+                };
+                Some((score, index))
+            })
+            .collect();
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        self.picker.set_matches(scored);
+    }
+
+    /// Draws the switcher. Opening an entry returns the index of its heading in the
+    /// table of contents.
+    pub fn show(&mut self, ctx: &Context) -> SwitcherAction<usize> {
+        let entries = &self.entries;
+        let action = self.picker.show(
+            ctx,
+            "Go to Heading",
+            "Find a heading of this document",
+            "No matching headings",
+            |ui, index, selected| {
+                let entry = &entries[index];
+                let response = ui.selectable_label(selected, &entry.title);
+                if !entry.parents.is_empty() {
+                    ui.weak(&entry.parents);
+                }
+                response
+            },
+        );
+        if self.picker.query_changed {
+            self.update_matches();
+        }
+        action.map(|index| self.entries[index].index)
+    }
+}
+
+/// The query field and match list shared by the switchers.
+#[derive(Default)]
+struct Picker {
+    /// Text typed by the user
+    query: String,
+    /// Whether the query was edited this frame
+    query_changed: bool,
+    /// Indices of the matching entries, best match first
+    matches: Vec<usize>,
+    /// Index into `matches` of the highlighted entry
+    selected: usize,
+}
+
+impl Picker {
+    /// Replaces the matches with scored entry indices, sorted best first.
+    fn set_matches(&mut self, scored: Vec<(i64, usize)>) {
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = 0;
+    }
+
+    /// Draws the query field and the matches, each drawn by `row`. Returns the index of
+    /// the chosen entry.
+    fn show(
+        &mut self,
+        ctx: &Context,
+        title: &str,
+        hint: &str,
+        no_matches: &str,
+        mut row: impl FnMut(&mut egui::Ui, usize, bool) -> egui::Response,
+    ) -> SwitcherAction<usize> {
+        let mut action = SwitcherAction::None;
+        self.query_changed = false;
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .title_bar(false)
+            .anchor(egui::Align2::CENTER_TOP, [0.0, 60.0])
+            .fixed_size([480.0, 0.0])
+            .show(ctx, |ui| {
+                let (down, up) = ui.input_mut(|i| {
+                    (
+                        i.consume_key(Modifiers::NONE, Key::ArrowDown),
+                        i.consume_key(Modifiers::NONE, Key::ArrowUp),
+                    )
+                });
+                let moved = down || up;
+                if down && self.selected + 1 < self.matches.len().min(MAX_LISTED) {
+                    self.selected += 1;
+                }
+                if up {
+                    self.selected = self.selected.saturating_sub(1);
+                }
+
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.query)
+                        .hint_text(hint)
+                        .desired_width(f32::INFINITY),
+                );
+                response.request_focus();
+                self.query_changed = response.changed();
+                if ui.input(|i| i.key_pressed(Key::Escape)) {
+                    action = SwitcherAction::Close;
+                }
+                if ui.input(|i| i.key_pressed(Key::Enter)) {
+                    if let Some(&index) = self.matches.get(self.selected) {
+                        action = SwitcherAction::Open(index);
+                    }
+                }
+
+                ui.separator();
+                if self.matches.is_empty() {
+                    ui.weak(no_matches);
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .max_height(320.0)
+                    .show(ui, |ui| {
+                        for (position, &index) in self.matches.iter().take(MAX_LISTED).enumerate() {
+                            let selected = position == self.selected;
+                            let response = row(ui, index, selected);
+                            if selected && moved {
+                                response.scroll_to_me(None);
+                            }
+                            if response.clicked() {
+                                action = SwitcherAction::Open(index);
+                            }
+                        }
+                    });
+                if self.matches.len() > MAX_LISTED {
+                    ui.weak(format!("{} more…", self.matches.len() - MAX_LISTED));
+                }
+            });
+        action
+    }
+}
+
+/// Reads the text of the first level-1 heading near the top of a file.
+fn read_title(path: &Path) -> Option<String> {
+    let file = File::open(path).ok()?;
+    BufReader::new(file)
+        .lines()
+        .take(TITLE_SEARCH_LINES)
+        .map_while(Result::ok)
+        .find_map(|line| {
+            line.strip_prefix("# ")
+                .map(|title| title.trim().to_string())
+                .filter(|title| !title.is_empty())
+        })
+}
@@ -0,0 +1,263 @@
+//! # Table Module
+//!
+//! This module holds the interaction state of rendered markdown tables — the focused
+//! cell for keyboard navigation, the selected rows and the column rows are sorted by —
+//! and converts table contents to
+//! tab-separated text for pasting into spreadsheets or CSV files for exporting, and back
+//! from CSV/TSV files opened as documents.
+
+use egui::{InputState, Key, Modifiers};
+use std::collections::BTreeSet;
+use std::fs;
+
+/// Keyboard focus and row selection of a table.
+#[derive(Debug, Clone, Default)]
+pub struct TableState {
+    /// Focused body cell as (row, column)
+    pub focus: Option<(usize, usize)>,
+    /// Selected body rows
+    pub selected: BTreeSet<usize>,
+    /// Row a shift-extended selection starts from
+    anchor: Option<usize>,
+    /// Whether the focused cell should be scrolled into view
+    pub scroll_to_focus: bool,
+    /// Column the rows are sorted by; None keeps the document order
+    pub sort: Option<SortOrder>,
+}
+
+/// Column a table's rows are sorted by, and in which direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SortOrder {
+    /// Index of the column
+    pub column: usize,
+    /// Whether the largest values come first
+    pub descending: bool,
+}
+
+/// What the keyboard asked the table to do, besides moving the focus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableCommand {
+    /// Copy the selected rows (or the whole table)
+    Copy,
+    /// Give up keyboard focus
+    Leave,
+}
+
+impl TableState {
+    /// Focuses a clicked cell; Ctrl toggles the row's selection and Shift selects the
+    /// range of rows from the previous click.
+    pub fn click(&mut self, row: usize, col: usize, modifiers: Modifiers) {
+        if modifiers.shift {
+            let anchor = self.anchor.unwrap_or(row);
+            self.selected = (anchor.min(row)..=anchor.max(row)).collect();
+        } else if modifiers.command {
+            if !self.selected.remove(&row) {
+                self.selected.insert(row);
+            }
+            self.anchor = Some(row);
+        } else {
+            self.selected.clear();
+            self.anchor = Some(row);
+        }
+        self.focus = Some((row, col));
+    }
+
+    /// Sorts by a clicked header's column: ascending first, then descending, then back to
+    /// the document order. The focus and selection are cleared, as rows move.
+    pub fn toggle_sort(&mut self, column: usize) {
+        self.sort = match self.sort {
+            Some(order) if order.column == column && !order.descending => Some(SortOrder {
+                column,
+                descending: true,
+            }),
+            Some(order) if order.column == column => None,
+            _ => Some(SortOrder {
+                column,
+                descending: false,
+            }),
+        };
+        self.focus = None;
+        self.selected.clear();
+        self.anchor = None;
+    }
+
+    /// Handles keyboard input while the table has focus.
+    ///
+    /// Arrow keys move the focused cell (Shift+Up/Down extends the row selection),
+    /// Space toggles the focused row, Escape clears the selection and then leaves the
+    /// table, and Ctrl+C copies.
+    pub fn handle_input(
+        &mut self,
+        input: &InputState,
+        rows: usize,
+        columns: usize,
+    ) -> Option<TableCommand> {
+        if rows == 0 || columns == 0 {
+            return None;
+        }
+        let (row, col) = self.focus.unwrap_or((0, 0));
+        let moved = if input.key_pressed(Key::ArrowDown) {
+            Some(((row + 1).min(rows - 1), col))
+        } else if input.key_pressed(Key::ArrowUp) {
+            Some((row.saturating_sub(1), col))
+        } else if input.key_pressed(Key::ArrowRight) {
+            Some((row, (col + 1).min(columns - 1)))
+        } else if input.key_pressed(Key::ArrowLeft) {
+            Some((row, col.saturating_sub(1)))
+        } else if input.key_pressed(Key::Home) {
+            Some((0, col))
+        } else if input.key_pressed(Key::End) {
+            Some((rows - 1, col))
+        } else {
+            None
+        };
+        if let Some((new_row, new_col)) = moved {
+            if input.modifiers.shift && new_row != row {
+                let anchor = *self.anchor.get_or_insert(row);
+                self.selected = (anchor.min(new_row)..=anchor.max(new_row)).collect();
+            } else if !input.modifiers.shift {
+                self.anchor = Some(new_row);
+            }
+            self.focus = Some((new_row, new_col));
+            self.scroll_to_focus = true;
+        }
+
+        if input.key_pressed(Key::Space) {
+            if !self.selected.remove(&row) {
+                self.selected.insert(row);
+            }
+            self.focus = Some((row, col));
+        }
+        if input.key_pressed(Key::Escape) {
+            if self.selected.is_empty() {
+                return Some(TableCommand::Leave);
+            }
+            self.selected.clear();
+        }
+        let copy = input
+            .events
+            .iter()
+            .any(|event| matches!(event, egui::Event::Copy));
+        copy.then_some(TableCommand::Copy)
+    }
+}
+
+/// Sorts rows by a column. Numbers compare as numbers and come before text, which
+/// compares case-insensitively; rows with equal cells keep their order.
+pub fn sorted_rows(rows: &[Vec<String>], order: SortOrder) -> Vec<Vec<String>> {
+    let mut sorted = rows.to_vec();
+    sorted.sort_by(|a, b| {
+        let a = a.get(order.column).map_or("", |cell| cell.trim());
+        let b = b.get(order.column).map_or("", |cell| cell.trim());
+        let ordering = match (a.parse::<f64>(), b.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.total_cmp(&b),
+            (Ok(_), Err(_)) => std::cmp::Ordering::Less,
+            (Err(_), Ok(_)) => std::cmp::Ordering::Greater,
+            (Err(_), Err(_)) => a.to_lowercase().cmp(&b.to_lowercase()),
+        };
+        if order.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    sorted
+}
+
+/// Formats a header and rows as tab-separated values, one line per row.
+///
+/// Tabs and line breaks inside cells are replaced with spaces so every row stays on
+/// one line.
+pub fn to_tsv<'a>(headers: &[String], rows: impl IntoIterator<Item = &'a Vec<String>>) -> String {
+    let clean = |cell: &String| cell.trim().replace(['\t', '\n', '\r'], " ");
+    let mut lines = Vec::new();
+    if !headers.is_empty() {
+        lines.push(headers.iter().map(clean).collect::<Vec<_>>().join("\t"));
+    }
+    for row in rows {
+        lines.push(row.iter().map(clean).collect::<Vec<_>>().join("\t"));
+    }
+    let mut tsv = lines.join("\n");
+    tsv.push('\n');
+    tsv
+}
+
+/// Formats a header and rows as comma-separated values (RFC 4180).
+///
+/// Cells containing commas, quotes or line breaks are quoted, with quotes doubled.
+pub fn to_csv<'a>(
+    headers: &'a [String],
+    rows: impl IntoIterator<Item = &'a Vec<String>>,
+) -> String {
+    let escape = |cell: &String| {
+        let cell = cell.trim();
+        if cell.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", cell.replace('"', "\"\""))
+        } else {
+            cell.to_owned()
+        }
+    };
+    let mut csv = String::new();
+    for row in std::iter::once(headers)
+        .filter(|headers| !headers.is_empty())
+        .chain(rows.into_iter().map(Vec::as_slice))
+    {
+        csv.push_str(&row.iter().map(escape).collect::<Vec<_>>().join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+/// Splits delimiter-separated values (CSV per RFC 4180, or TSV) into rows of cells.
+///
+/// Quoted cells may contain the delimiter, doubled quotes and line breaks. Blank lines are
+/// skipped.
+pub fn parse_delimited(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.next_if_eq(&'"').is_some() {
+                    cell.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if cell.is_empty() => quoted = true,
+            c if quoted => cell.push(c),
+            c if c == delimiter => row.push(std::mem::take(&mut cell)),
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut cell));
+                if row.iter().any(|cell| !cell.is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                }
+                row.clear();
+            }
+            c => cell.push(c),
+        }
+    }
+    row.push(cell);
+    if row.iter().any(|cell| !cell.is_empty()) {
+        rows.push(row);
+    }
+    rows
+}
+
+/// Asks for a file name and writes the table to it as CSV.
+pub fn export_csv(headers: &[String], rows: &[Vec<String>]) {
+    let Some(path) = rfd::FileDialog::new()
+        .add_filter("CSV", &["csv"])
+        .set_file_name("table.csv")
+        .save_file()
+    else {
+        return;
+    };
+    if let Err(e) = fs::write(&path, to_csv(headers, rows)) {
+        eprintln!("Error exporting table: {e}");
+    }
+}
@@ -0,0 +1,304 @@
+//! # Theme Module
+//!
+//! This module defines mdzen's color schemes, including user-defined ones loaded from
+//! `themes.toml` in the config directory, and the night-light filter that shifts any
+//! color toward warmer, lower-blue tones in the evening without changing the scheme itself.
+
+use crate::storage;
+use egui::{Color32, Visuals};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use toml_edit::{DocumentMut, Item, Table};
+
+/// File in the config directory holding user-defined color schemes.
+const THEMES_FILE: &str = "themes.toml";
+
+/// A named set of colors for the interface and the rendered document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorScheme {
+    /// Name shown in the Theme menu
+    pub name: String,
+    /// Window and panel background
+    pub background: Color32,
+    /// Body text
+    pub text: Color32,
+    /// Background of code blocks and text fields
+    pub code_background: Color32,
+    /// Links
+    pub link: Color32,
+    /// Heading colors from level 1 down; levels without an entry use the text color
+    pub headings: Vec<Color32>,
+}
+
+impl ColorScheme {
+    /// The default dark scheme, tuned for long reading sessions.
+    pub fn zen_dark() -> Self {
+        Self {
+            name: "Zen Dark".to_string(),
+            background: Color32::from_rgb(40, 44, 52),
+            text: Color32::from_rgb(171, 178, 191),
+            code_background: Color32::from_rgb(33, 37, 43),
+            link: Color32::from_rgb(90, 170, 255),
+            headings: Vec::new(),
+        }
+    }
+
+    /// A light scheme with warm paper tones.
+    pub fn zen_light() -> Self {
+        Self {
+            name: "Zen Light".to_string(),
+            background: Color32::from_rgb(250, 248, 242),
+            text: Color32::from_rgb(56, 58, 66),
+            code_background: Color32::from_rgb(238, 235, 226),
+            link: Color32::from_rgb(64, 120, 242),
+            headings: Vec::new(),
+        }
+    }
+
+    /// Whether the background is dark, which selects egui's dark widget styling.
+    pub fn is_dark(&self) -> bool {
+        let [r, g, b, _] = self.background.to_array();
+        (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000 < 128
+    }
+
+    /// Builds egui visuals from the scheme.
+    pub fn visuals(&self) -> Visuals {
+        let mut visuals = if self.is_dark() {
+            Visuals::dark()
+        } else {
+            Visuals::light()
+        };
+        visuals.window_fill = self.background;
+        visuals.panel_fill = self.background;
+        visuals.extreme_bg_color = self.code_background;
+        visuals.code_bg_color = self.code_background;
+        visuals.override_text_color = Some(self.text);
+        visuals.hyperlink_color = self.link;
+        visuals
+    }
+
+    /// Reads a `[[theme]]` table; colors missing from it are taken from `base`.
+    fn from_table(table: &Table, base: &ColorScheme) -> Result<Self, String> {
+        let name = table
+            .get("name")
+            .and_then(Item::as_str)
+            .ok_or("missing name")?
+            .to_string();
+        let color = |key: &str, fallback: Color32| match table.get(key) {
+            None => Ok(fallback),
+            Some(item) => item
+                .as_str()
+                .and_then(parse_color)
+                .ok_or(format!("invalid color for {key}")),
+        };
+        let headings = match table.get("headings") {
+            None => Vec::new(),
+            Some(item) => item
+                .as_array()
+                .ok_or("headings must be a list of colors")?
+                .iter()
+                .map(|value| value.as_str().and_then(parse_color))
+                .collect::<Option<_>>()
+                .ok_or("invalid color in headings")?,
+        };
+        Ok(Self {
+            background: color("background", base.background)?,
+            text: color("text", base.text)?,
+            code_background: color("code_background", base.code_background)?,
+            link: color("link", base.link)?,
+            headings,
+            name,
+        })
+    }
+}
+
+/// The built-in schemes followed by the user-defined ones from `themes.toml`.
+///
+/// The file holds one `[[theme]]` table per scheme, for example:
+///
+/// ```toml
+/// [[theme]]
+/// name = "Solarized"
+/// background = "#002b36"
+/// text = "#839496"
+/// code_background = "#073642"
+/// link = "#268bd2"
+/// headings = ["#b58900", "#cb4b16", "#d33682"]
+/// ```
+pub fn load_schemes() -> Vec<ColorScheme> {
+    let mut schemes = vec![ColorScheme::zen_dark(), ColorScheme::zen_light()];
+    let Some(content) =
+        storage::config_dir().and_then(|dir| fs::read_to_string(dir.join(THEMES_FILE)).ok())
+    else {
+        return schemes;
+    };
+    let document = match content.parse::<DocumentMut>() {
+        Ok(document) => document,
+        Err(e) => {
+            eprintln!("Error parsing {THEMES_FILE}: {e}");
+            return schemes;
+        }
+    };
+    let Some(tables) = document.get("theme").and_then(Item::as_array_of_tables) else {
+        return schemes;
+    };
+    let base = ColorScheme::zen_dark();
+    for table in tables.iter() {
+        match ColorScheme::from_table(table, &base) {
+            // A user scheme with a built-in name replaces the built-in
+            Ok(scheme) => match schemes.iter_mut().find(|s| s.name == scheme.name) {
+                Some(existing) => *existing = scheme,
+                None => schemes.push(scheme),
+            },
+            Err(e) => eprintln!("Error loading theme from {THEMES_FILE}: {e}"),
+        }
+    }
+    schemes
+}
+
+/// Parses a `#rrggbb` or `#rgb` color.
+fn parse_color(text: &str) -> Option<Color32> {
+    let hex = text.trim().strip_prefix('#')?;
+    let channel =
+        |i: usize, len: usize| u8::from_str_radix(hex.get(i * len..(i + 1) * len)?, 16).ok();
+    match hex.len() {
+        6 => Some(Color32::from_rgb(
+            channel(0, 2)?,
+            channel(1, 2)?,
+            channel(2, 2)?,
+        )),
+        3 => {
+            let [r, g, b] = [channel(0, 1)?, channel(1, 1)?, channel(2, 1)?].map(|c| c * 17);
+            Some(Color32::from_rgb(r, g, b))
+        }
+        _ => None,
+    }
+}
+
+/// Settings of the night-light (warm color) mode.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NightLight {
+    /// Whether night light is switched on
+    pub enabled: bool,
+    /// How strongly colors are warmed, from 0.0 (unchanged) to 1.0
+    pub strength: f32,
+    /// Whether night light only applies between `start_hour` and `end_hour`
+    pub scheduled: bool,
+    /// Local hour (0-23) at which the scheduled night light turns on
+    pub start_hour: u32,
+    /// Local hour (0-23) at which the scheduled night light turns off
+    pub end_hour: u32,
+}
+
+impl Default for NightLight {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            strength: 0.5,
+            scheduled: false,
+            start_hour: 20,
+            end_hour: 7,
+        }
+    }
+}
+
+impl NightLight {
+    /// Warmth to apply right now, taking the schedule into account.
+    pub fn current_strength(&self) -> f32 {
+        if !self.enabled {
+            return 0.0;
+        }
+        if self.scheduled {
+            let hour = local_hour();
+            let active = if self.start_hour <= self.end_hour {
+                (self.start_hour..self.end_hour).contains(&hour)
+            } else {
+                // The window wraps around midnight (e.g. 20:00 to 07:00)
+                hour >= self.start_hour || hour < self.end_hour
+            };
+            if !active {
+                return 0.0;
+            }
+        }
+        self.strength.clamp(0.0, 1.0)
+    }
+}
+
+/// Shifts a color toward warmer tones by attenuating blue (and a little green).
+pub fn warm_color(color: Color32, strength: f32) -> Color32 {
+    if strength <= 0.0 {
+        return color;
+    }
+    let [r, g, b, a] = color.to_array();
+    Color32::from_rgba_premultiplied(
+        r,
+        (g as f32 * (1.0 - 0.12 * strength)) as u8,
+        (b as f32 * (1.0 - 0.45 * strength)) as u8,
+        a,
+    )
+}
+
+/// Applies [`warm_color`] to every color of a set of visuals.
+pub fn warm_visuals(visuals: &mut Visuals, strength: f32) {
+    if strength <= 0.0 {
+        return;
+    }
+    let warm = |color: &mut Color32| *color = warm_color(*color, strength);
+
+    if let Some(color) = visuals.override_text_color.as_mut() {
+        warm(color);
+    }
+    warm(&mut visuals.hyperlink_color);
+    warm(&mut visuals.faint_bg_color);
+    warm(&mut visuals.extreme_bg_color);
+    warm(&mut visuals.code_bg_color);
+    warm(&mut visuals.warn_fg_color);
+    warm(&mut visuals.error_fg_color);
+    warm(&mut visuals.window_fill);
+    warm(&mut visuals.panel_fill);
+    warm(&mut visuals.window_stroke.color);
+    warm(&mut visuals.selection.bg_fill);
+    warm(&mut visuals.selection.stroke.color);
+    for widget in [
+        &mut visuals.widgets.noninteractive,
+        &mut visuals.widgets.inactive,
+        &mut visuals.widgets.hovered,
+        &mut visuals.widgets.active,
+        &mut visuals.widgets.open,
+    ] {
+        warm(&mut widget.bg_fill);
+        warm(&mut widget.weak_bg_fill);
+        warm(&mut widget.bg_stroke.color);
+        warm(&mut widget.fg_stroke.color);
+    }
+}
+
+/// Current local hour of the day (0-23).
+#[cfg(unix)]
+fn local_hour() -> u32 {
+    // SAFETY: `time` accepts a null pointer, and `localtime_r` only writes to the
+    // zero-initialised `tm` we own.
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return utc_hour();
+        }
+        tm.tm_hour as u32
+    }
+}
+
+/// Current hour of the day (0-23); local time zones are only resolved on unix.
+#[cfg(not(unix))]
+fn local_hour() -> u32 {
+    utc_hour()
+}
+
+fn utc_hour() -> u32 {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    (seconds / 3600 % 24) as u32
+}
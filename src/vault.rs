@@ -0,0 +1,153 @@
+//! # Vault Module
+//!
+//! This module indexes the markdown notes under a folder (an Obsidian-style "vault") and
+//! resolves note references such as `[[other-note]]` or `![[folder/note#Section]]` to files.
+
+use std::path::{Path, PathBuf};
+
+/// File extensions treated as markdown notes.
+pub const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown"];
+
+/// Upper bound on directory entries visited while indexing, so opening a file that lives
+/// in a huge directory (e.g. the home folder) doesn't stall the UI.
+const MAX_SCAN_ENTRIES: usize = 20_000;
+
+/// An index of the markdown notes below a root folder.
+#[derive(Debug, Clone, Default)]
+pub struct Vault {
+    /// Folder the index was built from
+    root: PathBuf,
+    /// All markdown files found below the root, sorted by path
+    notes: Vec<PathBuf>,
+}
+
+impl Vault {
+    /// Scans `root` recursively for markdown files, skipping hidden directories.
+    pub fn open(root: PathBuf) -> Self {
+        let mut notes: Vec<PathBuf> = walkdir::WalkDir::new(&root)
+            .into_iter()
+            .filter_entry(|entry| entry.depth() == 0 || !is_hidden(entry.path()))
+            .take(MAX_SCAN_ENTRIES)
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file() && is_markdown(entry.path()))
+            .map(|entry| entry.into_path())
+            .collect();
+        notes.sort();
+        Self { root, notes }
+    }
+
+    /// Folder the vault was opened from.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// All markdown files below the root, sorted by path.
+    pub fn notes(&self) -> &[PathBuf] {
+        &self.notes
+    }
+
+    /// Resolves a note reference (without `#section`) to a file.
+    ///
+    /// Tries the referencing file's folder first, then the vault root, and finally any
+    /// indexed note whose path ends with the reference (shortest path wins), mirroring
+    /// Obsidian's "shortest path when possible" link format.
+    pub fn resolve(&self, target: &str, from: Option<&Path>) -> Option<PathBuf> {
+        let target = target.trim();
+        if target.is_empty() {
+            return None;
+        }
+        let candidate = if is_markdown(Path::new(target)) {
+            PathBuf::from(target)
+        } else {
+            PathBuf::from(format!("{target}.md"))
+        };
+
+        if let Some(dir) = from.and_then(Path::parent) {
+            let path = dir.join(&candidate);
+            if path.is_file() {
+                return Some(path);
+            }
+        }
+        let path = self.root.join(&candidate);
+        if path.is_file() {
+            return Some(path);
+        }
+
+        let wanted: Vec<String> = candidate
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+            .collect();
+        self.notes
+            .iter()
+            .filter(|note| {
+                let components: Vec<String> = note
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+                    .collect();
+                components.ends_with(&wanted)
+            })
+            .min_by_key(|note| note.components().count())
+            .cloned()
+    }
+}
+
+/// Splits a reference like `note#Section` into the note name and optional section.
+pub fn split_target(target: &str) -> (&str, Option<&str>) {
+    match target.split_once('#') {
+        Some((note, section)) => (note.trim(), Some(section.trim())),
+        None => (target.trim(), None),
+    }
+}
+
+/// Returns whether the path has a markdown extension.
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| MARKDOWN_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+/// Extracts the markdown of the section introduced by `heading` (case-insensitive).
+///
+/// The section runs from the heading itself up to the next heading of the same or a
+/// higher level. Returns None if no heading matches.
+pub fn extract_section<'a>(markdown: &'a str, heading: &str) -> Option<&'a str> {
+    use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+    let wanted = heading.trim().to_lowercase();
+    let mut current: Option<(usize, pulldown_cmark::HeadingLevel, String)> = None;
+    let mut found: Option<(usize, pulldown_cmark::HeadingLevel)> = None;
+
+    for (event, range) in Parser::new(markdown).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                if let Some((start, found_level)) = found {
+                    if level <= found_level {
+                        return Some(&markdown[start..range.start]);
+                    }
+                }
+                current = Some((range.start, level, String::new()));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, _, ref mut title)) = current {
+                    title.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((start, level, title)) = current.take() {
+                    if found.is_none() && title.trim().to_lowercase() == wanted {
+                        found = Some((start, level));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    found.map(|(start, _)| &markdown[start..])
+}
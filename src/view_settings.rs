@@ -0,0 +1,104 @@
+//! # View Settings Module
+//!
+//! This module remembers view preferences (wide mode, font size, TOC visibility) per
+//! document, so a file always reopens the way it was last read, while documents without
+//! their own settings keep using the global defaults. The settings live in eframe's
+//! storage next to the preferences.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Key of the view settings in eframe's storage.
+const STORAGE_KEY: &str = "view_settings";
+
+/// View preferences of a document.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewSettings {
+    /// Whether wide mode is enabled
+    pub wide_mode: bool,
+    /// Base font size
+    pub font_size: f32,
+    /// Whether the TOC sidebar is shown
+    pub show_toc: bool,
+}
+
+impl Default for ViewSettings {
+    fn default() -> Self {
+        Self {
+            wide_mode: false,
+            font_size: 14.0,
+            show_toc: false,
+        }
+    }
+}
+
+/// Global default view settings plus the settings of individual documents.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewSettingsStore {
+    /// Settings for documents without settings of their own
+    pub defaults: ViewSettings,
+    /// Whether the TOC panel is docked on the right instead of the left
+    pub toc_on_right: bool,
+    /// Width of the TOC panel
+    pub toc_width: f32,
+    /// Whether TOC entries are numbered by section (1, 1.1, 1.1.2)
+    pub toc_numbering: bool,
+    /// Whether the TOC stays hidden until the pointer touches the window edge
+    pub toc_auto_hide: bool,
+    /// Settings keyed by document path
+    documents: HashMap<PathBuf, ViewSettings>,
+}
+
+impl Default for ViewSettingsStore {
+    fn default() -> Self {
+        Self {
+            defaults: ViewSettings::default(),
+            toc_on_right: false,
+            toc_width: 200.0,
+            toc_numbering: false,
+            toc_auto_hide: false,
+            documents: HashMap::new(),
+        }
+    }
+}
+
+impl ViewSettingsStore {
+    /// Loads the stored settings from eframe's storage.
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        storage
+            .and_then(|storage| eframe::get_value(storage, STORAGE_KEY))
+            .unwrap_or_default()
+    }
+
+    /// Writes the settings to eframe's storage.
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, STORAGE_KEY, self);
+    }
+
+    /// Settings to use for a document: its own if it has any, otherwise the defaults.
+    pub fn for_document(&self, path: &Path) -> ViewSettings {
+        self.documents.get(path).copied().unwrap_or(self.defaults)
+    }
+
+    /// Whether the document has settings of its own.
+    pub fn has_document(&self, path: &Path) -> bool {
+        self.documents.contains_key(path)
+    }
+
+    /// Remembers settings for a document; settings equal to the defaults are dropped.
+    pub fn remember(&mut self, path: &Path, settings: ViewSettings) {
+        if settings == self.defaults {
+            self.documents.remove(path);
+        } else {
+            self.documents.insert(path.to_path_buf(), settings);
+        }
+    }
+
+    /// Forgets the settings of a document so it follows the defaults again.
+    pub fn forget(&mut self, path: &Path) {
+        self.documents.remove(path);
+    }
+}
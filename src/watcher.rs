@@ -0,0 +1,90 @@
+//! # Watcher Module
+//!
+//! This module keeps the index of the folder opened in folder mode up to date by
+//! rescanning it periodically in the background, so created, renamed, deleted and
+//! modified files show up without reopening the folder.
+
+use crate::read_state::modified_secs;
+use crate::vault::Vault;
+use egui::Context;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// Time between two scans of the folder.
+const SCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Result of scanning a folder.
+pub struct FolderScan {
+    /// Index of the folder's markdown files
+    pub vault: Vault,
+    /// Modification times (seconds since the epoch) of the files
+    pub modified: HashMap<PathBuf, u64>,
+}
+
+impl FolderScan {
+    /// Indexes a folder and reads the modification times of its files.
+    pub fn run(root: PathBuf) -> Self {
+        let vault = Vault::open(root);
+        let modified = vault
+            .notes()
+            .iter()
+            .filter_map(|path| Some((path.clone(), modified_secs(path)?)))
+            .collect();
+        Self { vault, modified }
+    }
+}
+
+/// Periodically re-indexes a folder.
+pub struct FolderWatcher {
+    /// Folder being watched
+    root: PathBuf,
+    /// When the last scan finished
+    last_scan: Instant,
+    /// Scan running in the background
+    pending: Option<mpsc::Receiver<FolderScan>>,
+}
+
+impl FolderWatcher {
+    /// Starts watching a folder that was just indexed.
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            last_scan: Instant::now(),
+            pending: None,
+        }
+    }
+
+    /// Starts a scan when one is due and returns its result once it completes.
+    pub fn poll(&mut self, ctx: &Context) -> Option<FolderScan> {
+        if let Some(receiver) = &self.pending {
+            match receiver.try_recv() {
+                Ok(scan) => {
+                    self.pending = None;
+                    self.last_scan = Instant::now();
+                    return Some(scan);
+                }
+                Err(mpsc::TryRecvError::Empty) => return None,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    self.pending = None;
+                    self.last_scan = Instant::now();
+                }
+            }
+        }
+
+        if self.last_scan.elapsed() >= SCAN_INTERVAL {
+            let (sender, receiver) = mpsc::channel();
+            let root = self.root.clone();
+            let ctx = ctx.clone();
+            std::thread::spawn(move || {
+                let _ = sender.send(FolderScan::run(root));
+                ctx.request_repaint();
+            });
+            self.pending = Some(receiver);
+        } else {
+            ctx.request_repaint_after(SCAN_INTERVAL - self.last_scan.elapsed());
+        }
+        None
+    }
+}